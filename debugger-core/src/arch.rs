@@ -0,0 +1,88 @@
+use windows::Win32::System::SystemInformation::{
+    IMAGE_FILE_MACHINE,
+    IMAGE_FILE_MACHINE_AMD64,
+    IMAGE_FILE_MACHINE_ARM64,
+    IMAGE_FILE_MACHINE_I386,
+};
+
+/// The processor architecture of a target image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Architecture {
+    X86,
+    X64,
+    Arm64,
+}
+
+impl Architecture {
+    /// Gets the architecture from the `Machine` field of an image's file header.
+    pub fn from_machine(machine: IMAGE_FILE_MACHINE) -> Option<Architecture> {
+        match machine {
+            IMAGE_FILE_MACHINE_I386 => Some(Architecture::X86),
+            IMAGE_FILE_MACHINE_AMD64 => Some(Architecture::X64),
+            IMAGE_FILE_MACHINE_ARM64 => Some(Architecture::Arm64),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Architecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Architecture::X86 => "x86",
+            Architecture::X64 => "x64",
+            Architecture::Arm64 => "ARM64",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Architecture-independent access to a thread's registers.
+pub trait ThreadContext {
+    fn instruction_pointer(&self) -> u64;
+    fn set_instruction_pointer(&mut self, address: u64);
+    fn stack_pointer(&self) -> u64;
+    /// Makes the thread raise an EXCEPTION_SINGLE_STEP exception after executing the next instruction.
+    fn enable_single_step(&mut self);
+    /// Returns the value of the register named `name`, or `None` if there is no such register.
+    fn get_register(&self, name: &str) -> Option<u64>;
+    fn set_register(&mut self, name: &str, value: u64) -> Result<(), String>;
+    /// Prints the general-purpose registers and flags, highlighting the ones that differ from `previous`.
+    fn display_registers(&self, previous: Option<&Self>);
+}
+
+mod x64 {
+    use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+    use super::ThreadContext;
+    use crate::{registers, windows_wrapper};
+
+    impl ThreadContext for CONTEXT {
+        fn instruction_pointer(&self) -> u64 {
+            self.Rip
+        }
+
+        fn set_instruction_pointer(&mut self, address: u64) {
+            self.Rip = address;
+        }
+
+        fn stack_pointer(&self) -> u64 {
+            self.Rsp
+        }
+
+        fn enable_single_step(&mut self) {
+            self.EFlags |= windows_wrapper::TRAP_FLAG;
+        }
+
+        fn get_register(&self, name: &str) -> Option<u64> {
+            registers::get_register(self, name)
+        }
+
+        fn set_register(&mut self, name: &str, value: u64) -> Result<(), String> {
+            registers::set_register(self, name, value)
+        }
+
+        fn display_registers(&self, previous: Option<&Self>) {
+            registers::display_all(*self, previous);
+        }
+    }
+}
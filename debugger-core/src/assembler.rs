@@ -0,0 +1,230 @@
+use iced_x86::{
+    BlockEncoder,
+    BlockEncoderOptions,
+    Code,
+    Instruction,
+    InstructionBlock,
+    Register,
+};
+
+use crate::{
+    breakpoint::BreakpointManager,
+    memory::MemorySource,
+};
+
+// A small assembler for patching code from the prompt. iced-x86 can encode instructions but not parse them, so this parses
+// a subset of Intel syntax: general-purpose registers, immediates, and branches to absolute addresses. Memory operands
+// are not supported.
+
+const REGISTERS_64: [(&str, Register); 16] = [
+    ("rax", Register::RAX), ("rcx", Register::RCX), ("rdx", Register::RDX), ("rbx", Register::RBX),
+    ("rsp", Register::RSP), ("rbp", Register::RBP), ("rsi", Register::RSI), ("rdi", Register::RDI),
+    ("r8", Register::R8), ("r9", Register::R9), ("r10", Register::R10), ("r11", Register::R11),
+    ("r12", Register::R12), ("r13", Register::R13), ("r14", Register::R14), ("r15", Register::R15),
+];
+
+const REGISTERS_32: [(&str, Register); 16] = [
+    ("eax", Register::EAX), ("ecx", Register::ECX), ("edx", Register::EDX), ("ebx", Register::EBX),
+    ("esp", Register::ESP), ("ebp", Register::EBP), ("esi", Register::ESI), ("edi", Register::EDI),
+    ("r8d", Register::R8D), ("r9d", Register::R9D), ("r10d", Register::R10D), ("r11d", Register::R11D),
+    ("r12d", Register::R12D), ("r13d", Register::R13D), ("r14d", Register::R14D), ("r15d", Register::R15D),
+];
+
+/// Branches to an absolute address: (mnemonics, 64-bit code, 32-bit code).
+/// The block encoder picks the shortest encoding that reaches the target, so these are just the near forms.
+const BRANCHES: [(&[&str], Code, Code); 18] = [
+    (&["jmp"], Code::Jmp_rel32_64, Code::Jmp_rel32_32),
+    (&["call"], Code::Call_rel32_64, Code::Call_rel32_32),
+    (&["jo"], Code::Jo_rel32_64, Code::Jo_rel32_32),
+    (&["jno"], Code::Jno_rel32_64, Code::Jno_rel32_32),
+    (&["jb", "jc", "jnae"], Code::Jb_rel32_64, Code::Jb_rel32_32),
+    (&["jae", "jnb", "jnc"], Code::Jae_rel32_64, Code::Jae_rel32_32),
+    (&["je", "jz"], Code::Je_rel32_64, Code::Je_rel32_32),
+    (&["jne", "jnz"], Code::Jne_rel32_64, Code::Jne_rel32_32),
+    (&["jbe", "jna"], Code::Jbe_rel32_64, Code::Jbe_rel32_32),
+    (&["ja", "jnbe"], Code::Ja_rel32_64, Code::Ja_rel32_32),
+    (&["js"], Code::Js_rel32_64, Code::Js_rel32_32),
+    (&["jns"], Code::Jns_rel32_64, Code::Jns_rel32_32),
+    (&["jp", "jpe"], Code::Jp_rel32_64, Code::Jp_rel32_32),
+    (&["jnp", "jpo"], Code::Jnp_rel32_64, Code::Jnp_rel32_32),
+    (&["jl", "jnge"], Code::Jl_rel32_64, Code::Jl_rel32_32),
+    (&["jge", "jnl"], Code::Jge_rel32_64, Code::Jge_rel32_32),
+    (&["jle", "jng"], Code::Jle_rel32_64, Code::Jle_rel32_32),
+    (&["jg", "jnle"], Code::Jg_rel32_64, Code::Jg_rel32_32),
+];
+
+/// Instructions with no operands: (mnemonic, 64-bit code, 32-bit code).
+const NO_OPERANDS: [(&str, Code, Code); 6] = [
+    ("nop", Code::Nopd, Code::Nopd),
+    ("int3", Code::Int3, Code::Int3),
+    ("ret", Code::Retnq, Code::Retnd),
+    ("leave", Code::Leaveq, Code::Leaved),
+    ("hlt", Code::Hlt, Code::Hlt),
+    ("ud2", Code::Ud2, Code::Ud2),
+];
+
+/// Instructions with one register operand: (mnemonic, 64-bit register code, 32-bit register code).
+const ONE_REGISTER: [(&str, Code, Code); 4] = [
+    ("push", Code::Push_r64, Code::Push_r32),
+    ("pop", Code::Pop_r64, Code::Pop_r32),
+    ("inc", Code::Inc_rm64, Code::Inc_rm32),
+    ("dec", Code::Dec_rm64, Code::Dec_rm32),
+];
+
+/// Instructions with a register and then a register or immediate operand:
+/// (mnemonic, 64-bit register/register code, 32-bit register/register code, 64-bit register/immediate code, 32-bit register/immediate code).
+const TWO_OPERANDS: [(&str, Code, Code, Code, Code); 8] = [
+    ("mov", Code::Mov_r64_rm64, Code::Mov_r32_rm32, Code::Mov_r64_imm64, Code::Mov_r32_imm32),
+    ("add", Code::Add_r64_rm64, Code::Add_r32_rm32, Code::Add_rm64_imm32, Code::Add_rm32_imm32),
+    ("sub", Code::Sub_r64_rm64, Code::Sub_r32_rm32, Code::Sub_rm64_imm32, Code::Sub_rm32_imm32),
+    ("and", Code::And_r64_rm64, Code::And_r32_rm32, Code::And_rm64_imm32, Code::And_rm32_imm32),
+    ("or", Code::Or_r64_rm64, Code::Or_r32_rm32, Code::Or_rm64_imm32, Code::Or_rm32_imm32),
+    ("xor", Code::Xor_r64_rm64, Code::Xor_r32_rm32, Code::Xor_rm64_imm32, Code::Xor_rm32_imm32),
+    ("cmp", Code::Cmp_r64_rm64, Code::Cmp_r32_rm32, Code::Cmp_rm64_imm32, Code::Cmp_rm32_imm32),
+    ("test", Code::Test_rm64_r64, Code::Test_rm32_r32, Code::Test_rm64_imm32, Code::Test_rm32_imm32),
+];
+
+enum Operand {
+    /// The register, and whether it is 64-bit.
+    Register(Register, bool),
+    Immediate(u64),
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if let Some((_, register)) = REGISTERS_64.iter().find(|(name, _)| *name == text) {
+        return Ok(Operand::Register(*register, true));
+    }
+    if let Some((_, register)) = REGISTERS_32.iter().find(|(name, _)| *name == text) {
+        return Ok(Operand::Register(*register, false));
+    }
+    parse_number(text).map(Operand::Immediate)
+}
+
+/// Parses a decimal or `0x` hex number. Negative numbers are two's complement.
+fn parse_number(text: &str) -> Result<u64, String> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex_digits) => u64::from_str_radix(hex_digits, 16),
+        None => digits.parse::<u64>(),
+    };
+    let value = value.map_err(|_| format!("Unknown operand {text}"))?;
+    Ok(if negative { value.wrapping_neg() } else { value })
+}
+
+/// Checks that an immediate fits in 32 bits, as either a signed or an unsigned number.
+/// For 64-bit registers it is sign-extended, so only signed values fit.
+fn to_imm32(value: u64, is_64_bit: bool) -> Result<i32, String> {
+    let fits = if is_64_bit {
+        i32::try_from(value as i64).is_ok()
+    } else {
+        u32::try_from(value).is_ok() || i32::try_from(value as i64).is_ok()
+    };
+    if fits {
+        Ok(value as i32)
+    } else {
+        Err(format!("{value:#x} does not fit in a 32-bit immediate"))
+    }
+}
+
+fn create_instruction(mnemonic: &str, operands: &[Operand], bitness: u32) -> Result<Instruction, String> {
+    let is_64_bit_mode = bitness == 64;
+    let select = |code_64: Code, code_32: Code| if is_64_bit_mode { code_64 } else { code_32 };
+    let unsupported = || format!("Unsupported operands for {mnemonic}");
+
+    if let Some((_, code_64, code_32)) = NO_OPERANDS.iter().find(|(name, ..)| *name == mnemonic) {
+        return match operands {
+            [] => Ok(Instruction::with(select(*code_64, *code_32))),
+            _ => Err(unsupported()),
+        };
+    }
+
+    if let Some((_, code_64, code_32)) = BRANCHES.iter().find(|(names, ..)| names.contains(&mnemonic)) {
+        return match operands {
+            [Operand::Immediate(target)] => Instruction::with_branch(select(*code_64, *code_32), *target).map_err(|e| e.to_string()),
+            _ => Err(unsupported()),
+        };
+    }
+
+    if let Some((_, code_64, code_32)) = ONE_REGISTER.iter().find(|(name, ..)| *name == mnemonic) {
+        return match operands {
+            [Operand::Register(register, is_64_bit)] => {
+                Instruction::with1(if *is_64_bit { *code_64 } else { *code_32 }, *register).map_err(|e| e.to_string())
+            }
+            _ => Err(unsupported()),
+        };
+    }
+
+    if let Some((_, register_64, register_32, immediate_64, immediate_32)) = TWO_OPERANDS.iter().find(|(name, ..)| *name == mnemonic) {
+        return match operands {
+            [Operand::Register(destination, true), Operand::Register(source, true)] => {
+                Instruction::with2(*register_64, *destination, *source).map_err(|e| e.to_string())
+            }
+            [Operand::Register(destination, false), Operand::Register(source, false)] => {
+                Instruction::with2(*register_32, *destination, *source).map_err(|e| e.to_string())
+            }
+            [Operand::Register(..), Operand::Register(..)] => Err(String::from("The registers must be the same size")),
+            // `mov` is the only one with a full 64-bit immediate.
+            [Operand::Register(destination, true), Operand::Immediate(value)] if *immediate_64 == Code::Mov_r64_imm64 => {
+                Instruction::with2(*immediate_64, *destination, *value).map_err(|e| e.to_string())
+            }
+            [Operand::Register(destination, is_64_bit), Operand::Immediate(value)] => {
+                let code = if *is_64_bit { *immediate_64 } else { *immediate_32 };
+                Instruction::with2(code, *destination, to_imm32(*value, *is_64_bit)?).map_err(|e| e.to_string())
+            }
+            _ => Err(unsupported()),
+        };
+    }
+
+    Err(format!("Unknown or unsupported instruction {mnemonic}"))
+}
+
+/// Assembles one instruction, e.g. `jne 0x7ff6c0de1234` or `xor eax, eax`, to be placed at `address`.
+pub fn assemble_instruction(text: &str, address: u64, bitness: u32) -> Result<Vec<u8>, String> {
+    let text = text.trim().to_lowercase();
+    let (mnemonic, operands_text) = text.split_once(char::is_whitespace).unwrap_or((text.as_str(), ""));
+    let operands = if operands_text.trim().is_empty() {
+        Vec::new()
+    } else {
+        operands_text.split(',').map(|operand| parse_operand(operand.trim())).collect::<Result<Vec<_>, _>>()?
+    };
+
+    let instruction = create_instruction(mnemonic, &operands, bitness)?;
+    let instructions = [instruction];
+    let result = BlockEncoder::encode(bitness, InstructionBlock::new(&instructions, address), BlockEncoderOptions::NONE)
+        .map_err(|e| e.to_string())?;
+    Ok(result.code_buffer)
+}
+
+/// Reads instructions with `read_line`, one per line, and writes them to the target starting at `address`.
+/// An empty line, or the end of input, ends input. `read_line` is passed the prompt to show. Breakpoints in the written
+/// range stay set on the new instructions.
+pub fn run_assemble_prompt(
+    address: u64,
+    bitness: u32,
+    breakpoints: &mut BreakpointManager,
+    memory_source: &dyn MemorySource,
+    read_line: &mut dyn FnMut(&str) -> Option<String>,
+) {
+    let mut address = address;
+    loop {
+        let input = match read_line(&format!("{address:#018x} ")) {
+            Some(input) if !input.trim().is_empty() => input,
+            _ => return,
+        };
+
+        let bytes = match assemble_instruction(&input, address, bitness) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                print_error!("Could not assemble: {e}");
+                continue;
+            }
+        };
+        match breakpoints.write_memory(address, &bytes, memory_source) {
+            Ok(()) => address += bytes.len() as u64,
+            Err(e) => print_error!("Could not write to {address:#018x}: {e}"),
+        }
+    }
+}
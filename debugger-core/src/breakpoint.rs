@@ -0,0 +1,479 @@
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+use crate::{
+    arch::ThreadContext,
+    command::grammar::EvalExpr,
+    memory::MemorySource,
+    name_resolution,
+    output,
+    process::Process,
+    windows_wrapper::ThreadId,
+};
+
+/// The x86/x64 `int3` instruction, which raises an `EXCEPTION_BREAKPOINT` when executed.
+const INT3: u8 = 0xCC;
+
+struct Breakpoint {
+    /// Identifies the breakpoint in `list_breakpoints`, so that it can be removed without typing its address. Temporary
+    /// breakpoints aren't listed, so their id is 0.
+    id: u32,
+    address: u64,
+    /// The byte that the `int3` replaced, if the breakpoint is currently written to the target's memory.
+    original_byte: Option<u8>,
+    /// Only stop when this evaluates to non-zero.
+    condition: Option<EvalExpr>,
+    /// Print this message (see `format_log_message`) and continue instead of stopping.
+    log_message: Option<String>,
+    /// Temporary breakpoints are removed as soon as they are hit. They are used to implement stepping.
+    temporary: bool,
+    /// What the user typed for the address, e.g. a symbol or an expression, if it was more than a number.
+    location: Option<String>,
+    /// How many times a thread has executed the breakpoint, whether or not it stopped.
+    hit_count: u64,
+    /// Only stop on this thread. Other threads step past the breakpoint.
+    thread: Option<ThreadId>,
+    /// One-shot breakpoints are removed the first time they stop, but unlike temporary breakpoints, they are listed and
+    /// stop like other breakpoints.
+    one_shot: bool,
+}
+
+impl Breakpoint {
+    fn arm(&mut self, memory_source: &dyn MemorySource) -> Result<(), String> {
+        if self.original_byte.is_some() {
+            return Ok(());
+        }
+
+        let original_bytes = memory_source.read_raw_memory(self.address, 1);
+        let original_byte = *original_bytes.first()
+            .ok_or_else(|| format!("Could not read memory at {:#018x}", self.address))?;
+        memory_source.write_raw_memory(self.address, &[INT3])?;
+        tracing::trace!("Armed the breakpoint at {:#018x}, replacing {original_byte:#04x}", self.address);
+        self.original_byte = Some(original_byte);
+        Ok(())
+    }
+
+    fn disarm(&mut self, memory_source: &dyn MemorySource) -> Result<(), String> {
+        if let Some(original_byte) = self.original_byte {
+            memory_source.write_raw_memory(self.address, &[original_byte])?;
+            tracing::trace!("Disarmed the breakpoint at {:#018x}, restoring {original_byte:#04x}", self.address);
+            self.original_byte = None;
+        }
+        Ok(())
+    }
+}
+
+/// A breakpoint on a symbol in a module that has not been loaded yet.
+struct DeferredBreakpoint {
+    /// The breakpoint keeps its id when its module is loaded.
+    id: u32,
+    symbol: String,
+    condition: Option<EvalExpr>,
+    log_message: Option<String>,
+}
+
+#[derive(Default)]
+pub struct BreakpointManager {
+    // TODO: determine if it's better to use a HashMap instead.
+    breakpoints: Vec::<Breakpoint>,
+    deferred_breakpoints: Vec::<DeferredBreakpoint>,
+    /// The id of the most recently added breakpoint.
+    last_id: u32,
+}
+
+impl BreakpointManager {
+    pub fn new() -> BreakpointManager {
+        BreakpointManager {
+            breakpoints: Vec::new(),
+            deferred_breakpoints: Vec::new(),
+            last_id: 0,
+        }
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    /// Adds a breakpoint and writes it to the target's memory. `location` is what the user typed for the address, to
+    /// show in `list_breakpoints`.
+    pub fn add_breakpoint(
+        &mut self,
+        address: u64,
+        location: Option<String>,
+        condition: Option<EvalExpr>,
+        log_message: Option<String>,
+        memory_source: &dyn MemorySource,
+    ) -> Result<(), String> {
+        let id = self.next_id();
+        self.insert_breakpoint(id, address, location, condition, log_message, memory_source)
+    }
+
+    fn insert_breakpoint(
+        &mut self,
+        id: u32,
+        address: u64,
+        location: Option<String>,
+        condition: Option<EvalExpr>,
+        log_message: Option<String>,
+        memory_source: &dyn MemorySource,
+    ) -> Result<(), String> {
+        if self.contains_breakpoint(address) {
+            return Err(format!("Breakpoint already exists at {address:#018x}"));
+        }
+
+        let mut breakpoint = Breakpoint { id, address, original_byte: None, condition, log_message, temporary: false, location, hit_count: 0, thread: None, one_shot: false };
+        breakpoint.arm(memory_source)?;
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    /// Adds a breakpoint that is removed as soon as it is hit. See `is_temporary`.
+    pub fn add_temporary_breakpoint(&mut self, address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+        if self.contains_breakpoint(address) {
+            return Err(format!("Breakpoint already exists at {address:#018x}"));
+        }
+
+        let mut breakpoint = Breakpoint { id: 0, address, original_byte: None, condition: None, log_message: None, temporary: true, location: None, hit_count: 0, thread: None, one_shot: false };
+        breakpoint.arm(memory_source)?;
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    /// Adds a breakpoint that only stops on `thread`, and is removed once it does. See `is_one_shot`. Returns its id.
+    pub fn add_one_shot_breakpoint(&mut self, address: u64, thread: ThreadId, memory_source: &dyn MemorySource) -> Result<u32, String> {
+        let id = self.next_id();
+        self.insert_breakpoint(id, address, None, None, None, memory_source)?;
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|x| x.address == address) {
+            breakpoint.thread = Some(thread);
+            breakpoint.one_shot = true;
+        }
+        Ok(id)
+    }
+
+    /// Removes a breakpoint, restoring the original memory if it is currently armed.
+    pub fn remove_breakpoint(&mut self, address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+        for breakpoint in self.breakpoints.iter_mut().filter(|x| x.address == address) {
+            breakpoint.disarm(memory_source)?;
+        }
+        self.breakpoints.retain(|x| x.address != address);
+        Ok(())
+    }
+
+    /// Removes every breakpoint and deferred breakpoint, except temporary ones, which are still needed to finish stepping.
+    /// Breakpoints whose original memory can't be restored are kept. Returns the addresses of the ones that were removed,
+    /// and how many were kept.
+    pub fn remove_all_breakpoints(&mut self, memory_source: &dyn MemorySource) -> (Vec<u64>, usize) {
+        let mut removed = Vec::new();
+        let mut kept_count = 0;
+        for breakpoint in self.breakpoints.iter_mut().filter(|x| !x.temporary) {
+            match breakpoint.disarm(memory_source) {
+                Ok(()) => removed.push(breakpoint.address),
+                Err(e) => {
+                    print_error!("Could not remove breakpoint #{}: {e}", breakpoint.id);
+                    kept_count += 1;
+                }
+            }
+        }
+        self.breakpoints.retain(|x| !removed.contains(&x.address));
+        self.deferred_breakpoints.clear();
+        (removed, kept_count)
+    }
+
+    /// Removes a breakpoint or deferred breakpoint by the id that `list_breakpoints` shows. Returns the breakpoint's
+    /// address, or `None` if it was deferred.
+    pub fn remove_breakpoint_by_id(&mut self, id: u32, memory_source: &dyn MemorySource) -> Result<Option<u64>, String> {
+        if let Some(address) = self.breakpoints.iter().find(|x| x.id == id && !x.temporary).map(|x| x.address) {
+            self.remove_breakpoint(address, memory_source)?;
+            return Ok(Some(address));
+        }
+        let count = self.deferred_breakpoints.len();
+        self.deferred_breakpoints.retain(|x| x.id != id);
+        if self.deferred_breakpoints.len() == count {
+            return Err(format!("No breakpoint #{id}"));
+        }
+        Ok(None)
+    }
+
+    /// Gets the id of the breakpoint at `address`, unless there's none or it is temporary.
+    pub fn find_id(&self, address: u64) -> Option<u32> {
+        self.breakpoints.iter().find(|x| x.address == address && !x.temporary).map(|x| x.id)
+    }
+
+    /// Adds a breakpoint on `symbol` that is set once its module is loaded. See `resolve_deferred_breakpoints`.
+    pub fn add_deferred_breakpoint(&mut self, symbol: String, condition: Option<EvalExpr>, log_message: Option<String>) {
+        let id = self.next_id();
+        self.deferred_breakpoints.push(DeferredBreakpoint { id, symbol, condition, log_message });
+    }
+
+    /// Returns the id of the deferred breakpoint that was removed, if any.
+    pub fn remove_deferred_breakpoint(&mut self, symbol: &str) -> Option<u32> {
+        let index = self.deferred_breakpoints.iter().position(|x| x.symbol == symbol)?;
+        Some(self.deferred_breakpoints.remove(index).id)
+    }
+
+    /// Sets any deferred breakpoints whose symbols can now be resolved. Call this when a module is loaded.
+    pub fn resolve_deferred_breakpoints(&mut self, process: &mut Process, memory_source: &dyn MemorySource) {
+        let deferred_breakpoints = std::mem::take(&mut self.deferred_breakpoints);
+        for deferred in deferred_breakpoints {
+            match name_resolution::resolve_name_to_address(&deferred.symbol, process) {
+                Ok(address) => {
+                    println!("Resolved deferred breakpoint {symbol} to {address:#018x}", symbol = deferred.symbol);
+                    if let Err(e) = self.insert_breakpoint(deferred.id, address, Some(deferred.symbol), deferred.condition, deferred.log_message, memory_source) {
+                        print_error!("Could not add breakpoint: {e}");
+                    }
+                }
+                Err(e) => {
+                    if name_resolution::is_symbol_in_unloaded_module(&deferred.symbol, process) {
+                        self.deferred_breakpoints.push(deferred);
+                    } else {
+                        // The module was loaded, but the symbol isn't in it.
+                        print_error!("Could not resolve deferred breakpoint {symbol}: {e}", symbol = deferred.symbol);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn contains_breakpoint(&self, address: u64) -> bool {
+        self.breakpoints.iter().any(|x| x.address == address)
+    }
+
+    pub fn is_temporary(&self, address: u64) -> bool {
+        self.breakpoints.iter().any(|x| x.address == address && x.temporary)
+    }
+
+    pub fn is_one_shot(&self, address: u64) -> bool {
+        self.breakpoints.iter().any(|x| x.address == address && x.one_shot)
+    }
+
+    /// Whether the breakpoint at `address` stops on `thread`, as opposed to only on some other thread.
+    pub fn applies_to_thread(&self, address: u64, thread: ThreadId) -> bool {
+        self.breakpoints.iter()
+            .find(|x| x.address == address)
+            .and_then(|x| x.thread)
+            .is_none_or(|breakpoint_thread| breakpoint_thread == thread)
+    }
+
+    pub fn get_condition(&self, address: u64) -> Option<&EvalExpr> {
+        self.breakpoints.iter()
+            .find(|x| x.address == address)
+            .and_then(|x| x.condition.as_ref())
+    }
+
+    pub fn get_log_message(&self, address: u64) -> Option<&str> {
+        self.breakpoints.iter()
+            .find(|x| x.address == address)
+            .and_then(|x| x.log_message.as_deref())
+    }
+
+    /// Counts a thread executing the breakpoint at `address`. See `list_breakpoints`.
+    pub fn record_hit(&mut self, address: u64) {
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|x| x.address == address) {
+            breakpoint.hit_count += 1;
+        }
+    }
+
+    /// Temporarily restores the original byte of a breakpoint, so that the instruction at `address` can execute.
+    /// Call `rearm_breakpoint` after the thread has stepped past it.
+    pub fn disarm_breakpoint(&mut self, address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+        match self.breakpoints.iter_mut().find(|x| x.address == address) {
+            Some(breakpoint) => breakpoint.disarm(memory_source),
+            None => Err(format!("No breakpoint at {address:#018x}")),
+        }
+    }
+
+    /// Re-writes a breakpoint that was disarmed with `disarm_breakpoint`.
+    /// Does nothing if the breakpoint was removed in the meantime.
+    pub fn rearm_breakpoint(&mut self, address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+        match self.breakpoints.iter_mut().find(|x| x.address == address) {
+            Some(breakpoint) => breakpoint.arm(memory_source),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes `bytes` to the target at `address`. Any breakpoints in that range are disarmed first and re-armed after, so
+    /// that they save the new bytes as their original bytes instead of restoring the old ones when they are removed.
+    pub fn write_memory(&mut self, address: u64, bytes: &[u8], memory_source: &dyn MemorySource) -> Result<(), String> {
+        let end = address + bytes.len() as u64;
+        let mut disarmed = Vec::new();
+        for breakpoint in self.breakpoints.iter_mut().filter(|x| (address..end).contains(&x.address)) {
+            if breakpoint.original_byte.is_some() {
+                breakpoint.disarm(memory_source)?;
+                disarmed.push(breakpoint.address);
+            }
+        }
+
+        let result = memory_source.write_raw_memory(address, bytes);
+        for breakpoint in self.breakpoints.iter_mut().filter(|x| disarmed.contains(&x.address)) {
+            breakpoint.arm(memory_source)?;
+        }
+        result
+    }
+
+    pub fn list_breakpoints(&self, process: &mut Process) {
+        for breakpoint in self.breakpoints.iter().filter(|x| !x.temporary) {
+            let symbol = name_resolution::resolve_address_to_name(breakpoint.address, process);
+            let mut description = format!("#{} {}", breakpoint.id, output::address(breakpoint.address));
+            if let Some(symbol) = &symbol {
+                description += &format!(" ({})", output::symbol(symbol));
+            }
+            // The location is only interesting if it isn't the symbol that is already shown, e.g. `main+0x10`.
+            if let Some(location) = breakpoint.location.as_ref().filter(|location| Some(*location) != symbol.as_ref()) {
+                description += &format!(" from `{location}`");
+            }
+            let times = if breakpoint.hit_count == 1 { "time" } else { "times" };
+            description += &format!(", hit {} {times}", breakpoint.hit_count);
+            if let Some(thread) = breakpoint.thread {
+                description += &format!(", thread {thread:#x} only");
+            }
+            if breakpoint.one_shot {
+                description += ", one-shot";
+            }
+            println!("{description}{}", describe_options(breakpoint.condition.as_ref(), breakpoint.log_message.as_deref()));
+        }
+        for deferred in self.deferred_breakpoints.iter() {
+            println!("#{} {} [deferred until module load]{}", deferred.id, deferred.symbol, describe_options(deferred.condition.as_ref(), deferred.log_message.as_deref()));
+        }
+    }
+}
+
+/// Describes a breakpoint's condition and tracepoint message the way that they would be typed.
+fn describe_options(condition: Option<&EvalExpr>, log_message: Option<&str>) -> String {
+    let mut description = String::new();
+    if let Some(condition) = condition {
+        description += &format!(", if {condition}");
+    }
+    if let Some(message) = log_message {
+        description += &format!(", log \"{message}\"");
+    }
+    description
+}
+
+/// Formats a tracepoint message by replacing `{register}` or `{register:format}` with the register's value.
+/// Supported formats are `x`, `X`, `#x`, and `#X` for hex, or nothing for decimal. Use `{{` and `}}` for literal braces.
+pub fn format_log_message(message: &str, context: &CONTEXT) -> String {
+    let mut output = String::new();
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (name, format) = placeholder.split_once(':').unwrap_or((placeholder.as_str(), ""));
+                let formatted = match (context.get_register(name.trim()), format) {
+                    (Some(value), "") => format!("{value}"),
+                    (Some(value), "x") => format!("{value:x}"),
+                    (Some(value), "X") => format!("{value:X}"),
+                    (Some(value), "#x") => format!("{value:#x}"),
+                    (Some(value), "#X") => format!("{value:#X}"),
+                    (Some(_), _) => format!("<unknown format \"{format}\">"),
+                    (None, _) => format!("<unknown register \"{name}\">"),
+                };
+                output.push_str(&formatted);
+            }
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// The number of debug address registers (Dr0-Dr3) available for hardware breakpoints.
+const HARDWARE_BREAKPOINT_SLOT_COUNT: usize = 4;
+
+/// Hardware breakpoints, implemented with the x64 debug registers.
+/// These break on execute without modifying the target's code, so they work in read-only or self-checksumming code.
+#[derive(Default)]
+pub struct HardwareBreakpointManager {
+    /// The address of the breakpoint in each debug address register, if any.
+    slots: [Option<u64>; HARDWARE_BREAKPOINT_SLOT_COUNT],
+}
+
+impl HardwareBreakpointManager {
+    pub fn new() -> HardwareBreakpointManager {
+        HardwareBreakpointManager {
+            slots: [None; HARDWARE_BREAKPOINT_SLOT_COUNT],
+        }
+    }
+
+    /// Adds a breakpoint. Call `apply_to_context` for every thread afterwards so that it takes effect.
+    pub fn add_breakpoint(&mut self, address: u64) -> Result<(), String> {
+        if self.slots.contains(&Some(address)) {
+            return Err(format!("Hardware breakpoint already exists at {address:#018x}"));
+        }
+
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(address);
+                Ok(())
+            }
+            None => Err(format!("All {HARDWARE_BREAKPOINT_SLOT_COUNT} hardware breakpoints are in use")),
+        }
+    }
+
+    /// Removes a breakpoint. Call `apply_to_context` for every thread afterwards so that it takes effect.
+    pub fn remove_breakpoint(&mut self, address: u64) -> Result<(), String> {
+        match self.slots.iter_mut().find(|slot| **slot == Some(address)) {
+            Some(slot) => {
+                *slot = None;
+                Ok(())
+            }
+            None => Err(format!("No hardware breakpoint at {address:#018x}")),
+        }
+    }
+
+    /// Removes every breakpoint. Call `apply_to_context` for every thread afterwards so that it takes effect.
+    pub fn remove_all_breakpoints(&mut self) {
+        self.slots = [None; HARDWARE_BREAKPOINT_SLOT_COUNT];
+    }
+
+    /// Programs the debug registers in `context` to match the current breakpoints.
+    pub fn apply_to_context(&self, context: &mut CONTEXT) {
+        for (index, slot) in self.slots.iter().enumerate() {
+            let local_enable_bit = 1u64 << (index * 2);
+            // The condition (R/W) and length (LEN) bits. All zeros means "break on execute".
+            let condition_and_length_bits = 0b1111u64 << (16 + index * 4);
+
+            let address = slot.unwrap_or(0);
+            match index {
+                0 => context.Dr0 = address,
+                1 => context.Dr1 = address,
+                2 => context.Dr2 = address,
+                _ => context.Dr3 = address,
+            }
+
+            context.Dr7 &= !condition_and_length_bits;
+            if slot.is_some() {
+                context.Dr7 |= local_enable_bit;
+            } else {
+                context.Dr7 &= !local_enable_bit;
+            }
+        }
+    }
+
+    /// Returns the address of the hardware breakpoint that caused an `EXCEPTION_SINGLE_STEP`, if any.
+    pub fn get_hit_breakpoint(&self, context: &CONTEXT) -> Option<u64> {
+        // The low 4 bits of Dr6 indicate which debug address register triggered the exception.
+        (0..HARDWARE_BREAKPOINT_SLOT_COUNT)
+            .find(|index| context.Dr6 & (1 << index) != 0)
+            .and_then(|index| self.slots[index])
+    }
+
+    pub fn list_breakpoints(&self, process: &mut Process) {
+        for (index, address) in self.slots.iter().enumerate() {
+            if let Some(address) = *address {
+                if let Some(symbol) = name_resolution::resolve_address_to_name(address, process) {
+                    println!("{} ({}) [hardware Dr{index}]", output::address(address), output::symbol(&symbol));
+                } else {
+                    println!("{} [hardware Dr{index}]", output::address(address));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{disassembly, memory::MemorySource, name_resolution, process::Process};
+
+/// Stop tracing after this many instructions, in case the function never returns, e.g. because it waits in a loop.
+const MAX_INSTRUCTIONS: u64 = 1_000_000;
+
+/// How the instruction that the thread is stepping over changes the call depth.
+#[derive(Clone, Copy)]
+enum Flow {
+    /// Returns to `return_address`.
+    Call { return_address: u64 },
+    Return,
+    Other,
+}
+
+/// How to run the thread until `record_step` is called next.
+pub enum NextStep {
+    Step,
+    /// Run until the thread reaches the address, with a temporary breakpoint. Used for calls that are too deep to trace.
+    RunTo(u64),
+    Done,
+}
+
+/// A call that was too deep to trace, which runs until it returns.
+struct RunningCall {
+    return_address: u64,
+    /// The stack pointer when the call started. The call has returned when the thread reaches `return_address` with a
+    /// higher stack pointer. A lower one is a recursive call that returned to the same address.
+    stack_pointer: u64,
+    /// Whether the thread is stepping past `return_address` after a recursive call returned there, before running to it
+    /// again.
+    stepping_past: bool,
+}
+
+struct TracedCall {
+    /// 0 for the traced function, 1 for the calls it makes, and so on.
+    depth: usize,
+    name: String,
+    /// Not counting the instructions of the calls that it makes. Calls that were too deep to trace have none.
+    instruction_count: u64,
+    /// Including the instructions of the calls that it makes.
+    total_instruction_count: u64,
+}
+
+/// Records the calls that a function makes, by single-stepping it until it returns, like WinDbg's `wt`. Calls that are
+/// deeper than the maximum depth run without being stepped, which is much faster.
+pub struct CallTrace {
+    /// In the order that they were made. The first one is the traced function.
+    calls: Vec<TracedCall>,
+    /// Indexes into `calls` of the calls that haven't returned yet, innermost last.
+    active: Vec<usize>,
+    next_flow: Flow,
+    instruction_count: u64,
+    /// Calls deeper than this aren't traced.
+    max_depth: Option<usize>,
+    /// Whether to print each call when it is made, rather than all of them at the end.
+    print_calls: bool,
+    /// The call that was too deep to trace, while it runs.
+    running_to: Option<RunningCall>,
+}
+
+fn describe_address(address: u64, process: &mut Process) -> String {
+    name_resolution::resolve_address_to_name(address, process).unwrap_or_else(|| format!("{address:#018x}"))
+}
+
+fn get_flow(address: u64, memory_source: &dyn MemorySource, bitness: u32) -> Flow {
+    match disassembly::decode_instruction(memory_source, address, bitness) {
+        Ok(instruction) if disassembly::is_call(&instruction) => Flow::Call { return_address: instruction.next_ip() },
+        Ok(instruction) if disassembly::is_return(&instruction) => Flow::Return,
+        _ => Flow::Other,
+    }
+}
+
+impl CallTrace {
+    /// Starts tracing the function that the thread is in, at `address`. Step the thread, and call `record_step` after each
+    /// step. With `print_calls`, calls are printed as they are made, and `display` only summarizes them.
+    pub fn new(
+        address: u64,
+        max_depth: Option<usize>,
+        print_calls: bool,
+        process: &mut Process,
+        memory_source: &dyn MemorySource,
+        bitness: u32,
+    ) -> CallTrace {
+        let function = TracedCall { depth: 0, name: describe_address(address, process), instruction_count: 0, total_instruction_count: 0 };
+        CallTrace {
+            calls: vec![function],
+            active: vec![0],
+            next_flow: get_flow(address, memory_source, bitness),
+            instruction_count: 0,
+            max_depth,
+            print_calls,
+            running_to: None,
+        }
+    }
+
+    /// Records the instruction that the thread just stepped over, or the call that it ran, now that it is at `address`
+    /// with `stack_pointer`. Returns how to run the thread next.
+    pub fn record_step(
+        &mut self,
+        address: u64,
+        stack_pointer: u64,
+        process: &mut Process,
+        memory_source: &dyn MemorySource,
+        bitness: u32,
+    ) -> NextStep {
+        if let Some(running) = self.running_to.as_mut() {
+            if running.stepping_past {
+                running.stepping_past = false;
+                return NextStep::RunTo(running.return_address);
+            }
+            if address != running.return_address {
+                println!(
+                    "Stopped tracing: the call that returns to {return_address:#018x} stopped at {address:#018x} instead",
+                    return_address = running.return_address,
+                );
+                return NextStep::Done;
+            }
+            if stack_pointer <= running.stack_pointer {
+                // A recursive call returned, so step past its return address before running to it again.
+                running.stepping_past = true;
+                return NextStep::Step;
+            }
+            // The call that was too deep to trace returned.
+            self.running_to = None;
+            self.active.pop();
+            self.next_flow = get_flow(address, memory_source, bitness);
+            return NextStep::Step;
+        }
+
+        self.instruction_count += 1;
+        for index in self.active.iter() {
+            self.calls[*index].total_instruction_count += 1;
+        }
+        if let Some(index) = self.active.last() {
+            self.calls[*index].instruction_count += 1;
+        }
+
+        match self.next_flow {
+            Flow::Call { return_address } => {
+                let depth = self.active.len();
+                let name = describe_address(address, process);
+                if self.print_calls {
+                    println!("{}{name}", "  ".repeat(depth));
+                }
+                self.calls.push(TracedCall { depth, name, instruction_count: 0, total_instruction_count: 0 });
+                self.active.push(self.calls.len() - 1);
+                if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                    self.running_to = Some(RunningCall { return_address, stack_pointer, stepping_past: false });
+                    return NextStep::RunTo(return_address);
+                }
+            }
+            Flow::Return => {
+                self.active.pop();
+            }
+            Flow::Other => {}
+        }
+        if self.active.is_empty() {
+            return NextStep::Done;
+        }
+        if self.instruction_count >= MAX_INSTRUCTIONS {
+            println!("Stopped tracing after {MAX_INSTRUCTIONS} instructions");
+            return NextStep::Done;
+        }
+
+        self.next_flow = get_flow(address, memory_source, bitness);
+        NextStep::Step
+    }
+
+    /// The address that a call that was too deep to trace returns to, if it is running, to remove its temporary
+    /// breakpoint if the trace stops first.
+    pub fn running_return_address(&self) -> Option<u64> {
+        self.running_to.as_ref().map(|running| running.return_address)
+    }
+
+    /// Prints the calls as a tree, with how many instructions each one executed, itself and including its calls.
+    pub fn display(&self) {
+        if self.print_calls {
+            println!("{} calls", self.calls.len() - 1);
+            if !self.active.is_empty() {
+                println!("The function had not returned yet");
+            }
+            return;
+        }
+
+        println!("{:>10} {:>10}  Call", "Self", "Total");
+        for call in self.calls.iter() {
+            println!("{:>10} {:>10}  {}{}", call.instruction_count, call.total_instruction_count, "  ".repeat(call.depth), call.name);
+        }
+        println!();
+        println!("{} instructions, {} calls", self.instruction_count, self.calls.len() - 1);
+        if !self.active.is_empty() {
+            println!("The function had not returned yet");
+        }
+    }
+}
@@ -0,0 +1,520 @@
+// The grammar for the debugger's commands and expressions. The parser is generated from it by `build.rs`.
+
+// Keywords are matched by private `()` fields, which clippy mistakes for a way to make the structs non-exhaustive.
+#[allow(clippy::manual_non_exhaustive)]
+#[rust_sitter::grammar("command")]
+pub mod grammar {
+    /// One or more commands separated by `;`, which run in order. For example, `breakpoint-add main; continue`.
+    #[rust_sitter::language]
+    pub struct CommandList {
+        #[rust_sitter::repeat(non_empty = true)]
+        #[rust_sitter::delimited(
+            #[rust_sitter::leaf(text = ";")]
+            ()
+        )]
+        pub commands: Vec<CommandExpr>,
+    }
+
+    pub enum CommandExpr {
+        Help(#[rust_sitter::leaf(text = "help")] ()),
+        HelpAlias(#[rust_sitter::leaf(text = "h")] ()),
+        BreakIn(#[rust_sitter::leaf(text = "break")] ()),
+        BreakInAlias(#[rust_sitter::leaf(text = ".break")] ()),
+        Step(#[rust_sitter::leaf(text = "step")] (), Option<Box<EvalExpr>>),
+        StepAlias(#[rust_sitter::leaf(text = "s")] (), Option<Box<EvalExpr>>),
+        Next(#[rust_sitter::leaf(text = "next")] (), Option<Box<EvalExpr>>),
+        NextAlias(#[rust_sitter::leaf(text = "n")] (), Option<Box<EvalExpr>>),
+        StepLine(#[rust_sitter::leaf(text = "step-line")] ()),
+        StepLineAlias(#[rust_sitter::leaf(text = "sl")] ()),
+        NextLine(#[rust_sitter::leaf(text = "next-line")] ()),
+        NextLineAlias(#[rust_sitter::leaf(text = "nl")] ()),
+        Finish(#[rust_sitter::leaf(text = "finish")] ()),
+        FinishAlias(#[rust_sitter::leaf(text = "gu")] ()),
+        BreakOnReturn(#[rust_sitter::leaf(text = "break-on-return")] ()),
+        BreakOnReturnAlias(#[rust_sitter::leaf(text = "breakret")] ()),
+        WatchTrace(#[rust_sitter::leaf(text = "watch-trace")] ()),
+        WatchTraceAlias(#[rust_sitter::leaf(text = "wt")] ()),
+        TraceCalls(#[rust_sitter::leaf(text = "trace-calls")] (), Box<EvalExpr>),
+        TraceCallsAlias(#[rust_sitter::leaf(text = "tracecalls")] (), Box<EvalExpr>),
+        InstructionTrace(#[rust_sitter::leaf(text = "instruction-trace")] (), TraceAction),
+        InstructionTraceAlias(#[rust_sitter::leaf(text = "trace")] (), TraceAction),
+        ExecutionRecord(#[rust_sitter::leaf(text = "execution-record")] (), RecordAction),
+        ExecutionRecordAlias(#[rust_sitter::leaf(text = "record")] (), RecordAction),
+        ExecutionReplay(#[rust_sitter::leaf(text = "execution-replay")] (), Option<ReplayAction>),
+        ExecutionReplayAlias(#[rust_sitter::leaf(text = "replay")] (), Option<ReplayAction>),
+        Continue(#[rust_sitter::leaf(text = "continue")] ()),
+        ContinueAlias(#[rust_sitter::leaf(text = "c")] ()),
+        AddBreakpoint(#[rust_sitter::leaf(text = "breakpoint-add")] (), Box<EvalExpr>, Option<BreakpointCondition>, Option<BreakpointLog>),
+        AddBreakpointAlias(#[rust_sitter::leaf(text = "ba")] (), Box<EvalExpr>, Option<BreakpointCondition>, Option<BreakpointLog>),
+        RemoveBreakpoint(#[rust_sitter::leaf(text = "breakpoint-remove")] (), Box<EvalExpr>),
+        RemoveBreakpointAlias(#[rust_sitter::leaf(text = "br")] (), Box<EvalExpr>),
+        /// Like WinDbg's `bc`.
+        ClearBreakpointAlias(#[rust_sitter::leaf(text = "bc")] (), Box<EvalExpr>),
+        RemoveAllBreakpoints(#[rust_sitter::leaf(text = "breakpoint-remove")] (), #[rust_sitter::leaf(text = "*")] ()),
+        RemoveAllBreakpointsAlias(#[rust_sitter::leaf(text = "br")] (), #[rust_sitter::leaf(text = "*")] ()),
+        ClearAllBreakpointsAlias(#[rust_sitter::leaf(text = "bc")] (), #[rust_sitter::leaf(text = "*")] ()),
+        ListBreakpoint(#[rust_sitter::leaf(text = "breakpoint-list")] ()),
+        ListBreakpointAlias(#[rust_sitter::leaf(text = "bl")] ()),
+        AddHardwareBreakpoint(#[rust_sitter::leaf(text = "hardware-breakpoint-add")] (), Box<EvalExpr>),
+        AddHardwareBreakpointAlias(#[rust_sitter::leaf(text = "hba")] (), Box<EvalExpr>),
+        RemoveHardwareBreakpoint(#[rust_sitter::leaf(text = "hardware-breakpoint-remove")] (), Box<EvalExpr>),
+        RemoveHardwareBreakpointAlias(#[rust_sitter::leaf(text = "hbr")] (), Box<EvalExpr>),
+        AddMemoryWatchpoint(#[rust_sitter::leaf(text = "memory-watch-add")] (), Box<EvalExpr>, Box<EvalExpr>, Option<WatchAccessName>),
+        AddMemoryWatchpointAlias(#[rust_sitter::leaf(text = "mwa")] (), Box<EvalExpr>, Box<EvalExpr>, Option<WatchAccessName>),
+        RemoveMemoryWatchpoint(#[rust_sitter::leaf(text = "memory-watch-remove")] (), Box<EvalExpr>),
+        RemoveMemoryWatchpointAlias(#[rust_sitter::leaf(text = "mwr")] (), Box<EvalExpr>),
+        SwitchThread(#[rust_sitter::leaf(text = "thread")] (), Box<EvalExpr>),
+        SwitchThreadAlias(#[rust_sitter::leaf(text = "~s")] (), Box<EvalExpr>),
+        FreezeThread(#[rust_sitter::leaf(text = "freeze")] (), Box<EvalExpr>),
+        FreezeThreadAlias(#[rust_sitter::leaf(text = "~f")] (), Box<EvalExpr>),
+        ThawThread(#[rust_sitter::leaf(text = "thaw")] (), Box<EvalExpr>),
+        ThawThreadAlias(#[rust_sitter::leaf(text = "~u")] (), Box<EvalExpr>),
+        ListThreads(#[rust_sitter::leaf(text = "threads")] ()),
+        ListThreadsAlias(#[rust_sitter::leaf(text = "~")] ()),
+        SwitchProcess(#[rust_sitter::leaf(text = "process")] (), Box<EvalExpr>),
+        SwitchProcessAlias(#[rust_sitter::leaf(text = "|")] (), Box<EvalExpr>),
+        ListProcesses(#[rust_sitter::leaf(text = "processes")] ()),
+        ListProcessesAlias(#[rust_sitter::leaf(text = "|*")] ()),
+        Stack(#[rust_sitter::leaf(text = "stack")] ()),
+        StackAlias(#[rust_sitter::leaf(text = "k")] ()),
+        StackAll(#[rust_sitter::leaf(text = "stack-all")] ()),
+        StackAllAlias(#[rust_sitter::leaf(text = "~*k")] ()),
+        StackDump(#[rust_sitter::leaf(text = "stack-dump")] ()),
+        StackDumpAlias(#[rust_sitter::leaf(text = "!stackdump")] ()),
+        DisplayTeb(#[rust_sitter::leaf(text = "display-teb")] ()),
+        DisplayTebAlias(#[rust_sitter::leaf(text = "!teb")] ()),
+        DisplayPeb(#[rust_sitter::leaf(text = "display-peb")] ()),
+        DisplayPebAlias(#[rust_sitter::leaf(text = "!peb")] ()),
+        DisplayCommandLine(#[rust_sitter::leaf(text = "command-line")] ()),
+        DisplayCommandLineAlias(#[rust_sitter::leaf(text = "!cmdline")] ()),
+        DisplayEnvironment(#[rust_sitter::leaf(text = "environment")] ()),
+        DisplayEnvironmentAlias(#[rust_sitter::leaf(text = "!env")] ()),
+        MemoryMap(#[rust_sitter::leaf(text = "vmmap")] (), Option<Box<EvalExpr>>),
+        MemoryMapAlias(#[rust_sitter::leaf(text = "!address")] (), Option<Box<EvalExpr>>),
+        Allocate(#[rust_sitter::leaf(text = "allocate")] (), Box<EvalExpr>, Option<ProtectionName>),
+        AllocateAlias(#[rust_sitter::leaf(text = ".alloc")] (), Box<EvalExpr>, Option<ProtectionName>),
+        SetProtection(#[rust_sitter::leaf(text = "set-protection")] (), Box<EvalExpr>, Box<EvalExpr>, ProtectionName),
+        SetProtectionAlias(#[rust_sitter::leaf(text = "!vprot")] (), Box<EvalExpr>, Box<EvalExpr>, ProtectionName),
+        FindReferences(#[rust_sitter::leaf(text = "find-references")] (), Box<EvalExpr>, Option<SearchRange>),
+        FindReferencesAlias(#[rust_sitter::leaf(text = "refs")] (), Box<EvalExpr>, Option<SearchRange>),
+        Handles(#[rust_sitter::leaf(text = "handles")] (), Option<Box<EvalExpr>>),
+        HandlesAlias(#[rust_sitter::leaf(text = "!handle")] (), Option<Box<EvalExpr>>),
+        Heaps(#[rust_sitter::leaf(text = "heaps")] (), Option<Box<EvalExpr>>),
+        HeapsAlias(#[rust_sitter::leaf(text = "!heap")] (), Option<Box<EvalExpr>>),
+        DisplayRegisters(#[rust_sitter::leaf(text = "registers")] (), Option<RegisterSet>),
+        DisplayRegistersAlias(#[rust_sitter::leaf(text = "r")] (), Option<RegisterSet>),
+        SetRegister(#[rust_sitter::leaf(text = "set-register")] (), RegisterAssignment),
+        SetRegisterAlias(#[rust_sitter::leaf(text = "setreg")] (), RegisterAssignment),
+        PromptDisassembly(#[rust_sitter::leaf(text = "prompt-disassembly")] (), Box<EvalExpr>),
+        PromptDisassemblyAlias(#[rust_sitter::leaf(text = "pd")] (), Box<EvalExpr>),
+        Assemble(#[rust_sitter::leaf(text = "assemble")] (), Box<EvalExpr>),
+        AssembleAlias(#[rust_sitter::leaf(text = "a")] (), Box<EvalExpr>),
+        SymbolCache(#[rust_sitter::leaf(text = "symbol-cache")] (), Option<QuotedString>),
+        SymbolCacheAlias(#[rust_sitter::leaf(text = ".symcache")] (), Option<QuotedString>),
+        ReloadSymbols(#[rust_sitter::leaf(text = "reload-symbols")] (), Option<ModuleName>),
+        ReloadSymbolsAlias(#[rust_sitter::leaf(text = ".reload")] (), Option<ModuleName>),
+        ModuleDetails(#[rust_sitter::leaf(text = "module-details")] (), ModuleName),
+        ModuleDetailsAlias(#[rust_sitter::leaf(text = "lmv")] (), ModuleName),
+        TlsCallbacks(#[rust_sitter::leaf(text = "tls-callbacks")] (), ModuleName, Option<TlsCallbacksAction>),
+        TlsCallbacksAlias(#[rust_sitter::leaf(text = "!tls")] (), ModuleName, Option<TlsCallbacksAction>),
+        TraceModule(#[rust_sitter::leaf(text = "trace-module")] (), ModuleName),
+        TraceModuleAlias(#[rust_sitter::leaf(text = "tracemod")] (), ModuleName),
+        DisplayBytes(#[rust_sitter::leaf(text = "display-bytes")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayBytesAlias(#[rust_sitter::leaf(text = "db")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayWords(#[rust_sitter::leaf(text = "display-words")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayWordsAlias(#[rust_sitter::leaf(text = "dw")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayDwords(#[rust_sitter::leaf(text = "display-dwords")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayDwordsAlias(#[rust_sitter::leaf(text = "dd")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayQwords(#[rust_sitter::leaf(text = "display-qwords")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayQwordsAlias(#[rust_sitter::leaf(text = "dq")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayPointers(#[rust_sitter::leaf(text = "display-pointers")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayPointersAlias(#[rust_sitter::leaf(text = "dp")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayPointersWithSymbols(#[rust_sitter::leaf(text = "display-pointers-symbols")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayPointersWithSymbolsAlias(#[rust_sitter::leaf(text = "dps")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayAscii(#[rust_sitter::leaf(text = "display-ascii")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayAsciiAlias(#[rust_sitter::leaf(text = "da")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayUnicode(#[rust_sitter::leaf(text = "display-unicode")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        DisplayUnicodeAlias(#[rust_sitter::leaf(text = "du")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+        Evaluate(#[rust_sitter::leaf(text = "eval")] (), Box<EvalExpr>),
+        EvaluateAlias(#[rust_sitter::leaf(text = "?")] (), Box<EvalExpr>),
+        ListNearest(#[rust_sitter::leaf(text = "list-nearest")] (), Box<EvalExpr>),
+        ListNearestAlias(#[rust_sitter::leaf(text = "ln")] (), Box<EvalExpr>),
+        ExamineSymbols(#[rust_sitter::leaf(text = "examine-symbols")] (), SymbolPattern),
+        ExamineSymbolsAlias(#[rust_sitter::leaf(text = "x")] (), SymbolPattern),
+        DisplayLocals(#[rust_sitter::leaf(text = "locals")] ()),
+        DisplayLocalsAlias(#[rust_sitter::leaf(text = "dv")] ()),
+        DisplayType(#[rust_sitter::leaf(text = "display-type")] (), TypeName, Option<Box<EvalExpr>>),
+        DisplayTypeAlias(#[rust_sitter::leaf(text = "dt")] (), TypeName, Option<Box<EvalExpr>>),
+        BreakAtEntry(#[rust_sitter::leaf(text = "break-at-entry")] ()),
+        BreakAtEntryAlias(#[rust_sitter::leaf(text = ".entry")] ()),
+        DisplayExceptionRecord(#[rust_sitter::leaf(text = "exception-record")] ()),
+        DisplayExceptionRecordAlias(#[rust_sitter::leaf(text = ".exr")] ()),
+        DisplayLastEvent(#[rust_sitter::leaf(text = "last-event")] ()),
+        DisplayLastEventAlias(#[rust_sitter::leaf(text = ".lastevent")] ()),
+        EventHistory(#[rust_sitter::leaf(text = "event-history")] (), Option<Box<EvalExpr>>),
+        EventHistoryAlias(#[rust_sitter::leaf(text = "history")] (), Option<Box<EvalExpr>>),
+        SessionStats(#[rust_sitter::leaf(text = "session-stats")] ()),
+        SessionStatsAlias(#[rust_sitter::leaf(text = ".stats")] ()),
+        LogLevel(#[rust_sitter::leaf(text = "log-level")] (), Option<LogLevelName>),
+        LogLevelAlias(#[rust_sitter::leaf(text = ".loglevel")] (), Option<LogLevelName>),
+        BreakOnException(#[rust_sitter::leaf(text = "exception-break")] (), Box<EvalExpr>),
+        BreakOnExceptionAlias(#[rust_sitter::leaf(text = "sxe")] (), Box<EvalExpr>),
+        BreakOnSecondChanceException(#[rust_sitter::leaf(text = "exception-second-chance")] (), Box<EvalExpr>),
+        BreakOnSecondChanceExceptionAlias(#[rust_sitter::leaf(text = "sxd")] (), Box<EvalExpr>),
+        IgnoreException(#[rust_sitter::leaf(text = "exception-ignore")] (), Box<EvalExpr>),
+        IgnoreExceptionAlias(#[rust_sitter::leaf(text = "sxi")] (), Box<EvalExpr>),
+        BreakOnModuleLoad(#[rust_sitter::leaf(text = "module-load-break")] (), ModulePattern),
+        BreakOnModuleLoadAlias(#[rust_sitter::leaf(text = "sxe")] (), #[rust_sitter::leaf(text = "ld:")] (), ModulePattern),
+        IgnoreModuleLoad(#[rust_sitter::leaf(text = "module-load-ignore")] (), ModulePattern),
+        IgnoreModuleLoadAlias(#[rust_sitter::leaf(text = "sxd")] (), #[rust_sitter::leaf(text = "ld:")] (), ModulePattern),
+        SetEventPolicy(#[rust_sitter::leaf(text = "event-policy")] (), EventPolicyName, DebugEventName),
+        SetEventPolicyAlias(#[rust_sitter::leaf(text = "events")] (), EventPolicyName, DebugEventName),
+        ListExceptionPolicies(#[rust_sitter::leaf(text = "exception-list")] ()),
+        ListExceptionPoliciesAlias(#[rust_sitter::leaf(text = "sx")] ()),
+        DebugOutput(#[rust_sitter::leaf(text = "debug-output")] (), Option<DebugOutputCommand>),
+        DebugOutputAlias(#[rust_sitter::leaf(text = "dbgout")] (), Option<DebugOutputCommand>),
+        RunScript(#[rust_sitter::leaf(text = "script")] (), FilePath),
+        RunScriptAlias(#[rust_sitter::leaf(text = ".script")] (), FilePath),
+        RunRhaiScript(#[rust_sitter::leaf(text = "rhai-run")] (), FilePath),
+        RunRhaiScriptAlias(#[rust_sitter::leaf(text = ".rhai")] (), FilePath),
+        RunPythonScript(#[rust_sitter::leaf(text = "python-run")] (), FilePath),
+        RunPythonScriptAlias(#[rust_sitter::leaf(text = ".python")] (), FilePath),
+        OpenLog(#[rust_sitter::leaf(text = "log-open")] (), FilePath),
+        OpenLogAlias(#[rust_sitter::leaf(text = ".logopen")] (), FilePath),
+        CloseLog(#[rust_sitter::leaf(text = "log-close")] ()),
+        CloseLogAlias(#[rust_sitter::leaf(text = ".logclose")] ()),
+        Quit(#[rust_sitter::leaf(text = "quit")] ()),
+        QuitAlias(#[rust_sitter::leaf(text = "q")] ()),
+    }
+
+    #[rust_sitter::language]
+    pub enum EvalExpr {
+        Number(#[rust_sitter::leaf(pattern = r"(\d+|0x[0-9a-fA-F]+)", transform = parse_int)] u64),
+
+        /// A register of the current thread, e.g. `rsp`.
+        /// This must come before `Symbol` so that register names are lexed as registers rather than symbols.
+        Register(#[rust_sitter::leaf(pattern = r"(rax|rbx|rcx|rdx|rsi|rdi|rip|rsp|rbp|r8|r9|r10|r11|r12|r13|r14|r15|eax|ebx|ecx|edx|esi|edi|eip|esp|ebp|eflags)", transform = parse_symbol)] String),
+
+        Symbol(#[rust_sitter::leaf(pattern = r"(([a-zA-Z0-9_@#.]+!)?[a-zA-Z0-9_@#.]+)", transform = parse_symbol)] String),
+
+        /// e.g. `$ip`.
+        PseudoRegister(#[rust_sitter::leaf(pattern = r"\$[a-zA-Z]+", transform = parse_symbol)] String),
+
+        #[rust_sitter::prec_left(7)]
+        Add(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "+")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(7)]
+        Subtract(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "-")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(8)]
+        Multiply(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "*")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(8)]
+        Divide(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "/")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(8)]
+        Modulo(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "%")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(6)]
+        ShiftLeft(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "<<")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(6)]
+        ShiftRight(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = ">>")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(5)]
+        LessThan(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "<")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(5)]
+        GreaterThan(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = ">")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(4)]
+        Equal(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "==")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(4)]
+        NotEqual(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "!=")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(3)]
+        BitwiseAnd(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "&")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(2)]
+        BitwiseXor(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "^")] (),
+            Box<EvalExpr>,
+        ),
+
+        #[rust_sitter::prec_left(1)]
+        BitwiseOr(
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = "|")] (),
+            Box<EvalExpr>,
+        ),
+
+        Parenthesized(
+            #[rust_sitter::leaf(text = "(")] (),
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = ")")] (),
+        ),
+
+        /// Reads a pointer-sized value from memory, like WinDbg's `poi`.
+        /// There's no unary `*`, because commands that take two expressions, e.g. `display-bytes`, would be ambiguous.
+        Poi(
+            #[rust_sitter::leaf(text = "poi")] (),
+            #[rust_sitter::leaf(text = "(")] (),
+            Box<EvalExpr>,
+            #[rust_sitter::leaf(text = ")")] (),
+        ),
+    }
+
+    pub struct BreakpointCondition {
+        #[rust_sitter::leaf(text = "if")]
+        _if: (),
+        pub condition: Box<EvalExpr>,
+    }
+
+    /// Limits a search to `size` bytes at `start`.
+    pub struct SearchRange {
+        #[rust_sitter::leaf(text = "in")]
+        _in: (),
+        pub start: Box<EvalExpr>,
+        pub size: Box<EvalExpr>,
+    }
+
+    /// Turns a breakpoint into a tracepoint, which logs the message and continues instead of stopping.
+    pub struct BreakpointLog {
+        #[rust_sitter::leaf(text = "log")]
+        _log: (),
+        #[rust_sitter::leaf(pattern = r#""[^"]*""#, transform = parse_quoted_string)]
+        pub message: String,
+    }
+
+    /// A `module!symbol` pattern, where either part can contain `*` and `?` wildcards.
+    pub struct SymbolPattern {
+        #[rust_sitter::leaf(pattern = r"[a-zA-Z0-9_@#.*?]+![a-zA-Z0-9_@#.*?]+", transform = parse_symbol)]
+        pub pattern: String,
+    }
+
+    /// A module name that can contain `*` and `?` wildcards.
+    pub struct ModulePattern {
+        #[rust_sitter::leaf(pattern = r"[a-zA-Z0-9_@#.*?-]+", transform = parse_symbol)]
+        pub pattern: String,
+    }
+
+    /// A `module!type` name, e.g. `ntdll.dll!_PEB`. Type names can contain C++ scopes and template arguments.
+    pub struct TypeName {
+        #[rust_sitter::leaf(pattern = r"[a-zA-Z0-9_@#.]+![a-zA-Z0-9_@#:<>,$]+", transform = parse_symbol)]
+        pub name: String,
+    }
+
+    pub struct ModuleName {
+        #[rust_sitter::leaf(pattern = r"[a-zA-Z0-9_@#.]+", transform = parse_symbol)]
+        pub name: String,
+    }
+
+    /// A page protection, e.g. `readwrite`, `PAGE_EXECUTE_READ|PAGE_GUARD`, or `0x40`.
+    pub struct ProtectionName {
+        #[rust_sitter::leaf(pattern = r"[a-zA-Z0-9_|]+", transform = parse_symbol)]
+        pub name: String,
+    }
+
+    /// A path, which must be quoted if it contains spaces or `;`.
+    pub struct FilePath {
+        #[rust_sitter::leaf(pattern = r#"("[^"]*"|[^\s";]+)"#, transform = parse_path)]
+        pub path: String,
+    }
+
+    pub struct QuotedString {
+        #[rust_sitter::leaf(pattern = r#""[^"]*""#, transform = parse_quoted_string)]
+        pub value: String,
+    }
+
+    pub enum EventPolicyName {
+        Stop(#[rust_sitter::leaf(text = "stop")] ()),
+        Log(#[rust_sitter::leaf(text = "log")] ()),
+        Ignore(#[rust_sitter::leaf(text = "ignore")] ()),
+    }
+
+    pub enum DebugEventName {
+        CreateProcess(#[rust_sitter::leaf(text = "create-process")] ()),
+        InitialBreakpoint(#[rust_sitter::leaf(text = "initial-breakpoint")] ()),
+        CreateThread(#[rust_sitter::leaf(text = "create-thread")] ()),
+        ExitThread(#[rust_sitter::leaf(text = "exit-thread")] ()),
+        LoadModule(#[rust_sitter::leaf(text = "load-module")] ()),
+        UnloadModule(#[rust_sitter::leaf(text = "unload-module")] ()),
+        OutputDebugString(#[rust_sitter::leaf(text = "output-debug-string")] ()),
+    }
+
+    pub enum DebugOutputCommand {
+        Filter(#[rust_sitter::leaf(text = "filter")] (), QuotedString),
+        Suppress(#[rust_sitter::leaf(text = "suppress")] (), QuotedString),
+        Highlight(#[rust_sitter::leaf(text = "highlight")] (), QuotedString),
+        Clear(#[rust_sitter::leaf(text = "clear")] ()),
+        History(#[rust_sitter::leaf(text = "history")] (), Option<Box<EvalExpr>>),
+    }
+
+    /// Registers to display instead of the general-purpose ones.
+    pub enum RegisterSet {
+        Vector(#[rust_sitter::leaf(text = "xmm")] ()),
+        FloatingPoint(#[rust_sitter::leaf(text = "fp")] ()),
+    }
+
+    /// How much of the debugger's internal logging to show, from least to most.
+    pub enum LogLevelName {
+        Off(#[rust_sitter::leaf(text = "off")] ()),
+        Error(#[rust_sitter::leaf(text = "error")] ()),
+        Warn(#[rust_sitter::leaf(text = "warn")] ()),
+        Info(#[rust_sitter::leaf(text = "info")] ()),
+        Debug(#[rust_sitter::leaf(text = "debug")] ()),
+        Trace(#[rust_sitter::leaf(text = "trace")] ()),
+    }
+
+    /// Which accesses a memory watchpoint stops on.
+    pub enum WatchAccessName {
+        Write(#[rust_sitter::leaf(text = "write")] ()),
+        Any(#[rust_sitter::leaf(text = "any")] ()),
+    }
+
+    pub enum TraceAction {
+        /// Optionally records the registers too.
+        Start(#[rust_sitter::leaf(text = "start")] (), FilePath, Option<TraceOption>),
+        Stop(#[rust_sitter::leaf(text = "stop")] ()),
+    }
+
+    pub enum TraceOption {
+        Registers(#[rust_sitter::leaf(text = "registers")] ()),
+    }
+
+    pub enum RecordAction {
+        Start(#[rust_sitter::leaf(text = "start")] ()),
+        Stop(#[rust_sitter::leaf(text = "stop")] ()),
+    }
+
+    /// Moves through a recording, or shows its memory. Without one, the current step is shown.
+    pub enum ReplayAction {
+        Back(#[rust_sitter::leaf(text = "back")] (), Option<Box<EvalExpr>>),
+        Forward(#[rust_sitter::leaf(text = "forward")] (), Option<Box<EvalExpr>>),
+        Goto(#[rust_sitter::leaf(text = "goto")] (), Box<EvalExpr>),
+        /// An address, and optionally how many bytes to show.
+        Memory(#[rust_sitter::leaf(text = "memory")] (), Box<EvalExpr>, Option<Box<EvalExpr>>),
+    }
+
+    /// What to do with a module's TLS callbacks besides listing them.
+    pub enum TlsCallbacksAction {
+        Break(#[rust_sitter::leaf(text = "break")] ()),
+    }
+
+    pub struct RegisterAssignment {
+        #[rust_sitter::leaf(pattern = r"[a-zA-Z][a-zA-Z0-9]*", transform = parse_symbol)]
+        pub register: String,
+        #[rust_sitter::leaf(text = "=")]
+        _equals: (),
+        pub value: Box<EvalExpr>,
+    }
+
+    #[rust_sitter::extra]
+    #[allow(dead_code)]
+    struct Whitespace {
+        #[rust_sitter::leaf(pattern = r"\s")]
+        _whitespace: (),
+    }
+
+    fn parse_int(text: &str) -> u64 {
+        let text = text.trim();
+        if text.starts_with("0x") {
+            let text = text.split_at(2).1;
+            u64::from_str_radix(text, 16).unwrap()
+        } else {
+            text.parse().unwrap()
+        }
+    }
+
+    fn parse_symbol(text: &str) -> String {
+        text.to_owned()
+    }
+
+    fn parse_path(text: &str) -> String {
+        let text = text.trim();
+        text.strip_prefix('"').and_then(|text| text.strip_suffix('"')).unwrap_or(text).to_owned()
+    }
+
+    fn parse_quoted_string(text: &str) -> String {
+        let text = text.trim();
+        text[1..text.len() - 1].to_owned()
+    }
+}
+
+/// Formats an expression the way it would be typed, e.g. to show a breakpoint's condition.
+impl std::fmt::Display for grammar::EvalExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use grammar::EvalExpr;
+        let (x, operator, y) = match self {
+            EvalExpr::Number(x) => return write!(f, "{x:#x}"),
+            EvalExpr::Register(name) | EvalExpr::Symbol(name) | EvalExpr::PseudoRegister(name) => return f.write_str(name),
+            EvalExpr::Parenthesized(_, x, _) => return write!(f, "({x})"),
+            EvalExpr::Poi(_, _, x, _) => return write!(f, "poi({x})"),
+            EvalExpr::Add(x, _, y) => (x, "+", y),
+            EvalExpr::Subtract(x, _, y) => (x, "-", y),
+            EvalExpr::Multiply(x, _, y) => (x, "*", y),
+            EvalExpr::Divide(x, _, y) => (x, "/", y),
+            EvalExpr::Modulo(x, _, y) => (x, "%", y),
+            EvalExpr::ShiftLeft(x, _, y) => (x, "<<", y),
+            EvalExpr::ShiftRight(x, _, y) => (x, ">>", y),
+            EvalExpr::LessThan(x, _, y) => (x, "<", y),
+            EvalExpr::GreaterThan(x, _, y) => (x, ">", y),
+            EvalExpr::Equal(x, _, y) => (x, "==", y),
+            EvalExpr::NotEqual(x, _, y) => (x, "!=", y),
+            EvalExpr::BitwiseAnd(x, _, y) => (x, "&", y),
+            EvalExpr::BitwiseXor(x, _, y) => (x, "^", y),
+            EvalExpr::BitwiseOr(x, _, y) => (x, "|", y),
+        };
+        write!(f, "{x} {operator} {y}")
+    }
+}
@@ -0,0 +1,373 @@
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+use crate::{
+    engine::{self, Debuggee, ExceptionEvent, ThreadState, ThreadStates},
+    memory::MemorySource,
+    name_resolution,
+    process::Process,
+    watchpoints::{WatchAccess, WatchpointHit},
+    windows_wrapper::{
+        self,
+        AutoClosedHandle,
+        DebugContinueStatus,
+        DebugEvent,
+        DebugEventContext,
+        ExceptionRecord,
+        LaunchOptions,
+        ProcessId,
+        ThreadId,
+    },
+};
+
+// An event-driven API for controlling a target from code, e.g. in automation tools or fuzzer triage harnesses, without
+// the interactive prompt. Callbacks registered with `on_breakpoint`, `on_exception`, and `on_module_load` decide whether
+// `continue_execution` keeps running the target or returns so that the caller can inspect it.
+// Breakpoints, stepping past them, and watchpoints are handled by `engine`, like in the command-line debugger.
+// Only the first process is debugged. Events from its child processes are continued without calling the callbacks.
+
+/// What a callback wants to happen after an event.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Keep running the target.
+    Continue,
+    /// Return from `continue_execution`, leaving the target stopped at the event.
+    Stop,
+}
+
+/// Why `continue_execution` returned.
+pub enum StopReason {
+    /// A software or hardware breakpoint.
+    Breakpoint { address: u64 },
+    Watchpoint { hit: WatchpointHit },
+    Exception { first_chance: bool, record: ExceptionRecord },
+    ModuleLoad { name: String, address: u64 },
+    Exited { exit_code: u32 },
+}
+
+type BreakpointCallback = Box<dyn FnMut(&mut Debugger, u64) -> Action>;
+type ExceptionCallback = Box<dyn FnMut(&mut Debugger, &ExceptionRecord, bool) -> Action>;
+type ModuleLoadCallback = Box<dyn FnMut(&mut Debugger, &str, u64) -> Action>;
+
+/// The debug event that the target is stopped at. It is continued by the next `continue_execution`.
+struct StoppedEvent {
+    context: DebugEventContext,
+    continue_status: DebugContinueStatus,
+}
+
+pub struct Debugger {
+    /// The ID of the process being debugged, once its `CreateProcess` event has happened.
+    process_id: Option<ProcessId>,
+    debuggee: Debuggee,
+    thread_states: ThreadStates,
+    /// Without a callback, breakpoints and exceptions stop and module loads continue.
+    on_breakpoint: Option<BreakpointCallback>,
+    on_exception: Option<ExceptionCallback>,
+    on_module_load: Option<ModuleLoadCallback>,
+    stopped_event: Option<StoppedEvent>,
+    exit_code: Option<u32>,
+    /// Kept open until the debugger is dropped. `debuggee` uses it.
+    _process_handle: AutoClosedHandle,
+}
+
+impl Debugger {
+    fn new(process_handle: AutoClosedHandle) -> Debugger {
+        Debugger {
+            process_id: None,
+            // The EXE's address is known once the process is created.
+            debuggee: Debuggee::new(process_handle.handle(), 0),
+            thread_states: ThreadStates::new(),
+            on_breakpoint: None,
+            on_exception: None,
+            on_module_load: None,
+            stopped_event: None,
+            exit_code: None,
+            _process_handle: process_handle,
+        }
+    }
+
+    /// Launches a process for debugging. Nothing runs until the first `continue_execution`. Only the launched process is
+    /// debugged, so `follow_child_processes` isn't supported.
+    pub fn launch(command_line_args: &[String], options: &LaunchOptions) -> Result<Debugger, String> {
+        if options.follow_child_processes {
+            return Err(String::from("Following child processes is not supported"));
+        }
+        let launched = windows_wrapper::launch_process_for_debugging(command_line_args, options)?;
+        if let Some(thread) = &launched.suspended_thread {
+            windows_wrapper::resume_thread(thread).map_err(|e| format!("Could not resume the process: {e}"))?;
+        }
+        Ok(Debugger::new(launched.process))
+    }
+
+    pub fn attach(process_id: ProcessId) -> Result<Debugger, String> {
+        Ok(Debugger::new(windows_wrapper::attach_to_process(process_id)?))
+    }
+
+    pub fn on_breakpoint(&mut self, callback: impl FnMut(&mut Debugger, u64) -> Action + 'static) {
+        self.on_breakpoint = Some(Box::new(callback));
+    }
+
+    /// The callback is also given whether it is the first chance, before the target's exception handlers run.
+    pub fn on_exception(&mut self, callback: impl FnMut(&mut Debugger, &ExceptionRecord, bool) -> Action + 'static) {
+        self.on_exception = Some(Box::new(callback));
+    }
+
+    /// The callback is given the module's name and base address. The EXE counts as a module.
+    pub fn on_module_load(&mut self, callback: impl FnMut(&mut Debugger, &str, u64) -> Action + 'static) {
+        self.on_module_load = Some(Box::new(callback));
+    }
+
+    /// Runs the target until a callback returns `Action::Stop` (or an event without a callback stops), or the process
+    /// exits. Fails if the target can't be run, e.g. because the debugger was detached from it.
+    pub fn continue_execution(&mut self) -> Result<StopReason, String> {
+        loop {
+            if let Some(exit_code) = self.exit_code {
+                return Ok(StopReason::Exited { exit_code });
+            }
+            if let Some(stopped_event) = self.stopped_event.take() {
+                // A thread that is stopped on a breakpoint steps past it first, so that it can be re-armed.
+                if let Some(process_id) = self.process_id {
+                    engine::begin_continue_stopped_threads(&mut self.thread_states, process_id, self.debuggee.process.is_wow64);
+                }
+                // The target may change its memory once it runs.
+                self.debuggee.memory_source.invalidate_cache();
+                windows_wrapper::continue_debug_event(stopped_event.context, stopped_event.continue_status)?;
+            }
+
+            let (context, event) = windows_wrapper::wait_for_debug_event(|process_id| {
+                (Some(process_id) == self.process_id).then_some(self.debuggee.memory_source.as_ref())
+            })?;
+            let is_first_process = match self.process_id {
+                Some(process_id) => context.process == process_id,
+                None => matches!(event, DebugEvent::CreateProcess { .. }),
+            };
+            let continue_status = if !is_first_process && matches!(event, DebugEvent::Exception { .. }) {
+                DebugContinueStatus::ExceptionNotHandled
+            } else {
+                DebugContinueStatus::Continue
+            };
+            let (process_id, thread_id) = (context.process, context.thread);
+            self.stopped_event = Some(StoppedEvent { context, continue_status });
+
+            if !is_first_process {
+                continue;
+            }
+            if let Some(stop_reason) = self.handle_event(process_id, thread_id, event) {
+                return Ok(stop_reason);
+            }
+        }
+    }
+
+    /// Returns a reason to stop, or `None` to keep running.
+    fn handle_event(&mut self, process_id: ProcessId, thread_id: ThreadId, event: DebugEvent) -> Option<StopReason> {
+        match event {
+            DebugEvent::CreateProcess { name, base_addr, thread_start_address, .. } => {
+                self.process_id = Some(process_id);
+                self.debuggee.exe_address = base_addr;
+                self.add_thread(process_id, thread_id, thread_start_address);
+                self.load_module(base_addr, name)
+            }
+            DebugEvent::LoadDll { name, base_addr } => self.load_module(base_addr, name),
+            DebugEvent::UnloadDll { base_addr } => {
+                self.debuggee.process.remove_module(base_addr);
+                None
+            }
+            DebugEvent::CreateThread { start_address } => {
+                self.add_thread(process_id, thread_id, start_address);
+                None
+            }
+            DebugEvent::ExitThread { .. } => {
+                self.debuggee.process.remove_thread(thread_id);
+                self.thread_states.remove(&(process_id, thread_id));
+                None
+            }
+            DebugEvent::ExitProcess { exit_code } => {
+                // Let the process finish exiting, since nothing can be done with it now.
+                if let Some(stopped_event) = self.stopped_event.take() {
+                    if let Err(e) = windows_wrapper::continue_debug_event(stopped_event.context, stopped_event.continue_status) {
+                        print_error!("{e}");
+                    }
+                }
+                self.thread_states.clear();
+                self.exit_code = Some(exit_code);
+                Some(StopReason::Exited { exit_code })
+            }
+            DebugEvent::Exception { first_chance, record } => self.handle_exception(process_id, thread_id, first_chance, record),
+            DebugEvent::OutputDebugString(_) | DebugEvent::Rip { .. } => None,
+        }
+    }
+
+    /// Registers a thread, and programs the hardware breakpoints into it.
+    fn add_thread(&mut self, process_id: ProcessId, thread_id: ThreadId, start_address: u64) {
+        self.debuggee.process.add_thread(thread_id, start_address);
+        let thread = match engine::get_thread_handle(&self.thread_states, process_id, thread_id) {
+            Ok(thread) => thread,
+            Err(e) => {
+                print_error!("Could not open thread {thread_id:#x}: {e}");
+                return;
+            }
+        };
+        self.apply_hardware_breakpoints_to_thread(&thread);
+        self.thread_states.insert((process_id, thread_id), ThreadState::new(thread));
+    }
+
+    fn handle_exception(&mut self, process_id: ProcessId, thread_id: ThreadId, first_chance: bool, record: ExceptionRecord) -> Option<StopReason> {
+        let is_wow64 = self.debuggee.process.is_wow64;
+        let Some(thread_state) = self.thread_states.get_mut(&(process_id, thread_id)) else {
+            print_error!("Exception for unknown thread {thread_id:#x}");
+            return None;
+        };
+        let thread = thread_state.handle.clone();
+        let mut thread_context = match windows_wrapper::get_thread_context(&thread, is_wow64) {
+            Ok(thread_context) => thread_context,
+            Err(e) => {
+                // The exception can't be handled without the thread's registers, so let the target continue.
+                print_error!("Could not handle the exception: {e}");
+                return None;
+            }
+        };
+
+        let exception_event = engine::handle_exception(&record, thread_state, &mut self.debuggee, &mut thread_context.context);
+        engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+        match exception_event {
+            // Nothing here steps except to step past breakpoints, which then continue.
+            ExceptionEvent::Stepped { .. } | ExceptionEvent::TemporaryBreakpoint { .. } | ExceptionEvent::InitialBreakpoint => None,
+            ExceptionEvent::Watchpoint { hit: None } => None,
+            ExceptionEvent::Watchpoint { hit: Some(hit) } => Some(StopReason::Watchpoint { hit }),
+            ExceptionEvent::Breakpoint { address } | ExceptionEvent::HardwareBreakpoint { address } => {
+                let action = match self.on_breakpoint.take() {
+                    Some(mut callback) => {
+                        let action = callback(self, address);
+                        self.on_breakpoint.get_or_insert(callback);
+                        action
+                    }
+                    None => Action::Stop,
+                };
+                (action == Action::Stop).then_some(StopReason::Breakpoint { address })
+            }
+            ExceptionEvent::BreakIn | ExceptionEvent::Other { .. } => {
+                self.set_continue_status(DebugContinueStatus::ExceptionNotHandled);
+                let action = match self.on_exception.take() {
+                    Some(mut callback) => {
+                        let action = callback(self, &record, first_chance);
+                        self.on_exception.get_or_insert(callback);
+                        action
+                    }
+                    None => Action::Stop,
+                };
+                (action == Action::Stop).then_some(StopReason::Exception { first_chance, record })
+            }
+        }
+    }
+
+    fn load_module(&mut self, address: u64, name: Option<String>) -> Option<StopReason> {
+        let name = match self.debuggee.process.add_module(address, name, self.debuggee.memory_source.as_ref()) {
+            Ok(module) => module.name.clone(),
+            Err(e) => {
+                print_error!("Could not load the module at {address:#018x}: {e}");
+                return None;
+            }
+        };
+        self.debuggee.breakpoints.resolve_deferred_breakpoints(&mut self.debuggee.process, self.debuggee.memory_source.as_ref());
+
+        let action = match self.on_module_load.take() {
+            Some(mut callback) => {
+                let action = callback(self, &name, address);
+                self.on_module_load.get_or_insert(callback);
+                action
+            }
+            None => Action::Continue,
+        };
+        (action == Action::Stop).then_some(StopReason::ModuleLoad { name, address })
+    }
+
+    fn set_continue_status(&mut self, continue_status: DebugContinueStatus) {
+        if let Some(stopped_event) = &mut self.stopped_event {
+            stopped_event.continue_status = continue_status;
+        }
+    }
+
+    /// Reads up to `len` bytes, stopping at the first byte that can't be read.
+    pub fn read_memory(&self, address: u64, len: usize) -> Vec<u8> {
+        self.debuggee.memory_source.read_raw_memory(address, len)
+    }
+
+    pub fn write_memory(&self, address: u64, data: &[u8]) -> Result<(), String> {
+        self.debuggee.memory_source.write_raw_memory(address, data)
+    }
+
+    /// Gets the registers of the thread that raised the event that the target is stopped at, or `None` if it isn't
+    /// stopped at an event, e.g. because it exited.
+    pub fn get_context(&self) -> Option<CONTEXT> {
+        let stopped_event = self.stopped_event.as_ref()?;
+        let thread_state = self.thread_states.get(&(stopped_event.context.process, stopped_event.context.thread))?;
+        windows_wrapper::get_thread_context(&thread_state.handle, self.debuggee.process.is_wow64).ok().map(|context| context.context)
+    }
+
+    /// Adds a breakpoint at an address, or at a symbol such as `kernel32!CreateFileW`. Breakpoints on symbols in modules
+    /// that aren't loaded yet are set when the module loads. Returns the address, if it is known yet.
+    pub fn add_breakpoint(&mut self, location: &str) -> Result<Option<u64>, String> {
+        let address = match location.strip_prefix("0x").map(|hex| u64::from_str_radix(hex, 16)) {
+            Some(address) => address.map_err(|e| format!("Invalid address {location}: {e}"))?,
+            None if name_resolution::is_symbol_in_unloaded_module(location, &mut self.debuggee.process) => {
+                self.debuggee.breakpoints.add_deferred_breakpoint(location.to_string(), None, None);
+                return Ok(None);
+            }
+            None => name_resolution::resolve_name_to_address(location, &mut self.debuggee.process)?,
+        };
+        self.debuggee.breakpoints.add_breakpoint(address, Some(location.to_string()), None, None, self.debuggee.memory_source.as_ref())?;
+        Ok(Some(address))
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u64) -> Result<(), String> {
+        self.debuggee.breakpoints.remove_breakpoint(address, self.debuggee.memory_source.as_ref())
+    }
+
+    /// Adds a breakpoint in the debug registers, which doesn't modify the target's code. Only a few can be set.
+    pub fn add_hardware_breakpoint(&mut self, address: u64) -> Result<(), String> {
+        self.debuggee.hardware_breakpoints.add_breakpoint(address)?;
+        self.apply_hardware_breakpoints();
+        Ok(())
+    }
+
+    pub fn remove_hardware_breakpoint(&mut self, address: u64) -> Result<(), String> {
+        self.debuggee.hardware_breakpoints.remove_breakpoint(address)?;
+        self.apply_hardware_breakpoints();
+        Ok(())
+    }
+
+    /// Programs the hardware breakpoints into the debug registers of every thread.
+    fn apply_hardware_breakpoints(&self) {
+        for thread_state in self.thread_states.values() {
+            self.apply_hardware_breakpoints_to_thread(&thread_state.handle);
+        }
+    }
+
+    fn apply_hardware_breakpoints_to_thread(&self, thread: &AutoClosedHandle) {
+        let is_wow64 = self.debuggee.process.is_wow64;
+        let result = windows_wrapper::get_thread_context(thread, is_wow64).and_then(|mut thread_context| {
+            self.debuggee.hardware_breakpoints.apply_to_context(&mut thread_context.context);
+            windows_wrapper::set_thread_context(thread, &thread_context.context, is_wow64)
+        });
+        if let Err(e) = result {
+            print_error!("Could not apply the hardware breakpoints: {e}");
+        }
+    }
+
+    /// Stops when the target accesses `size` bytes at `address`, using guard pages, so there is no limit on the size.
+    pub fn add_watchpoint(&mut self, address: u64, size: u64, access: WatchAccess) -> Result<(), String> {
+        self.debuggee.watchpoints.add_watchpoint(self.debuggee.process_handle, address, size, access)
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u64) -> Result<(), String> {
+        self.debuggee.watchpoints.remove_watchpoint(self.debuggee.process_handle, address)
+    }
+
+    /// The process's modules and threads, e.g. for resolving symbols or walking the stack with `stack::unwind_context`.
+    pub fn process(&mut self) -> &mut Process {
+        &mut self.debuggee.process
+    }
+
+    pub fn memory_source(&self) -> &dyn MemorySource {
+        self.debuggee.memory_source.as_ref()
+    }
+}
@@ -0,0 +1,119 @@
+use iced_x86::{
+    Decoder,
+    DecoderOptions,
+    FlowControl,
+    Formatter,
+    Instruction,
+    InstructionInfoFactory,
+    IntelFormatter,
+    OpAccess,
+    Register,
+};
+
+use crate::{memory::MemorySource, output};
+
+/// The longest possible x86/x64 instruction.
+pub const MAX_INSTRUCTION_LENGTH: usize = 15;
+
+/// Gets the instruction set bitness to decode with: 32 for WOW64 processes, which run x86 code, and 64 otherwise.
+pub fn get_bitness(is_wow64: bool) -> u32 {
+    if is_wow64 { 32 } else { 64 }
+}
+
+/// Decodes the x86/x64 instruction at `address`.
+pub fn decode_instruction(memory_source: &dyn MemorySource, address: u64, bitness: u32) -> Result<Instruction, String> {
+    let bytes = memory_source.read_raw_memory(address, MAX_INSTRUCTION_LENGTH);
+    let mut decoder = Decoder::with_ip(bitness, &bytes, address, DecoderOptions::NONE);
+    let instruction = decoder.decode();
+    if instruction.is_invalid() {
+        Err(format!("Could not decode instruction at {address:#018x}"))
+    } else {
+        Ok(instruction)
+    }
+}
+
+pub fn is_call(instruction: &Instruction) -> bool {
+    matches!(instruction.flow_control(), FlowControl::Call | FlowControl::IndirectCall)
+}
+
+pub fn is_return(instruction: &Instruction) -> bool {
+    instruction.flow_control() == FlowControl::Return
+}
+
+/// Formats `count` instructions starting at `address` as `(address, text)`, where the text has the instruction's bytes
+/// followed by the instruction. Stops early at an instruction that can't be decoded, whose text ends with `??`.
+pub fn format_instructions(memory_source: &dyn MemorySource, address: u64, count: usize, bitness: u32) -> Vec<(u64, String)> {
+    let bytes = memory_source.read_raw_memory(address, count * MAX_INSTRUCTION_LENGTH);
+    let mut decoder = Decoder::with_ip(bitness, &bytes, address, DecoderOptions::NONE);
+    let mut formatter = IntelFormatter::new();
+    let mut instruction = Instruction::default();
+    let mut lines = Vec::with_capacity(count);
+    for _ in 0..count {
+        if !decoder.can_decode() {
+            lines.push((decoder.ip(), String::from("??")));
+            break;
+        }
+        decoder.decode_out(&mut instruction);
+        let offset = (instruction.ip() - address) as usize;
+        let instruction_bytes: String = bytes[offset..offset + instruction.len()].iter().map(|byte| format!("{byte:02x}")).collect();
+        if instruction.is_invalid() {
+            lines.push((instruction.ip(), format!("{instruction_bytes:<20} ??")));
+            break;
+        }
+        let mut text = String::new();
+        formatter.format(&instruction, &mut text);
+        lines.push((instruction.ip(), format!("{instruction_bytes:<20} {text}")));
+    }
+    lines
+}
+
+/// Prints `count` instructions starting at `address`, one per line, with their bytes.
+pub fn display_instructions(memory_source: &dyn MemorySource, address: u64, count: usize, bitness: u32) {
+    for (address, text) in format_instructions(memory_source, address, count, bitness) {
+        println!("{} {text}", output::address(address));
+    }
+}
+
+/// A memory location that an instruction reads or writes.
+pub struct MemoryAccess {
+    pub address: u64,
+    pub size: usize,
+    pub is_write: bool,
+}
+
+/// Gets the memory that `instruction` accesses, given the registers before it executes. `get_register` gets a
+/// general-purpose register by name (e.g. `rax`), and `segment_base` is the base address of the FS or GS segment, i.e.
+/// the TEB. Accesses whose address can't be worked out, e.g. ones that use vector registers, are left out.
+pub fn get_memory_accesses(instruction: &Instruction, get_register: impl Fn(&str) -> Option<u64>, segment_base: u64) -> Vec<MemoryAccess> {
+    let mut factory = InstructionInfoFactory::new();
+    let info = factory.info(instruction);
+    let mut get_register_value = |register: Register, _element_index: usize, _element_size: usize| -> Option<u64> {
+        if register.is_segment_register() {
+            return Some(if matches!(register, Register::FS | Register::GS) { segment_base } else { 0 });
+        }
+        let full_name = format!("{:?}", register.full_register()).to_lowercase();
+        let value = get_register(&full_name)?;
+        Some(match register.size() {
+            1 => value & 0xff,
+            2 => value & 0xffff,
+            4 => value & 0xffff_ffff,
+            _ => value,
+        })
+    };
+    let mut accesses = Vec::new();
+    for used in info.used_memory() {
+        let is_write = match used.access() {
+            OpAccess::Read | OpAccess::CondRead => false,
+            OpAccess::Write | OpAccess::CondWrite | OpAccess::ReadWrite | OpAccess::ReadCondWrite => true,
+            _ => continue,
+        };
+        let size = used.memory_size().size();
+        if size == 0 {
+            continue;
+        }
+        if let Some(address) = used.virtual_address(0, &mut get_register_value) {
+            accesses.push(MemoryAccess { address, size, is_write });
+        }
+    }
+    accesses
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use gimli::{AttributeValue, EndianSlice, LittleEndian};
+
+use crate::module::{LineRecord, LineTable};
+
+// Reads DWARF debug info, which MinGW and the Rust `*-windows-gnu` toolchains embed in the image instead of writing a PDB.
+// The DWARF sections have names longer than 8 characters, e.g. `.debug_info`, so the section table refers to them by their
+// offset in the COFF string table, e.g. `/4`. The string table isn't mapped into memory, so this reads the file on disk.
+// See https://learn.microsoft.com/en-us/windows/win32/debug/pe-format#section-table-section-headers.
+
+const SECTION_HEADER_SIZE: usize = 40;
+const COFF_SYMBOL_SIZE: u64 = 18;
+
+type Reader<'a> = EndianSlice<'a, LittleEndian>;
+
+/// The functions and line table from a module's DWARF info. It is empty if the module has no DWARF info.
+#[derive(Default)]
+pub struct DwarfSymbols {
+    /// The RVAs and names of functions.
+    pub functions: Vec<(u32, String)>,
+    pub line_table: LineTable,
+}
+
+fn read_at(file: &mut File, offset: u64, size: usize) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![0u8; size];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut buffer))
+        .map_err(|e| format!("Could not read {size:#x} bytes at {offset:#x}: {e}"))?;
+    Ok(buffer)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads the DWARF sections from the image file, keyed by name. Returns an empty map if there is no `.debug_info`.
+/// Also returns the image base that the DWARF addresses are relative to.
+fn read_dwarf_sections(file: &mut File) -> Result<(HashMap<String, Vec<u8>>, u64), String> {
+    let dos_header = read_at(file, 0, 0x40)?;
+    let pe_header_offset = read_u32(&dos_header, 0x3c) as u64;
+    // The signature (4 bytes), the file header (20 bytes), and the start of the optional header.
+    let pe_header = read_at(file, pe_header_offset, 4 + 20 + 32)?;
+    if &pe_header[..4] != b"PE\0\0" {
+        return Err(String::from("Not a PE image"));
+    }
+    let section_count = read_u16(&pe_header, 6) as usize;
+    let symbol_table_offset = read_u32(&pe_header, 12) as u64;
+    let symbol_count = read_u32(&pe_header, 16) as u64;
+    let optional_header_size = read_u16(&pe_header, 20) as u64;
+    let image_base = if read_u16(&pe_header, 24) == 0x20b {
+        u64::from_le_bytes(pe_header[24 + 24..24 + 32].try_into().unwrap())
+    } else {
+        read_u32(&pe_header, 24 + 28) as u64
+    };
+
+    let section_table_offset = pe_header_offset + 24 + optional_header_size;
+    let section_table = read_at(file, section_table_offset, section_count * SECTION_HEADER_SIZE)?;
+    // The string table comes right after the symbol table, and starts with its size.
+    let string_table_offset = symbol_table_offset + symbol_count * COFF_SYMBOL_SIZE;
+
+    let mut sections = HashMap::new();
+    for header in section_table.chunks_exact(SECTION_HEADER_SIZE) {
+        let short_name = String::from_utf8_lossy(&header[..8]).trim_end_matches('\0').to_string();
+        let name = match short_name.strip_prefix('/').and_then(|offset| offset.parse::<u64>().ok()) {
+            Some(offset) if symbol_table_offset != 0 => {
+                let bytes = read_at(file, string_table_offset + offset, 64).unwrap_or_default();
+                let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+                String::from_utf8_lossy(&bytes[..end]).to_string()
+            }
+            _ => short_name,
+        };
+        if !name.starts_with(".debug_") {
+            continue;
+        }
+        // The raw size is rounded up to the file alignment, and the virtual size is the actual size.
+        let virtual_size = read_u32(header, 8) as usize;
+        let raw_size = read_u32(header, 16) as usize;
+        let raw_offset = read_u32(header, 20) as u64;
+        let size = if virtual_size != 0 { virtual_size.min(raw_size) } else { raw_size };
+        sections.insert(name, read_at(file, raw_offset, size)?);
+    }
+
+    if !sections.contains_key(".debug_info") {
+        sections.clear();
+    }
+    Ok((sections, image_base))
+}
+
+/// Gets a function's name, which may be on the declaration that it refers to rather than on the definition itself.
+fn get_function_name(
+    dwarf: &gimli::Dwarf<Reader>,
+    unit: &gimli::Unit<Reader>,
+    entry: &gimli::DebuggingInformationEntry<Reader>,
+) -> Result<Option<String>, gimli::Error> {
+    if let Some(name) = entry.attr_value(gimli::DW_AT_name)? {
+        return Ok(Some(dwarf.attr_string(unit, name)?.to_string_lossy().into_owned()));
+    }
+    for reference in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+        if let Some(AttributeValue::UnitRef(offset)) = entry.attr_value(reference)? {
+            let declaration = unit.entry(offset)?;
+            if let Some(name) = declaration.attr_value(gimli::DW_AT_name)? {
+                return Ok(Some(dwarf.attr_string(unit, name)?.to_string_lossy().into_owned()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn read_functions(
+    dwarf: &gimli::Dwarf<Reader>,
+    unit: &gimli::Unit<Reader>,
+    image_base: u64,
+    functions: &mut Vec<(u32, String)>,
+) -> Result<(), gimli::Error> {
+    let mut entries = unit.entries();
+    while let Some((_, entry)) = entries.next_dfs()? {
+        if entry.tag() != gimli::DW_TAG_subprogram {
+            continue;
+        }
+        let address = match entry.attr_value(gimli::DW_AT_low_pc)? {
+            Some(low_pc) => dwarf.attr_address(unit, low_pc)?,
+            None => None,
+        };
+        // Functions that the linker discarded have an address of 0.
+        let rva = match address.and_then(|address| address.checked_sub(image_base)) {
+            Some(rva) => rva as u32,
+            None => continue,
+        };
+        if let Some(name) = get_function_name(dwarf, unit, entry)? {
+            functions.push((rva, name));
+        }
+    }
+    Ok(())
+}
+
+fn read_lines(
+    dwarf: &gimli::Dwarf<Reader>,
+    unit: &gimli::Unit<Reader>,
+    image_base: u64,
+    files: &mut Vec<String>,
+    file_indices: &mut HashMap<String, usize>,
+    records: &mut Vec<LineRecord>,
+) -> Result<(), gimli::Error> {
+    let program = match unit.line_program.clone() {
+        Some(program) => program,
+        None => return Ok(()),
+    };
+    let mut rows = program.rows();
+    // The previous row in the sequence, which extends up to the current row.
+    let mut previous: Option<(u64, usize, u32)> = None;
+    while let Some((header, row)) = rows.next_row()? {
+        if let Some((address, file_index, line)) = previous {
+            if row.address() > address && address >= image_base {
+                records.push(LineRecord {
+                    rva: (address - image_base) as u32,
+                    length: (row.address() - address) as u32,
+                    file_index,
+                    line,
+                });
+            }
+        }
+        if row.end_sequence() {
+            previous = None;
+            continue;
+        }
+
+        let file_name = match row.file(header) {
+            Some(file) => {
+                let name = dwarf.attr_string(unit, file.path_name())?.to_string_lossy().into_owned();
+                match file.directory(header) {
+                    Some(directory) => {
+                        let directory = dwarf.attr_string(unit, directory)?.to_string_lossy().into_owned();
+                        Path::new(&directory).join(name).to_string_lossy().into_owned()
+                    }
+                    None => name,
+                }
+            }
+            None => String::from("<unknown>"),
+        };
+        let file_index = *file_indices.entry(file_name).or_insert_with_key(|file_name| {
+            files.push(file_name.clone());
+            files.len() - 1
+        });
+        let line = row.line().map_or(0, |line| line.get() as u32);
+        previous = Some((row.address(), file_index, line));
+    }
+    Ok(())
+}
+
+/// Reads the functions and line table from the DWARF info in the image file at `path`.
+pub fn read_dwarf_symbols(path: &Path) -> Result<DwarfSymbols, String> {
+    let mut file = File::open(path).map_err(|e| format!("Could not open {}: {e}", path.display()))?;
+    let (sections, image_base) = read_dwarf_sections(&mut file)?;
+    if sections.is_empty() {
+        return Ok(DwarfSymbols::default());
+    }
+
+    let dwarf = gimli::Dwarf::load(|id| -> Result<Reader, gimli::Error> {
+        let data = sections.get(id.name()).map_or(&[][..], |data| data.as_slice());
+        Ok(EndianSlice::new(data, LittleEndian))
+    }).map_err(|e| e.to_string())?;
+
+    let mut functions = Vec::new();
+    let mut files = Vec::new();
+    let mut file_indices = HashMap::new();
+    let mut records = Vec::new();
+    let mut read = || -> Result<(), gimli::Error> {
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            read_functions(&dwarf, &unit, image_base, &mut functions)?;
+            read_lines(&dwarf, &unit, image_base, &mut files, &mut file_indices, &mut records)?;
+        }
+        Ok(())
+    };
+    read().map_err(|e| e.to_string())?;
+
+    functions.sort();
+    Ok(DwarfSymbols { functions, line_table: LineTable::new(files, records) })
+}
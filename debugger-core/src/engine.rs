@@ -0,0 +1,488 @@
+use std::{collections::{HashMap, HashSet}, rc::Rc};
+
+use windows::Win32::{Foundation::HANDLE, System::Diagnostics::Debug::CONTEXT};
+
+use crate::{
+    arch::ThreadContext,
+    breakpoint::{BreakpointManager, HardwareBreakpointManager},
+    call_trace::{CallTrace, NextStep},
+    disassembly,
+    memory::{self, MemorySource},
+    module::{ExportTarget, SourceLine},
+    name_resolution,
+    process::Process,
+    watchpoints::{MemoryWatchpointManager, WatchpointHit},
+    windows_wrapper::{self, AutoClosedHandle, ExceptionRecord, ProcessId, ThreadId},
+};
+
+// The state machine behind breakpoints and stepping, shared by the command-line debugger and `debugger::Debugger`.
+// Breakpoints are disarmed when they are hit, so that the original instruction can execute, and the thread single-steps
+// past them before they are re-armed. Memory watchpoints' guard pages work the same way. Stepping over calls and out of
+// functions runs to temporary breakpoints.
+
+#[derive(Copy, Clone, Debug)]
+pub enum StepKind {
+    /// Step a single instruction, including into calls.
+    Into,
+    /// Step a single instruction, but run until calls return.
+    Over,
+}
+
+pub struct ThreadState {
+    /// Opened once when the thread is created, and closed when it exits. See `get_thread_handle`.
+    pub handle: Rc<AutoClosedHandle>,
+    pub expect_step_exception: bool,
+    /// A breakpoint that was disarmed so that this thread could execute the original instruction.
+    /// It is re-armed once the thread has stepped past it.
+    pub breakpoint_to_rearm: Option<u64>,
+    /// The memory watchpoints' pages that guard page exceptions disarmed so that this thread could access them, e.g. two
+    /// pages for an access that spans both. They are re-armed once the thread has stepped past the access.
+    pub pages_to_rearm: HashSet<u64>,
+    /// Whether to continue without prompting once the breakpoint or page has been re-armed.
+    pub continue_after_rearm: bool,
+    /// How many more steps of a repeated `step` or `next` command to take without prompting.
+    pub steps_remaining: u64,
+    pub step_kind: StepKind,
+    /// The source line that a `step-line` or `next-line` command started on. Stepping repeats until the line changes.
+    pub step_source_line: Option<SourceLine>,
+    /// The calls made so far by a `watch-trace` command, which steps until the function returns.
+    pub call_trace: Option<CallTrace>,
+    /// The registers when the thread last stopped, to highlight the ones that changed.
+    pub previous_context: Option<CONTEXT>,
+}
+
+impl ThreadState {
+    pub fn new(handle: Rc<AutoClosedHandle>) -> Self {
+        ThreadState{
+            handle,
+            expect_step_exception: false,
+            breakpoint_to_rearm: None,
+            pages_to_rearm: HashSet::new(),
+            continue_after_rearm: false,
+            steps_remaining: 0,
+            step_kind: StepKind::Into,
+            step_source_line: None,
+            call_trace: None,
+            previous_context: None,
+        }
+    }
+
+    /// Enables single-stepping, which will throw an EXCEPTION_SINGLE_STEP exception after executing the next instruction.
+    /// The caller must write the context back to the thread.
+    pub fn begin_step(&mut self, context: &mut CONTEXT) {
+        context.enable_single_step();
+        self.expect_step_exception = true;
+        self.continue_after_rearm = false;
+    }
+
+    /// If the thread is stopped on a disarmed breakpoint or watched page, steps past it first so that it can be re-armed,
+    /// and then continues. The caller must write the context back to the thread.
+    pub fn begin_continue(&mut self, context: &mut CONTEXT) {
+        if self.breakpoint_to_rearm.is_some() || !self.pages_to_rearm.is_empty() {
+            context.enable_single_step();
+            self.expect_step_exception = true;
+            self.continue_after_rearm = true;
+        }
+    }
+}
+
+pub type ThreadStates = HashMap<(ProcessId, ThreadId), ThreadState>;
+
+/// A process being debugged.
+pub struct Debuggee {
+    pub process: Process,
+    pub memory_source: Box<dyn MemorySource>,
+    /// Valid until the process exits.
+    pub process_handle: HANDLE,
+    /// Whether the breakpoint that ntdll raises when the process is ready to be debugged has happened.
+    pub initial_breakpoint_done: bool,
+    /// The base address of the process's EXE.
+    pub exe_address: u64,
+    /// The one-shot breakpoint from `add_entry_breakpoint`, until it is hit.
+    pub entry_breakpoint: Option<u64>,
+    /// The software breakpoints, which are written to this process's memory.
+    pub breakpoints: BreakpointManager,
+    /// The hardware breakpoints, which are programmed into the debug registers of this process's threads.
+    pub hardware_breakpoints: HardwareBreakpointManager,
+    pub watchpoints: MemoryWatchpointManager,
+}
+
+impl Debuggee {
+    /// Starts debugging a process, from its `CreateProcess` event.
+    pub fn new(process_handle: HANDLE, exe_address: u64) -> Debuggee {
+        Debuggee {
+            process: Process::new(windows_wrapper::is_wow64_process(process_handle)),
+            memory_source: memory::make_caching_memory_source(memory::make_live_memory_source(process_handle)),
+            process_handle,
+            initial_breakpoint_done: false,
+            exe_address,
+            entry_breakpoint: None,
+            breakpoints: BreakpointManager::new(),
+            hardware_breakpoints: HardwareBreakpointManager::new(),
+            watchpoints: MemoryWatchpointManager::new(),
+        }
+    }
+}
+
+/// Adds a one-shot breakpoint where the program's own code starts: at `main` or `wmain` if the EXE has symbols for them,
+/// and otherwise at the EXE's entry point, which is usually the C runtime's startup code. Returns the address.
+pub fn add_entry_breakpoint(
+    process: &mut Process,
+    exe_address: u64,
+    breakpoints: &mut BreakpointManager,
+    memory_source: &dyn MemorySource,
+) -> Result<u64, String> {
+    let module = process.get_containing_module_mut(exe_address)
+        .ok_or_else(|| format!("No module is loaded at {exe_address:#018x}"))?;
+    let address = ["main", "wmain"].iter()
+        .find_map(|function| match name_resolution::resolve_function_in_module(module, function) {
+            Some(ExportTarget::Rva(address)) => Some(address),
+            _ => None,
+        })
+        .or(module.entry_point)
+        .ok_or_else(|| format!("{} has no entry point", module.name))?;
+    breakpoints.add_temporary_breakpoint(address, memory_source)?;
+    Ok(address)
+}
+
+/// What an exception was, once `handle_exception` has done the debugger's part, e.g. re-arming a breakpoint.
+pub enum ExceptionEvent {
+    /// The thread finished a single step that the debugger started. `resume` is whether it was only stepping past a
+    /// breakpoint or watched page, and should continue.
+    Stepped { resume: bool },
+    /// The thread's resume flag is set, so that the breakpoint doesn't fire again when the instruction executes.
+    HardwareBreakpoint { address: u64 },
+    /// A temporary breakpoint, e.g. from stepping over a call. It has been removed, and the thread rewound to it.
+    TemporaryBreakpoint { address: u64 },
+    /// The breakpoint has been disarmed and the thread rewound to it. To continue, use `ThreadState::begin_continue` to
+    /// step past it, which re-arms it.
+    Breakpoint { address: u64 },
+    /// An access to a page with a memory watchpoint, which has been disarmed. `hit` is `None` if the access was to another
+    /// part of the page, in which case the thread has already been set to step past it and continue.
+    Watchpoint { hit: Option<WatchpointHit> },
+    /// The breakpoint that ntdll raises once the process is initialized (or attached to), to give the debugger a chance
+    /// to set things up before the target's code runs.
+    InitialBreakpoint,
+    /// The breakpoint that `windows_wrapper::request_break_in` raised.
+    BreakIn,
+    /// An exception that the debugger didn't cause. WOW64's own breakpoint and single step codes are mapped to the native
+    /// ones.
+    Other { code: u32 },
+}
+
+/// Handles the debugger's part of an exception in one of the debuggee's threads. `context` is updated in place, and the
+/// caller must write it back to the thread.
+pub fn handle_exception(
+    record: &ExceptionRecord,
+    thread_state: &mut ThreadState,
+    debuggee: &mut Debuggee,
+    context: &mut CONTEXT,
+) -> ExceptionEvent {
+    // 32-bit code in a WOW64 process raises its own single step and breakpoint codes.
+    let code = windows_wrapper::get_native_exception_code(record.code, debuggee.process.is_wow64);
+    let address = record.address;
+    let memory_source = debuggee.memory_source.as_ref();
+
+    // Assume that the first EXCEPTION_SINGLE_STEP exception from a thread after we step (via trap) is from our trap.
+    if thread_state.expect_step_exception && code == windows_wrapper::EXCEPTION_CODE_SINGLE_STEP {
+        thread_state.expect_step_exception = false;
+
+        // The thread has stepped past a breakpoint or a watched page access, so put the breakpoint or guard back.
+        let breakpoint_to_rearm = thread_state.breakpoint_to_rearm.take();
+        if let Some(breakpoint_address) = breakpoint_to_rearm {
+            if let Err(e) = debuggee.breakpoints.rearm_breakpoint(breakpoint_address, memory_source) {
+                print_error!("Could not re-arm breakpoint at {breakpoint_address:#018x}: {e}");
+            }
+        }
+        let pages_to_rearm = std::mem::take(&mut thread_state.pages_to_rearm);
+        for page in pages_to_rearm.iter() {
+            if let Err(e) = debuggee.watchpoints.rearm_page(debuggee.process_handle, *page) {
+                print_error!("Could not re-arm memory watchpoint page at {page:#018x}: {e}");
+            }
+        }
+        let mut resume = false;
+        if breakpoint_to_rearm.is_some() || !pages_to_rearm.is_empty() {
+            resume = thread_state.continue_after_rearm;
+            thread_state.continue_after_rearm = false;
+        }
+        ExceptionEvent::Stepped { resume }
+    } else if let Some(breakpoint_address) = debuggee.hardware_breakpoints.get_hit_breakpoint(context).filter(|_| code == windows_wrapper::EXCEPTION_CODE_SINGLE_STEP) {
+        context.EFlags |= windows_wrapper::RESUME_FLAG;
+        context.Dr6 = 0;
+        ExceptionEvent::HardwareBreakpoint { address: breakpoint_address }
+    } else if code == windows_wrapper::EXCEPTION_CODE_BREAKPOINT && debuggee.breakpoints.is_temporary(address) {
+        if let Err(e) = debuggee.breakpoints.remove_breakpoint(address, memory_source) {
+            print_error!("Could not remove temporary breakpoint at {address:#018x}: {e}");
+        }
+        context.set_instruction_pointer(address);
+        ExceptionEvent::TemporaryBreakpoint { address }
+    } else if code == windows_wrapper::EXCEPTION_CODE_BREAKPOINT && debuggee.breakpoints.contains_breakpoint(address) {
+        // Put back the original instruction and rewind the instruction pointer to before the `int3`, so that the original
+        // instruction executes when the thread continues.
+        debuggee.breakpoints.record_hit(address);
+        match debuggee.breakpoints.disarm_breakpoint(address, memory_source) {
+            Ok(()) => thread_state.breakpoint_to_rearm = Some(address),
+            Err(e) => print_error!("Could not disarm breakpoint at {address:#018x}: {e}"),
+        }
+        context.set_instruction_pointer(address);
+        ExceptionEvent::Breakpoint { address }
+    } else if let Some((page, hit)) = (code == windows_wrapper::EXCEPTION_CODE_GUARD_PAGE).then(|| debuggee.watchpoints.handle_guard_page_exception(record)).flatten() {
+        // The access restarts when the thread continues, so step past it before guarding the page again.
+        thread_state.pages_to_rearm.insert(page);
+        if hit.is_none() {
+            thread_state.begin_continue(context);
+        }
+        ExceptionEvent::Watchpoint { hit }
+    } else if code == windows_wrapper::EXCEPTION_CODE_BREAKPOINT && !debuggee.initial_breakpoint_done {
+        debuggee.initial_breakpoint_done = true;
+        ExceptionEvent::InitialBreakpoint
+    } else if code == windows_wrapper::EXCEPTION_CODE_BREAKPOINT && windows_wrapper::take_break_in_request() {
+        ExceptionEvent::BreakIn
+    } else {
+        ExceptionEvent::Other { code: code.0 as u32 }
+    }
+}
+
+/// Gets a thread's handle, which is kept open while the thread runs so that it isn't reopened for every event. Threads
+/// that haven't been registered yet are opened.
+pub fn get_thread_handle(thread_states: &ThreadStates, process_id: ProcessId, thread_id: ThreadId) -> Result<Rc<AutoClosedHandle>, String> {
+    match thread_states.get(&(process_id, thread_id)) {
+        Some(thread_state) => Ok(thread_state.handle.clone()),
+        None => windows_wrapper::open_thread(&thread_id).map(Rc::new),
+    }
+}
+
+/// Writes a thread's context back to the thread, printing an error if it fails.
+pub fn set_thread_context(thread: &AutoClosedHandle, context: &CONTEXT, is_wow64: bool) {
+    if let Err(e) = windows_wrapper::set_thread_context(thread, context, is_wow64) {
+        print_error!("Could not set the thread's context: {e}");
+    }
+}
+
+/// Makes every thread of the process that is stopped on a disarmed breakpoint or watched page, and that isn't already
+/// stepping, step past it so that it is re-armed. Otherwise, after switching threads, the thread that hit the breakpoint
+/// would run with it disarmed, and no thread would hit it again. Call this before letting the target run.
+pub fn begin_continue_stopped_threads(thread_states: &mut ThreadStates, process_id: ProcessId, is_wow64: bool) {
+    for ((thread_process_id, thread_id), thread_state) in thread_states.iter_mut() {
+        let needs_rearm = thread_state.breakpoint_to_rearm.is_some() || !thread_state.pages_to_rearm.is_empty();
+        if *thread_process_id != process_id || !needs_rearm || thread_state.expect_step_exception {
+            continue;
+        }
+        match windows_wrapper::get_thread_context(&thread_state.handle, is_wow64) {
+            Ok(mut thread_context) => {
+                thread_state.begin_continue(&mut thread_context.context);
+                set_thread_context(&thread_state.handle, &thread_context.context, is_wow64);
+            }
+            Err(e) => print_error!("Could not step thread {thread_id:#x} past its breakpoint: {e}"),
+        }
+    }
+}
+
+/// Programs the hardware breakpoints into the debug registers of every thread in the process. Returns whether every
+/// thread was updated.
+/// The current thread's context is updated in place, because it is cached (and may be written back) while at the prompt.
+pub fn apply_hardware_breakpoints(
+    hardware_breakpoints: &HardwareBreakpointManager,
+    thread_states: &ThreadStates,
+    process_id: ProcessId,
+    process: &Process,
+    current_thread_id: ThreadId,
+    current_thread: &AutoClosedHandle,
+    current_context: &mut CONTEXT,
+) -> bool {
+    let mut all_applied = true;
+    for thread_id in process.iterate_threads() {
+        let result = if *thread_id == current_thread_id {
+            hardware_breakpoints.apply_to_context(current_context);
+            windows_wrapper::set_thread_context(current_thread, current_context, process.is_wow64)
+        } else {
+            apply_hardware_breakpoints_to_thread(hardware_breakpoints, thread_states, process_id, process, *thread_id)
+        };
+        if let Err(e) = result {
+            print_error!("Could not apply the hardware breakpoints to thread {thread_id:#x}: {e}");
+            all_applied = false;
+        }
+    }
+    all_applied
+}
+
+/// Like `apply_hardware_breakpoints`, for a process that has no current thread, e.g. because another process is current.
+pub fn apply_hardware_breakpoints_to_process(
+    hardware_breakpoints: &HardwareBreakpointManager,
+    thread_states: &ThreadStates,
+    process_id: ProcessId,
+    process: &Process,
+) -> bool {
+    let mut all_applied = true;
+    for thread_id in process.iterate_threads() {
+        if let Err(e) = apply_hardware_breakpoints_to_thread(hardware_breakpoints, thread_states, process_id, process, *thread_id) {
+            print_error!("Could not apply the hardware breakpoints to thread {thread_id:#x}: {e}");
+            all_applied = false;
+        }
+    }
+    all_applied
+}
+
+fn apply_hardware_breakpoints_to_thread(
+    hardware_breakpoints: &HardwareBreakpointManager,
+    thread_states: &ThreadStates,
+    process_id: ProcessId,
+    process: &Process,
+    thread_id: ThreadId,
+) -> Result<(), String> {
+    let thread = get_thread_handle(thread_states, process_id, thread_id)?;
+    let mut thread_context = windows_wrapper::get_thread_context(&thread, process.is_wow64)?;
+    hardware_breakpoints.apply_to_context(&mut thread_context.context);
+    windows_wrapper::set_thread_context(&thread, &thread_context.context, process.is_wow64)
+}
+
+/// Begins a `step` or `next` command. The caller must write the context back to the thread.
+pub fn begin_step_command(
+    step_kind: StepKind,
+    thread_state: &mut ThreadState,
+    is_wow64: bool,
+    context: &mut CONTEXT,
+    breakpoints: &mut BreakpointManager,
+    memory_source: &dyn MemorySource,
+) -> Result<(), String> {
+    match step_kind {
+        StepKind::Into => thread_state.begin_step(context),
+        StepKind::Over => {
+            let instruction = disassembly::decode_instruction(memory_source, context.instruction_pointer(), disassembly::get_bitness(is_wow64))?;
+            if disassembly::is_call(&instruction) {
+                // Step over the call by running until it returns.
+                let return_address = instruction.next_ip();
+                if !breakpoints.contains_breakpoint(return_address) {
+                    breakpoints.add_temporary_breakpoint(return_address, memory_source)
+                        .map_err(|e| format!("Could not set a breakpoint after the call: {e}"))?;
+                }
+                thread_state.begin_continue(context);
+            } else {
+                thread_state.begin_step(context);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs a `step` or `next` command, which repeats `count` times without prompting in between.
+/// Returns whether the thread should continue executing.
+#[allow(clippy::too_many_arguments)]
+pub fn run_step_command(
+    step_kind: StepKind,
+    count: u64,
+    thread_state: &mut ThreadState,
+    thread: &AutoClosedHandle,
+    is_wow64: bool,
+    context: &mut CONTEXT,
+    breakpoints: &mut BreakpointManager,
+    memory_source: &dyn MemorySource,
+) -> bool {
+    if count == 0 {
+        println!("The step count must be at least 1");
+        return false;
+    }
+
+    match begin_step_command(step_kind, thread_state, is_wow64, context, breakpoints, memory_source)
+        .and_then(|()| windows_wrapper::set_thread_context(thread, context, is_wow64))
+    {
+        Ok(()) => {
+            thread_state.steps_remaining = count - 1;
+            thread_state.step_kind = step_kind;
+            true
+        }
+        Err(e) => {
+            print_error!("Cannot step: {e}");
+            false
+        }
+    }
+}
+
+/// Repeats a `step` or `next` command that was given a count, or that is stepping by source line, or keeps tracing with
+/// `watch-trace`, after the thread stopped. `step_completed` is whether a step that the user asked for completed, and
+/// `resuming` is whether the thread continues anyway, e.g. after a breakpoint that only logs. Anything else that stops
+/// the thread (e.g. a breakpoint) cancels the remaining steps.
+/// Returns whether the thread should keep running, in which case the caller must write the context back to the thread.
+#[allow(clippy::too_many_arguments)]
+pub fn continue_step_command(
+    thread_state: &mut ThreadState,
+    step_completed: bool,
+    resuming: bool,
+    is_wow64: bool,
+    context: &mut CONTEXT,
+    process: &mut Process,
+    breakpoints: &mut BreakpointManager,
+    memory_source: &dyn MemorySource,
+) -> bool {
+    let mut keep_running = false;
+    let mut repeat_step = false;
+    if let Some(call_trace) = thread_state.call_trace.as_mut() {
+        let bitness = disassembly::get_bitness(is_wow64);
+        let next_step = if step_completed {
+            call_trace.record_step(context.instruction_pointer(), context.stack_pointer(), process, memory_source, bitness)
+        } else if resuming {
+            // Something else happened while the call ran, which didn't stop it.
+            NextStep::Step
+        } else {
+            NextStep::Done
+        };
+        match next_step {
+            NextStep::Step => repeat_step = step_completed,
+            NextStep::RunTo(return_address) => {
+                // Run the call, which is too deep to trace, until it returns.
+                let result = if breakpoints.contains_breakpoint(return_address) {
+                    Ok(())
+                } else {
+                    breakpoints.add_temporary_breakpoint(return_address, memory_source)
+                };
+                match result {
+                    Ok(()) => {
+                        thread_state.begin_continue(context);
+                        keep_running = true;
+                    }
+                    Err(e) => {
+                        print_error!("Could not set a breakpoint where the call returns: {e}");
+                        call_trace.display();
+                        thread_state.call_trace = None;
+                    }
+                }
+            }
+            NextStep::Done => {
+                if let Some(return_address) = call_trace.running_return_address().filter(|address| breakpoints.is_temporary(*address)) {
+                    if let Err(e) = breakpoints.remove_breakpoint(return_address, memory_source) {
+                        print_error!("Could not remove temporary breakpoint at {return_address:#018x}: {e}");
+                    }
+                }
+                call_trace.display();
+                thread_state.call_trace = None;
+            }
+        }
+    } else if step_completed {
+        if thread_state.steps_remaining > 0 {
+            thread_state.steps_remaining -= 1;
+            repeat_step = true;
+        } else if let Some(start_line) = &thread_state.step_source_line {
+            let current_line = name_resolution::resolve_address_to_line(context.instruction_pointer(), process);
+            repeat_step = current_line.as_ref() == Some(start_line);
+        }
+    }
+
+    if repeat_step {
+        let step_kind = thread_state.step_kind;
+        match begin_step_command(step_kind, thread_state, is_wow64, context, breakpoints, memory_source) {
+            Ok(()) => keep_running = true,
+            Err(e) => {
+                print_error!("Cannot step: {e}");
+                thread_state.steps_remaining = 0;
+                thread_state.step_source_line = None;
+                if let Some(call_trace) = thread_state.call_trace.take() {
+                    call_trace.display();
+                }
+            }
+        }
+    } else if !resuming && !keep_running {
+        thread_state.steps_remaining = 0;
+        thread_state.step_source_line = None;
+    }
+    keep_running
+}
@@ -0,0 +1,184 @@
+use crate::memory::{self, MemorySource};
+
+// Offsets of fields in the x64 thread and process environment blocks (TEB and PEB), and the structures they point to.
+// These are documented (partially) at https://learn.microsoft.com/en-us/windows/win32/api/winternl/ and are stable in practice.
+
+const TEB_STACK_BASE: u64 = 0x08;
+const TEB_STACK_LIMIT: u64 = 0x10;
+const TEB_SELF: u64 = 0x30;
+const TEB_CLIENT_ID_PROCESS: u64 = 0x40;
+const TEB_CLIENT_ID_THREAD: u64 = 0x48;
+const TEB_THREAD_LOCAL_STORAGE_POINTER: u64 = 0x58;
+const TEB_PROCESS_ENVIRONMENT_BLOCK: u64 = 0x60;
+const TEB_LAST_ERROR_VALUE: u64 = 0x68;
+
+/// A WOW64 thread also has a 32-bit TEB, for its 32-bit stack, which follows its x64 TEB. Its FS segment points to it.
+pub const WOW64_TEB32_OFFSET: u64 = 0x2000;
+const TEB32_STACK_BASE: u64 = 0x04;
+const TEB32_STACK_LIMIT: u64 = 0x08;
+
+const PEB_BEING_DEBUGGED: u64 = 0x02;
+const PEB_IMAGE_BASE_ADDRESS: u64 = 0x10;
+const PEB_LDR: u64 = 0x18;
+const PEB_PROCESS_PARAMETERS: u64 = 0x20;
+const PEB_PROCESS_HEAP: u64 = 0x30;
+const PEB_NT_GLOBAL_FLAG: u64 = 0xBC;
+const PEB_NUMBER_OF_HEAPS: u64 = 0xE8;
+const PEB_PROCESS_HEAPS: u64 = 0xF0;
+
+const PROCESS_PARAMETERS_CURRENT_DIRECTORY: u64 = 0x38;
+const PROCESS_PARAMETERS_IMAGE_PATH_NAME: u64 = 0x60;
+const PROCESS_PARAMETERS_COMMAND_LINE: u64 = 0x70;
+const PROCESS_PARAMETERS_ENVIRONMENT: u64 = 0x80;
+const PROCESS_PARAMETERS_ENVIRONMENT_SIZE: u64 = 0x3F0;
+
+const LDR_IN_LOAD_ORDER_MODULE_LIST: u64 = 0x10;
+const LDR_ENTRY_DLL_BASE: u64 = 0x30;
+const LDR_ENTRY_BASE_DLL_NAME: u64 = 0x58;
+
+/// Stop walking the loader's module list after this many entries, in case it is corrupt.
+const MAX_LOADER_ENTRIES: usize = 4096;
+/// The environment block has no size limit (only each variable is limited to 32767 characters), but don't read more than
+/// this, in case the size is corrupt.
+const MAX_ENVIRONMENT_SIZE: u64 = 0x100000;
+
+pub(crate) fn read_u64(memory_source: &dyn MemorySource, address: u64) -> Result<u64, String> {
+    memory::read_memory_full_array::<u64>(memory_source, address, 1)
+        .map(|data| data[0])
+        .map_err(|_| format!("Could not read memory at {address:#018x}"))
+}
+
+pub(crate) fn read_u32(memory_source: &dyn MemorySource, address: u64) -> Result<u32, String> {
+    memory::read_memory_full_array::<u32>(memory_source, address, 1)
+        .map(|data| data[0])
+        .map_err(|_| format!("Could not read memory at {address:#018x}"))
+}
+
+fn read_u8(memory_source: &dyn MemorySource, address: u64) -> Result<u8, String> {
+    memory::read_memory_full_array::<u8>(memory_source, address, 1)
+        .map(|data| data[0])
+        .map_err(|_| format!("Could not read memory at {address:#018x}"))
+}
+
+/// Reads a `UNICODE_STRING`, which is a length in bytes followed by a pointer to a (not necessarily null-terminated) buffer.
+fn read_unicode_string(memory_source: &dyn MemorySource, address: u64) -> Result<String, String> {
+    let length_in_bytes = memory::read_memory_full_array::<u16>(memory_source, address, 1)
+        .map_err(|_| format!("Could not read string at {address:#018x}"))?[0];
+    let buffer_address = read_u64(memory_source, address + 8)?;
+    let characters = memory::read_memory_full_array::<u16>(memory_source, buffer_address, length_in_bytes as usize / 2)
+        .map_err(|_| format!("Could not read string buffer at {buffer_address:#018x}"))?;
+    Ok(String::from_utf16_lossy(&characters))
+}
+
+/// Prints the key fields of the thread environment block (TEB) at `teb_address`.
+pub fn display_teb(teb_address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+    println!("TEB at {teb_address:#018x}");
+    println!("    StackBase:          {:#018x}", read_u64(memory_source, teb_address + TEB_STACK_BASE)?);
+    println!("    StackLimit:         {:#018x}", read_u64(memory_source, teb_address + TEB_STACK_LIMIT)?);
+    println!("    Self:               {:#018x}", read_u64(memory_source, teb_address + TEB_SELF)?);
+    println!("    ClientId:           {:#x} . {:#x}", read_u64(memory_source, teb_address + TEB_CLIENT_ID_PROCESS)?, read_u64(memory_source, teb_address + TEB_CLIENT_ID_THREAD)?);
+    println!("    TLS Storage:        {:#018x}", read_u64(memory_source, teb_address + TEB_THREAD_LOCAL_STORAGE_POINTER)?);
+    println!("    PEB Address:        {:#018x}", read_u64(memory_source, teb_address + TEB_PROCESS_ENVIRONMENT_BLOCK)?);
+    println!("    LastErrorValue:     {}", read_u32(memory_source, teb_address + TEB_LAST_ERROR_VALUE)?);
+    Ok(())
+}
+
+/// Gets the thread's stack limit (the lowest committed address) and base (the end of the stack).
+pub fn get_stack_limits(teb_address: u64, memory_source: &dyn MemorySource) -> Result<(u64, u64), String> {
+    let stack_limit = read_u64(memory_source, teb_address + TEB_STACK_LIMIT)?;
+    let stack_base = read_u64(memory_source, teb_address + TEB_STACK_BASE)?;
+    Ok((stack_limit, stack_base))
+}
+
+/// Gets a WOW64 thread's 32-bit stack limit and base, like `get_stack_limits`, from its x64 TEB.
+pub fn get_wow64_stack_limits(teb_address: u64, memory_source: &dyn MemorySource) -> Result<(u64, u64), String> {
+    let teb32_address = teb_address + WOW64_TEB32_OFFSET;
+    let stack_limit = read_u32(memory_source, teb32_address + TEB32_STACK_LIMIT)?;
+    let stack_base = read_u32(memory_source, teb32_address + TEB32_STACK_BASE)?;
+    Ok((stack_limit as u64, stack_base as u64))
+}
+
+/// Gets the addresses of the process's heaps. Each heap's address is also the start of its first segment.
+pub fn get_process_heaps(peb_address: u64, memory_source: &dyn MemorySource) -> Result<Vec<u64>, String> {
+    let heap_count = read_u32(memory_source, peb_address + PEB_NUMBER_OF_HEAPS)?;
+    let heaps_address = read_u64(memory_source, peb_address + PEB_PROCESS_HEAPS)?;
+    memory::read_memory_full_array::<u64>(memory_source, heaps_address, heap_count as usize)
+        .map_err(|_| format!("Could not read the heap list at {heaps_address:#018x}"))
+}
+
+/// Gets the address of the process's default heap, which `GetProcessHeap` returns.
+pub fn get_process_heap(peb_address: u64, memory_source: &dyn MemorySource) -> Result<u64, String> {
+    read_u64(memory_source, peb_address + PEB_PROCESS_HEAP)
+}
+
+/// Gets the address of the process environment block (PEB) from a thread environment block (TEB) in the process.
+pub fn get_peb_address(teb_address: u64, memory_source: &dyn MemorySource) -> Result<u64, String> {
+    read_u64(memory_source, teb_address + TEB_PROCESS_ENVIRONMENT_BLOCK)
+}
+
+/// Prints the command line that the process was started with, and its current directory.
+pub fn display_command_line(peb_address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+    let process_parameters = read_u64(memory_source, peb_address + PEB_PROCESS_PARAMETERS)?;
+    println!("CommandLine:      {}", read_unicode_string(memory_source, process_parameters + PROCESS_PARAMETERS_COMMAND_LINE)?);
+    println!("CurrentDirectory: {}", read_unicode_string(memory_source, process_parameters + PROCESS_PARAMETERS_CURRENT_DIRECTORY)?);
+    Ok(())
+}
+
+/// Prints the process's environment variables, one `NAME=value` per line.
+pub fn display_environment(peb_address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+    let process_parameters = read_u64(memory_source, peb_address + PEB_PROCESS_PARAMETERS)?;
+    let environment = read_u64(memory_source, process_parameters + PROCESS_PARAMETERS_ENVIRONMENT)?;
+    let full_size = read_u64(memory_source, process_parameters + PROCESS_PARAMETERS_ENVIRONMENT_SIZE)?;
+    let size = full_size.min(MAX_ENVIRONMENT_SIZE);
+    // The block is a list of null-terminated strings, ending with an empty one. Stop there, even if it isn't full.
+    let characters = memory::read_memory_array::<u16>(memory_source, environment, size as usize / 2);
+    if characters.is_empty() {
+        return Err(format!("Could not read the environment at {environment:#018x}"));
+    }
+    let mut terminated = false;
+    for variable in characters.split(|&c| c == 0) {
+        if variable.is_empty() {
+            terminated = true;
+            break;
+        }
+        println!("{}", String::from_utf16_lossy(variable));
+    }
+    if !terminated {
+        println!("The environment was truncated after {:#x} of its {full_size:#x} bytes", characters.len() * 2);
+    }
+    Ok(())
+}
+
+/// Prints the key fields of the process environment block (PEB) at `peb_address`, including the loaded modules.
+pub fn display_peb(peb_address: u64, memory_source: &dyn MemorySource) -> Result<(), String> {
+    println!("PEB at {peb_address:#018x}");
+    println!("    BeingDebugged:      {}", read_u8(memory_source, peb_address + PEB_BEING_DEBUGGED)? != 0);
+    println!("    ImageBaseAddress:   {:#018x}", read_u64(memory_source, peb_address + PEB_IMAGE_BASE_ADDRESS)?);
+    println!("    ProcessHeap:        {:#018x}", read_u64(memory_source, peb_address + PEB_PROCESS_HEAP)?);
+    println!("    NtGlobalFlag:       {:#x}", read_u32(memory_source, peb_address + PEB_NT_GLOBAL_FLAG)?);
+
+    let process_parameters = read_u64(memory_source, peb_address + PEB_PROCESS_PARAMETERS)?;
+    println!("    ProcessParameters:  {process_parameters:#018x}");
+    println!("        CurrentDirectory: {}", read_unicode_string(memory_source, process_parameters + PROCESS_PARAMETERS_CURRENT_DIRECTORY)?);
+    println!("        ImagePathName:    {}", read_unicode_string(memory_source, process_parameters + PROCESS_PARAMETERS_IMAGE_PATH_NAME)?);
+    println!("        CommandLine:      {}", read_unicode_string(memory_source, process_parameters + PROCESS_PARAMETERS_COMMAND_LINE)?);
+
+    let ldr = read_u64(memory_source, peb_address + PEB_LDR)?;
+    println!("    Ldr:                {ldr:#018x}");
+    println!("        InLoadOrderModuleList:");
+
+    // The list is circular, with the head in the loader data. The links are the first field of each entry.
+    let list_head = ldr + LDR_IN_LOAD_ORDER_MODULE_LIST;
+    let mut entry = read_u64(memory_source, list_head)?;
+    for _ in 0..MAX_LOADER_ENTRIES {
+        if entry == list_head {
+            return Ok(());
+        }
+        let dll_base = read_u64(memory_source, entry + LDR_ENTRY_DLL_BASE)?;
+        let dll_name = read_unicode_string(memory_source, entry + LDR_ENTRY_BASE_DLL_NAME)?;
+        println!("            {dll_base:#018x} {dll_name}");
+        entry = read_u64(memory_source, entry)?;
+    }
+    println!("            Stopping after {MAX_LOADER_ENTRIES} entries");
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+use crate::{
+    arch::ThreadContext,
+    command::grammar::EvalExpr,
+    environment_blocks,
+    memory::{self, MemorySource},
+    name_resolution::resolve_name_to_address,
+    process::Process,
+    stack,
+};
+
+pub struct EvalContext<'a> {
+    pub process: &'a mut Process,
+    /// The registers of the current thread.
+    pub register_context: &'a CONTEXT,
+    pub memory_source: &'a dyn MemorySource,
+    /// The address of the current thread's environment block (TEB), if known.
+    pub teb_address: Option<u64>,
+    /// The address of the last exception raised by the target, if any.
+    pub last_exception_address: Option<u64>,
+}
+
+pub fn evaluate_expression(expr: &EvalExpr, context: &mut EvalContext) -> Result<u64, String> {
+    match expr {
+        EvalExpr::Number(x) => Ok(*x),
+        EvalExpr::Add(x, _, y) => Ok(evaluate_expression(x, context)?.wrapping_add(evaluate_expression(y, context)?)),
+        EvalExpr::Subtract(x, _, y) => Ok(evaluate_expression(x, context)?.wrapping_sub(evaluate_expression(y, context)?)),
+        EvalExpr::Multiply(x, _, y) => Ok(evaluate_expression(x, context)?.wrapping_mul(evaluate_expression(y, context)?)),
+        EvalExpr::Divide(x, _, y) => {
+            let (x, y) = (evaluate_expression(x, context)?, evaluate_expression(y, context)?);
+            x.checked_div(y).ok_or_else(|| String::from("Division by zero"))
+        }
+        EvalExpr::Modulo(x, _, y) => {
+            let (x, y) = (evaluate_expression(x, context)?, evaluate_expression(y, context)?);
+            x.checked_rem(y).ok_or_else(|| String::from("Division by zero"))
+        }
+        EvalExpr::ShiftLeft(x, _, y) => {
+            let (x, y) = (evaluate_expression(x, context)?, evaluate_expression(y, context)?);
+            Ok(x.checked_shl(y.try_into().unwrap_or(u32::MAX)).unwrap_or(0))
+        }
+        EvalExpr::ShiftRight(x, _, y) => {
+            let (x, y) = (evaluate_expression(x, context)?, evaluate_expression(y, context)?);
+            Ok(x.checked_shr(y.try_into().unwrap_or(u32::MAX)).unwrap_or(0))
+        }
+        EvalExpr::LessThan(x, _, y) => Ok((evaluate_expression(x, context)? < evaluate_expression(y, context)?) as u64),
+        EvalExpr::GreaterThan(x, _, y) => Ok((evaluate_expression(x, context)? > evaluate_expression(y, context)?) as u64),
+        EvalExpr::Equal(x, _, y) => Ok((evaluate_expression(x, context)? == evaluate_expression(y, context)?) as u64),
+        EvalExpr::NotEqual(x, _, y) => Ok((evaluate_expression(x, context)? != evaluate_expression(y, context)?) as u64),
+        EvalExpr::BitwiseAnd(x, _, y) => Ok(evaluate_expression(x, context)? & evaluate_expression(y, context)?),
+        EvalExpr::BitwiseXor(x, _, y) => Ok(evaluate_expression(x, context)? ^ evaluate_expression(y, context)?),
+        EvalExpr::BitwiseOr(x, _, y) => Ok(evaluate_expression(x, context)? | evaluate_expression(y, context)?),
+        EvalExpr::Parenthesized(_, x, _) => evaluate_expression(x, context),
+        EvalExpr::Poi(_, _, x, _) => {
+            let address = evaluate_expression(x, context)?;
+            read_pointer(address, context)
+        }
+        EvalExpr::Symbol(symbol) => {
+            resolve_name_to_address(symbol, context.process)
+        }
+        EvalExpr::Register(name) => {
+            context.register_context.get_register(name).ok_or_else(|| format!("Unknown register {name}"))
+        }
+        EvalExpr::PseudoRegister(name) => evaluate_pseudo_register(name, context),
+    }
+}
+
+/// Reads a pointer-sized value from the target's memory.
+fn read_pointer(address: u64, context: &EvalContext) -> Result<u64, String> {
+    let pointer_size = context.process.pointer_size();
+    let bytes = memory::read_memory_full_array::<u8>(context.memory_source, address, pointer_size)
+        .map_err(|_| format!("Could not read memory at {address:#018x}"))?;
+    Ok(bytes.iter().rev().fold(0u64, |value, &byte| (value << 8) | byte as u64))
+}
+
+fn evaluate_pseudo_register(name: &str, context: &mut EvalContext) -> Result<u64, String> {
+    match name {
+        "$ip" => Ok(context.register_context.instruction_pointer()),
+        "$sp" => Ok(context.register_context.stack_pointer()),
+        "$retaddr" => stack::unwind_context(context.register_context, context.process, context.memory_source)
+            .map(|caller_context| caller_context.instruction_pointer()),
+        "$exr" => context.last_exception_address.ok_or_else(|| String::from("No exception has been raised")),
+        "$peb" => {
+            let teb_address = context.teb_address.ok_or_else(|| String::from("The thread environment block is not known"))?;
+            environment_blocks::get_peb_address(teb_address, context.memory_source)
+        }
+        "$alloc" => context.process.last_allocation.ok_or_else(|| String::from("No memory has been allocated with `allocate`")),
+        _ => Err(format!("Unknown pseudo-register {name}")),
+    }
+}
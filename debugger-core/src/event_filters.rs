@@ -0,0 +1,182 @@
+use crate::{exceptions, name_resolution};
+
+/// What to do when the target raises an exception. Like WinDbg's `sxe`, `sxd`, and `sxi`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionPolicy {
+    /// Stop on the first chance, before the target's exception handlers run.
+    Break,
+    /// Only stop on the second chance, when the target didn't handle the exception.
+    SecondChance,
+    /// Never stop. The exception is still passed to the target.
+    Ignore,
+}
+
+impl std::fmt::Display for ExceptionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExceptionPolicy::Break => write!(f, "break on first chance"),
+            ExceptionPolicy::SecondChance => write!(f, "break on second chance"),
+            ExceptionPolicy::Ignore => write!(f, "ignore"),
+        }
+    }
+}
+
+/// What to do when a debug event other than an exception happens.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EventPolicy {
+    /// Print the event and stop at the prompt.
+    Stop,
+    /// Print the event and continue.
+    Log,
+    /// Continue without printing anything.
+    Ignore,
+}
+
+impl std::fmt::Display for EventPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventPolicy::Stop => write!(f, "stop"),
+            EventPolicy::Log => write!(f, "log"),
+            EventPolicy::Ignore => write!(f, "ignore"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    CreateProcess,
+    /// The breakpoint that ntdll raises once a process is initialized or attached to.
+    InitialBreakpoint,
+    CreateThread,
+    ExitThread,
+    LoadModule,
+    UnloadModule,
+    OutputDebugString,
+}
+
+impl EventKind {
+    const ALL: [EventKind; 7] = [
+        EventKind::CreateProcess,
+        EventKind::InitialBreakpoint,
+        EventKind::CreateThread,
+        EventKind::ExitThread,
+        EventKind::LoadModule,
+        EventKind::UnloadModule,
+        EventKind::OutputDebugString,
+    ];
+
+    /// Processes load a lot of modules, so by default they don't stop. See `EventFilters::should_break_on_module_load`.
+    fn default_policy(&self) -> EventPolicy {
+        match self {
+            EventKind::LoadModule => EventPolicy::Log,
+            _ => EventPolicy::Stop,
+        }
+    }
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventKind::CreateProcess => write!(f, "create-process"),
+            EventKind::InitialBreakpoint => write!(f, "initial-breakpoint"),
+            EventKind::CreateThread => write!(f, "create-thread"),
+            EventKind::ExitThread => write!(f, "exit-thread"),
+            EventKind::LoadModule => write!(f, "load-module"),
+            EventKind::UnloadModule => write!(f, "unload-module"),
+            EventKind::OutputDebugString => write!(f, "output-debug-string"),
+        }
+    }
+}
+
+/// Decides which debug events stop the debugger.
+#[derive(Default)]
+pub struct EventFilters {
+    /// Exceptions that aren't in the list break on the first chance.
+    exception_policies: Vec<(u32, ExceptionPolicy)>,
+    /// Stop when a module whose name matches one of these is loaded, regardless of the load-module policy.
+    /// They can contain `*` and `?` wildcards.
+    module_load_patterns: Vec<String>,
+    /// Events that aren't in the list use their default policy.
+    event_policies: Vec<(EventKind, EventPolicy)>,
+}
+
+impl EventFilters {
+    pub fn new() -> EventFilters {
+        EventFilters { exception_policies: Vec::new(), module_load_patterns: Vec::new(), event_policies: Vec::new() }
+    }
+
+    pub fn set_exception_policy(&mut self, code: u32, policy: ExceptionPolicy) {
+        self.exception_policies.retain(|(existing_code, _)| *existing_code != code);
+        if policy != ExceptionPolicy::Break {
+            self.exception_policies.push((code, policy));
+            self.exception_policies.sort_by_key(|(code, _)| *code);
+        }
+    }
+
+    pub fn get_exception_policy(&self, code: u32) -> ExceptionPolicy {
+        self.exception_policies.iter()
+            .find(|(existing_code, _)| *existing_code == code)
+            .map_or(ExceptionPolicy::Break, |(_, policy)| *policy)
+    }
+
+    /// Returns whether to stop for an exception.
+    pub fn should_break_on_exception(&self, code: u32, first_chance: bool) -> bool {
+        match self.get_exception_policy(code) {
+            ExceptionPolicy::Break => true,
+            ExceptionPolicy::SecondChance => !first_chance,
+            ExceptionPolicy::Ignore => false,
+        }
+    }
+
+    pub fn set_event_policy(&mut self, event: EventKind, policy: EventPolicy) {
+        self.event_policies.retain(|(existing_event, _)| *existing_event != event);
+        self.event_policies.push((event, policy));
+    }
+
+    pub fn get_event_policy(&self, event: EventKind) -> EventPolicy {
+        self.event_policies.iter()
+            .find(|(existing_event, _)| *existing_event == event)
+            .map_or(event.default_policy(), |(_, policy)| *policy)
+    }
+
+    pub fn add_module_load_pattern(&mut self, pattern: &str) {
+        if !self.module_load_patterns.iter().any(|existing| existing.eq_ignore_ascii_case(pattern)) {
+            self.module_load_patterns.push(pattern.to_string());
+        }
+    }
+
+    /// Returns whether the pattern was found.
+    pub fn remove_module_load_pattern(&mut self, pattern: &str) -> bool {
+        let count = self.module_load_patterns.len();
+        self.module_load_patterns.retain(|existing| !existing.eq_ignore_ascii_case(pattern));
+        self.module_load_patterns.len() != count
+    }
+
+    /// Returns whether to stop when the module at `path` is loaded. The patterns match the file name with or without
+    /// its extension, e.g. `ntdll.dll` or `ntdll`.
+    pub fn should_break_on_module_load(&self, path: &str) -> bool {
+        let file_name = path.rsplit('\\').next().unwrap_or(path);
+        let stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem);
+        self.module_load_patterns.iter().any(|pattern| {
+            name_resolution::matches_wildcard(pattern, file_name) || name_resolution::matches_wildcard(pattern, stem)
+        })
+    }
+
+    pub fn list(&self) {
+        for (code, name) in exceptions::EXCEPTION_NAMES.iter() {
+            println!("{code:#010x} {name:<24} {}", self.get_exception_policy(*code));
+        }
+        for (code, policy) in self.exception_policies.iter() {
+            if exceptions::get_exception_name(*code).is_none() {
+                println!("{code:#010x} {:<24} {policy}", "");
+            }
+        }
+        println!("Other exceptions break on the first chance.");
+        for event in EventKind::ALL {
+            println!("{:<24} {}", event.to_string(), self.get_event_policy(event));
+        }
+        for pattern in self.module_load_patterns.iter() {
+            println!("Break when a module matching {pattern} is loaded");
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{
+    name_resolution,
+    output,
+    process::Process,
+    windows_wrapper::ExceptionRecord,
+};
+
+const EXCEPTION_ACCESS_VIOLATION: u32 = 0xc0000005;
+const EXCEPTION_IN_PAGE_ERROR: u32 = 0xc0000006;
+const EXCEPTION_NONCONTINUABLE: u32 = 0x1;
+
+/// Common exception codes and their names.
+pub const EXCEPTION_NAMES: [(u32, &str); 13] = [
+    (0x80000003, "breakpoint"),
+    (0x80000004, "single step"),
+    (0x4000001f, "WOW64 breakpoint"),
+    (0x4000001e, "WOW64 single step"),
+    (EXCEPTION_ACCESS_VIOLATION, "access violation"),
+    (0xc0000008, "invalid handle"),
+    (0xc000001d, "illegal instruction"),
+    (0xc0000094, "integer divide by zero"),
+    (0xc0000096, "privileged instruction"),
+    (0xc00000fd, "stack overflow"),
+    (0xc0000374, "heap corruption"),
+    (0xc0000409, "stack buffer overrun"),
+    (0xe06d7363, "C++ exception"),
+];
+
+pub fn get_exception_name(code: u32) -> Option<&'static str> {
+    EXCEPTION_NAMES.iter().find(|(known_code, _)| *known_code == code).map(|(_, name)| *name)
+}
+
+/// Explains the parameters of the exceptions whose parameters are documented.
+/// See https://learn.microsoft.com/en-us/windows/win32/api/winnt/ns-winnt-exception_record.
+fn describe_parameters(code: u32, parameters: &[u64]) -> Option<String> {
+    if code != EXCEPTION_ACCESS_VIOLATION && code != EXCEPTION_IN_PAGE_ERROR {
+        return None;
+    }
+    let access = match parameters.first()? {
+        0 => "read from",
+        1 => "write to",
+        8 => "execute",
+        _ => return None,
+    };
+    let address = parameters.get(1)?;
+    Some(format!("Attempt to {access} address {address:#018x}"))
+}
+
+/// Prints an exception record, like WinDbg's `.exr`, followed by the records chained to it.
+pub fn display_exception_record(record: &ExceptionRecord, process: &mut Process) {
+    let mut record = Some(record);
+    let mut depth = 0;
+    while let Some(current) = record {
+        if depth > 0 {
+            println!("\nNested exception record:");
+        }
+        let code = current.code.0 as u32;
+        let symbol = name_resolution::resolve_address_to_name(current.address, process)
+            .map_or(String::new(), |symbol| format!(" ({})", output::symbol(&symbol)));
+        println!("ExceptionAddress: {}{symbol}", output::address(current.address));
+        match get_exception_name(code) {
+            Some(name) => println!("   ExceptionCode: {code:#010x} ({name})"),
+            None => println!("   ExceptionCode: {code:#010x}"),
+        }
+        let noncontinuable = if current.flags & EXCEPTION_NONCONTINUABLE != 0 { " (noncontinuable)" } else { "" };
+        println!("  ExceptionFlags: {:#010x}{noncontinuable}", current.flags);
+        println!("NumberParameters: {}", current.parameters.len());
+        for (index, parameter) in current.parameters.iter().enumerate() {
+            println!("   Parameter[{index}]: {parameter:#018x}");
+        }
+        if let Some(description) = describe_parameters(code, &current.parameters) {
+            println!("{description}");
+        }
+
+        record = current.nested.as_deref();
+        depth += 1;
+    }
+}
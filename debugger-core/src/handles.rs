@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use windows::Win32::Foundation::HANDLE;
+
+use crate::windows_wrapper::{self, HandleEntry};
+
+fn format_attributes(attributes: u32) -> String {
+    let mut names = Vec::new();
+    if attributes & windows_wrapper::HANDLE_ATTRIBUTE_INHERIT != 0 {
+        names.push("Inherit");
+    }
+    if attributes & windows_wrapper::HANDLE_ATTRIBUTE_PROTECT_FROM_CLOSE != 0 {
+        names.push("ProtectFromClose");
+    }
+    if names.is_empty() {
+        String::from("None")
+    } else {
+        names.join(" | ")
+    }
+}
+
+/// Prints the process's open handles, with the type and name of the objects that they refer to, followed by how many
+/// there are of each type. Growing counts between stops point to a handle leak.
+pub fn display_handles(process_handle: HANDLE) {
+    let handles = match windows_wrapper::enumerate_handles(process_handle) {
+        Ok(handles) => handles,
+        Err(e) => {
+            print_error!("{e}");
+            return;
+        }
+    };
+
+    println!("{:<10} {:<24} Name", "Handle", "Type");
+    let mut type_counts = BTreeMap::<&str, usize>::new();
+    for handle in handles.iter() {
+        let type_name = handle.type_name.as_deref().unwrap_or("<unknown>");
+        *type_counts.entry(type_name).or_default() += 1;
+        println!("{:<#10x} {type_name:<24} {}", handle.value, handle.object_name.as_deref().unwrap_or_default());
+    }
+
+    println!("\n{count} handles", count = handles.len());
+    for (type_name, count) in type_counts {
+        println!("  {count:>6} {type_name}");
+    }
+}
+
+/// Prints the details of the process's handle with the value `value`.
+pub fn display_handle(process_handle: HANDLE, value: u64) {
+    let handles = match windows_wrapper::enumerate_handles(process_handle) {
+        Ok(handles) => handles,
+        Err(e) => {
+            print_error!("{e}");
+            return;
+        }
+    };
+
+    match handles.iter().find(|handle| handle.value == value) {
+        Some(HandleEntry { value, granted_access, attributes, type_name, object_name }) => {
+            println!("Handle {value:#x}");
+            println!("  Type:           {}", type_name.as_deref().unwrap_or("<unknown>"));
+            println!("  Name:           {}", object_name.as_deref().unwrap_or("<none>"));
+            println!("  Granted access: {granted_access:#x}");
+            println!("  Attributes:     {}", format_attributes(*attributes));
+        }
+        None => println!("No handle {value:#x} in the process"),
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{
+    environment_blocks::{self, read_u32, read_u64},
+    memory::{self, MemorySource},
+};
+
+// Offsets of fields in the x64 NT heap's structures (`_HEAP`, `_HEAP_SEGMENT`, `_HEAP_ENTRY`, and
+// `_HEAP_UCR_DESCRIPTOR`). These are undocumented, but match ntdll's public symbols on Windows 10 and 11.
+// A heap starts with its first segment, so the segment fields are also at the start of the heap.
+
+const SEGMENT_LIST_ENTRY: u64 = 0x18;
+const SEGMENT_FIRST_ENTRY: u64 = 0x40;
+const SEGMENT_LAST_VALID_ENTRY: u64 = 0x48;
+const SEGMENT_UCR_SEGMENT_LIST: u64 = 0x60;
+
+const HEAP_FLAGS: u64 = 0x70;
+const HEAP_ENCODE_FLAG_MASK: u64 = 0x7C;
+const HEAP_ENCODING: u64 = 0x80;
+const HEAP_SIGNATURE: u64 = 0x98;
+const HEAP_SEGMENT_LIST: u64 = 0x120;
+
+const UCR_DESCRIPTOR_SEGMENT_ENTRY: u64 = 0x10;
+const UCR_DESCRIPTOR_ADDRESS: u64 = 0x20;
+const UCR_DESCRIPTOR_SIZE: u64 = 0x28;
+
+const NT_HEAP_SIGNATURE: u32 = 0xEEFFEEFF;
+/// The signature of a segment heap (`_SEGMENT_HEAP`), which is laid out differently, at `SEGMENT_HEAP_SIGNATURE_OFFSET`.
+const SEGMENT_HEAP_SIGNATURE: u32 = 0xDDEEDDEE;
+const SEGMENT_HEAP_SIGNATURE_OFFSET: u64 = 0x10;
+
+/// Blocks are measured in units of their header's size.
+const HEAP_ENTRY_SIZE: u64 = 0x10;
+const HEAP_ENTRY_BUSY: u8 = 0x01;
+
+/// Stop walking after this many of each, in case the heap is corrupt.
+const MAX_SEGMENTS: usize = 1024;
+const MAX_UNCOMMITTED_RANGES: usize = 0x10000;
+const MAX_BLOCKS: usize = 1_000_000;
+
+/// The end of the user-mode address space on x64. Heap links past it are corrupt.
+const MAX_USER_ADDRESS: u64 = 0x7FFF_FFFF_FFFF;
+
+/// A block from the heap's back end. The low-fragmentation heap (LFH) carves its own blocks out of large busy blocks.
+struct HeapBlock {
+    /// The address of the block's header. Its data follows the header.
+    address: u64,
+    /// The size of the block, including its header.
+    size: u64,
+    busy: bool,
+    /// How much of a busy block wasn't asked for, including its header.
+    unused_bytes: u64,
+}
+
+/// The blocks in a segment, up to the first one that couldn't be walked.
+struct SegmentWalk {
+    blocks: Vec<HeapBlock>,
+    error: Option<String>,
+}
+
+/// Gets the address of the structure that contains the list link `link` at `offset`, or `None` if the link is null or
+/// outside of the user-mode address space, because the heap is corrupt.
+fn get_linked_structure(link: u64, offset: u64) -> Option<u64> {
+    if link == 0 || link > MAX_USER_ADDRESS {
+        return None;
+    }
+    link.checked_sub(offset)
+}
+
+/// Gets the ranges of the segment that haven't been committed yet, as `(address, size)`, which have no blocks.
+fn get_uncommitted_ranges(segment: u64, memory_source: &dyn MemorySource) -> Result<Vec<(u64, u64)>, String> {
+    // The list is circular, with the head in the segment. The links are in the middle of each descriptor.
+    let list_head = segment + SEGMENT_UCR_SEGMENT_LIST;
+    let mut link = read_u64(memory_source, list_head)?;
+    let mut ranges = Vec::new();
+    for _ in 0..MAX_UNCOMMITTED_RANGES {
+        if link == list_head {
+            return Ok(ranges);
+        }
+        let descriptor = get_linked_structure(link, UCR_DESCRIPTOR_SEGMENT_ENTRY)
+            .ok_or_else(|| format!("The segment at {segment:#018x} has a corrupt uncommitted range link {link:#018x}"))?;
+        ranges.push((read_u64(memory_source, descriptor + UCR_DESCRIPTOR_ADDRESS)?, read_u64(memory_source, descriptor + UCR_DESCRIPTOR_SIZE)?));
+        link = read_u64(memory_source, link)?;
+    }
+    Err(format!("The segment at {segment:#018x} has more than {MAX_UNCOMMITTED_RANGES} uncommitted ranges"))
+}
+
+/// Walks the blocks from `first_entry` to `last_valid_entry`. `encoding` is XORed with each block's header, unless the
+/// heap doesn't encode them.
+fn walk_segment(
+    first_entry: u64,
+    last_valid_entry: u64,
+    uncommitted_ranges: &[(u64, u64)],
+    encoding: Option<&[u8]>,
+    memory_source: &dyn MemorySource,
+) -> SegmentWalk {
+    let mut blocks = Vec::new();
+    let mut address = first_entry;
+    while address < last_valid_entry {
+        if let Some((_, size)) = uncommitted_ranges.iter().find(|(range_address, _)| *range_address == address) {
+            address += size;
+            continue;
+        }
+        if blocks.len() >= MAX_BLOCKS {
+            return SegmentWalk { blocks, error: Some(format!("Stopping after {MAX_BLOCKS} blocks")) };
+        }
+
+        let mut header = match memory::read_memory_full_array::<u8>(memory_source, address, HEAP_ENTRY_SIZE as usize) {
+            Ok(header) => header,
+            Err(_) => return SegmentWalk { blocks, error: Some(format!("Could not read the block at {address:#018x}")) },
+        };
+        // Only the second half of the header, which has the size and flags, is encoded.
+        if let Some(encoding) = encoding {
+            for (byte, key) in header[8..].iter_mut().zip(&encoding[8..]) {
+                *byte ^= key;
+            }
+        }
+        let size = u16::from_le_bytes([header[8], header[9]]) as u64 * HEAP_ENTRY_SIZE;
+        if size == 0 {
+            return SegmentWalk { blocks, error: Some(format!("The block at {address:#018x} is corrupt: its size is 0")) };
+        }
+        blocks.push(HeapBlock {
+            address,
+            size,
+            busy: header[10] & HEAP_ENTRY_BUSY != 0,
+            unused_bytes: header[15] as u64,
+        });
+        address += size;
+    }
+    SegmentWalk { blocks, error: None }
+}
+
+fn display_segment(segment: u64, encoding: Option<&[u8]>, show_blocks: bool, memory_source: &dyn MemorySource) -> Result<(), String> {
+    let first_entry = read_u64(memory_source, segment + SEGMENT_FIRST_ENTRY)?;
+    let last_valid_entry = read_u64(memory_source, segment + SEGMENT_LAST_VALID_ENTRY)?;
+    let uncommitted_ranges = get_uncommitted_ranges(segment, memory_source)?;
+    let walk = walk_segment(first_entry, last_valid_entry, &uncommitted_ranges, encoding, memory_source);
+
+    let (busy_blocks, free_blocks): (Vec<&HeapBlock>, Vec<&HeapBlock>) = walk.blocks.iter().partition(|block| block.busy);
+    println!(
+        "    Segment {segment:#018x}  {first_entry:#018x} - {last_valid_entry:#018x}  Busy: {busy_size:#x} in {busy_count} blocks  Free: {free_size:#x} in {free_count} blocks  Uncommitted: {uncommitted_size:#x}",
+        busy_size = busy_blocks.iter().map(|block| block.size).sum::<u64>(),
+        busy_count = busy_blocks.len(),
+        free_size = free_blocks.iter().map(|block| block.size).sum::<u64>(),
+        free_count = free_blocks.len(),
+        uncommitted_size = uncommitted_ranges.iter().map(|(_, size)| size).sum::<u64>(),
+    );
+
+    if show_blocks {
+        for block in walk.blocks.iter() {
+            if block.busy {
+                println!(
+                    "        {address:#018x} {size:#10x}  Busy  Data {data:#018x} ({requested:#x} bytes)",
+                    address = block.address,
+                    size = block.size,
+                    data = block.address + HEAP_ENTRY_SIZE,
+                    requested = block.size.saturating_sub(block.unused_bytes),
+                );
+            } else {
+                println!("        {address:#018x} {size:#10x}  Free", address = block.address, size = block.size);
+            }
+        }
+    }
+    if let Some(error) = walk.error {
+        print_error!("    {error}");
+    }
+    Ok(())
+}
+
+fn display_heap(heap: u64, is_process_heap: bool, show_blocks: bool, memory_source: &dyn MemorySource) -> Result<(), String> {
+    let process_heap_marker = if is_process_heap { "  (process heap)" } else { "" };
+    if read_u32(memory_source, heap + HEAP_SIGNATURE)? != NT_HEAP_SIGNATURE {
+        if read_u32(memory_source, heap + SEGMENT_HEAP_SIGNATURE_OFFSET)? == SEGMENT_HEAP_SIGNATURE {
+            println!("Heap {heap:#018x}  Segment heap{process_heap_marker}");
+            println!("    Segment heaps can't be walked");
+            return Ok(());
+        }
+        return Err(format!("The heap at {heap:#018x} doesn't have a heap signature"));
+    }
+
+    let flags = read_u32(memory_source, heap + HEAP_FLAGS)?;
+    println!("Heap {heap:#018x}  Flags {flags:#x}{process_heap_marker}");
+    let encoding = match read_u32(memory_source, heap + HEAP_ENCODE_FLAG_MASK)? {
+        0 => None,
+        _ => Some(memory::read_memory_full_array::<u8>(memory_source, heap + HEAP_ENCODING, HEAP_ENTRY_SIZE as usize)
+            .map_err(|_| format!("Could not read the heap's encoding at {:#018x}", heap + HEAP_ENCODING))?),
+    };
+
+    // The list is circular, with the head in the heap. The links are in the middle of each segment.
+    let list_head = heap + HEAP_SEGMENT_LIST;
+    let mut link = read_u64(memory_source, list_head)?;
+    for _ in 0..MAX_SEGMENTS {
+        if link == list_head {
+            return Ok(());
+        }
+        let segment = get_linked_structure(link, SEGMENT_LIST_ENTRY)
+            .ok_or_else(|| format!("The heap at {heap:#018x} has a corrupt segment link {link:#018x}"))?;
+        display_segment(segment, encoding.as_deref(), show_blocks, memory_source)?;
+        link = read_u64(memory_source, link)?;
+    }
+    println!("    Stopping after {MAX_SEGMENTS} segments");
+    Ok(())
+}
+
+/// Prints the process's heaps and their segments, with how much of each segment is busy, free, and uncommitted.
+/// If `heap_address` is given, only prints that heap, along with its blocks.
+/// `peb_address` must be the 64-bit PEB. The 32-bit heaps of WOW64 processes aren't supported, since their structures
+/// have different offsets, so only their 64-bit heaps are printed.
+pub fn display_heaps(peb_address: u64, heap_address: Option<u64>, is_wow64: bool, memory_source: &dyn MemorySource) -> Result<(), String> {
+    if is_wow64 {
+        println!("The 32-bit heaps of WOW64 processes aren't supported. Showing the process's 64-bit heaps.");
+    }
+    let heaps = environment_blocks::get_process_heaps(peb_address, memory_source)?;
+    let process_heap = environment_blocks::get_process_heap(peb_address, memory_source)?;
+    if let Some(heap_address) = heap_address.filter(|heap_address| !heaps.contains(heap_address)) {
+        return Err(format!("{heap_address:#018x} is not one of the process's heaps"));
+    }
+
+    for heap in heaps.into_iter().filter(|heap| heap_address.is_none_or(|heap_address| heap_address == *heap)) {
+        // Keep going, since the other heaps may be fine.
+        if let Err(e) = display_heap(heap, heap == process_heap, heap_address.is_some(), memory_source) {
+            print_error!("{e}");
+        }
+    }
+    Ok(())
+}
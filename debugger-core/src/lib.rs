@@ -0,0 +1,110 @@
+//! The engine of a Windows debugger: launching and attaching to processes, reading and writing their memory, breakpoints,
+//! stepping, stack walking, symbols, and evaluating expressions. The `debugger` binary is a command-line front-end for it.
+//!
+//! The simplest way to control a target is `debugger::Debugger`, which launches or attaches to it and calls back on
+//! breakpoints, exceptions, and module loads. For full control, launch the target with
+//! `windows_wrapper::launch_process_for_debugging`, then repeatedly call `windows_wrapper::wait_for_debug_event` and
+//! `windows_wrapper::continue_debug_event`. The modules that a `Process` loads are tracked with `Process::add_module`,
+//! and its memory is read through a `memory::MemorySource`.
+//!
+//! Output, e.g. from `stack::print_stack`, goes to stdout and the session log. See `session_log`. Front-ends that draw
+//! the screen themselves can capture it instead, with `output::start_capture`.
+
+// These replace the standard macros in the whole crate, so that all output is also written to the session log.
+// They must be defined before the modules that use them. They are exported so that front-ends can use them too.
+
+/// Like `std::print!`, but also writes to the session log. See `session_log`.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {{
+        let text = format!($($arg)*);
+        $crate::output::write(&text);
+        $crate::session_log::write(&text);
+    }};
+}
+
+/// Like `std::println!`, but also writes to the session log. See `session_log`.
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {{
+        let text = format!("{}\n", format_args!($($arg)*));
+        $crate::output::write(&text);
+        $crate::session_log::write(&text);
+    }};
+}
+
+/// Like `println!`, but in the error color. See `output`.
+#[macro_export]
+macro_rules! print_error {
+    ($($arg:tt)*) => {
+        $crate::println!("{}", $crate::output::paint($crate::output::Style::Error, format!($($arg)*)))
+    };
+}
+
+/// Describes the processor architectures that targets can run on, and accesses threads' registers independently of them.
+pub mod arch;
+/// Assembles instructions typed at the prompt, for patching code.
+pub mod assembler;
+/// Software (`int3`) and hardware breakpoints.
+pub mod breakpoint;
+/// Traces the calls that a function makes, like WinDbg's `wt`.
+pub mod call_trace;
+/// The grammar for commands and expressions.
+pub mod command;
+/// Controls a target with callbacks, for embedding the debugger in other tools.
+pub mod debugger;
+/// Disassembles the target's code.
+pub mod disassembly;
+/// Symbols from DWARF debug info, for images built with MinGW.
+pub mod dwarf;
+/// Handles debug events for breakpoints, stepping, and memory watchpoints.
+pub mod engine;
+/// The thread and process environment blocks (TEB and PEB).
+pub mod environment_blocks;
+/// Evaluates expressions against a thread's registers and the target's memory.
+pub mod eval;
+/// Decides which exceptions and other debug events stop the debugger.
+pub mod event_filters;
+/// Describes exception codes and records.
+pub mod exceptions;
+/// Lists the target's open handles.
+pub mod handles;
+/// Walks the target's NT heaps.
+pub mod heaps;
+/// Local variables and parameters, from the PDB.
+pub mod locals;
+/// Reads and writes the target's memory.
+pub mod memory;
+/// Displays memory as bytes, words, strings, etc.
+pub mod memory_display;
+/// Lists the regions of the target's address space.
+pub mod memory_map;
+/// Reads minidump files.
+pub mod minidump;
+/// The images loaded in a process: their exports, unwind data, and symbols.
+pub mod module;
+/// Displays a module's headers, version, and PDB.
+pub mod module_details;
+/// Converts between symbol names and addresses.
+pub mod name_resolution;
+/// Colors output.
+pub mod output;
+/// The modules and threads of a process being debugged.
+pub mod process;
+/// Displays registers.
+pub mod registers;
+/// Copies output to a log file.
+pub mod session_log;
+/// Walks the call stack.
+pub mod stack;
+/// Finds PDBs using the symbol path.
+pub mod symbols;
+/// Displays types from the PDB.
+pub mod types;
+/// Memory watchpoints for ranges too large for hardware breakpoints, implemented with guard pages.
+pub mod watchpoints;
+/// Wraps the Windows debugging APIs.
+pub mod windows_wrapper;
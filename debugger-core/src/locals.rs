@@ -0,0 +1,443 @@
+use std::fs::File;
+
+use pdb::{
+    AddressMap, FallibleIterator, IdData, IdFinder, IdIndex, ProcedureSymbol, SymbolData, SymbolIndex, SymbolIter, TypeData,
+    TypeFinder, TypeIndex, PDB,
+};
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+use crate::{
+    arch::ThreadContext,
+    memory::{self, MemorySource},
+    module::Module,
+    process::Process,
+};
+
+// Local variables are described by the symbols nested inside each function's `S_GPROC32`/`S_LPROC32` symbol, in the
+// module symbol streams. See https://llvm.org/docs/PDB/CodeViewSymbols.html for the formats.
+// The pdb crate doesn't parse the `S_FRAMEPROC` and `S_DEFRANGE_*` symbols, so they are read from the raw bytes.
+
+const S_FRAMEPROC: u16 = 0x1012;
+const S_LPROC32_ID: u16 = 0x1146;
+const S_GPROC32_ID: u16 = 0x1147;
+const S_DEFRANGE_REGISTER: u16 = 0x1141;
+const S_DEFRANGE_FRAMEPOINTER_REL: u16 = 0x1142;
+const S_DEFRANGE_FRAMEPOINTER_REL_FULL_SCOPE: u16 = 0x1144;
+const S_DEFRANGE_REGISTER_REL: u16 = 0x1145;
+
+/// Where a variable lives.
+#[derive(Copy, Clone)]
+pub enum VariableLocation {
+    Register(&'static str),
+    /// In memory, at an offset from a register.
+    RegisterRelative(&'static str, i32),
+    /// In memory, at an offset from the function's frame pointer (see `FunctionVariables::local_base_register`).
+    FrameRelative(i32),
+    /// For example, because the variable was optimized out.
+    Unknown,
+}
+
+pub struct Variable {
+    pub name: String,
+    pub is_parameter: bool,
+    pub location: VariableLocation,
+}
+
+/// The parameters and local variables of a function, as of an address inside it.
+pub struct FunctionVariables {
+    pub function_name: String,
+    pub variables: Vec<Variable>,
+    /// The registers that frame-relative locals and parameters are relative to, if known.
+    local_base_register: Option<&'static str>,
+    parameter_base_register: Option<&'static str>,
+}
+
+impl FunctionVariables {
+    /// Gets the address of a variable that lives in memory.
+    pub fn get_address(&self, variable: &Variable, context: &CONTEXT) -> Option<u64> {
+        match variable.location {
+            VariableLocation::RegisterRelative(register, offset) => {
+                Some(context.get_register(register)?.wrapping_add_signed(offset as i64))
+            }
+            VariableLocation::FrameRelative(offset) => {
+                let base_register = if variable.is_parameter { self.parameter_base_register } else { self.local_base_register };
+                Some(context.get_register(base_register?)?.wrapping_add_signed(offset as i64))
+            }
+            VariableLocation::Register(_) | VariableLocation::Unknown => None,
+        }
+    }
+
+    /// Gets the raw value of a variable, reading `size` bytes if it lives in memory.
+    pub fn get_value(&self, variable: &Variable, context: &CONTEXT, size: usize, memory_source: &dyn MemorySource) -> Option<Vec<u8>> {
+        if let VariableLocation::Register(register) = variable.location {
+            let value = context.get_register(register)?;
+            return Some(value.to_le_bytes()[..size.min(8)].to_vec());
+        }
+        let address = self.get_address(variable, context)?;
+        memory::read_memory_full_array::<u8>(memory_source, address, size).ok()
+    }
+
+    /// Formats the raw value of a variable. Without type information, this shows a pointer-sized value, or `?` if the
+    /// value can't be read.
+    pub fn format_value(&self, variable: &Variable, context: &CONTEXT, pointer_size: usize, memory_source: &dyn MemorySource) -> String {
+        match self.get_value(variable, context, pointer_size, memory_source) {
+            Some(bytes) => {
+                let value = bytes.iter().rev().fold(0u64, |value, &byte| (value << 8) | byte as u64);
+                format!("{value:#0width$x}", width = pointer_size * 2 + 2)
+            }
+            None => String::from("?"),
+        }
+    }
+}
+
+impl std::fmt::Display for VariableLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableLocation::Register(register) => write!(f, "@{register}"),
+            VariableLocation::RegisterRelative(register, offset) if *offset < 0 => write!(f, "[{register}-{:#x}]", offset.unsigned_abs()),
+            VariableLocation::RegisterRelative(register, offset) => write!(f, "[{register}+{offset:#x}]"),
+            VariableLocation::FrameRelative(offset) if *offset < 0 => write!(f, "[frame-{:#x}]", offset.unsigned_abs()),
+            VariableLocation::FrameRelative(offset) => write!(f, "[frame+{offset:#x}]"),
+            VariableLocation::Unknown => write!(f, "<unknown>"),
+        }
+    }
+}
+
+/// Maps a CodeView register number to the name that `ThreadContext::get_register` uses.
+fn get_register_name(register: u16) -> Option<&'static str> {
+    let name = match register {
+        17 => "eax",
+        18 => "ecx",
+        19 => "edx",
+        20 => "ebx",
+        21 => "esp",
+        22 => "ebp",
+        23 => "esi",
+        24 => "edi",
+        328 => "rax",
+        329 => "rbx",
+        330 => "rcx",
+        331 => "rdx",
+        332 => "rsi",
+        333 => "rdi",
+        334 => "rbp",
+        335 => "rsp",
+        336 => "r8",
+        337 => "r9",
+        338 => "r10",
+        339 => "r11",
+        340 => "r12",
+        341 => "r13",
+        342 => "r14",
+        343 => "r15",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Decodes a frame pointer from the flags of `S_FRAMEPROC`, which uses 2 bits: none, the stack pointer, the frame
+/// pointer, or an alternate register.
+fn get_frame_base_register(encoded: u32, is_32_bit: bool) -> Option<&'static str> {
+    match (encoded, is_32_bit) {
+        (1, false) => Some("rsp"),
+        (2, false) => Some("rbp"),
+        (3, false) => Some("r13"),
+        // 1 on x86 is the "virtual frame", which needs the frame data from the PDB to compute.
+        (2, true) => Some("ebp"),
+        (3, true) => Some("ebx"),
+        _ => None,
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().unwrap()))
+}
+
+/// Reads a `S_DEFRANGE_*` symbol, which gives the location of the preceding `S_LOCAL` symbol.
+/// The raw bytes start with the 2-byte symbol kind.
+// TODO: Each of these is only valid for a range of addresses, which is ignored.
+fn parse_def_range(kind: u16, bytes: &[u8]) -> Option<VariableLocation> {
+    match kind {
+        S_DEFRANGE_REGISTER => Some(VariableLocation::Register(get_register_name(read_u16(bytes, 2)?)?)),
+        S_DEFRANGE_FRAMEPOINTER_REL | S_DEFRANGE_FRAMEPOINTER_REL_FULL_SCOPE => {
+            Some(VariableLocation::FrameRelative(read_u32(bytes, 2)? as i32))
+        }
+        S_DEFRANGE_REGISTER_REL => {
+            let register = get_register_name(read_u16(bytes, 2)?)?;
+            Some(VariableLocation::RegisterRelative(register, read_u32(bytes, 6)? as i32))
+        }
+        _ => None,
+    }
+}
+
+/// Reads the variables of `procedure` that are in scope at `rva`. `symbols` must be positioned just after the procedure.
+fn read_procedure_variables(
+    procedure: &ProcedureSymbol,
+    symbols: &mut SymbolIter,
+    rva: u32,
+    address_map: &AddressMap,
+    is_32_bit: bool,
+) -> Result<FunctionVariables, pdb::Error> {
+    let mut function = FunctionVariables {
+        function_name: procedure.name.to_string().to_string(),
+        variables: Vec::new(),
+        local_base_register: None,
+        parameter_base_register: None,
+    };
+    // Whether the last variable was an `S_LOCAL`, which is followed by `S_DEFRANGE_*` symbols that give its location.
+    let mut expecting_def_range = false;
+
+    while let Some(symbol) = symbols.next()? {
+        if symbol.index() == procedure.end {
+            break;
+        }
+
+        let kind = symbol.raw_kind();
+        if kind == S_FRAMEPROC {
+            // The flags are after 5 32-bit fields and a 16-bit field.
+            if let Some(flags) = read_u32(symbol.raw_bytes(), 2 + 22) {
+                function.local_base_register = get_frame_base_register((flags >> 14) & 3, is_32_bit);
+                function.parameter_base_register = get_frame_base_register((flags >> 16) & 3, is_32_bit);
+            }
+            continue;
+        }
+        if let Some(location) = parse_def_range(kind, symbol.raw_bytes()) {
+            if expecting_def_range {
+                if let Some(variable) = function.variables.last_mut() {
+                    variable.location = location;
+                }
+                expecting_def_range = false;
+            }
+            continue;
+        }
+
+        match symbol.parse() {
+            Ok(SymbolData::Block(block)) => {
+                // Skip blocks that don't contain the address, since their variables are out of scope.
+                let start = block.offset.to_rva(address_map).map(|rva| rva.0);
+                if !start.is_some_and(|start| rva >= start && rva < start + block.len) {
+                    symbols.skip_to(block.end)?;
+                }
+            }
+            Ok(SymbolData::InlineSite(inline_site)) => {
+                symbols.skip_to(inline_site.end)?;
+            }
+            Ok(SymbolData::RegisterRelative(data)) => {
+                if let Some(register) = get_register_name(data.register.0) {
+                    function.variables.push(Variable {
+                        name: data.name.to_string().to_string(),
+                        // `S_REGREL32` doesn't record whether the variable is a parameter.
+                        is_parameter: false,
+                        location: VariableLocation::RegisterRelative(register, data.offset),
+                    });
+                }
+            }
+            Ok(SymbolData::RegisterVariable(data)) => {
+                function.variables.push(Variable {
+                    name: data.name.to_string().to_string(),
+                    is_parameter: false,
+                    location: get_register_name(data.register.0).map_or(VariableLocation::Unknown, VariableLocation::Register),
+                });
+            }
+            Ok(SymbolData::Local(data)) => {
+                function.variables.push(Variable {
+                    name: data.name.to_string().to_string(),
+                    is_parameter: data.flags.isparam,
+                    location: VariableLocation::Unknown,
+                });
+                expecting_def_range = true;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(function)
+}
+
+/// Gets the number of parameters of a function type, including the `this` parameter of member functions.
+fn get_parameter_count(type_finder: &TypeFinder, function_type: TypeIndex) -> Result<Option<usize>, pdb::Error> {
+    let count = match type_finder.find(function_type)?.parse()? {
+        TypeData::Procedure(procedure) => Some(procedure.parameter_count as usize),
+        TypeData::MemberFunction(function) => {
+            Some(function.parameter_count as usize + function.this_pointer_type.map_or(0, |_| 1))
+        }
+        _ => None,
+    };
+    Ok(count)
+}
+
+/// Gets the type of a procedure. The `_ID` variants of the procedure symbols refer to a function ID in the IPI stream,
+/// which in turn refers to the type.
+fn get_procedure_type(id_finder: &IdFinder, symbol_kind: u16, type_index: TypeIndex) -> Result<Option<TypeIndex>, pdb::Error> {
+    if symbol_kind != S_LPROC32_ID && symbol_kind != S_GPROC32_ID {
+        return Ok(Some(type_index));
+    }
+    let function_type = match id_finder.find(IdIndex(type_index.0))?.parse()? {
+        IdData::Function(function) => Some(function.function_type),
+        IdData::MemberFunction(function) => Some(function.function_type),
+        _ => None,
+    };
+    Ok(function_type)
+}
+
+/// A function in the PDB, and where to find its symbol.
+#[derive(Copy, Clone)]
+struct IndexedProcedure {
+    start: u32,
+    len: u32,
+    /// The index of the compiland whose symbol stream has the procedure.
+    module_index: usize,
+    symbol_index: SymbolIndex,
+    parameter_count: usize,
+}
+
+/// The functions in a PDB, sorted by RVA, so that the function containing an address can be found without scanning
+/// every module's symbols.
+#[derive(Default)]
+pub struct ProcedureIndex {
+    procedures: Vec<IndexedProcedure>,
+}
+
+impl ProcedureIndex {
+    /// Reads every module's symbols once, and resolves each function's parameter count from the TPI and IPI streams.
+    pub fn build(pdb: &mut PDB<'static, File>) -> Result<ProcedureIndex, pdb::Error> {
+        let type_information = pdb.type_information()?;
+        let mut type_finder = type_information.finder();
+        let mut types = type_information.iter();
+        while types.next()?.is_some() {
+            type_finder.update(&types);
+        }
+        let id_information = pdb.id_information()?;
+        let mut id_finder = id_information.finder();
+        let mut ids = id_information.iter();
+        while ids.next()?.is_some() {
+            id_finder.update(&ids);
+        }
+
+        let address_map = pdb.address_map()?;
+        let debug_information = pdb.debug_information()?;
+        let mut modules = debug_information.modules()?;
+        let mut procedures = Vec::new();
+        let mut module_index = 0;
+        while let Some(compiland) = modules.next()? {
+            if let Some(module_info) = pdb.module_info(&compiland)? {
+                let mut symbols = module_info.symbols()?;
+                while let Some(symbol) = symbols.next()? {
+                    if let Ok(SymbolData::Procedure(procedure)) = symbol.parse() {
+                        if let Some(start) = procedure.offset.to_rva(&address_map) {
+                            // Parameter counts are only used to label variables, so a type that can't be read isn't an error.
+                            let parameter_count = get_procedure_type(&id_finder, symbol.raw_kind(), procedure.type_index)
+                                .ok()
+                                .flatten()
+                                .and_then(|function_type| get_parameter_count(&type_finder, function_type).ok().flatten())
+                                .unwrap_or(0);
+                            procedures.push(IndexedProcedure {
+                                start: start.0,
+                                len: procedure.len,
+                                module_index,
+                                symbol_index: symbol.index(),
+                                parameter_count,
+                            });
+                        }
+                        // Skip the procedure's nested symbols.
+                        symbols.skip_to(procedure.end)?;
+                    }
+                }
+            }
+            module_index += 1;
+        }
+        procedures.sort_by_key(|procedure| procedure.start);
+        Ok(ProcedureIndex { procedures })
+    }
+
+    fn find(&self, rva: u32) -> Option<&IndexedProcedure> {
+        let index = self.procedures.partition_point(|procedure| procedure.start <= rva);
+        let procedure = self.procedures[..index].last()?;
+        (rva < procedure.start + procedure.len).then_some(procedure)
+    }
+}
+
+fn read_indexed_procedure_variables(
+    pdb: &mut PDB<'static, File>,
+    indexed: IndexedProcedure,
+    rva: u32,
+    is_32_bit: bool,
+) -> Result<Option<FunctionVariables>, pdb::Error> {
+    let address_map = pdb.address_map()?;
+    let debug_information = pdb.debug_information()?;
+    let compiland = match debug_information.modules()?.nth(indexed.module_index)? {
+        Some(compiland) => compiland,
+        None => return Ok(None),
+    };
+    let module_info = match pdb.module_info(&compiland)? {
+        Some(module_info) => module_info,
+        None => return Ok(None),
+    };
+    let mut symbols = module_info.symbols_at(indexed.symbol_index)?;
+    let procedure = match symbols.next()?.map(|symbol| symbol.parse()) {
+        Some(Ok(SymbolData::Procedure(procedure))) => procedure,
+        _ => return Ok(None),
+    };
+    let mut function = read_procedure_variables(&procedure, &mut symbols, rva, &address_map, is_32_bit)?;
+    // Mark the first variables as parameters, for PDBs that describe them with `S_REGREL32` or `S_REGISTER`, which
+    // don't say whether a variable is a parameter. The compiler emits the parameters first, in order.
+    if !function.variables.iter().any(|variable| variable.is_parameter) {
+        for variable in function.variables.iter_mut().take(indexed.parameter_count) {
+            variable.is_parameter = true;
+        }
+    }
+    Ok(Some(function))
+}
+
+/// Finds the function that contains `rva` in the module's PDB, and reads its variables.
+/// Returns `None` if the module has no PDB or the PDB has no function there.
+pub fn find_function_variables(module: &mut Module, rva: u32, is_32_bit: bool) -> Result<Option<FunctionVariables>, String> {
+    let indexed = match module.get_procedure_index().and_then(|index| index.find(rva)) {
+        Some(indexed) => *indexed,
+        None => return Ok(None),
+    };
+    match module.get_pdb() {
+        Ok(pdb) => read_indexed_procedure_variables(pdb, indexed, rva, is_32_bit).map_err(|e| e.to_string()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Prints the parameters and local variables of the function that `context` is in, with their locations and raw values.
+pub fn display_locals(context: &CONTEXT, process: &mut Process, memory_source: &dyn MemorySource) {
+    let address = context.instruction_pointer();
+    let is_32_bit = process.is_wow64;
+    let pointer_size = process.pointer_size();
+    let module = match process.get_containing_module_mut(address) {
+        Some(module) => module,
+        None => {
+            println!("No module contains {address:#018x}");
+            return;
+        }
+    };
+    let rva = (address - module.address) as u32;
+    let function = match find_function_variables(module, rva, is_32_bit) {
+        Ok(Some(function)) => function,
+        Ok(None) => {
+            println!("No symbols for the function at {address:#018x}");
+            return;
+        }
+        Err(e) => {
+            print_error!("Could not read symbols: {e}");
+            return;
+        }
+    };
+
+    println!("{}:", function.function_name);
+    if function.variables.is_empty() {
+        println!("    No locals");
+    }
+    for variable in &function.variables {
+        let kind = if variable.is_parameter { "param" } else { "local" };
+        let value = function.format_value(variable, context, pointer_size, memory_source);
+        println!("    {kind} {:<24} {:<16} {value}", variable.name, variable.location.to_string());
+    }
+}
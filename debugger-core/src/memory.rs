@@ -0,0 +1,305 @@
+use core::ffi::c_void;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use windows::{
+    Win32::Foundation::HANDLE,
+    Win32::System::Diagnostics::Debug::{
+        FlushInstructionCache,
+        ReadProcessMemory,
+        WriteProcessMemory,
+    },
+};
+
+use crate::windows_wrapper::PAGE_SIZE;
+
+pub trait MemorySource {
+    /// Read `len` bytes, and return `Option<u8>` to represent what bytes are available in the range.
+    fn read_memory(&self, address: u64, len: usize) -> Result<Vec<Option<u8>>, String>;
+
+    /// Read up to `len` bytes, and stop at the first failure.
+    fn read_raw_memory(&self, address: u64, len: usize) -> Vec<u8>;
+
+    /// Write all of `data` to `address`, or return an error.
+    fn write_raw_memory(&self, address: u64, data: &[u8]) -> Result<(), String>;
+
+    /// Discard any cached memory, because the target may have changed it (e.g. because it ran).
+    fn invalidate_cache(&self) {}
+}
+
+/// Reads up to `max_count` items
+pub fn read_memory_array<T: Sized + Default>(
+    source: &dyn MemorySource,
+    address: u64,
+    max_count: usize,
+) -> Vec<T> {
+    let element_size = ::core::mem::size_of::<T>();
+    let max_bytes = max_count * element_size;
+    let raw_bytes = source.read_raw_memory(address, max_bytes);
+    let mut data = Vec::<T>::new();
+    let mut offset: usize = 0;
+    while offset + element_size <= raw_bytes.len() {
+        let mut item = T::default();
+        let dst: *mut u8 = &mut item as *mut T as *mut u8;
+        let src = &raw_bytes[offset] as *const u8;
+        unsafe { std::ptr::copy_nonoverlapping(src, dst, element_size) };
+        data.push(item);
+        offset += element_size;
+    }
+    data
+}
+
+/// Reads exactly `count` items, or returns an error.
+pub fn read_memory_full_array<T: Sized + Default>(
+    source: &dyn MemorySource,
+    address: u64,
+    count: usize,
+) -> Result<Vec<T>, &'static str> {
+    let arr = read_memory_array(source, address, count);
+    if arr.len() != count {
+        Err("Could not read all items")
+    } else {
+        Ok(arr)
+    }
+}
+
+pub fn read_memory_data<T: Sized + Default + Copy>(
+    source: &dyn MemorySource,
+    address: u64,
+) -> T {
+    let data = read_memory_array::<T>(source, address, 1);
+    data[0]
+}
+
+/// Read a null-terminated string from memory.
+pub fn read_memory_string(
+    source: &dyn MemorySource,
+    address: u64,
+    max_count: usize,
+    is_wide: bool,
+) -> String {
+    if is_wide {
+        let mut words = read_memory_array::<u16>(source, address, max_count);
+        let maybe_null_pos = words.iter().position(|&v| v == 0);
+        if let Some(null_pos) = maybe_null_pos {
+            words.truncate(null_pos);
+        }
+        String::from_utf16_lossy(&words)
+    } else {
+        let mut bytes = read_memory_array::<u8>(source, address, max_count);
+        let maybe_null_pos = bytes.iter().position(|&v| v == 0);
+        if let Some(null_pos) = maybe_null_pos {
+            bytes.truncate(null_pos);
+        }
+        // TODO: this is not quite right. Most strings read here are in the ANSI code page, so any non-ASCII characters that
+        // aren't valid UTF-8 are replaced instead of decoded.
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+/// Reads a string whose address is at `address`.
+pub fn read_memory_string_indirect(
+    source: &dyn MemorySource,
+    address: u64,
+    max_count: usize,
+    is_wide: bool,
+) -> String {
+    let string_addr = read_memory_data::<u64>(source, address);
+    read_memory_string(source, string_addr, max_count, is_wide)
+}
+
+/// How many bytes have been read from live targets, to see how much work the debugger does. See `get_bytes_read`.
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+
+/// Gets how many bytes have been read from the memory of live targets this session, not counting cached reads.
+pub fn get_bytes_read() -> u64 {
+    BYTES_READ.load(Ordering::Relaxed)
+}
+
+// Could have other memory sources in the future, like for dump files.
+struct LiveMemorySource {
+    process: HANDLE,
+}
+
+pub fn make_live_memory_source(process: HANDLE) -> Box<dyn MemorySource> {
+    Box::new(LiveMemorySource { process })
+}
+
+impl MemorySource for LiveMemorySource {
+    fn read_memory(&self, address: u64, len: usize) -> Result<Vec<Option<u8>>, String> {
+        let mut buffer: Vec<u8> = vec![0; len];
+        let mut data: Vec<Option<u8>> = vec![None; len];
+        let mut offset: usize = 0;
+
+        while offset < len {
+            let mut bytes_read: usize = 0;
+            let len_left = len - offset;
+            let cur_address = address + (offset as u64);
+
+            let result = unsafe {
+                ReadProcessMemory(
+                    self.process,
+                    cur_address as *const c_void,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    len_left,
+                    Some(&mut bytes_read as *mut usize),
+                )
+            };
+            // Reads fail when part of the range isn't readable, but still return the bytes before it, so the failure
+            // only matters if nothing was read.
+            if result.is_err() && bytes_read == 0 {
+                // The rest of the page can't be read either.
+                offset += (PAGE_SIZE - cur_address % PAGE_SIZE).min(len_left as u64) as usize;
+                continue;
+            }
+
+            BYTES_READ.fetch_add(bytes_read as u64, Ordering::Relaxed);
+            #[allow(clippy::needless_range_loop)]
+            for index in 0..bytes_read {
+                let data_index = offset + index;
+                data[data_index] = Some(buffer[index]);
+            }
+
+            if bytes_read > 0 {
+                offset += bytes_read;
+            } else {
+                // TODO: is this the right way to handle reading 0 bytes?
+                offset += 1;
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn read_raw_memory(&self, address: u64, len: usize) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0; len];
+        let mut bytes_read: usize = 0;
+
+        let result = unsafe {
+            ReadProcessMemory(
+                self.process,
+                address as *const c_void,
+                buffer.as_mut_ptr() as *mut c_void,
+                len,
+                Some(&mut bytes_read as *mut usize),
+            )
+        };
+
+        if result.is_err() {
+            bytes_read = 0;
+        }
+        BYTES_READ.fetch_add(bytes_read as u64, Ordering::Relaxed);
+
+        buffer.truncate(bytes_read);
+        buffer
+    }
+
+    fn write_raw_memory(&self, address: u64, data: &[u8]) -> Result<(), String> {
+        let mut bytes_written: usize = 0;
+
+        let result = unsafe {
+            WriteProcessMemory(
+                self.process,
+                address as *const c_void,
+                data.as_ptr() as *const c_void,
+                data.len(),
+                Some(&mut bytes_written as *mut usize),
+            )
+        };
+        result.map_err(|error| format!("WriteProcessMemory failed at {address:#x}: {error}"))?;
+
+        if bytes_written != data.len() {
+            return Err(format!("Only wrote {bytes_written} of {len} bytes at {address:#x}", len = data.len()));
+        }
+
+        // The written memory may be code (e.g. a breakpoint), so make sure the processor doesn't execute stale instructions.
+        let result = unsafe {
+            FlushInstructionCache(self.process, Some(address as *const c_void), data.len())
+        };
+        result.map_err(|error| format!("FlushInstructionCache failed at {address:#x}: {error}"))
+    }
+}
+
+/// Caches page-sized reads from another memory source, so that repeatedly reading the same memory (e.g. PE headers and
+/// export tables during symbol resolution) doesn't re-read it from the target. The cache must be invalidated whenever the
+/// target runs.
+struct CachingMemorySource {
+    inner: Box<dyn MemorySource>,
+    /// The readable bytes of each page, keyed by page address. Pages are shorter than `PAGE_SIZE` (or empty) if only
+    /// part of them (or none of them) could be read.
+    pages: RefCell<HashMap<u64, Vec<u8>>>,
+}
+
+pub fn make_caching_memory_source(inner: Box<dyn MemorySource>) -> Box<dyn MemorySource> {
+    Box::new(CachingMemorySource {
+        inner,
+        pages: RefCell::new(HashMap::new()),
+    })
+}
+
+impl CachingMemorySource {
+    /// Calls `f` with the bytes of each page in `[address, address + len)`, along with the offset within the page that
+    /// the range starts at, and the number of bytes from that offset that are in the range.
+    fn for_each_page(&self, address: u64, len: usize, mut f: impl FnMut(&[u8], usize, usize)) {
+        let mut pages = self.pages.borrow_mut();
+        let mut current_address = address;
+        let mut remaining = len;
+        while remaining > 0 {
+            let page_address = current_address & !(PAGE_SIZE - 1);
+            let offset = (current_address - page_address) as usize;
+            let count = (PAGE_SIZE as usize - offset).min(remaining);
+            let page = pages.entry(page_address)
+                .or_insert_with(|| self.inner.read_raw_memory(page_address, PAGE_SIZE as usize));
+            f(page, offset, count);
+
+            remaining -= count;
+            current_address = match current_address.checked_add(count as u64) {
+                Some(next_address) => next_address,
+                None => break,
+            };
+        }
+    }
+}
+
+impl MemorySource for CachingMemorySource {
+    fn read_memory(&self, address: u64, len: usize) -> Result<Vec<Option<u8>>, String> {
+        let mut data = Vec::<Option<u8>>::with_capacity(len);
+        self.for_each_page(address, len, |page, offset, count| {
+            data.extend((offset..offset + count).map(|index| page.get(index).copied()));
+        });
+        data.resize(len, None);
+        Ok(data)
+    }
+
+    fn read_raw_memory(&self, address: u64, len: usize) -> Vec<u8> {
+        let mut data = Vec::<u8>::with_capacity(len);
+        let mut failed = false;
+        self.for_each_page(address, len, |page, offset, count| {
+            if failed {
+                return;
+            }
+            let end = (offset + count).min(page.len());
+            if offset < end {
+                data.extend_from_slice(&page[offset..end]);
+            }
+            failed = end < offset + count;
+        });
+        data
+    }
+
+    fn write_raw_memory(&self, address: u64, data: &[u8]) -> Result<(), String> {
+        // Drop the affected pages rather than patching them, so that they are re-read with whatever the write did.
+        let first_page = address & !(PAGE_SIZE - 1);
+        let last_page = address.saturating_add(data.len().max(1) as u64 - 1) & !(PAGE_SIZE - 1);
+        self.pages.borrow_mut().retain(|&page_address, _| page_address < first_page || page_address > last_page);
+        self.inner.write_raw_memory(address, data)
+    }
+
+    fn invalidate_cache(&self) {
+        self.pages.borrow_mut().clear();
+        self.inner.invalidate_cache();
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    memory::{self, MemorySource},
+    name_resolution,
+    output,
+    process::Process,
+};
+
+/// How to interpret memory for display.
+#[derive(Copy, Clone, Debug)]
+pub enum DisplayFormat {
+    Bytes,
+    Words,
+    Dwords,
+    Qwords,
+    Pointers,
+    /// Pointer-sized values, one per line, with the symbol that each one points to, e.g. to read a stack or a vtable.
+    PointersWithSymbols,
+    Ascii,
+    Unicode,
+}
+
+impl DisplayFormat {
+    fn element_size(self, pointer_size: usize) -> usize {
+        match self {
+            DisplayFormat::Bytes | DisplayFormat::Ascii => 1,
+            DisplayFormat::Words | DisplayFormat::Unicode => 2,
+            DisplayFormat::Dwords => 4,
+            DisplayFormat::Qwords => 8,
+            DisplayFormat::Pointers | DisplayFormat::PointersWithSymbols => pointer_size,
+        }
+    }
+
+    /// The number of elements to display when no count is given. These match WinDbg.
+    pub fn default_count(self) -> u64 {
+        match self {
+            DisplayFormat::Bytes => 0x80,
+            DisplayFormat::Words => 0x40,
+            DisplayFormat::Dwords => 0x20,
+            DisplayFormat::Qwords | DisplayFormat::Pointers | DisplayFormat::PointersWithSymbols => 0x10,
+            // Strings stop at the null terminator, so this is just an upper bound.
+            DisplayFormat::Ascii | DisplayFormat::Unicode => 0x100,
+        }
+    }
+}
+
+/// We'll arbitrarily limit how much memory is displayed at once, so that a typo doesn't flood the console.
+const MAX_DISPLAY_BYTES: u64 = 0x10000;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Displays `count` elements of memory at `address`. For strings, `count` is the maximum length.
+/// The process is used for the size of a pointer, and to look up symbols.
+pub fn display_memory(format: DisplayFormat, address: u64, count: u64, process: &mut Process, memory_source: &dyn MemorySource) {
+    let pointer_size = process.pointer_size();
+    let element_size = format.element_size(pointer_size);
+    if count.saturating_mul(element_size as u64) > MAX_DISPLAY_BYTES {
+        println!("Count {count:#x} is too large. At most {MAX_DISPLAY_BYTES:#x} bytes can be displayed.");
+        return;
+    }
+
+    match format {
+        DisplayFormat::Ascii => {
+            let mut bytes = memory_source.read_raw_memory(address, count as usize);
+            if let Some(null_pos) = bytes.iter().position(|&byte| byte == 0) {
+                bytes.truncate(null_pos);
+            }
+            println!("{address:#018x}  \"{}\"", String::from_utf8_lossy(&bytes));
+        }
+        DisplayFormat::Unicode => {
+            let text = memory::read_memory_string(memory_source, address, count as usize, true);
+            println!("{address:#018x}  \"{text}\"");
+        }
+        _ => {
+            let bytes = match memory_source.read_memory(address, count as usize * element_size) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    print_error!("Could not read memory at {address:#018x}: {e}");
+                    return;
+                }
+            };
+            if bytes.iter().all(Option::is_none) {
+                print_error!("Could not read memory at {address:#018x}");
+                return;
+            }
+
+            if let DisplayFormat::PointersWithSymbols = format {
+                for (index, element) in bytes.chunks_exact(element_size).enumerate() {
+                    let element_address = address + (index * element_size) as u64;
+                    let symbol = get_element_value(element)
+                        .and_then(|value| name_resolution::resolve_address_to_name(value, process))
+                        .map(|name| output::symbol(&name))
+                        .unwrap_or_default();
+                    println!("{element_address:#018x}  {} {symbol}", format_element(element));
+                }
+                return;
+            }
+
+            // Bytes that can't be read are shown as `??`, like WinDbg, so that the rest of the range is still shown.
+            for (line_index, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+                let line_address = address + (line_index * BYTES_PER_LINE) as u64;
+                print!("{line_address:#018x} ");
+                for element in line.chunks_exact(element_size) {
+                    print!(" {}", format_element(element));
+                }
+
+                // Show the characters alongside the bytes, like a typical hex dump.
+                if let DisplayFormat::Bytes = format {
+                    let padding = (BYTES_PER_LINE - line.len()) * 3;
+                    let characters: String = line.iter()
+                        .map(|byte| match byte {
+                            Some(byte) if byte.is_ascii_graphic() || *byte == b' ' => *byte as char,
+                            Some(_) => '.',
+                            None => '?',
+                        })
+                        .collect();
+                    print!("{:padding$}  {characters}", "");
+                }
+                println!();
+            }
+        }
+    }
+}
+
+/// Gets a little-endian integer, or `None` if any of its bytes couldn't be read.
+fn get_element_value(element: &[Option<u8>]) -> Option<u64> {
+    element.iter().rev().try_fold(0u64, |value, byte| byte.map(|byte| (value << 8) | byte as u64))
+}
+
+/// Formats a little-endian integer as zero-padded hex, or as `?`s if any of its bytes couldn't be read.
+fn format_element(element: &[Option<u8>]) -> String {
+    let width = element.len() * 2;
+    match get_element_value(element) {
+        Some(value) => format!("{value:0width$x}"),
+        None => "?".repeat(width),
+    }
+}
@@ -0,0 +1,155 @@
+use windows::Win32::Foundation::HANDLE;
+
+use crate::{
+    environment_blocks,
+    memory::{self, MemorySource},
+    name_resolution,
+    output,
+    process::Process,
+    windows_wrapper::{self, MemoryRegion},
+};
+
+/// Stop searching for references after this many, in case the value is common, e.g. a small number.
+const MAX_REFERENCES: usize = 1000;
+
+/// Search regions in chunks of this size, so that a large region isn't read into one huge buffer.
+const SEARCH_CHUNK_SIZE: u64 = 0x10_0000;
+
+/// What a region of memory is used for, as far as we can tell.
+struct RegionUsages {
+    /// Allocation bases of thread stacks, with the thread that owns them.
+    stacks: Vec<(u64, String)>,
+    /// Allocation bases of heap segments.
+    heaps: Vec<u64>,
+}
+
+impl RegionUsages {
+    fn find(process_handle: HANDLE, process: &Process, memory_source: &dyn MemorySource) -> RegionUsages {
+        let mut usages = RegionUsages {
+            stacks: Vec::new(),
+            heaps: Vec::new(),
+        };
+
+        // Usage information is best-effort, so ignore threads whose TEB can't be read.
+        for thread_id in process.iterate_threads() {
+            let Ok(teb_address) = windows_wrapper::open_thread(thread_id)
+                .and_then(|thread| windows_wrapper::get_thread_environment_block_address(&thread)) else {
+                continue;
+            };
+
+            if let Ok((stack_limit, _)) = environment_blocks::get_stack_limits(teb_address, memory_source) {
+                // The whole reservation is the stack, not just the committed part starting at the limit.
+                if let Some(region) = windows_wrapper::query_memory_region(process_handle, stack_limit) {
+                    usages.stacks.push((region.allocation_base, format!("Stack (thread {thread_id:#x})")));
+                }
+            }
+
+            if usages.heaps.is_empty() {
+                if let Ok(heaps) = environment_blocks::get_peb_address(teb_address, memory_source)
+                    .and_then(|peb_address| environment_blocks::get_process_heaps(peb_address, memory_source)) {
+                    usages.heaps = heaps;
+                }
+            }
+        }
+
+        usages
+    }
+
+    fn describe(&self, region: &MemoryRegion, process: &Process) -> String {
+        if region.is_free() {
+            return String::from("Free");
+        }
+        if region.is_image() {
+            return match process.get_containing_module(region.base_address) {
+                Some(module) => format!("Image ({name})", name = module.name),
+                None => String::from("Image"),
+            };
+        }
+        if let Some((_, stack)) = self.stacks.iter().find(|(allocation_base, _)| *allocation_base == region.allocation_base) {
+            return stack.clone();
+        }
+        if self.heaps.contains(&region.allocation_base) {
+            return String::from("Heap");
+        }
+        String::new()
+    }
+}
+
+fn print_region(region: &MemoryRegion, usage: &str) {
+    println!(
+        "{base:#018x} {end:#018x} {size:#014x} {state:<11} {region_type:<11} {protection:<30} {usage}",
+        base = region.base_address,
+        end = region.base_address + region.size,
+        size = region.size,
+        state = region.state_name(),
+        region_type = region.type_name(),
+        protection = region.protection_name(),
+    );
+}
+
+fn print_header() {
+    println!("{:<18} {:<18} {:<14} {:<11} {:<11} {:<30} Usage", "Base", "End", "Size", "State", "Type", "Protection");
+}
+
+/// Prints the memory regions in use in the process's address space.
+pub fn display_memory_map(process_handle: HANDLE, process: &Process, memory_source: &dyn MemorySource) {
+    let usages = RegionUsages::find(process_handle, process, memory_source);
+    print_header();
+    for region in windows_wrapper::enumerate_memory_regions(process_handle).iter().filter(|region| !region.is_free()) {
+        print_region(region, &usages.describe(region, process));
+    }
+}
+
+/// Prints every location in the process's readable memory that holds a pointer to `target`, with what its region is used
+/// for, to find what refers to an object. Only pointer-aligned locations are searched. `range` is a start address and a
+/// size to search instead of the whole address space.
+pub fn display_references(target: u64, range: Option<(u64, u64)>, process_handle: HANDLE, process: &mut Process, memory_source: &dyn MemorySource) {
+    let usages = RegionUsages::find(process_handle, process, memory_source);
+    let (start, end) = range.map_or((0, u64::MAX), |(start, size)| (start, start.saturating_add(size)));
+    // Read the target directly, because the cache would keep a copy of every page that is searched.
+    let live_memory_source = memory::make_live_memory_source(process_handle);
+    let pointer_size = process.pointer_size();
+
+    let mut count = 0;
+    for region in windows_wrapper::enumerate_memory_regions(process_handle).iter().filter(|region| region.is_readable()) {
+        let region_end = (region.base_address + region.size).min(end);
+        let mut chunk_address = region.base_address.max(start).next_multiple_of(pointer_size as u64);
+        while chunk_address < region_end {
+            let chunk_size = (region_end - chunk_address).min(SEARCH_CHUNK_SIZE);
+            let bytes = live_memory_source.read_raw_memory(chunk_address, chunk_size as usize);
+            for (index, value) in bytes.chunks_exact(pointer_size).enumerate() {
+                let value = value.iter().rev().fold(0u64, |value, byte| (value << 8) | *byte as u64);
+                if value != target {
+                    continue;
+                }
+                let address = chunk_address + (index * pointer_size) as u64;
+                // Name locations in modules, e.g. global variables.
+                let name = if region.is_image() {
+                    name_resolution::resolve_address_to_name(address, process).map(|name| format!(" {}", output::symbol(&name))).unwrap_or_default()
+                } else {
+                    String::new()
+                };
+                println!("{} {:<30}{name}", output::address(address), usages.describe(region, process));
+                count += 1;
+                if count == MAX_REFERENCES {
+                    println!("Stopping after {MAX_REFERENCES} references");
+                    return;
+                }
+            }
+            chunk_address += chunk_size;
+        }
+    }
+    println!("Found {count} references to {target:#018x}");
+}
+
+/// Prints the memory region that contains `address`.
+pub fn display_memory_region(process_handle: HANDLE, address: u64, process: &Process, memory_source: &dyn MemorySource) {
+    match windows_wrapper::query_memory_region(process_handle, address) {
+        Some(region) => {
+            let usages = RegionUsages::find(process_handle, process, memory_source);
+            print_header();
+            print_region(&region, &usages.describe(&region, process));
+        }
+        None => println!("{address:#018x} is not in the process's address space"),
+    }
+}
@@ -0,0 +1,277 @@
+use std::path::Path;
+
+use windows::Win32::{Foundation::NTSTATUS, System::Diagnostics::Debug::CONTEXT};
+
+use crate::{
+    memory::MemorySource,
+    windows_wrapper::{ExceptionRecord, ThreadId},
+};
+
+// See https://learn.microsoft.com/en-us/windows/win32/api/minidumpapiset/ for the format.
+// The structures are packed to 4 bytes, so they are read field by field instead of being mapped onto Rust structs.
+
+const MINIDUMP_SIGNATURE: u32 = 0x504D_444D; // "MDMP"
+
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const EXCEPTION_STREAM: u32 = 6;
+const MEMORY64_LIST_STREAM: u32 = 9;
+
+const HEADER_SIZE: usize = 32;
+const DIRECTORY_ENTRY_SIZE: usize = 12;
+const THREAD_SIZE: usize = 48;
+const MODULE_SIZE: usize = 108;
+const MEMORY_DESCRIPTOR_SIZE: usize = 16;
+const MEMORY_DESCRIPTOR64_SIZE: usize = 16;
+
+pub struct MinidumpThread {
+    pub id: ThreadId,
+    pub suspend_count: u32,
+    /// The address of the thread environment block (TEB).
+    pub teb: u64,
+    pub context: CONTEXT,
+}
+
+pub struct MinidumpModule {
+    pub base_address: u64,
+    pub size: u32,
+    pub name: String,
+}
+
+pub struct MinidumpException {
+    pub thread: ThreadId,
+    pub record: ExceptionRecord,
+}
+
+pub struct Minidump {
+    pub threads: Vec<MinidumpThread>,
+    pub modules: Vec<MinidumpModule>,
+    /// The exception that the dump was written for, if any.
+    pub exception: Option<MinidumpException>,
+    pub memory_source: Box<dyn MemorySource>,
+}
+
+impl Minidump {
+    /// Whether the dump is of a WOW64 process. Minidumps don't record this, so it is guessed from whether the WOW64 layer's
+    /// `wow64.dll` is loaded. The threads' contexts in such a dump are their 64-bit ones.
+    pub fn is_wow64(&self) -> bool {
+        self.modules.iter().any(|module| {
+            let file_name = module.name.rsplit(['\\', '/']).next().unwrap_or(&module.name);
+            file_name.eq_ignore_ascii_case("wow64.dll")
+        })
+    }
+}
+
+/// A range of the dumped process's memory that is stored in the dump file.
+struct MemoryRange {
+    address: u64,
+    size: u64,
+    file_offset: usize,
+}
+
+/// Reads the dumped process's memory from the dump file.
+struct MinidumpMemorySource {
+    data: Vec<u8>,
+    /// Sorted by address.
+    ranges: Vec<MemoryRange>,
+}
+
+impl MinidumpMemorySource {
+    fn find_range(&self, address: u64) -> Option<&MemoryRange> {
+        let index = self.ranges.partition_point(|range| range.address + range.size <= address);
+        self.ranges.get(index).filter(|range| range.address <= address)
+    }
+}
+
+impl MemorySource for MinidumpMemorySource {
+    fn read_memory(&self, address: u64, len: usize) -> Result<Vec<Option<u8>>, String> {
+        let mut data = Vec::<Option<u8>>::with_capacity(len);
+        for offset in 0..len as u64 {
+            let byte = self.find_range(address + offset)
+                .map(|range| self.data[range.file_offset + (address + offset - range.address) as usize]);
+            data.push(byte);
+        }
+        Ok(data)
+    }
+
+    fn read_raw_memory(&self, address: u64, len: usize) -> Vec<u8> {
+        let mut data = Vec::<u8>::with_capacity(len);
+        while data.len() < len {
+            let current_address = address + data.len() as u64;
+            let range = match self.find_range(current_address) {
+                Some(range) => range,
+                None => break,
+            };
+            let offset_in_range = (current_address - range.address) as usize;
+            let count = (range.size as usize - offset_in_range).min(len - data.len());
+            let start = range.file_offset + offset_in_range;
+            data.extend_from_slice(&self.data[start..start + count]);
+        }
+        data
+    }
+
+    fn write_raw_memory(&self, address: u64, _data: &[u8]) -> Result<(), String> {
+        Err(format!("Cannot write to {address:#x} because the target is a dump file"))
+    }
+}
+
+/// Bounds-checked little-endian reads from the dump file.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8], String> {
+        offset.checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| format!("The dump is truncated: could not read {len} bytes at offset {offset:#x}"))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(offset, 8)?.try_into().unwrap()))
+    }
+
+    /// Reads a `MINIDUMP_STRING`, which is a length in bytes followed by UTF-16 characters.
+    fn string(&self, offset: usize) -> Result<String, String> {
+        let length_in_bytes = self.u32(offset)? as usize;
+        let bytes = self.bytes(offset + 4, length_in_bytes)?;
+        let characters: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        Ok(String::from_utf16_lossy(&characters))
+    }
+
+    /// Reads a `CONTEXT` from a location descriptor, which is a size followed by an offset.
+    fn context(&self, location_offset: usize) -> Result<CONTEXT, String> {
+        let size = self.u32(location_offset)? as usize;
+        let offset = self.u32(location_offset + 4)? as usize;
+        let bytes = self.bytes(offset, size.min(std::mem::size_of::<CONTEXT>()))?;
+        let mut context: CONTEXT = unsafe { std::mem::zeroed() };
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), &mut context as *mut CONTEXT as *mut u8, bytes.len()) };
+        Ok(context)
+    }
+}
+
+/// Loads a minidump file, such as one written by `MiniDumpWriteDump` or Task Manager.
+pub fn load_minidump(path: &str) -> Result<Minidump, String> {
+    let data = std::fs::read(path).map_err(|error| format!("Could not read {path}: {error}"))?;
+    let reader = Reader { data: &data };
+
+    if data.len() < HEADER_SIZE || reader.u32(0)? != MINIDUMP_SIGNATURE {
+        return Err(format!("{path} is not a minidump file"));
+    }
+    let stream_count = reader.u32(8)? as usize;
+    let directory_offset = reader.u32(12)? as usize;
+
+    let mut threads = Vec::<MinidumpThread>::new();
+    let mut modules = Vec::<MinidumpModule>::new();
+    let mut exception = None;
+    let mut exception_context = None;
+    let mut ranges = Vec::<MemoryRange>::new();
+
+    for stream_index in 0..stream_count {
+        let entry_offset = directory_offset + stream_index * DIRECTORY_ENTRY_SIZE;
+        let stream_type = reader.u32(entry_offset)?;
+        let stream_offset = reader.u32(entry_offset + 8)? as usize;
+
+        match stream_type {
+            THREAD_LIST_STREAM => {
+                let count = reader.u32(stream_offset)? as usize;
+                for index in 0..count {
+                    let thread_offset = stream_offset + 4 + index * THREAD_SIZE;
+                    threads.push(MinidumpThread {
+                        id: ThreadId::from(reader.u32(thread_offset)?),
+                        suspend_count: reader.u32(thread_offset + 4)?,
+                        teb: reader.u64(thread_offset + 16)?,
+                        context: reader.context(thread_offset + 40)?,
+                    });
+                }
+            }
+            MODULE_LIST_STREAM => {
+                let count = reader.u32(stream_offset)? as usize;
+                for index in 0..count {
+                    let module_offset = stream_offset + 4 + index * MODULE_SIZE;
+                    let path = reader.string(reader.u32(module_offset + 20)? as usize)?;
+                    // Use the file name, like we do for modules in live processes.
+                    let name = Path::new(&path)
+                        .file_name()
+                        .map_or(path.clone(), |name| name.to_string_lossy().to_string());
+                    modules.push(MinidumpModule {
+                        base_address: reader.u64(module_offset)?,
+                        size: reader.u32(module_offset + 8)?,
+                        name,
+                    });
+                }
+            }
+            MEMORY_LIST_STREAM => {
+                let count = reader.u32(stream_offset)? as usize;
+                for index in 0..count {
+                    let descriptor_offset = stream_offset + 4 + index * MEMORY_DESCRIPTOR_SIZE;
+                    let range = MemoryRange {
+                        address: reader.u64(descriptor_offset)?,
+                        size: reader.u32(descriptor_offset + 8)? as u64,
+                        file_offset: reader.u32(descriptor_offset + 12)? as usize,
+                    };
+                    reader.bytes(range.file_offset, range.size as usize)?;
+                    ranges.push(range);
+                }
+            }
+            MEMORY64_LIST_STREAM => {
+                // The memory for all ranges is stored contiguously, starting at the base offset.
+                let count = reader.u64(stream_offset)? as usize;
+                let mut file_offset = reader.u64(stream_offset + 8)? as usize;
+                for index in 0..count {
+                    let descriptor_offset = stream_offset + 16 + index * MEMORY_DESCRIPTOR64_SIZE;
+                    let range = MemoryRange {
+                        address: reader.u64(descriptor_offset)?,
+                        size: reader.u64(descriptor_offset + 8)?,
+                        file_offset,
+                    };
+                    reader.bytes(range.file_offset, range.size as usize)?;
+                    file_offset += range.size as usize;
+                    ranges.push(range);
+                }
+            }
+            EXCEPTION_STREAM => {
+                // The `MINIDUMP_EXCEPTION` record follows the thread ID and padding. Nested records aren't in the dump.
+                let record_offset = stream_offset + 8;
+                let parameter_count = (reader.u32(record_offset + 24)? as usize).min(15);
+                let parameters = (0..parameter_count)
+                    .map(|index| reader.u64(record_offset + 32 + index * 8))
+                    .collect::<Result<Vec<u64>, String>>()?;
+                exception = Some(MinidumpException {
+                    thread: ThreadId::from(reader.u32(stream_offset)?),
+                    record: ExceptionRecord {
+                        code: NTSTATUS(reader.u32(record_offset)? as i32),
+                        flags: reader.u32(record_offset + 4)?,
+                        address: reader.u64(record_offset + 16)?,
+                        parameters,
+                        nested: None,
+                    },
+                });
+                exception_context = Some(reader.context(stream_offset + 160)?);
+            }
+            _ => {}
+        }
+    }
+
+    // The exception's context is where the exception happened, which is more useful than the thread's context in the
+    // thread list, which is inside the code that wrote the dump.
+    if let (Some(exception), Some(context)) = (&exception, exception_context) {
+        if let Some(thread) = threads.iter_mut().find(|thread| thread.id == exception.thread) {
+            thread.context = context;
+        }
+    }
+
+    ranges.sort_by_key(|range| range.address);
+
+    Ok(Minidump {
+        threads,
+        modules,
+        exception,
+        memory_source: Box::new(MinidumpMemorySource { data, ranges }),
+    })
+}
@@ -0,0 +1,616 @@
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    mem::size_of,
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+use pdb::{FallibleIterator, PDB};
+
+use windows::Win32::System::{
+    Diagnostics::Debug::{
+        IMAGE_DEBUG_DIRECTORY,
+        IMAGE_DEBUG_TYPE_CODEVIEW,
+        IMAGE_DIRECTORY_ENTRY_DEBUG,
+        IMAGE_DIRECTORY_ENTRY_EXCEPTION,
+        IMAGE_DATA_DIRECTORY,
+        IMAGE_DIRECTORY_ENTRY_EXPORT,
+        IMAGE_DIRECTORY_ENTRY_TLS,
+        IMAGE_NT_HEADERS32,
+        IMAGE_NT_HEADERS64,
+        IMAGE_NT_OPTIONAL_HDR32_MAGIC,
+    },
+    SystemServices::{
+        IMAGE_DOS_HEADER,
+        IMAGE_EXPORT_DIRECTORY,
+        IMAGE_TLS_DIRECTORY32,
+        IMAGE_TLS_DIRECTORY64,
+    },
+};
+
+use crate::{
+    arch::Architecture,
+    dwarf::{self, DwarfSymbols},
+    locals::ProcedureIndex,
+    memory::{*, self},
+    symbols,
+};
+
+type ModuleName = String;
+type PdbName = String;
+type PdbLoadError = String;
+/// Symbols' addresses and names.
+type SymbolList = Vec<(u64, String)>;
+
+/// More than any real image has. Stops us from reading forever if the callback array isn't null-terminated.
+const MAX_TLS_CALLBACKS: usize = 256;
+
+pub struct Module {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+    /// `None` if the image's machine type isn't one that we know about.
+    pub architecture: Option<Architecture>,
+    /// The address of `AddressOfEntryPoint`, or `None` if the image doesn't have one (e.g. resource-only DLLs).
+    pub entry_point: Option<u64>,
+    pub exports: Vec::<Export>,
+    pub pdb_name: Option<String>,
+    pub pdb_info: Option<PdbInfo>,
+    /// Use `get_pdb`, which waits for the PDB to be found if it is still being looked for.
+    pdb: Result<PDB<'static, File>, PdbLoadError>,
+    /// The result of looking for the PDB on another thread, until it's needed. See `symbols::find_pdb_in_background`.
+    pending_pdb: Option<Receiver<Result<PathBuf, String>>>,
+    /// Sorted by address.
+    pub runtime_functions: Vec::<RuntimeFunction>,
+    /// The TLS directory, if any. The callbacks are read from it on demand. See `read_tls_callbacks`.
+    tls_directory: IMAGE_DATA_DIRECTORY,
+    /// Read from the PDB on first use, because it's slow to read. See `get_line_table`.
+    line_table: Option<LineTable>,
+    /// Built on first use. See `get_symbol_index`.
+    symbol_index: Option<SymbolIndex>,
+    /// Built from the PDB on first use. See `get_procedure_index`.
+    procedure_index: Option<ProcedureIndex>,
+    /// Read from the image file on first use, for modules without a PDB. See `get_dwarf_symbols`.
+    dwarf_symbols: Option<DwarfSymbols>,
+}
+
+/// Maps addresses to the names of exports and PDB public functions, for finding the symbol nearest to an address.
+#[derive(Default)]
+pub struct SymbolIndex {
+    /// Sorted by address. When an export and a public symbol have the same address, the public symbol comes last.
+    symbols: Vec<(u64, String)>,
+    /// The PDB's public symbols that aren't functions, e.g. global variables. They're kept out of `symbols` so that code
+    /// addresses are always described relative to a function.
+    data_symbols: Vec<(u64, String)>,
+}
+
+impl SymbolIndex {
+    /// Finds the symbol with the closest address at or before `address`.
+    pub fn find_nearest(&self, address: u64) -> Option<(u64, &str)> {
+        let index = self.symbols.partition_point(|(symbol_address, _)| *symbol_address <= address);
+        if index == 0 {
+            return None;
+        }
+        let (symbol_address, name) = &self.symbols[index - 1];
+        Some((*symbol_address, name))
+    }
+
+    /// Finds the address of the symbol with exactly this name.
+    pub fn find_by_name(&self, name: &str) -> Option<u64> {
+        self.iterate().find(|(_, symbol_name)| symbol_name == name).map(|(address, _)| *address)
+    }
+
+    /// Iterates over the functions and then the data symbols, as `(address, name)`.
+    pub fn iterate(&self) -> impl Iterator<Item = &(u64, String)> {
+        self.symbols.iter().chain(self.data_symbols.iter())
+    }
+}
+
+/// Maps addresses to source lines.
+#[derive(Default)]
+pub struct LineTable {
+    files: Vec<String>,
+    /// Sorted by address.
+    records: Vec<LineRecord>,
+}
+
+pub struct LineRecord {
+    pub rva: u32,
+    /// 0 means the line continues until the next one.
+    pub length: u32,
+    /// An index into the line table's files.
+    pub file_index: usize,
+    pub line: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: u32,
+}
+
+impl std::fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+impl LineTable {
+    pub fn new(files: Vec<String>, mut records: Vec<LineRecord>) -> LineTable {
+        records.sort_by_key(|record| record.rva);
+        for index in 1..records.len() {
+            if records[index - 1].length == 0 {
+                records[index - 1].length = records[index].rva - records[index - 1].rva;
+            }
+        }
+        LineTable { files, records }
+    }
+
+    pub fn find_line(&self, rva: u32) -> Option<SourceLine> {
+        let index = self.records.partition_point(|record| record.rva <= rva);
+        if index == 0 {
+            return None;
+        }
+
+        let record = &self.records[index - 1];
+        if rva - record.rva < record.length {
+            Some(SourceLine { file: self.files[record.file_index].clone(), line: record.line })
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Export {
+    pub name: Option<String>,
+    /// This is the "biased" ordinal.
+    pub ordinal: u32,
+    pub target: ExportTarget,
+}
+
+impl std::fmt::Display for Export {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(str) = &self.name {
+            write!(f, "{}", str)
+        } else {
+            write!(f, "Ordinal{}", self.ordinal)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ExportTarget {
+    /// Relative Virtual Address
+    Rva(u64),
+
+    /// Will be forwarded to the export in a target DLL
+    /// Explanation: https://devblogs.microsoft.com/oldnewthing/20060719-24/?p=30473
+    Forwarder(String),
+}
+
+/// An entry in the exception directory (`.pdata`), which describes how to unwind a function's stack frame.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct RuntimeFunction {
+    pub begin_address: u32,
+    pub end_address: u32,
+    pub unwind_info_address: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct PdbInfo {
+    pub signature: u32,
+    pub guid: windows::core::GUID,
+    pub age: u32,
+    // Null terminated name goes after the end.
+}
+
+impl Default for PdbInfo {
+    fn default() -> Self {
+        unsafe { ::core::mem::zeroed() }
+    }
+}
+
+impl Module {
+    pub fn from_memory_view(
+        module_address: u64,
+        module_name: Option<String>,
+        memory_source: &dyn MemorySource,
+    ) -> Result<Module, String> {
+        let dos_header: IMAGE_DOS_HEADER = memory::read_memory_data(memory_source, module_address);
+
+        // TODO: We assume that the headers are accurate, even if it means we could read outside the bounds of the module.
+        //       Ideally this would do a bounds check.
+        let pe_header_addr = module_address + dos_header.e_lfanew as u64;
+
+        // The file header is the same for 32-bit and 64-bit images, but the optional header that follows it is not.
+        // The optional header's magic number says which it is.
+        let pe_header: IMAGE_NT_HEADERS64 = memory::read_memory_data(memory_source, pe_header_addr);
+        let (size_of_image, entry_point_rva, data_directories) = if pe_header.OptionalHeader.Magic == IMAGE_NT_OPTIONAL_HDR32_MAGIC {
+            let pe_header: IMAGE_NT_HEADERS32 = memory::read_memory_data(memory_source, pe_header_addr);
+            (pe_header.OptionalHeader.SizeOfImage, pe_header.OptionalHeader.AddressOfEntryPoint, pe_header.OptionalHeader.DataDirectory)
+        } else {
+            (pe_header.OptionalHeader.SizeOfImage, pe_header.OptionalHeader.AddressOfEntryPoint, pe_header.OptionalHeader.DataDirectory)
+        };
+        let architecture = Architecture::from_machine(pe_header.FileHeader.Machine);
+
+        let (pdb_info, pdb_name) = Module::read_debug_info(&data_directories, module_address, memory_source);
+        // Looking for the PDB can take a while, e.g. if it's downloaded, so it's done in the background. Modules are
+        // usually loaded in bursts, which are then searched for at the same time.
+        let pending_pdb = match (&pdb_name, &pdb_info) {
+            (Some(pdb_name), Some(pdb_info)) => Some(symbols::find_pdb_in_background(pdb_name.clone(), *pdb_info)),
+            _ => None,
+        };
+        let (exports, export_table_module_name) = Module::read_exports(&data_directories, module_address, memory_source)?;
+        // 32-bit x86 images don't have unwind data. Their exception directory, if any, is in a different format.
+        let runtime_functions = if architecture == Some(Architecture::X86) {
+            Vec::new()
+        } else {
+            Module::read_runtime_functions(&data_directories, module_address, memory_source)
+        };
+
+        let module_name = module_name
+            .or(export_table_module_name)
+            .unwrap_or_else(|| format!("module_{module_address:X}"));
+        tracing::debug!(
+            "Read {module_name} at {module_address:#018x}: {} exports, {} runtime functions, PDB {}",
+            exports.len(),
+            runtime_functions.len(),
+            pdb_name.as_deref().unwrap_or("<none>"),
+        );
+
+        Ok(Module {
+            name: module_name,
+            address: module_address,
+            size: size_of_image as u64,
+            architecture,
+            entry_point: (entry_point_rva != 0).then_some(module_address + entry_point_rva as u64),
+            exports,
+            pdb_name,
+            pdb_info,
+            pdb: Err(String::from("No matching PDB")),
+            pending_pdb,
+            runtime_functions,
+            tls_directory: data_directories[IMAGE_DIRECTORY_ENTRY_TLS.0 as usize],
+            line_table: None,
+            symbol_index: None,
+            procedure_index: None,
+            dwarf_symbols: None,
+        })
+    }
+
+    /// Finds the PDB again, e.g. after the symbol path changed or the PDB was copied into place, and discards everything
+    /// that was read from the old one.
+    pub fn reload_symbols(&mut self) {
+        if let (Some(pdb_name), Some(pdb_info)) = (&self.pdb_name, &self.pdb_info) {
+            self.pending_pdb = None;
+            self.pdb = symbols::find_pdb(pdb_name, pdb_info);
+        }
+        self.line_table = None;
+        self.symbol_index = None;
+        self.procedure_index = None;
+        self.dwarf_symbols = None;
+    }
+
+    /// Gets the functions and line table from the module's DWARF info, reading it on first use.
+    /// This is empty if the module has no DWARF info.
+    pub fn get_dwarf_symbols(&mut self) -> &DwarfSymbols {
+        if self.dwarf_symbols.is_none() {
+            // TODO: handle errors.
+            self.dwarf_symbols = Some(dwarf::read_dwarf_symbols(Path::new(&self.name)).unwrap_or_default());
+        }
+        self.dwarf_symbols.as_ref().unwrap()
+    }
+
+    /// Gets the PDB, or why there isn't one. Waits for it to be found if it's still being looked for.
+    pub fn get_pdb(&mut self) -> Result<&mut PDB<'static, File>, &PdbLoadError> {
+        self.finish_finding_pdb();
+        self.pdb.as_mut().map_err(|e| &*e)
+    }
+
+    fn finish_finding_pdb(&mut self) {
+        if let Some(pending_pdb) = self.pending_pdb.take() {
+            let path = pending_pdb.recv().unwrap_or_else(|_| Err(String::from("The search for the PDB stopped")));
+            self.pdb = path.and_then(|path| symbols::open_pdb(&path));
+            if let Err(e) = &self.pdb {
+                tracing::debug!("No symbols for {}: {e}", self.name);
+            }
+        }
+    }
+
+    /// Gets the line table from the PDB, or from the DWARF info if there is no PDB, reading it on first use.
+    pub fn get_line_table(&mut self) -> Option<&LineTable> {
+        self.finish_finding_pdb();
+        if self.pdb.is_err() {
+            return Some(&self.get_dwarf_symbols().line_table);
+        }
+        if self.line_table.is_none() {
+            let pdb = self.pdb.as_mut().ok()?;
+            // TODO: handle errors.
+            self.line_table = Some(Module::read_line_table(pdb).unwrap_or_default());
+        }
+        self.line_table.as_ref()
+    }
+
+    /// Gets the index of the functions in the PDB, building it on first use. Returns `None` if there is no PDB.
+    pub fn get_procedure_index(&mut self) -> Option<&ProcedureIndex> {
+        if self.procedure_index.is_none() {
+            let pdb = self.get_pdb().ok()?;
+            let procedure_index = ProcedureIndex::build(pdb).unwrap_or_else(|e| {
+                tracing::warn!("Could not index the functions in the PDB: {e}");
+                ProcedureIndex::default()
+            });
+            self.procedure_index = Some(procedure_index);
+        }
+        self.procedure_index.as_ref()
+    }
+
+    fn read_line_table(pdb: &mut PDB<'static, File>) -> Result<LineTable, pdb::Error> {
+        let address_map = pdb.address_map()?;
+        let string_table = pdb.string_table()?;
+        let debug_info = pdb.debug_information()?;
+
+        let mut files = Vec::<String>::new();
+        let mut file_indices = HashMap::<String, usize>::new();
+        let mut records = Vec::<LineRecord>::new();
+
+        let mut modules = debug_info.modules()?;
+        while let Some(module) = modules.next()? {
+            let module_info = match pdb.module_info(&module)? {
+                Some(module_info) => module_info,
+                None => continue,
+            };
+            let line_program = module_info.line_program()?;
+            let mut lines = line_program.lines();
+            while let Some(line_info) = lines.next()? {
+                let rva = match line_info.offset.to_rva(&address_map) {
+                    Some(rva) => rva.0,
+                    None => continue,
+                };
+
+                let file_info = line_program.get_file_info(line_info.file_index)?;
+                let file_name = file_info.name.to_string_lossy(&string_table)?.into_owned();
+                let file_index = *file_indices.entry(file_name).or_insert_with_key(|file_name| {
+                    files.push(file_name.clone());
+                    files.len() - 1
+                });
+
+                records.push(LineRecord { rva, length: line_info.length.unwrap_or(0), file_index, line: line_info.line_start });
+            }
+        }
+
+        Ok(LineTable::new(files, records))
+    }
+
+    /// Gets the index of exports and PDB public symbols (or DWARF functions), building it on first use. The PDB's symbol
+    /// stream is slow to read, so this is what name lookups should use.
+    pub fn get_symbol_index(&mut self) -> &SymbolIndex {
+        if self.symbol_index.is_none() {
+            let mut symbols: Vec<(u64, String)> = self.exports.iter()
+                .filter_map(|export| match export.target {
+                    ExportTarget::Rva(address) => Some((address, export.to_string())),
+                    ExportTarget::Forwarder(_) => None,
+                })
+                .collect();
+            let mut data_symbols = Vec::new();
+            self.finish_finding_pdb();
+            match self.pdb.as_mut() {
+                Ok(pdb) => {
+                    // TODO: handle errors.
+                    if let Ok((mut functions, data)) = Module::read_public_symbols(pdb, self.address) {
+                        symbols.append(&mut functions);
+                        data_symbols = data;
+                    }
+                }
+                Err(_) => {
+                    let module_address = self.address;
+                    let functions = &self.get_dwarf_symbols().functions;
+                    symbols.extend(functions.iter().map(|(rva, name)| (module_address + *rva as u64, name.clone())));
+                }
+            }
+            // The sort is stable, so public symbols stay after exports with the same address.
+            symbols.sort_by_key(|(address, _)| *address);
+            data_symbols.sort_by_key(|(address, _)| *address);
+            self.symbol_index = Some(SymbolIndex { symbols, data_symbols });
+        }
+        self.symbol_index.as_ref().unwrap()
+    }
+
+    /// Gets the names of the symbols that are known without reading the PDB, for tab completion. These are the exports, and
+    /// also the PDB's functions if the symbol index has already been built.
+    pub fn get_loaded_symbol_names(&self) -> Vec<String> {
+        match &self.symbol_index {
+            Some(symbol_index) => symbol_index.iterate().map(|(_, name)| name.clone()).collect(),
+            None => self.exports.iter().filter_map(|export| export.name.clone()).collect(),
+        }
+    }
+
+    pub fn has_symbol_index(&self) -> bool {
+        self.symbol_index.is_some()
+    }
+
+    /// Reads the public symbols, as the functions and the other symbols.
+    fn read_public_symbols(pdb: &mut PDB<'static, File>, module_address: u64) -> Result<(SymbolList, SymbolList), pdb::Error> {
+        let symbol_table = pdb.global_symbols()?;
+        let address_map = pdb.address_map()?;
+        let mut functions = Vec::new();
+        let mut data_symbols = Vec::new();
+        let mut symbols = symbol_table.iter();
+        while let Some(symbol) = symbols.next()? {
+            if let Ok(pdb::SymbolData::Public(data)) = symbol.parse() {
+                if let Some(rva) = data.offset.to_rva(&address_map) {
+                    let symbol = (module_address + rva.0 as u64, data.name.to_string().to_string());
+                    if data.function {
+                        functions.push(symbol);
+                    } else {
+                        data_symbols.push(symbol);
+                    }
+                }
+            }
+        }
+        Ok((functions, data_symbols))
+    }
+
+    pub fn contains_address(&self, address: u64) -> bool {
+        let end = self.address + self.size;
+        self.address <= address && address < end
+    }
+
+    /// Finds the unwind data for the function containing `address`.
+    /// Returns `None` for leaf functions, which don't have unwind data.
+    pub fn find_runtime_function(&self, address: u64) -> Option<&RuntimeFunction> {
+        let rva = address.checked_sub(self.address)?;
+        let index = self.runtime_functions.partition_point(|function| function.begin_address as u64 <= rva);
+        if index == 0 {
+            return None;
+        }
+
+        let function = &self.runtime_functions[index - 1];
+        if rva < function.end_address as u64 {
+            Some(function)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the addresses of the TLS callbacks, which the loader calls before the entry point, and again whenever a
+    /// thread starts or exits. They're read when asked for rather than at load, because the array holds absolute
+    /// addresses that aren't fixed up until the loader relocates the image.
+    pub fn read_tls_callbacks(&self, memory_source: &dyn MemorySource) -> Result<Vec<u64>, String> {
+        if self.tls_directory.VirtualAddress == 0 {
+            return Ok(Vec::new());
+        }
+
+        let directory_address = self.address + self.tls_directory.VirtualAddress as u64;
+        let is_32_bit = self.architecture == Some(Architecture::X86);
+        let callbacks_address = if is_32_bit {
+            let directory = memory::read_memory_full_array::<IMAGE_TLS_DIRECTORY32>(memory_source, directory_address, 1)?;
+            directory[0].AddressOfCallBacks as u64
+        } else {
+            let directory = memory::read_memory_full_array::<IMAGE_TLS_DIRECTORY64>(memory_source, directory_address, 1)?;
+            directory[0].AddressOfCallBacks
+        };
+        if callbacks_address == 0 {
+            return Ok(Vec::new());
+        }
+
+        let callbacks: Vec<u64> = if is_32_bit {
+            memory::read_memory_array::<u32>(memory_source, callbacks_address, MAX_TLS_CALLBACKS)
+                .into_iter()
+                .map(u64::from)
+                .collect()
+        } else {
+            memory::read_memory_array::<u64>(memory_source, callbacks_address, MAX_TLS_CALLBACKS)
+        };
+        Ok(callbacks.into_iter().take_while(|&callback| callback != 0).collect())
+    }
+
+    fn read_debug_info(
+        data_directories: &[IMAGE_DATA_DIRECTORY],
+        module_address: u64,
+        memory_source: &dyn MemorySource,
+    ) -> (Option<PdbInfo>, Option<PdbName>) {
+        let mut pdb_info_result: Option<PdbInfo> = None;
+        let mut pdb_name_result: Option<PdbName> = None;
+
+        let debug_table_info = data_directories[IMAGE_DIRECTORY_ENTRY_DEBUG.0 as usize];
+        if debug_table_info.VirtualAddress != 0 {
+            let dir_size = size_of::<IMAGE_DEBUG_DIRECTORY>() as u64;
+            // We'll arbitrarily limit to 20 entries to keep it sane.
+            let count = std::cmp::min(debug_table_info.Size as u64 / dir_size, 20);
+            for dir_index in 0..count {
+                let debug_dir_addr = module_address + (debug_table_info.VirtualAddress as u64) + (dir_index * dir_size);
+                let debug_dir: IMAGE_DEBUG_DIRECTORY = memory::read_memory_data(memory_source, debug_dir_addr);
+                if debug_dir.Type == IMAGE_DEBUG_TYPE_CODEVIEW {
+                    let pdb_info_addr = module_address + debug_dir.AddressOfRawData as u64;
+                    let pdb_info: PdbInfo = memory::read_memory_data(memory_source, pdb_info_addr);
+                    // TODO: verify that `pdb_info.signature` is `RSDS`.
+                    let pdb_name_addr = pdb_info_addr + size_of::<PdbInfo>() as u64;
+                    let pdb_name_max_size = debug_dir.SizeOfData as usize - size_of::<PdbInfo>();
+                    let pdb_name = memory::read_memory_string(memory_source, pdb_name_addr, pdb_name_max_size, false);
+
+                    pdb_info_result = Some(pdb_info);
+                    pdb_name_result = Some(pdb_name);
+                }
+            }
+        }
+
+        (pdb_info_result, pdb_name_result)
+    }
+
+    fn read_exports(
+        data_directories: &[IMAGE_DATA_DIRECTORY],
+        module_address: u64,
+        memory_source: &dyn MemorySource,
+    ) -> Result<(Vec::<Export>, Option<ModuleName>), &'static str> {
+        let mut exports = Vec::<Export>::new();
+        let mut module_name: Option<ModuleName> = None;
+
+        let export_table_info = data_directories[IMAGE_DIRECTORY_ENTRY_EXPORT.0 as usize];
+        if export_table_info.VirtualAddress != 0 {
+            let export_table_addr = module_address + export_table_info.VirtualAddress as u64;
+            let export_table_end = export_table_addr + export_table_info.Size as u64;
+            let export_directory: IMAGE_EXPORT_DIRECTORY = memory::read_memory_data(memory_source, export_table_addr);
+
+            // This is a fallback that lets us find a name if none was available.
+            if export_directory.Name != 0 {
+                let name_addr = module_address + export_directory.Name as u64;
+                module_name = Some(memory::read_memory_string(memory_source, name_addr, 512, false));
+            }
+
+            // Read the name table first, which is essentially a list of (ordinal, name) pairs that give names
+            // to some or all of the exports. The table is stored as parallel arrays of ordinals and name pointers.
+            let ordinal_array_addr = module_address + export_directory.AddressOfNameOrdinals as u64;
+            let ordinal_array = memory::read_memory_full_array::<u16>(memory_source, ordinal_array_addr, export_directory.NumberOfNames as usize)?;
+            let name_array_addr = module_address + export_directory.AddressOfNames as u64;
+            let name_array = memory::read_memory_full_array::<u32>(memory_source, name_array_addr, export_directory.NumberOfNames as usize)?;
+
+            let address_table_addr = module_address + export_directory.AddressOfFunctions as u64;
+            let address_table = memory::read_memory_full_array::<u32>(memory_source, address_table_addr, export_directory.NumberOfFunctions as usize)?;
+
+            for (unbiased_ordinal, function_addr) in address_table.iter().enumerate() {
+                let ordinal = export_directory.Base + unbiased_ordinal as u32;
+                let target_addr = module_address + *function_addr as u64;
+
+                let name_index = ordinal_array.iter().position(|&o| o == unbiased_ordinal as u16);
+                let export_name = name_index.map(|idx| {
+                    let name_addr = module_address + name_array[idx] as u64;
+                    memory::read_memory_string(memory_source, name_addr, 4096, false)
+                });
+
+                // An address that falls inside the export directory is actually a forwarder.
+                let target = if target_addr >= export_table_addr && target_addr < export_table_end {
+                    // Unsure if there is a max size for a forwarder name, but 4K is probably reasonable.
+                    let forwarding_name = memory::read_memory_string(memory_source, target_addr, 4096, false);
+                    ExportTarget::Forwarder(forwarding_name)
+                } else {
+                    ExportTarget::Rva(target_addr)
+                };
+                exports.push(Export { name: export_name, ordinal, target });
+            }
+        }
+
+        Ok((exports, module_name))
+    }
+    fn read_runtime_functions(
+        data_directories: &[IMAGE_DATA_DIRECTORY],
+        module_address: u64,
+        memory_source: &dyn MemorySource,
+    ) -> Vec::<RuntimeFunction> {
+        let exception_table_info = data_directories[IMAGE_DIRECTORY_ENTRY_EXCEPTION.0 as usize];
+        if exception_table_info.VirtualAddress == 0 {
+            return Vec::new();
+        }
+
+        let exception_table_addr = module_address + exception_table_info.VirtualAddress as u64;
+        let count = exception_table_info.Size as usize / size_of::<RuntimeFunction>();
+        let mut runtime_functions = memory::read_memory_array::<RuntimeFunction>(memory_source, exception_table_addr, count);
+
+        // The table is supposed to be sorted already, but don't rely on it because lookups binary search it.
+        runtime_functions.sort_by_key(|function| function.begin_address);
+        runtime_functions
+    }
+}
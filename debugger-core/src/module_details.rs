@@ -0,0 +1,178 @@
+use windows::Win32::System::{
+    Diagnostics::Debug::{
+        IMAGE_DIRECTORY_ENTRY_RESOURCE,
+        IMAGE_NT_HEADERS32,
+        IMAGE_NT_HEADERS64,
+        IMAGE_NT_OPTIONAL_HDR32_MAGIC,
+        IMAGE_SECTION_HEADER,
+    },
+    SystemServices::IMAGE_DOS_HEADER,
+};
+
+use crate::{
+    memory::{self, MemorySource},
+    module::Module,
+};
+
+// Displays the details of a module that `lmv` shows in WinDbg, read from the image's headers in memory.
+// See https://learn.microsoft.com/en-us/windows/win32/debug/pe-format.
+
+const RT_VERSION: u32 = 16;
+const VS_FIXEDFILEINFO_SIGNATURE: u32 = 0xFEEF04BD;
+/// The size of the `IMAGE_RESOURCE_DIRECTORY` header that precedes the entries.
+const RESOURCE_DIRECTORY_SIZE: u64 = 16;
+/// The high bit of a resource directory entry's offset means that it points to another directory.
+const RESOURCE_SUBDIRECTORY_FLAG: u32 = 0x8000_0000;
+
+/// The header fields that aren't kept in `Module`.
+struct ImageHeaders {
+    timestamp: u32,
+    checksum: u32,
+    entry_point: u32,
+    resource_directory: u32,
+    sections: Vec<IMAGE_SECTION_HEADER>,
+}
+
+fn read_image_headers(module_address: u64, memory_source: &dyn MemorySource) -> Result<ImageHeaders, String> {
+    let dos_header: IMAGE_DOS_HEADER = memory::read_memory_data(memory_source, module_address);
+    let pe_header_addr = module_address + dos_header.e_lfanew as u64;
+    let pe_header: IMAGE_NT_HEADERS64 = memory::read_memory_data(memory_source, pe_header_addr);
+    let (checksum, entry_point, resource_directory) = if pe_header.OptionalHeader.Magic == IMAGE_NT_OPTIONAL_HDR32_MAGIC {
+        let pe_header: IMAGE_NT_HEADERS32 = memory::read_memory_data(memory_source, pe_header_addr);
+        let optional_header = pe_header.OptionalHeader;
+        (optional_header.CheckSum, optional_header.AddressOfEntryPoint, optional_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_RESOURCE.0 as usize].VirtualAddress)
+    } else {
+        let optional_header = pe_header.OptionalHeader;
+        (optional_header.CheckSum, optional_header.AddressOfEntryPoint, optional_header.DataDirectory[IMAGE_DIRECTORY_ENTRY_RESOURCE.0 as usize].VirtualAddress)
+    };
+
+    // The section table follows the optional header, whose size is in the file header.
+    let section_table_addr = pe_header_addr + 4 + std::mem::size_of_val(&pe_header.FileHeader) as u64
+        + pe_header.FileHeader.SizeOfOptionalHeader as u64;
+    let sections = memory::read_memory_full_array::<IMAGE_SECTION_HEADER>(memory_source, section_table_addr, pe_header.FileHeader.NumberOfSections as usize)
+        .map_err(|e| format!("Could not read the section table: {e}"))?;
+
+    Ok(ImageHeaders {
+        timestamp: pe_header.FileHeader.TimeDateStamp,
+        checksum,
+        entry_point,
+        resource_directory,
+        sections,
+    })
+}
+
+fn read_u32(memory_source: &dyn MemorySource, address: u64) -> Option<u32> {
+    memory::read_memory_full_array::<u32>(memory_source, address, 1).ok().map(|data| data[0])
+}
+
+/// Finds an entry in a resource directory and returns its offset field. `id` of `None` means the first entry.
+fn find_resource_entry(directory_addr: u64, id: Option<u32>, memory_source: &dyn MemorySource) -> Option<u32> {
+    let header = memory::read_memory_full_array::<u16>(memory_source, directory_addr + 12, 2).ok()?;
+    let entry_count = header[0] as u64 + header[1] as u64;
+    for index in 0..entry_count {
+        let entry_addr = directory_addr + RESOURCE_DIRECTORY_SIZE + index * 8;
+        let name = read_u32(memory_source, entry_addr)?;
+        if id.is_none_or(|id| id == name) {
+            return read_u32(memory_source, entry_addr + 4);
+        }
+    }
+    None
+}
+
+/// Reads the file and product versions from the version resource, as (file version, product version).
+/// The resource tree is type, then name, then language. Any name and language will do.
+fn read_versions(module_address: u64, resource_directory: u32, memory_source: &dyn MemorySource) -> Option<(String, String)> {
+    if resource_directory == 0 {
+        return None;
+    }
+    let root_addr = module_address + resource_directory as u64;
+    let mut offset = find_resource_entry(root_addr, Some(RT_VERSION), memory_source)?;
+    for _ in 0..2 {
+        if offset & RESOURCE_SUBDIRECTORY_FLAG == 0 {
+            return None;
+        }
+        offset = find_resource_entry(root_addr + (offset & !RESOURCE_SUBDIRECTORY_FLAG) as u64, None, memory_source)?;
+    }
+
+    // This is an `IMAGE_RESOURCE_DATA_ENTRY`, which starts with the RVA of the data.
+    let data_addr = module_address + read_u32(memory_source, root_addr + offset as u64)? as u64;
+    // The data is a `VS_VERSIONINFO`, with 3 16-bit fields and the UTF-16 string "VS_VERSION_INFO" before the
+    // 32-bit aligned `VS_FIXEDFILEINFO`.
+    let fixed_file_info_addr = (data_addr + 6 + ("VS_VERSION_INFO".len() as u64 + 1) * 2 + 3) & !3;
+    let fixed_file_info = memory::read_memory_full_array::<u32>(memory_source, fixed_file_info_addr, 6).ok()?;
+    if fixed_file_info[0] != VS_FIXEDFILEINFO_SIGNATURE {
+        return None;
+    }
+    let format_version = |most_significant: u32, least_significant: u32| {
+        format!("{}.{}.{}.{}", most_significant >> 16, most_significant & 0xFFFF, least_significant >> 16, least_significant & 0xFFFF)
+    };
+    Some((format_version(fixed_file_info[2], fixed_file_info[3]), format_version(fixed_file_info[4], fixed_file_info[5])))
+}
+
+/// Formats a Unix timestamp as a UTC date and time.
+fn format_timestamp(timestamp: u32) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // Convert days since 1970-01-01 to a civil date. See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// Prints a module's version, headers, PDB, and sections.
+pub fn display_module_details(module: &mut Module, memory_source: &dyn MemorySource) -> Result<(), String> {
+    let headers = read_image_headers(module.address, memory_source)?;
+
+    println!("{}", module.name);
+    println!("    Address:         {:#018x} - {:#018x}", module.address, module.address + module.size);
+    if let Some(architecture) = module.architecture {
+        println!("    Architecture:    {architecture}");
+    }
+    match read_versions(module.address, headers.resource_directory, memory_source) {
+        Some((file_version, product_version)) => {
+            println!("    File version:    {file_version}");
+            println!("    Product version: {product_version}");
+        }
+        None => println!("    File version:    <none>"),
+    }
+    // Reproducible builds put a hash in the timestamp instead, so it may not be a real date.
+    println!("    Timestamp:       {:#010x} ({})", headers.timestamp, format_timestamp(headers.timestamp));
+    println!("    Checksum:        {:#010x}", headers.checksum);
+    let entry_point = if headers.entry_point == 0 {
+        String::from("<none>")
+    } else {
+        format!("{:#018x}", module.address + headers.entry_point as u64)
+    };
+    println!("    Entry point:     {entry_point}");
+
+    match (&module.pdb_name, &module.pdb_info) {
+        (Some(pdb_name), Some(pdb_info)) => {
+            println!("    PDB name:        {pdb_name}");
+            println!("    PDB GUID:        {:?}", pdb_info.guid);
+            println!("    PDB age:         {}", pdb_info.age);
+        }
+        _ => println!("    PDB name:        <none>"),
+    }
+    let symbols = match module.get_pdb() {
+        Ok(_) => String::from("PDB loaded"),
+        Err(e) => e.clone(),
+    };
+    println!("    Symbols:         {symbols}");
+
+    println!("    Sections:");
+    for section in headers.sections.iter() {
+        let name_length = section.Name.iter().position(|&byte| byte == 0).unwrap_or(section.Name.len());
+        let name = String::from_utf8_lossy(&section.Name[..name_length]);
+        let start = module.address + section.VirtualAddress as u64;
+        let size = unsafe { section.Misc.VirtualSize };
+        println!("        {name:<8} {start:#018x} - {:#018x}  Characteristics: {:#010x}", start + size as u64, section.Characteristics.0);
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use crate::{
+    process::Process,
+    module::{
+        ExportTarget,
+        Module,
+        SourceLine,
+    },
+};
+
+/// Forwarders can chain, e.g. from kernel32 to kernelbase to ntdll, so follow a few of them, but not forever in case of a cycle.
+const MAX_FORWARDER_DEPTH: usize = 8;
+
+pub fn resolve_name_to_address(symbol: &str, process: &mut Process) -> Result<u64, String> {
+    match symbol.chars().position(|c| c == '!') {
+        // Symbols take priority, so that e.g. `main` in `main.exe` is the function rather than the module.
+        None => resolve_name_in_all_modules(symbol, process)
+            .or_else(|e| find_module_base(symbol, process).ok_or(e)),
+        Some(pos) => {
+            let module_name = &symbol[..pos];
+            let func_name = &symbol[pos + 1..];
+            match resolve_name_in_module(module_name, func_name, process, 0)? {
+                Some(addr) => Ok(addr),
+                None => Err(format!("Could not find {func_name} in module {module_name}")),
+            }
+        }
+    }
+}
+
+/// Finds the base address of the module named `name`, which can be given with or without its extension, so that
+/// module-relative addresses like `ntdll+0x1a2b` work.
+fn find_module_base(name: &str, process: &mut Process) -> Option<u64> {
+    process.iterate_modules_mut()
+        .find(|module| {
+            let file_name = module.name.rsplit('\\').next().unwrap_or(&module.name);
+            let stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem);
+            file_name.eq_ignore_ascii_case(name) || stem.eq_ignore_ascii_case(name)
+        })
+        .map(|module| module.address)
+}
+
+/// Resolves a symbol in a module, following forwarded exports into the modules that they forward to.
+/// Returns `None` if the module doesn't have the symbol.
+fn resolve_name_in_module(module_name: &str, func_name: &str, process: &mut Process, depth: usize) -> Result<Option<u64>, String> {
+    let module = process.get_module_by_name_mut(module_name)
+        .ok_or_else(|| format!("Could not find module {module_name}"))?;
+    match resolve_function_in_module(module, func_name) {
+        None => Ok(None),
+        Some(ExportTarget::Rva(addr)) => Ok(Some(addr)),
+        Some(ExportTarget::Forwarder(forwarder)) => {
+            if depth >= MAX_FORWARDER_DEPTH {
+                return Err(format!("Too many forwarders while resolving {module_name}!{func_name}"));
+            }
+            // Forwarders are `<dll name without extension>.<function name or #ordinal>`, e.g. `NTDLL.RtlAllocateHeap`.
+            // TODO: Resolve API set names, e.g. `api-ms-win-core-file-l1-1-0`, to the DLLs that implement them.
+            let (target_module, target_func) = forwarder.rsplit_once('.')
+                .ok_or_else(|| format!("{module_name}!{func_name} has an invalid forwarder: {forwarder}"))?;
+            let target_module = format!("{target_module}.dll");
+            match resolve_name_in_module(&target_module, target_func, process, depth + 1)? {
+                Some(addr) => Ok(Some(addr)),
+                None => Err(format!("Could not find {target_func} in module {target_module}, which {module_name}!{func_name} is forwarded to")),
+            }
+        }
+    }
+}
+
+/// Searches every module for a symbol without a module prefix. It's an error if more than one module has it.
+fn resolve_name_in_all_modules(symbol: &str, process: &mut Process) -> Result<u64, String> {
+    let module_names: Vec<String> = process.iterate_modules_mut().map(|module| module.name.clone()).collect();
+    let mut matches = Vec::<(u64, String)>::new();
+    for module_name in module_names {
+        if let Ok(Some(address)) = resolve_name_in_module(&module_name, symbol, process, 0) {
+            matches.push((address, module_name));
+        }
+    }
+    // A forwarded export resolves to the same address as the export it forwards to, which isn't ambiguous.
+    matches.sort();
+    matches.dedup_by_key(|(address, _)| *address);
+
+    match matches.as_slice() {
+        [] => Err(format!("Could not find {symbol} in any module")),
+        [(address, _)] => Ok(*address),
+        _ => {
+            let candidates: Vec<String> = matches.iter()
+                .map(|(address, module_name)| format!("{module_name}!{symbol} ({address:#x})"))
+                .collect();
+            Err(format!("{symbol} is ambiguous. Add the module name to pick one of: {}", candidates.join(", ")))
+        }
+    }
+}
+
+/// Returns whether `symbol` is of the form `module!function` where `module` is not loaded (yet).
+pub fn is_symbol_in_unloaded_module(symbol: &str, process: &mut Process) -> bool {
+    match symbol.chars().position(|c| c == '!') {
+        None => false,
+        Some(pos) => process.get_module_by_name_mut(&symbol[..pos]).is_none(),
+    }
+}
+
+/// Finds a function in a module, by name or by `#<ordinal>`. Forwarded exports aren't followed, because the module that
+/// they forward to is a different module. See `resolve_name_in_module`.
+pub fn resolve_function_in_module(module: &mut Module, func: &str) -> Option<ExportTarget> {
+    // Search exports first and then private symbols.
+    let ordinal = func.strip_prefix('#').and_then(|ordinal| ordinal.parse::<u32>().ok());
+    for export in module.exports.iter() {
+        let is_match = match ordinal {
+            Some(ordinal) => export.ordinal == ordinal,
+            None => export.name.as_deref() == Some(func),
+        };
+        if is_match {
+            return Some(export.target.clone());
+        }
+    }
+
+    // The index has the PDB's public symbols, or the DWARF functions for modules without a PDB.
+    module.get_symbol_index().find_by_name(func).map(ExportTarget::Rva)
+}
+
+pub fn resolve_address_to_name(address: u64, process: &mut Process) -> Option<String> {
+    let module = process.get_containing_module_mut(address)?;
+    let module_name = module.name.clone();
+    let (symbol_address, name) = module.get_symbol_index().find_nearest(address)?;
+
+    let offset = address - symbol_address;
+    let sym_with_offset = if offset == 0 {
+        format!("{module_name}!{name}")
+    } else {
+        format!("{module_name}!{name}+{offset:#x}")
+    };
+    Some(sym_with_offset)
+}
+
+/// Returns whether `text` matches `pattern`, where `*` matches any sequence of characters and `?` matches any one character.
+/// Like WinDbg, the comparison ignores case.
+pub fn matches_wildcard(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // `matches[j]` is whether the pattern so far matches the first `j` characters of the text.
+    let mut matches = vec![false; text.len() + 1];
+    matches[0] = true;
+    for pattern_char in pattern {
+        let mut next_matches = vec![false; text.len() + 1];
+        for j in 0..=text.len() {
+            next_matches[j] = match pattern_char {
+                '*' => matches[j] || (j > 0 && next_matches[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                c => j > 0 && matches[j - 1] && text[j - 1] == c,
+            };
+        }
+        matches = next_matches;
+    }
+    matches[text.len()]
+}
+
+/// Finds the exports and PDB public symbols that match `pattern`, which is of the form `module!symbol`.
+/// Both parts can contain `*` and `?` wildcards. The module can be given with or without its extension.
+/// Returns the addresses and names (as `module!symbol`), sorted by address.
+pub fn find_matching_symbols(pattern: &str, process: &mut Process) -> Result<Vec<(u64, String)>, String> {
+    let (module_pattern, symbol_pattern) = pattern.split_once('!')
+        .ok_or_else(|| format!("Expected <module>!<symbol>, but got {pattern}"))?;
+
+    let mut matches = Vec::<(u64, String)>::new();
+    for module in process.iterate_modules_mut() {
+        let file_name = module.name.rsplit('\\').next().unwrap_or(&module.name).to_string();
+        let stem = file_name.rsplit_once('.').map_or(file_name.as_str(), |(stem, _)| stem);
+        if !matches_wildcard(module_pattern, &file_name) && !matches_wildcard(module_pattern, stem) {
+            continue;
+        }
+
+        let mut module_matches = Vec::<(u64, String)>::new();
+        for export in module.exports.iter() {
+            if let (Some(name), ExportTarget::Rva(address)) = (&export.name, &export.target) {
+                if matches_wildcard(symbol_pattern, name) {
+                    module_matches.push((*address, format!("{file_name}!{name}")));
+                }
+            }
+        }
+
+        for (address, name) in module.get_symbol_index().iterate() {
+            if matches_wildcard(symbol_pattern, name) {
+                module_matches.push((*address, format!("{file_name}!{name}")));
+            }
+        }
+
+        // Most exports are also public symbols, so remove the duplicates.
+        module_matches.sort();
+        module_matches.dedup();
+        matches.append(&mut module_matches);
+    }
+
+    matches.sort();
+    Ok(matches)
+}
+
+pub fn resolve_address_to_line(address: u64, process: &mut Process) -> Option<SourceLine> {
+    let module = process.get_containing_module_mut(address)?;
+    let rva = (address - module.address) as u32;
+    module.get_line_table()?.find_line(rva)
+}
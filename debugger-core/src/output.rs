@@ -0,0 +1,117 @@
+use std::{
+    fmt::Display,
+    sync::{atomic::{AtomicBool, Ordering}, Mutex},
+};
+
+use crate::windows_wrapper;
+
+// Colors output with ANSI escape sequences, to make addresses, symbols, changed values, and errors stand out.
+// Colors are off when the `NO_COLOR` environment variable is set (see https://no-color.org), with `--no-color`, and when
+// the output isn't a console.
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Output that is held for a front-end to display, instead of being printed. See `start_capture`.
+static CAPTURED: Mutex<Option<String>> = Mutex::new(None);
+
+pub enum Style {
+    Address,
+    Symbol,
+    /// A value that changed since the target last stopped, e.g. a register.
+    Changed,
+    Error,
+    /// Text that the user asked to stand out, e.g. matching debug output.
+    Highlight,
+}
+
+impl Style {
+    fn color_code(&self) -> &'static str {
+        match self {
+            Style::Address => "36",
+            Style::Symbol => "33",
+            Style::Changed => "91",
+            Style::Error => "31",
+            Style::Highlight => "1;93",
+        }
+    }
+}
+
+/// Decides whether to use colors. Call this before printing anything.
+pub fn init(no_color: bool) {
+    let no_color = no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let enabled = !no_color && windows_wrapper::enable_virtual_terminal_processing();
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Holds output from `print!` and `println!` instead of printing it, until `take_captured` is called.
+/// This is for front-ends that draw the whole screen themselves, e.g. the `--tui` mode.
+pub fn start_capture() {
+    CAPTURED.lock().unwrap().get_or_insert_with(String::new);
+}
+
+/// Prints output normally again. Returns the output that was captured and not yet taken.
+pub fn stop_capture() -> String {
+    CAPTURED.lock().unwrap().take().unwrap_or_default()
+}
+
+/// Returns the output captured since the last call, and clears it.
+pub fn take_captured() -> String {
+    CAPTURED.lock().unwrap().as_mut().map(std::mem::take).unwrap_or_default()
+}
+
+/// Prints `text` to stdout, or captures it. This is what `print!` and `println!` use.
+pub fn write(text: &str) {
+    match CAPTURED.lock().unwrap().as_mut() {
+        Some(captured) => captured.push_str(text),
+        None => std::print!("{text}"),
+    }
+}
+
+/// Formats `text` in the style's color, if colors are enabled.
+pub fn paint(style: Style, text: impl Display) -> String {
+    if is_color_enabled() {
+        format!("\x1b[{}m{text}\x1b[0m", style.color_code())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Formats an address as 16 hex digits, e.g. `0x00007ff6c0de1234`.
+pub fn address(address: u64) -> String {
+    paint(Style::Address, format!("{address:#018x}"))
+}
+
+pub fn symbol(name: &str) -> String {
+    paint(Style::Symbol, name)
+}
+
+/// Formats a value, highlighting it if it differs from the previous value.
+pub fn value_with_change(text: String, value: u64, previous_value: Option<u64>) -> String {
+    match previous_value {
+        Some(previous_value) if previous_value != value => paint(Style::Changed, text),
+        _ => text,
+    }
+}
+
+/// Removes the ANSI escape sequences that `paint` adds, e.g. for writing to a file.
+pub fn strip_colors(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of the sequence, which is a letter, e.g. the `m` in `\x1b[36m`.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
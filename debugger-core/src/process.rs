@@ -0,0 +1,115 @@
+use crate::{
+    memory::MemorySource,
+    module::Module,
+    windows_wrapper::ThreadId,
+};
+
+pub struct Thread {
+    pub id: ThreadId,
+    /// The address that the thread started executing at, or 0 if it is not known.
+    pub start_address: u64,
+    /// Whether the user suspended the thread, to keep it from running when the target continues.
+    pub frozen: bool,
+}
+
+pub struct Process {
+    modules: Vec<Module>,
+    threads: Vec<Thread>,
+    /// Whether this is a 32-bit process running under WOW64. Its pointers are 4 bytes.
+    pub is_wow64: bool,
+    /// The memory that `allocate` last allocated in the process, which expressions can use as `$alloc`.
+    pub last_allocation: Option<u64>,
+}
+
+impl Process {
+    pub fn new(is_wow64: bool) -> Process {
+        Process {
+            modules: Vec::new(),
+            threads: Vec::new(),
+            is_wow64,
+            last_allocation: None,
+        }
+    }
+
+    /// The size of a pointer in the process, in bytes.
+    pub fn pointer_size(&self) -> usize {
+        if self.is_wow64 { 4 } else { 8 }
+    }
+
+    pub fn add_module(
+        &mut self,
+        address: u64,
+        name: Option<String>,
+        memory_source: &dyn MemorySource
+    ) -> Result<&Module, String> {
+        let module = Module::from_memory_view(address, name, memory_source)?;
+        // A module can be loaded again at the same address after it is unloaded. Replace it in case we missed the unload.
+        self.remove_module(address);
+        self.modules.push(module);
+        Ok(self.modules.last().unwrap())
+    }
+
+    /// Removes the module loaded at `address`, returning it if there was one.
+    pub fn remove_module(&mut self, address: u64) -> Option<Module> {
+        let index = self.modules.iter().position(|module| module.address == address)?;
+        Some(self.modules.remove(index))
+    }
+
+    pub fn add_thread(&mut self, thread: ThreadId, start_address: u64) {
+        self.threads.push(Thread { id: thread, start_address, frozen: false });
+    }
+
+    pub fn remove_thread(&mut self, thread: ThreadId) {
+        self.threads.retain(|x| x.id != thread);
+    }
+
+    pub fn iterate_threads(&self) -> impl Iterator<Item = &ThreadId> {
+        self.threads.iter().map(|thread| &thread.id)
+    }
+
+    pub fn get_threads(&self) -> &[Thread] {
+        &self.threads
+    }
+
+    pub fn get_thread_mut(&mut self, thread: ThreadId) -> Option<&mut Thread> {
+        self.threads.iter_mut().find(|x| x.id == thread)
+    }
+
+    pub fn iterate_modules(&self) -> impl Iterator<Item = &Module> {
+        self.modules.iter()
+    }
+
+    pub fn iterate_modules_mut(&mut self) -> impl Iterator<Item = &mut Module> {
+        self.modules.iter_mut()
+    }
+
+    pub fn get_containing_module(&self, address: u64) -> Option<&Module> {
+        self.modules.iter().find(|&module| module.contains_address(address))
+    }
+
+    pub fn get_containing_module_mut(&mut self, address: u64) -> Option<&mut Module> {
+        self.modules.iter_mut().find(|module| module.contains_address(address))
+    }
+
+    pub fn get_module_by_name_mut(&mut self, module_name: &str) -> Option<&mut Module> {
+        let mut potential_trimmed_match = None;
+
+        for module in self.modules.iter_mut() {
+            // Exact match
+            if module.name == module_name {
+                return Some(module);
+            }
+
+            // Trimmed match: the file part of the path matches
+            // Keep looping even if we find a trimmed match, because an exact match is higher priority.
+            if potential_trimmed_match.is_none() {
+                let trimmed = module.name.rsplit('\\').next().unwrap_or(&module.name);
+                if trimmed.to_lowercase() == module_name.to_lowercase() {
+                    potential_trimmed_match = Some(module)
+                }
+            }
+        }
+
+        potential_trimmed_match
+    }
+}
\ No newline at end of file
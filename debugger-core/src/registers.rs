@@ -0,0 +1,203 @@
+use windows::Win32::System::Diagnostics::Debug::{CONTEXT, M128A};
+
+use crate::output;
+
+/// Formats a register's value, highlighting it if it changed since the target last stopped.
+fn format_register(name: &str, value: u64, previous_value: Option<u64>, is_32_bit: bool) -> String {
+    let text = if is_32_bit { format!("{value:#010x}") } else { format!("{value:#018x}") };
+    format!("{name}={}", output::value_with_change(text, value, previous_value))
+}
+
+fn display_flags(eflags: u32, previous_eflags: Option<u32>) {
+    let flags: Vec<String> = EFLAGS_BITS.iter()
+        .map(|(name, bit)| {
+            let value = ((eflags >> bit) & 1) as u64;
+            let previous_value = previous_eflags.map(|previous_eflags| ((previous_eflags >> bit) & 1) as u64);
+            format!("{}={}", name.to_uppercase(), output::value_with_change(value.to_string(), value, previous_value))
+        })
+        .collect();
+    println!("{}", flags.join(" "));
+}
+
+/// Prints the general-purpose registers. The ones that changed since `previous`, the registers when the thread last
+/// stopped, are highlighted.
+pub fn display_all(context: CONTEXT, previous: Option<&CONTEXT>) {
+    let r = |name, get: fn(&CONTEXT) -> u64| format_register(name, get(&context), previous.map(get), false);
+    let eflags = format_register("eflags", context.EFlags as u64, previous.map(|previous| previous.EFlags as u64), true);
+    println!("{} {} {}", r("rax", |c| c.Rax), r("rbx", |c| c.Rbx), r("rcx", |c| c.Rcx));
+    println!("{} {} {}", r("rdx", |c| c.Rdx), r("rsi", |c| c.Rsi), r("rdi", |c| c.Rdi));
+    println!("{} {} {}", r("rip", |c| c.Rip), r("rsp", |c| c.Rsp), r("rbp", |c| c.Rbp));
+    println!("{} {} {}", r(" r8", |c| c.R8), r(" r9", |c| c.R9), r("r10", |c| c.R10));
+    println!("{} {} {}", r("r11", |c| c.R11), r("r12", |c| c.R12), r("r13", |c| c.R13));
+    println!("{} {} {eflags}", r("r14", |c| c.R14), r("r15", |c| c.R15));
+    display_flags(context.EFlags, previous.map(|previous| previous.EFlags));
+}
+
+/// Prints the registers of a 32-bit (WOW64) thread, which are in the low halves of the 64-bit registers.
+pub fn display_all_x86(context: CONTEXT, previous: Option<&CONTEXT>) {
+    let r = |name, get: fn(&CONTEXT) -> u64| {
+        format_register(name, get(&context) as u32 as u64, previous.map(|previous| get(previous) as u32 as u64), true)
+    };
+    let eflags = format_register("eflags", context.EFlags as u64, previous.map(|previous| previous.EFlags as u64), true);
+    println!("{} {} {} {}", r("eax", |c| c.Rax), r("ebx", |c| c.Rbx), r("ecx", |c| c.Rcx), r("edx", |c| c.Rdx));
+    println!("{} {} {} {}", r("esi", |c| c.Rsi), r("edi", |c| c.Rdi), r("ebp", |c| c.Rbp), r("esp", |c| c.Rsp));
+    println!("{} {eflags}", r("eip", |c| c.Rip));
+    display_flags(context.EFlags, previous.map(|previous| previous.EFlags));
+}
+
+/// The bits of EFLAGS that can be read and written individually as registers, e.g. `zf`.
+const EFLAGS_BITS: [(&str, u32); 9] = [
+    ("cf", 0),
+    ("pf", 2),
+    ("af", 4),
+    ("zf", 6),
+    ("sf", 7),
+    ("tf", 8),
+    ("if", 9),
+    ("df", 10),
+    ("of", 11),
+];
+
+fn get_flag_bit(name: &str) -> Option<u32> {
+    EFLAGS_BITS.iter().find(|(flag_name, _)| *flag_name == name).map(|(_, bit)| *bit)
+}
+
+/// Prints the SSE (XMM) registers, as raw bits and as packed floats, doubles, and integers.
+// TODO: Display the upper halves of the YMM registers, which are in the extended state that `CONTEXT` doesn't include.
+pub fn display_vector(context: CONTEXT) {
+    let float_save = unsafe { context.Anonymous.FltSave };
+    println!("mxcsr={:#010x}", float_save.MxCsr);
+    for (index, register) in float_save.XmmRegisters.iter().enumerate() {
+        let bytes = m128a_to_bytes(register);
+        let f32s: Vec<String> = bytes.chunks_exact(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()).to_string()).collect();
+        let f64s: Vec<String> = bytes.chunks_exact(8).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()).to_string()).collect();
+        let i32s: Vec<String> = bytes.chunks_exact(4).map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()).to_string()).collect();
+        let i64s: Vec<String> = bytes.chunks_exact(8).map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()).to_string()).collect();
+        println!("{:>5}={:016x}{:016x}", format!("xmm{index}"), register.High as u64, register.Low);
+        println!("      f32: {}", f32s.join(" "));
+        println!("      f64: {}", f64s.join(" "));
+        println!("      i32: {}", i32s.join(" "));
+        println!("      i64: {}", i64s.join(" "));
+    }
+}
+
+/// Prints the x87 floating-point registers and status.
+pub fn display_floating_point(context: CONTEXT) {
+    let float_save = unsafe { context.Anonymous.FltSave };
+    println!("fpcw={:#06x} fpsw={:#06x} fptw={:#04x}", float_save.ControlWord, float_save.StatusWord, float_save.TagWord);
+    for (index, register) in float_save.FloatRegisters.iter().enumerate() {
+        let bytes = m128a_to_bytes(register);
+        // The registers are 80-bit, and are stored in the low 10 bytes.
+        let raw: [u8; 10] = bytes[..10].try_into().unwrap();
+        let raw_hex: String = raw.iter().rev().map(|byte| format!("{byte:02x}")).collect();
+        println!("st{index}={raw_hex} {}", extended_precision_to_f64(&raw));
+    }
+}
+
+fn m128a_to_bytes(value: &M128A) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&value.Low.to_le_bytes());
+    bytes[8..].copy_from_slice(&value.High.to_le_bytes());
+    bytes
+}
+
+/// Converts an x87 80-bit extended precision value to the nearest `f64`.
+/// The format is a sign bit, a 15-bit exponent, and a 64-bit mantissa with an explicit integer bit.
+fn extended_precision_to_f64(raw: &[u8; 10]) -> f64 {
+    let mantissa = u64::from_le_bytes(raw[..8].try_into().unwrap());
+    let sign_and_exponent = u16::from_le_bytes([raw[8], raw[9]]);
+    let sign = if sign_and_exponent & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (sign_and_exponent & 0x7FFF) as i32;
+
+    if exponent == 0x7FFF {
+        // Ignore the integer bit when checking for infinity.
+        return if mantissa << 1 == 0 { sign * f64::INFINITY } else { f64::NAN };
+    }
+
+    // Scale in two steps, since the exponent can be out of range for `powi` to produce a finite intermediate value.
+    const EXPONENT_BIAS: i32 = 16383;
+    let scale = exponent - EXPONENT_BIAS - 63;
+    sign * (mantissa as f64) * 2f64.powi(scale / 2) * 2f64.powi(scale - scale / 2)
+}
+
+/// Returns the 64-bit register named `name` (e.g. `rax`), or `None` if there is no such register.
+fn get_register_mut<'a>(context: &'a mut CONTEXT, name: &str) -> Option<&'a mut u64> {
+    let register = match name {
+        "rax" => &mut context.Rax,
+        "rbx" => &mut context.Rbx,
+        "rcx" => &mut context.Rcx,
+        "rdx" => &mut context.Rdx,
+        "rsi" => &mut context.Rsi,
+        "rdi" => &mut context.Rdi,
+        "rip" => &mut context.Rip,
+        "rsp" => &mut context.Rsp,
+        "rbp" => &mut context.Rbp,
+        "r8" => &mut context.R8,
+        "r9" => &mut context.R9,
+        "r10" => &mut context.R10,
+        "r11" => &mut context.R11,
+        "r12" => &mut context.R12,
+        "r13" => &mut context.R13,
+        "r14" => &mut context.R14,
+        "r15" => &mut context.R15,
+        _ => return None,
+    };
+    Some(register)
+}
+
+/// Gets the 64-bit register that the 32-bit register `name` (e.g. `eax`) is the low half of.
+fn get_wide_register_name(name: &str) -> Option<&'static str> {
+    let wide_name = match name {
+        "eax" => "rax",
+        "ebx" => "rbx",
+        "ecx" => "rcx",
+        "edx" => "rdx",
+        "esi" => "rsi",
+        "edi" => "rdi",
+        "eip" => "rip",
+        "esp" => "rsp",
+        "ebp" => "rbp",
+        _ => return None,
+    };
+    Some(wide_name)
+}
+
+/// Returns the value of the register named `name` (e.g. `rax` or `zf`), or `None` if there is no such register.
+pub fn get_register(context: &CONTEXT, name: &str) -> Option<u64> {
+    if name == "eflags" {
+        return Some(context.EFlags as u64);
+    }
+    if let Some(bit) = get_flag_bit(name) {
+        return Some(((context.EFlags >> bit) & 1) as u64);
+    }
+    let mut context = *context;
+    if let Some(wide_name) = get_wide_register_name(name) {
+        return get_register_mut(&mut context, wide_name).map(|register| *register as u32 as u64);
+    }
+    get_register_mut(&mut context, name).map(|register| *register)
+}
+
+/// Sets the register named `name` (e.g. `rax` or `zf`) to `value`.
+pub fn set_register(context: &mut CONTEXT, name: &str, value: u64) -> Result<(), String> {
+    if name == "eflags" {
+        context.EFlags = u32::try_from(value).map_err(|_| format!("{value:#x} is too large for eflags"))?;
+        return Ok(());
+    }
+    if let Some(bit) = get_flag_bit(name) {
+        match value {
+            0 => context.EFlags &= !(1 << bit),
+            1 => context.EFlags |= 1 << bit,
+            _ => return Err(format!("{name} is a flag, so it must be 0 or 1")),
+        }
+        return Ok(());
+    }
+    if let Some(wide_name) = get_wide_register_name(name) {
+        // Like writing a 32-bit register on x64, this clears the upper half.
+        let value = u32::try_from(value).map_err(|_| format!("{value:#x} is too large for {name}"))?;
+        *get_register_mut(context, wide_name).unwrap() = value as u64;
+        return Ok(());
+    }
+    let register = get_register_mut(context, name).ok_or_else(|| format!("Unknown register {name}"))?;
+    *register = value;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::{
+    fs::File,
+    io::Write,
+    sync::Mutex,
+};
+
+use crate::{output, windows_wrapper};
+
+// Copies the debugger's output to a log file, so that a session can be reviewed later or attached to a bug report.
+// Everything printed with `print!` and `println!` is logged, because lib.rs replaces them with versions that also call
+// `write`. Each line starts with the local time that it was written.
+
+struct SessionLog {
+    path: String,
+    file: File,
+    /// Whether the next output starts a new line, which needs a timestamp.
+    at_line_start: bool,
+}
+
+static SESSION_LOG: Mutex<Option<SessionLog>> = Mutex::new(None);
+
+/// Starts logging to the file at `path`, replacing its contents. Closes the log that was open, if any.
+pub fn open(path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Could not create log file {path}: {e}"))?;
+    close();
+    *SESSION_LOG.lock().unwrap() = Some(SessionLog { path: path.to_string(), file, at_line_start: true });
+    Ok(())
+}
+
+/// Stops logging. Returns the path of the log that was open, if any.
+pub fn close() -> Option<String> {
+    SESSION_LOG.lock().unwrap().take().map(|log| log.path)
+}
+
+/// Writes output to the log, if one is open.
+pub fn write(text: &str) {
+    let mut log = SESSION_LOG.lock().unwrap();
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+
+    let mut buffer = String::new();
+    for line in output::strip_colors(text).split_inclusive('\n') {
+        if log.at_line_start {
+            buffer.push_str(&format!("[{}] ", windows_wrapper::format_local_time()));
+        }
+        buffer.push_str(line);
+        log.at_line_start = line.ends_with('\n');
+    }
+    // Printing to the console doesn't fail if the log can't be written, so neither does this.
+    let _ = log.file.write_all(buffer.as_bytes());
+}
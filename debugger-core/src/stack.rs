@@ -0,0 +1,330 @@
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+use crate::{
+    disassembly,
+    locals::{self, VariableLocation},
+    memory::{self, MemorySource},
+    module::RuntimeFunction,
+    name_resolution,
+    output,
+    process::Process,
+};
+
+// See https://learn.microsoft.com/en-us/cpp/build/exception-handling-x64 for the format of the unwind data.
+
+const UNW_FLAG_CHAININFO: u8 = 0x4;
+
+const UWOP_PUSH_NONVOL: u8 = 0;
+const UWOP_ALLOC_LARGE: u8 = 1;
+const UWOP_ALLOC_SMALL: u8 = 2;
+const UWOP_SET_FPREG: u8 = 3;
+const UWOP_SAVE_NONVOL: u8 = 4;
+const UWOP_SAVE_NONVOL_FAR: u8 = 5;
+const UWOP_EPILOG: u8 = 6;
+const UWOP_SPARE_CODE: u8 = 7;
+const UWOP_SAVE_XMM128: u8 = 8;
+const UWOP_SAVE_XMM128_FAR: u8 = 9;
+const UWOP_PUSH_MACHFRAME: u8 = 10;
+
+/// We'll arbitrarily limit the depth of chained unwind info to keep it sane.
+const MAX_CHAINED_UNWIND_INFO_DEPTH: usize = 32;
+
+/// Stop walking the stack after this many frames, in case the stack is corrupt or recursion is unbounded.
+pub const MAX_STACK_FRAMES: usize = 1000;
+
+/// Stop dumping the stack after this many bytes, so that deep recursion doesn't flood the console.
+const MAX_STACK_DUMP_BYTES: u64 = 0x10000;
+
+/// The shortest and longest call instructions, e.g. `call rax` and `call qword ptr [rip+0x1234]` with a prefix.
+const MIN_CALL_LENGTH: u64 = 2;
+const MAX_CALL_LENGTH: u64 = 7;
+
+fn read_u64(memory_source: &dyn MemorySource, address: u64) -> Result<u64, String> {
+    memory::read_memory_full_array::<u64>(memory_source, address, 1)
+        .map(|data| data[0])
+        .map_err(|_| format!("Could not read stack memory at {address:#018x}"))
+}
+
+fn read_u32(memory_source: &dyn MemorySource, address: u64) -> Result<u32, String> {
+    memory::read_memory_full_array::<u32>(memory_source, address, 1)
+        .map(|data| data[0])
+        .map_err(|_| format!("Could not read stack memory at {address:#018x}"))
+}
+
+/// Gets the integer register with the given number, as used by the unwind codes.
+fn get_register_mut(context: &mut CONTEXT, register_number: u8) -> &mut u64 {
+    match register_number {
+        0 => &mut context.Rax,
+        1 => &mut context.Rcx,
+        2 => &mut context.Rdx,
+        3 => &mut context.Rbx,
+        4 => &mut context.Rsp,
+        5 => &mut context.Rbp,
+        6 => &mut context.Rsi,
+        7 => &mut context.Rdi,
+        8 => &mut context.R8,
+        9 => &mut context.R9,
+        10 => &mut context.R10,
+        11 => &mut context.R11,
+        12 => &mut context.R12,
+        13 => &mut context.R13,
+        14 => &mut context.R14,
+        _ => &mut context.R15,
+    }
+}
+
+/// Returns the caller's context, by undoing the effects of the current function's prolog and then popping the return address.
+/// Only the integer registers are unwound.
+// TODO: Detect when the instruction pointer is inside an epilog, which has already undone some of the prolog.
+pub fn unwind_context(context: &CONTEXT, process: &Process, memory_source: &dyn MemorySource) -> Result<CONTEXT, String> {
+    if process.is_wow64 {
+        return unwind_x86_context(context, memory_source);
+    }
+
+    let mut context = *context;
+    let address = context.Rip;
+
+    // Leaf functions don't have unwind data, and have the return address at the top of the stack.
+    let mut popped_machine_frame = false;
+    if let Some(module) = process.get_containing_module(address) {
+        if let Some(function) = module.find_runtime_function(address) {
+            let offset_in_function = address - (module.address + function.begin_address as u64);
+            popped_machine_frame = apply_unwind_info(&mut context, module.address, function, Some(offset_in_function), memory_source, 0)?;
+        }
+    }
+
+    if !popped_machine_frame {
+        context.Rip = read_u64(memory_source, context.Rsp)?;
+        context.Rsp += 8;
+    }
+
+    Ok(context)
+}
+
+/// Returns the caller's context for a 32-bit x86 function, which has no unwind data.
+/// This assumes that the function uses the standard `push ebp; mov ebp, esp` frame, and follows the chain of saved frame pointers.
+// TODO: Handle functions that omit the frame pointer, which requires the FPO data from the PDB.
+fn unwind_x86_context(context: &CONTEXT, memory_source: &dyn MemorySource) -> Result<CONTEXT, String> {
+    let mut context = *context;
+    let frame_pointer = context.Rbp;
+    context.Rip = read_u32(memory_source, frame_pointer + 4)? as u64;
+    context.Rbp = read_u32(memory_source, frame_pointer)? as u64;
+    context.Rsp = frame_pointer + 8;
+    Ok(context)
+}
+
+/// Prints the call stack, starting from the given context, one frame per line.
+pub fn print_stack(context: &CONTEXT, process: &mut Process, memory_source: &dyn MemorySource) {
+    println!(" #  Child-SP           Return Address     Call Site");
+    let mut context = *context;
+    for frame_index in 0..MAX_STACK_FRAMES {
+        let caller_context = unwind_context(&context, process, memory_source);
+        let return_address = caller_context.as_ref().map_or(0, |caller_context| caller_context.Rip);
+        let call_site = match name_resolution::resolve_address_to_name(context.Rip, process) {
+            Some(symbol) => output::symbol(&symbol),
+            None => output::address(context.Rip),
+        };
+        let arguments = format_arguments(&context, frame_index == 0, process, memory_source).unwrap_or_default();
+        println!("{frame_index:02}  {:#018x} {} {call_site}{arguments}", context.Rsp, output::address(return_address));
+
+        match caller_context {
+            // A return address of 0 marks the bottom of the stack.
+            Ok(caller_context) if caller_context.Rip == 0 => return,
+            // The stack pointer only moves up as we unwind, so anything else means the unwind went wrong.
+            Ok(caller_context) if caller_context.Rsp <= context.Rsp => {
+                println!("Stopping because the stack pointer did not increase");
+                return;
+            }
+            Ok(caller_context) => context = caller_context,
+            Err(e) => {
+                print_error!("Could not unwind further: {e}");
+                return;
+            }
+        }
+    }
+    println!("Stopping after {MAX_STACK_FRAMES} frames");
+}
+
+/// Prints the raw stack from the stack pointer up to `stack_base`, one pointer-sized value per line, with the symbol of each
+/// value that is in a module. Values that are just after a call instruction are marked, since they are probably return
+/// addresses. This doesn't unwind, so it still shows the callers when the stack is too corrupt to walk with `stack`.
+pub fn print_stack_dump(context: &CONTEXT, stack_base: u64, process: &mut Process, memory_source: &dyn MemorySource) {
+    let pointer_size = process.pointer_size() as u64;
+    let bitness = disassembly::get_bitness(process.is_wow64);
+    let stack_pointer = context.Rsp;
+    if stack_pointer >= stack_base {
+        print_error!("The stack pointer {stack_pointer:#018x} is not below the stack base {stack_base:#018x}");
+        return;
+    }
+    let end = stack_base.min(stack_pointer + MAX_STACK_DUMP_BYTES);
+
+    let mut return_address_count = 0;
+    for address in (stack_pointer..end).step_by(pointer_size as usize) {
+        let bytes = memory_source.read_raw_memory(address, pointer_size as usize);
+        if bytes.len() < pointer_size as usize {
+            print_error!("Could not read stack memory at {address:#018x}");
+            return;
+        }
+        let value = bytes.iter().rev().fold(0u64, |value, byte| (value << 8) | *byte as u64);
+        let annotation = if process.get_containing_module(value).is_some() {
+            let name = name_resolution::resolve_address_to_name(value, process).unwrap_or_else(|| format!("{value:#x}"));
+            if follows_call(value, memory_source, bitness) {
+                return_address_count += 1;
+                format!("{} (return address)", output::symbol(&name))
+            } else {
+                output::symbol(&name)
+            }
+        } else {
+            String::new()
+        };
+        let width = pointer_size as usize * 2;
+        println!("{}  {value:0width$x}  {annotation}", output::address(address));
+    }
+    if end < stack_base {
+        println!("Stopping after {MAX_STACK_DUMP_BYTES:#x} bytes, {:#x} bytes before the stack base", stack_base - end);
+    }
+    println!("{return_address_count} likely return addresses");
+}
+
+/// Whether the instruction before `address` is a call, i.e. whether `address` could be a return address.
+fn follows_call(address: u64, memory_source: &dyn MemorySource, bitness: u32) -> bool {
+    (MIN_CALL_LENGTH..=MAX_CALL_LENGTH).any(|length| {
+        disassembly::decode_instruction(memory_source, address - length, bitness)
+            .is_ok_and(|instruction| disassembly::is_call(&instruction) && instruction.next_ip() == address)
+    })
+}
+
+/// Formats the parameters of the frame's function from the PDB, e.g. `(argc=0x1, argv=0x1f2e3d4c5b6a)`.
+/// Returns `None` if the function has no symbols.
+fn format_arguments(context: &CONTEXT, is_top_frame: bool, process: &mut Process, memory_source: &dyn MemorySource) -> Option<String> {
+    let is_32_bit = process.is_wow64;
+    let pointer_size = process.pointer_size();
+    // Callers' instruction pointers are return addresses, which can be just past the end of the calling function.
+    let address = if is_top_frame { context.Rip } else { context.Rip - 1 };
+    let module = process.get_containing_module_mut(address)?;
+    let rva = (address - module.address) as u32;
+    let function = locals::find_function_variables(module, rva, is_32_bit).ok()??;
+
+    let arguments: Vec<String> = function.variables.iter()
+        .filter(|variable| variable.is_parameter)
+        .map(|variable| {
+            // Only nonvolatile registers are restored when unwinding, so parameters in registers are only known in the top frame.
+            let value = match variable.location {
+                VariableLocation::Register(_) if !is_top_frame => String::from("?"),
+                _ => function.format_value(variable, context, pointer_size, memory_source),
+            };
+            format!("{}={value}", variable.name)
+        })
+        .collect();
+    Some(format!("({})", arguments.join(", ")))
+}
+
+/// Undoes the prolog operations described by the function's unwind info.
+/// `offset_in_function` is used to skip operations that haven't executed yet. `None` means the whole prolog has executed.
+/// Returns whether a machine frame was popped, in which case the instruction pointer has already been restored.
+fn apply_unwind_info(
+    context: &mut CONTEXT,
+    module_address: u64,
+    function: &RuntimeFunction,
+    offset_in_function: Option<u64>,
+    memory_source: &dyn MemorySource,
+    depth: usize,
+) -> Result<bool, String> {
+    if depth > MAX_CHAINED_UNWIND_INFO_DEPTH {
+        return Err(String::from("Too many chained unwind infos"));
+    }
+
+    let unwind_info_addr = module_address + function.unwind_info_address as u64;
+    let header = memory::read_memory_full_array::<u8>(memory_source, unwind_info_addr, 4)
+        .map_err(|_| format!("Could not read unwind info at {unwind_info_addr:#018x}"))?;
+    let flags = header[0] >> 3;
+    let code_count = header[2] as usize;
+    let frame_register = header[3] & 0xF;
+    let frame_offset = (header[3] >> 4) as u64 * 16;
+    let codes = memory::read_memory_full_array::<u16>(memory_source, unwind_info_addr + 4, code_count)
+        .map_err(|_| format!("Could not read unwind codes at {unwind_info_addr:#018x}"))?;
+
+    let has_executed = |code_offset: u64| offset_in_function.is_none_or(|offset| offset >= code_offset);
+
+    // Saved registers are relative to the stack pointer at the end of the prolog. If the function uses a frame pointer,
+    // the stack pointer may have moved since (e.g. `alloca`), so use the frame pointer instead.
+    let uses_frame_pointer = codes.iter().any(|code| ((code >> 8) & 0xF) as u8 == UWOP_SET_FPREG && has_executed((code & 0xFF) as u64));
+    let frame_base = if frame_register != 0 && uses_frame_pointer {
+        *get_register_mut(context, frame_register) - frame_offset
+    } else {
+        context.Rsp
+    };
+
+    let mut index = 0;
+    while index < code_count {
+        let code_offset = (codes[index] & 0xFF) as u64;
+        let operation = ((codes[index] >> 8) & 0xF) as u8;
+        let operation_info = (codes[index] >> 12) as u8;
+
+        let slot_count = match operation {
+            UWOP_ALLOC_LARGE if operation_info == 0 => 2,
+            UWOP_ALLOC_LARGE => 3,
+            UWOP_SAVE_NONVOL | UWOP_EPILOG | UWOP_SAVE_XMM128 => 2,
+            UWOP_SAVE_NONVOL_FAR | UWOP_SPARE_CODE | UWOP_SAVE_XMM128_FAR => 3,
+            _ => 1,
+        };
+        if index + slot_count > code_count {
+            return Err(format!("Malformed unwind info at {unwind_info_addr:#018x}"));
+        }
+
+        // Skip operations that haven't executed yet because the instruction pointer is still inside the prolog.
+        if has_executed(code_offset) {
+            match operation {
+                UWOP_PUSH_NONVOL => {
+                    *get_register_mut(context, operation_info) = read_u64(memory_source, context.Rsp)?;
+                    context.Rsp += 8;
+                }
+                UWOP_ALLOC_LARGE => {
+                    let size = if operation_info == 0 {
+                        codes[index + 1] as u64 * 8
+                    } else {
+                        codes[index + 1] as u64 | ((codes[index + 2] as u64) << 16)
+                    };
+                    context.Rsp += size;
+                }
+                UWOP_ALLOC_SMALL => {
+                    context.Rsp += operation_info as u64 * 8 + 8;
+                }
+                UWOP_SET_FPREG => {
+                    context.Rsp = frame_base;
+                }
+                UWOP_SAVE_NONVOL => {
+                    let offset = codes[index + 1] as u64 * 8;
+                    *get_register_mut(context, operation_info) = read_u64(memory_source, frame_base + offset)?;
+                }
+                UWOP_SAVE_NONVOL_FAR => {
+                    let offset = codes[index + 1] as u64 | ((codes[index + 2] as u64) << 16);
+                    *get_register_mut(context, operation_info) = read_u64(memory_source, frame_base + offset)?;
+                }
+                UWOP_PUSH_MACHFRAME => {
+                    // The machine frame may be preceded by an error code.
+                    if operation_info == 1 {
+                        context.Rsp += 8;
+                    }
+                    context.Rip = read_u64(memory_source, context.Rsp)?;
+                    context.Rsp = read_u64(memory_source, context.Rsp + 24)?;
+                    return Ok(true);
+                }
+                // Saved XMM registers don't affect the integer registers.
+                _ => {}
+            }
+        }
+
+        index += slot_count;
+    }
+
+    if flags & UNW_FLAG_CHAININFO != 0 {
+        // The chained function entry comes after the unwind codes, which are padded to an even count.
+        let chained_function_addr = unwind_info_addr + 4 + 2 * ((code_count as u64 + 1) & !1);
+        let chained_function = memory::read_memory_full_array::<RuntimeFunction>(memory_source, chained_function_addr, 1)
+            .map_err(|_| format!("Could not read chained unwind info at {chained_function_addr:#018x}"))?[0];
+        return apply_unwind_info(context, module_address, &chained_function, None, memory_source, depth + 1);
+    }
+
+    Ok(false)
+}
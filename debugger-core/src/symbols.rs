@@ -0,0 +1,219 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+        Mutex,
+    },
+};
+
+use pdb::PDB;
+use windows::{
+    core::{IUnknown, HSTRING},
+    Win32::System::Com::{IBindStatusCallback, Urlmon::URLDownloadToFileW},
+};
+
+use crate::module::PdbInfo;
+
+// Finds PDBs the way WinDbg does, using the symbol path in `_NT_SYMBOL_PATH`. The path is a `;`-separated list of
+// elements. Each element is either a local directory, or `srv*<cache>*<server>` to download from a symbol server
+// (e.g. `srv*C:\Symbols*https://msdl.microsoft.com/download/symbols`). See
+// https://learn.microsoft.com/en-us/windows-hardware/drivers/debugger/symbol-path for the full syntax.
+
+const SYMBOL_PATH_VARIABLE: &str = "_NT_SYMBOL_PATH";
+
+/// The local cache (downstream store) that is checked before the symbol path, and that symbol servers download into
+/// unless the symbol path names another cache. `None` means the default, see `get_default_cache_directory`.
+static CACHE_DIRECTORY: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// How many PDBs are looked for at once. Most of the time is spent waiting for the disk or a symbol server, so this can be
+/// more than the number of processors.
+const SEARCH_THREAD_COUNT: usize = 8;
+
+/// Sends searches to the threads that run them. Started on first use. See `find_pdb_in_background`.
+static SEARCHES: Mutex<Option<Sender<PdbSearch>>> = Mutex::new(None);
+
+struct PdbSearch {
+    pdb_name: String,
+    pdb_info: PdbInfo,
+    result: Sender<Result<PathBuf, String>>,
+}
+
+enum SymbolPathElement {
+    Directory(PathBuf),
+    /// Local caches (downstream stores) to check first, and servers to download from into the first cache.
+    Server { caches: Vec<PathBuf>, servers: Vec<String> },
+}
+
+fn parse_symbol_path(symbol_path: &str) -> Vec<SymbolPathElement> {
+    symbol_path.split(';')
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .map(|element| {
+            let parts: Vec<&str> = element.split('*').collect();
+            match parts[0].to_lowercase().as_str() {
+                "srv" | "symsrv" => {
+                    // `symsrv*symsrv.dll*...` names the DLL that implements the protocol, which we don't need.
+                    let stores = parts[1..].iter().filter(|part| !part.eq_ignore_ascii_case("symsrv.dll"));
+                    let (servers, caches): (Vec<&str>, Vec<&str>) = stores
+                        .filter(|part| !part.is_empty())
+                        .partition(|part| part.starts_with("http://") || part.starts_with("https://"));
+                    let mut caches: Vec<PathBuf> = caches.into_iter().map(PathBuf::from).collect();
+                    if caches.is_empty() {
+                        caches.push(get_cache_directory());
+                    }
+                    SymbolPathElement::Server {
+                        caches,
+                        servers: servers.into_iter().map(|server| server.trim_end_matches('/').to_string()).collect(),
+                    }
+                }
+                _ => SymbolPathElement::Directory(PathBuf::from(element)),
+            }
+        })
+        .collect()
+}
+
+fn get_default_cache_directory() -> PathBuf {
+    match std::env::var_os("LOCALAPPDATA") {
+        Some(local_app_data) => PathBuf::from(local_app_data).join("debugger").join("sym"),
+        None => std::env::temp_dir().join("debugger").join("sym"),
+    }
+}
+
+pub fn get_cache_directory() -> PathBuf {
+    CACHE_DIRECTORY.lock().unwrap().clone().unwrap_or_else(get_default_cache_directory)
+}
+
+/// Changes the local symbol cache. This only affects modules that are loaded afterwards.
+pub fn set_cache_directory(directory: PathBuf) {
+    *CACHE_DIRECTORY.lock().unwrap() = Some(directory);
+}
+
+/// Gets the path of the PDB within a symbol store, which is `<name>/<GUID><age>/<name>` with the GUID and age in hex.
+fn get_symbol_store_path(pdb_file_name: &str, pdb_info: &PdbInfo) -> String {
+    let guid = &pdb_info.guid;
+    let data4: String = guid.data4.iter().map(|byte| format!("{byte:02X}")).collect();
+    let key = format!("{:08X}{:04X}{:04X}{data4}{:X}", guid.data1, guid.data2, guid.data3, pdb_info.age);
+    format!("{pdb_file_name}/{key}/{pdb_file_name}")
+}
+
+fn download_file(url: &str, destination: &Path) -> Result<(), String> {
+    if let Some(directory) = destination.parent() {
+        std::fs::create_dir_all(directory).map_err(|e| format!("Could not create {}: {e}", directory.display()))?;
+    }
+    let url_wide = HSTRING::from(url);
+    let destination_wide = HSTRING::from(destination.as_os_str());
+    unsafe { URLDownloadToFileW(None::<&IUnknown>, &url_wide, &destination_wide, 0, None::<&IBindStatusCallback>) }
+        .map_err(|e| format!("Could not download {url}: {e}"))
+}
+
+/// Starts looking for a module's PDB on another thread, so that loading a module doesn't wait for the symbol path to be
+/// searched or for a download. Receives the path of the matching PDB, which can then be opened with `open_pdb`.
+/// The PDB itself isn't opened on the other thread, because it can't be sent between threads.
+pub fn find_pdb_in_background(pdb_name: String, pdb_info: PdbInfo) -> Receiver<Result<PathBuf, String>> {
+    let (result, receiver) = mpsc::channel();
+    let mut searches = SEARCHES.lock().unwrap();
+    let sender = searches.get_or_insert_with(start_search_threads);
+    if let Err(mpsc::SendError(search)) = sender.send(PdbSearch { pdb_name, pdb_info, result }) {
+        // The threads are gone, so search on this one.
+        let _ = search.result.send(find_pdb_path(&search.pdb_name, &search.pdb_info));
+    }
+    receiver
+}
+
+fn start_search_threads() -> Sender<PdbSearch> {
+    let (sender, receiver) = mpsc::channel::<PdbSearch>();
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..SEARCH_THREAD_COUNT {
+        let receiver = receiver.clone();
+        std::thread::spawn(move || {
+            // The lock is only held while waiting for a search, not while running it.
+            while let Ok(search) = receiver.lock().unwrap().recv() {
+                // The module may have been unloaded in the meantime, so nobody may be waiting for the result.
+                let _ = search.result.send(find_pdb_path(&search.pdb_name, &search.pdb_info));
+            }
+        });
+    }
+    sender
+}
+
+/// Opens a PDB that `find_pdb_in_background` found.
+pub fn open_pdb(path: &Path) -> Result<PDB<'static, File>, String> {
+    let file = File::open(path).map_err(|e| format!("Could not open {}: {e}", path.display()))?;
+    PDB::open(file).map_err(|e| format!("Could not read {}: {e}", path.display()))
+}
+
+/// Returns `path` if there is a PDB there that matches the module.
+fn check_pdb(path: &Path, pdb_info: &PdbInfo) -> Option<PathBuf> {
+    tracing::trace!("Looking for symbols at {}", path.display());
+    let file = File::open(path).ok()?;
+    let mut pdb = PDB::open(file).ok()?;
+    // Only the GUID is compared, because the age in the PDB's information stream can be larger than the module's.
+    let information = pdb.pdb_information().ok()?;
+    let (data1, data2, data3, data4) = information.guid.as_fields();
+    let guid = &pdb_info.guid;
+    if data1 == guid.data1 && data2 == guid.data2 && data3 == guid.data3 && *data4 == guid.data4 {
+        tracing::debug!("Found symbols at {}", path.display());
+        Some(path.to_path_buf())
+    } else {
+        tracing::info!("Ignoring {} because it does not match the module", path.display());
+        None
+    }
+}
+
+/// Finds and opens the PDB for a module. See `find_pdb_path`.
+pub fn find_pdb(pdb_name: &str, pdb_info: &PdbInfo) -> Result<PDB<'static, File>, String> {
+    open_pdb(&find_pdb_path(pdb_name, pdb_info)?)
+}
+
+/// Finds the PDB for a module. `pdb_name` is the path that was recorded in the module when it was built.
+/// Searches the local cache, then the symbol path, downloading from symbol servers if needed, and then falls back to
+/// `pdb_name` itself.
+fn find_pdb_path(pdb_name: &str, pdb_info: &PdbInfo) -> Result<PathBuf, String> {
+    let pdb_file_name = Path::new(pdb_name)
+        .file_name()
+        .map_or(pdb_name.to_string(), |name| name.to_string_lossy().to_string());
+    let store_path = get_symbol_store_path(&pdb_file_name, pdb_info);
+
+    // Check the cache first, so that symbols are only downloaded once.
+    if let Some(path) = check_pdb(&get_cache_directory().join(&store_path), pdb_info) {
+        return Ok(path);
+    }
+
+    let symbol_path = std::env::var(SYMBOL_PATH_VARIABLE).unwrap_or_default();
+    for element in parse_symbol_path(&symbol_path) {
+        match element {
+            SymbolPathElement::Directory(directory) => {
+                for candidate in [directory.join(&pdb_file_name), directory.join(&store_path)] {
+                    if let Some(path) = check_pdb(&candidate, pdb_info) {
+                        return Ok(path);
+                    }
+                }
+            }
+            SymbolPathElement::Server { caches, servers } => {
+                for cache in &caches {
+                    if let Some(path) = check_pdb(&cache.join(&store_path), pdb_info) {
+                        return Ok(path);
+                    }
+                }
+                let destination = caches[0].join(&store_path);
+                for server in &servers {
+                    let url = format!("{server}/{store_path}");
+                    tracing::info!("Downloading symbols from {url}");
+                    match download_file(&url, &destination) {
+                        Ok(()) => {
+                            if let Some(path) = check_pdb(&destination, pdb_info) {
+                                return Ok(path);
+                            }
+                        }
+                        Err(e) => tracing::warn!("{e}"),
+                    }
+                }
+            }
+        }
+    }
+
+    check_pdb(Path::new(pdb_name), pdb_info)
+        .ok_or_else(|| format!("Could not find {pdb_file_name} in the symbol path or at {pdb_name}"))
+}
@@ -0,0 +1,327 @@
+use std::fs::File;
+
+use pdb::{FallibleIterator, Indirection, PrimitiveKind, TypeData, TypeFinder, TypeIndex, Variant, PDB};
+
+use crate::{
+    memory::MemorySource,
+    process::Process,
+};
+
+// Displays types from the PDB's type stream (TPI), like WinDbg's `dt`.
+// See https://llvm.org/docs/PDB/TpiStream.html and https://llvm.org/docs/PDB/CodeViewTypes.html for the formats.
+
+/// Stop following forwarding types (modifiers, etc.) after this many, in case the PDB is malformed.
+const MAX_TYPE_DEPTH: usize = 32;
+
+fn get_primitive_size(kind: PrimitiveKind) -> u64 {
+    match kind {
+        PrimitiveKind::NoType | PrimitiveKind::Void => 0,
+        PrimitiveKind::Char | PrimitiveKind::UChar | PrimitiveKind::RChar
+        | PrimitiveKind::I8 | PrimitiveKind::U8 | PrimitiveKind::Bool8 => 1,
+        PrimitiveKind::WChar | PrimitiveKind::RChar16 | PrimitiveKind::Short | PrimitiveKind::UShort
+        | PrimitiveKind::I16 | PrimitiveKind::U16 | PrimitiveKind::F16 | PrimitiveKind::Bool16 => 2,
+        PrimitiveKind::RChar32 | PrimitiveKind::Long | PrimitiveKind::ULong | PrimitiveKind::I32 | PrimitiveKind::U32
+        | PrimitiveKind::F32 | PrimitiveKind::F32PP | PrimitiveKind::Bool32 | PrimitiveKind::HRESULT => 4,
+        PrimitiveKind::F48 => 6,
+        PrimitiveKind::Quad | PrimitiveKind::UQuad | PrimitiveKind::I64 | PrimitiveKind::U64
+        | PrimitiveKind::F64 | PrimitiveKind::Bool64 | PrimitiveKind::Complex32 => 8,
+        PrimitiveKind::F80 => 10,
+        PrimitiveKind::Octa | PrimitiveKind::UOcta | PrimitiveKind::I128 | PrimitiveKind::U128
+        | PrimitiveKind::F128 | PrimitiveKind::Complex64 => 16,
+        PrimitiveKind::Complex80 => 20,
+        PrimitiveKind::Complex128 => 32,
+        _ => 0,
+    }
+}
+
+fn get_primitive_name(kind: PrimitiveKind) -> &'static str {
+    match kind {
+        PrimitiveKind::NoType => "<no type>",
+        PrimitiveKind::Void => "void",
+        PrimitiveKind::Char | PrimitiveKind::RChar => "char",
+        PrimitiveKind::UChar => "unsigned char",
+        PrimitiveKind::WChar => "wchar_t",
+        PrimitiveKind::RChar16 => "char16_t",
+        PrimitiveKind::RChar32 => "char32_t",
+        PrimitiveKind::I8 => "int8_t",
+        PrimitiveKind::U8 => "uint8_t",
+        PrimitiveKind::Short | PrimitiveKind::I16 => "short",
+        PrimitiveKind::UShort | PrimitiveKind::U16 => "unsigned short",
+        PrimitiveKind::Long => "long",
+        PrimitiveKind::ULong => "unsigned long",
+        PrimitiveKind::I32 => "int",
+        PrimitiveKind::U32 => "unsigned int",
+        PrimitiveKind::Quad | PrimitiveKind::I64 => "int64",
+        PrimitiveKind::UQuad | PrimitiveKind::U64 => "uint64",
+        PrimitiveKind::Octa | PrimitiveKind::I128 => "int128",
+        PrimitiveKind::UOcta | PrimitiveKind::U128 => "uint128",
+        PrimitiveKind::F16 => "half",
+        PrimitiveKind::F32 | PrimitiveKind::F32PP => "float",
+        PrimitiveKind::F64 => "double",
+        PrimitiveKind::F80 => "long double",
+        PrimitiveKind::Bool8 | PrimitiveKind::Bool16 | PrimitiveKind::Bool32 | PrimitiveKind::Bool64 => "bool",
+        PrimitiveKind::HRESULT => "HRESULT",
+        _ => "<unknown primitive>",
+    }
+}
+
+/// Gets the size of a type in bytes, or 0 if it is unknown.
+fn get_type_size(type_finder: &TypeFinder, type_index: TypeIndex, pointer_size: usize, depth: usize) -> u64 {
+    if depth > MAX_TYPE_DEPTH {
+        return 0;
+    }
+    let type_data = match type_finder.find(type_index).and_then(|item| item.parse()) {
+        Ok(type_data) => type_data,
+        Err(_) => return 0,
+    };
+    match type_data {
+        TypeData::Primitive(primitive) => match primitive.indirection {
+            Some(Indirection::Near32) => 4,
+            Some(Indirection::Near64) => 8,
+            Some(_) => pointer_size as u64,
+            None => get_primitive_size(primitive.kind),
+        },
+        TypeData::Pointer(pointer) => match pointer.attributes.size() {
+            0 => pointer_size as u64,
+            size => size as u64,
+        },
+        TypeData::Class(class) => class.size,
+        TypeData::Union(union) => union.size,
+        TypeData::Enumeration(enumeration) => get_type_size(type_finder, enumeration.underlying_type, pointer_size, depth + 1),
+        TypeData::Modifier(modifier) => get_type_size(type_finder, modifier.underlying_type, pointer_size, depth + 1),
+        TypeData::Bitfield(bitfield) => get_type_size(type_finder, bitfield.underlying_type, pointer_size, depth + 1),
+        // The last dimension is the total size in bytes.
+        TypeData::Array(array) => array.dimensions.last().copied().unwrap_or(0) as u64,
+        _ => 0,
+    }
+}
+
+/// Gets a C-like name for a type, e.g. `_LIST_ENTRY*` or `wchar_t[260]`.
+fn get_type_name(type_finder: &TypeFinder, type_index: TypeIndex, pointer_size: usize, depth: usize) -> String {
+    if depth > MAX_TYPE_DEPTH {
+        return String::from("...");
+    }
+    let type_data = match type_finder.find(type_index).and_then(|item| item.parse()) {
+        Ok(type_data) => type_data,
+        Err(_) => return format!("<type {:#x}>", type_index.0),
+    };
+    match type_data {
+        TypeData::Primitive(primitive) => {
+            let name = get_primitive_name(primitive.kind);
+            match primitive.indirection {
+                Some(_) => format!("{name}*"),
+                None => name.to_string(),
+            }
+        }
+        TypeData::Pointer(pointer) => format!("{}*", get_type_name(type_finder, pointer.underlying_type, pointer_size, depth + 1)),
+        TypeData::Modifier(modifier) => {
+            let name = get_type_name(type_finder, modifier.underlying_type, pointer_size, depth + 1);
+            if modifier.constant { format!("const {name}") } else { name }
+        }
+        TypeData::Array(array) => {
+            let element_size = get_type_size(type_finder, array.element_type, pointer_size, depth + 1);
+            let total_size = array.dimensions.last().copied().unwrap_or(0) as u64;
+            let count = total_size.checked_div(element_size).unwrap_or(0);
+            format!("{}[{count}]", get_type_name(type_finder, array.element_type, pointer_size, depth + 1))
+        }
+        TypeData::Bitfield(bitfield) => {
+            format!("{} (bits {}-{})", get_type_name(type_finder, bitfield.underlying_type, pointer_size, depth + 1),
+                bitfield.position, bitfield.position as u32 + bitfield.length as u32 - 1)
+        }
+        TypeData::Procedure(_) | TypeData::MemberFunction(_) => String::from("<function>"),
+        type_data => type_data.name().map_or_else(|| String::from("<unknown>"), |name| name.to_string().to_string()),
+    }
+}
+
+/// Reads a scalar (integer, pointer, enum, or bitfield) value. Returns `None` for aggregates such as structs and arrays,
+/// and `Some(None)` if the value couldn't be read.
+fn read_scalar_value(
+    type_finder: &TypeFinder,
+    type_index: TypeIndex,
+    address: u64,
+    pointer_size: usize,
+    memory_source: &dyn MemorySource,
+    depth: usize,
+) -> Option<Option<u64>> {
+    if depth > MAX_TYPE_DEPTH {
+        return None;
+    }
+    let type_data = type_finder.find(type_index).and_then(|item| item.parse()).ok()?;
+    let read = |size: u64| -> Option<Option<u64>> {
+        if size == 0 || size > 8 {
+            return None;
+        }
+        let bytes = memory_source.read_memory(address, size as usize).unwrap_or_else(|_| vec![None; size as usize]);
+        Some(bytes.iter().rev().try_fold(0u64, |value, byte| byte.map(|byte| (value << 8) | byte as u64)))
+    };
+    match type_data {
+        TypeData::Primitive(_) | TypeData::Pointer(_) | TypeData::Enumeration(_) => {
+            read(get_type_size(type_finder, type_index, pointer_size, depth))
+        }
+        TypeData::Modifier(modifier) => {
+            read_scalar_value(type_finder, modifier.underlying_type, address, pointer_size, memory_source, depth + 1)
+        }
+        TypeData::Bitfield(bitfield) => {
+            let value = read(get_type_size(type_finder, bitfield.underlying_type, pointer_size, depth + 1))?;
+            let mask = if bitfield.length >= 64 { u64::MAX } else { (1u64 << bitfield.length) - 1 };
+            Some(value.map(|value| (value >> bitfield.position) & mask))
+        }
+        _ => None,
+    }
+}
+
+fn format_variant(value: &Variant) -> String {
+    match *value {
+        Variant::U8(value) => format!("{value:#x}"),
+        Variant::U16(value) => format!("{value:#x}"),
+        Variant::U32(value) => format!("{value:#x}"),
+        Variant::U64(value) => format!("{value:#x}"),
+        Variant::I8(value) => value.to_string(),
+        Variant::I16(value) => value.to_string(),
+        Variant::I32(value) => value.to_string(),
+        Variant::I64(value) => value.to_string(),
+    }
+}
+
+/// Gets the fields of a field list, following its continuations.
+fn get_fields<'t>(type_finder: &TypeFinder<'t>, fields: TypeIndex) -> Result<Vec<TypeData<'t>>, pdb::Error> {
+    let mut result = Vec::new();
+    let mut next = Some(fields);
+    while let Some(fields) = next {
+        match type_finder.find(fields)?.parse()? {
+            TypeData::FieldList(list) => {
+                result.extend(list.fields);
+                next = list.continuation;
+            }
+            _ => break,
+        }
+    }
+    Ok(result)
+}
+
+/// Prints the fields of a struct, class, or union, with their offsets and types, and their values if `address` is given.
+fn display_fields(
+    type_finder: &TypeFinder,
+    fields: TypeIndex,
+    address: Option<u64>,
+    pointer_size: usize,
+    memory_source: &dyn MemorySource,
+) -> Result<(), pdb::Error> {
+    for field in get_fields(type_finder, fields)? {
+        match field {
+            TypeData::BaseClass(base) => {
+                let name = get_type_name(type_finder, base.base_class, pointer_size, 0);
+                println!("   +{:#05x} (base class) : {name}", base.offset);
+            }
+            TypeData::VirtualFunctionTablePointer(_) => {
+                // The vtable pointer is always at the start.
+                println!("   +0x000 (vtable pointer)");
+            }
+            TypeData::Member(member) => {
+                let type_name = get_type_name(type_finder, member.field_type, pointer_size, 0);
+                let value = address
+                    .and_then(|address| read_scalar_value(type_finder, member.field_type, address + member.offset, pointer_size, memory_source, 0))
+                    .map_or(String::new(), |value| match value {
+                        Some(value) => format!(" = {value:#x}"),
+                        // Like WinDbg, show that the field is there even though its memory can't be read.
+                        None => String::from(" = ??"),
+                    });
+                println!("   +{:#05x} {:<24} : {type_name}{value}", member.offset, member.name.to_string());
+            }
+            TypeData::StaticMember(member) => {
+                let type_name = get_type_name(type_finder, member.field_type, pointer_size, 0);
+                println!("   static {:<24} : {type_name}", member.name.to_string());
+            }
+            // Methods and nested types aren't part of the layout.
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Prints a type from the PDB, optionally with the values of its fields at `address`.
+fn display_type_from_pdb(
+    pdb: &mut PDB<'static, File>,
+    type_name: &str,
+    address: Option<u64>,
+    pointer_size: usize,
+    memory_source: &dyn MemorySource,
+) -> Result<bool, pdb::Error> {
+    let type_information = pdb.type_information()?;
+    let mut type_finder = type_information.finder();
+    let mut types = type_information.iter();
+    let mut found = None;
+    while let Some(item) = types.next()? {
+        type_finder.update(&types);
+        if found.is_some() {
+            continue;
+        }
+        // Skip forward references, which have no fields, to find the full definition.
+        let is_match = match item.parse() {
+            Ok(TypeData::Class(class)) => !class.properties.forward_reference() && class.name.to_string() == type_name,
+            Ok(TypeData::Union(union)) => !union.properties.forward_reference() && union.name.to_string() == type_name,
+            Ok(TypeData::Enumeration(enumeration)) => {
+                !enumeration.properties.forward_reference() && enumeration.name.to_string() == type_name
+            }
+            _ => false,
+        };
+        if is_match {
+            found = Some(item.index());
+        }
+    }
+
+    let type_index = match found {
+        Some(type_index) => type_index,
+        None => return Ok(false),
+    };
+    match type_finder.find(type_index)?.parse()? {
+        TypeData::Class(class) => {
+            println!("{type_name} (size {:#x})", class.size);
+            if let Some(fields) = class.fields {
+                display_fields(&type_finder, fields, address, pointer_size, memory_source)?;
+            }
+        }
+        TypeData::Union(union) => {
+            println!("union {type_name} (size {:#x})", union.size);
+            display_fields(&type_finder, union.fields, address, pointer_size, memory_source)?;
+        }
+        TypeData::Enumeration(enumeration) => {
+            let underlying_type = get_type_name(&type_finder, enumeration.underlying_type, pointer_size, 0);
+            println!("enum {type_name} : {underlying_type}");
+            if let Some(address) = address {
+                match read_scalar_value(&type_finder, type_index, address, pointer_size, memory_source, 0) {
+                    Some(Some(value)) => println!("   Value = {value:#x}"),
+                    _ => println!("   Could not read the value at {address:#018x}"),
+                }
+            }
+            for field in get_fields(&type_finder, enumeration.fields)? {
+                if let TypeData::Enumerate(enumerate) = field {
+                    println!("   {:<24} = {}", enumerate.name.to_string(), format_variant(&enumerate.value));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Prints a type, given as `module!type`, with its fields. If `address` is given, also prints the values of the fields there.
+pub fn display_type(
+    type_path: &str,
+    address: Option<u64>,
+    process: &mut Process,
+    memory_source: &dyn MemorySource,
+) -> Result<(), String> {
+    let (module_name, type_name) = type_path.split_once('!')
+        .ok_or_else(|| format!("Expected module!type, but got {type_path}"))?;
+    let pointer_size = process.pointer_size();
+    let module = process.get_module_by_name_mut(module_name)
+        .ok_or_else(|| format!("Could not find module {module_name}"))?;
+    let pdb = module.get_pdb().map_err(|e| format!("No symbols for {module_name}: {e}"))?;
+    let found = display_type_from_pdb(pdb, type_name, address, pointer_size, memory_source).map_err(|e| e.to_string())?;
+    if found {
+        Ok(())
+    } else {
+        Err(format!("Could not find type {type_name} in {module_name}"))
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use windows::Win32::Foundation::HANDLE;
+
+use crate::{
+    name_resolution,
+    output,
+    process::Process,
+    windows_wrapper::{self, ExceptionRecord, PAGE_SIZE},
+};
+
+// Hardware breakpoints can only watch a few bytes, so larger ranges, e.g. a whole buffer or struct, are watched by making
+// the pages that contain them guard pages. The first access to a guard page raises `EXCEPTION_GUARD_PAGE` before the
+// access happens, and removes the guard. If the access is in a watched range, the debugger stops. Either way, the thread
+// is single-stepped past the access and the guard is put back, like stepping past a software breakpoint.
+//
+// Everything else on the same pages raises exceptions too, so watching memory that is accessed often slows the target
+// down.
+
+/// The `ExceptionInformation` values of an `EXCEPTION_GUARD_PAGE` exception are the kind of access, and its address.
+const ACCESS_WRITE: u64 = 1;
+
+/// Which accesses stop the debugger.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WatchAccess {
+    Write,
+    /// Reads, writes, and executes.
+    Any,
+}
+
+struct MemoryWatchpoint {
+    address: u64,
+    size: u64,
+    access: WatchAccess,
+}
+
+impl MemoryWatchpoint {
+    fn pages(&self) -> impl Iterator<Item = u64> {
+        let first_page = self.address & !(PAGE_SIZE - 1);
+        (first_page..self.address + self.size).step_by(PAGE_SIZE as usize)
+    }
+}
+
+/// Describes a watchpoint that an access hit.
+pub struct WatchpointHit {
+    /// The address of the watchpoint, not of the access.
+    pub watchpoint_address: u64,
+    pub access_address: u64,
+    pub is_write: bool,
+}
+
+/// Memory watchpoints for one process, which are implemented with guard pages.
+#[derive(Default)]
+pub struct MemoryWatchpointManager {
+    watchpoints: Vec<MemoryWatchpoint>,
+    /// Pages that a guard page exception removed the guard from, until a thread has stepped past the access.
+    disarmed_pages: HashSet<u64>,
+}
+
+impl MemoryWatchpointManager {
+    pub fn new() -> MemoryWatchpointManager {
+        MemoryWatchpointManager::default()
+    }
+
+    /// Watches `size` bytes at `address` by making the pages that contain them guard pages.
+    pub fn add_watchpoint(&mut self, process: HANDLE, address: u64, size: u64, access: WatchAccess) -> Result<(), String> {
+        if size == 0 {
+            return Err(String::from("The size must be at least 1"));
+        }
+        if self.watchpoints.iter().any(|watchpoint| watchpoint.address == address) {
+            return Err(format!("Memory watchpoint already exists at {address:#018x}"));
+        }
+
+        let watchpoint = MemoryWatchpoint { address, size, access };
+        let mut guarded_pages = Vec::new();
+        for page in watchpoint.pages() {
+            if self.is_watched_page(page) {
+                continue;
+            }
+            match windows_wrapper::set_page_guard(process, page, true) {
+                Ok(false) => guarded_pages.push(page),
+                // Guard pages that the target uses itself, e.g. to grow the stack, can't be shared.
+                Ok(true) => {
+                    self.unguard_pages(process, &guarded_pages);
+                    return Err(format!("{page:#018x} is already a guard page"));
+                }
+                Err(e) => {
+                    self.unguard_pages(process, &guarded_pages);
+                    return Err(e);
+                }
+            }
+        }
+        self.watchpoints.push(watchpoint);
+        Ok(())
+    }
+
+    pub fn remove_watchpoint(&mut self, process: HANDLE, address: u64) -> Result<(), String> {
+        let index = self.watchpoints.iter().position(|watchpoint| watchpoint.address == address)
+            .ok_or_else(|| format!("No memory watchpoint at {address:#018x}"))?;
+        let watchpoint = self.watchpoints.remove(index);
+        let unwatched_pages: Vec<u64> = watchpoint.pages().filter(|page| !self.is_watched_page(*page)).collect();
+        self.unguard_pages(process, &unwatched_pages);
+        Ok(())
+    }
+
+    /// Removes every watchpoint, for `bc *`. Watchpoints whose pages can't be unguarded are kept. Returns how many were
+    /// kept.
+    pub fn remove_all_watchpoints(&mut self, process: HANDLE) -> usize {
+        let mut unguarded_pages = HashSet::new();
+        let mut kept = Vec::new();
+        for watchpoint in std::mem::take(&mut self.watchpoints) {
+            // A disarmed page's guard was already removed by the access.
+            let pages: Vec<u64> = watchpoint.pages()
+                .filter(|page| !self.disarmed_pages.contains(page) && !unguarded_pages.contains(page))
+                .collect();
+            match pages.iter().try_for_each(|page| windows_wrapper::set_page_guard(process, *page, false).map(|_| ())) {
+                Ok(()) => unguarded_pages.extend(pages),
+                Err(e) => {
+                    print_error!("Could not remove memory watchpoint at {:#018x}: {e}", watchpoint.address);
+                    kept.push(watchpoint);
+                }
+            }
+        }
+        self.watchpoints = kept;
+        let disarmed_pages = std::mem::take(&mut self.disarmed_pages);
+        self.disarmed_pages = disarmed_pages.into_iter().filter(|page| self.is_watched_page(*page)).collect();
+        self.watchpoints.len()
+    }
+
+    fn unguard_pages(&mut self, process: HANDLE, pages: &[u64]) {
+        for page in pages {
+            self.disarmed_pages.remove(page);
+            // The memory may have been freed, in which case there's nothing to undo.
+            let _ = windows_wrapper::set_page_guard(process, *page, false);
+        }
+    }
+
+    fn is_watched_page(&self, page: u64) -> bool {
+        self.watchpoints.iter().any(|watchpoint| watchpoint.pages().any(|watched_page| watched_page == page))
+    }
+
+    /// Handles an `EXCEPTION_GUARD_PAGE` exception. Returns `None` if the page isn't watched, which means that the exception
+    /// belongs to the target. Otherwise, the page is disarmed until `rearm_page` is called with the returned page, after
+    /// the thread has stepped past the access, and the watchpoint that was hit is returned, if any.
+    pub fn handle_guard_page_exception(&mut self, record: &ExceptionRecord) -> Option<(u64, Option<WatchpointHit>)> {
+        let (access_kind, access_address) = match record.parameters[..] {
+            [access_kind, access_address, ..] => (access_kind, access_address),
+            _ => return None,
+        };
+        let page = access_address & !(PAGE_SIZE - 1);
+        if !self.is_watched_page(page) {
+            return None;
+        }
+
+        self.disarmed_pages.insert(page);
+        let is_write = access_kind == ACCESS_WRITE;
+        let hit = self.watchpoints.iter()
+            .find(|watchpoint| {
+                let in_range = watchpoint.address <= access_address && access_address < watchpoint.address + watchpoint.size;
+                in_range && (is_write || watchpoint.access == WatchAccess::Any)
+            })
+            .map(|watchpoint| WatchpointHit { watchpoint_address: watchpoint.address, access_address, is_write });
+        Some((page, hit))
+    }
+
+    /// Puts the guard back on a page that `handle_guard_page_exception` disarmed.
+    pub fn rearm_page(&mut self, process: HANDLE, page: u64) -> Result<(), String> {
+        // Do nothing if the watchpoint was removed in the meantime.
+        if self.disarmed_pages.remove(&page) && self.is_watched_page(page) {
+            windows_wrapper::set_page_guard(process, page, true)?;
+        }
+        Ok(())
+    }
+
+    pub fn list_watchpoints(&self, process: &mut Process) {
+        for watchpoint in self.watchpoints.iter() {
+            let access = match watchpoint.access {
+                WatchAccess::Write => "write",
+                WatchAccess::Any => "any access",
+            };
+            let name = name_resolution::resolve_address_to_name(watchpoint.address, process)
+                .map(|name| format!(" ({})", output::symbol(&name)))
+                .unwrap_or_default();
+            println!("{}{name} [memory {:#x} bytes, {access}]", output::address(watchpoint.address), watchpoint.size);
+        }
+    }
+}
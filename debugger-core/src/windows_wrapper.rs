@@ -0,0 +1,1123 @@
+use std::{
+    ffi::{c_void, OsStr, OsString},
+    fmt::{self, Debug},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::Path,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Wdk::{
+        Foundation::{NtQueryObject, ObjectTypeInformation, OBJECT_INFORMATION_CLASS},
+        System::{
+            SystemInformation::{NtQuerySystemInformation, SYSTEM_INFORMATION_CLASS},
+            Threading::{NtQueryInformationThread, ThreadBasicInformation},
+        },
+    },
+    Win32::{
+        Foundation::*,
+        Storage::FileSystem::*,
+        System::{
+            Console::*,
+            Diagnostics::Debug::*,
+            Diagnostics::ToolHelp::*,
+            Memory::{
+                VirtualAllocEx,
+                VirtualProtectEx,
+                VirtualQueryEx,
+                MEMORY_BASIC_INFORMATION,
+                MEM_COMMIT,
+                MEM_FREE,
+                MEM_IMAGE,
+                MEM_MAPPED,
+                MEM_PRIVATE,
+                MEM_RESERVE,
+                PAGE_EXECUTE,
+                PAGE_EXECUTE_READ,
+                PAGE_EXECUTE_READWRITE,
+                PAGE_EXECUTE_WRITECOPY,
+                PAGE_GUARD,
+                PAGE_NOACCESS,
+                PAGE_NOCACHE,
+                PAGE_PROTECTION_FLAGS,
+                PAGE_READONLY,
+                PAGE_READWRITE,
+                PAGE_WRITECOMBINE,
+                PAGE_WRITECOPY,
+            },
+            SystemInformation::GetLocalTime,
+            Threading::*,
+        },
+    },
+};
+
+use crate::memory::{self, MemorySource};
+
+pub const TRAP_FLAG: u32 = 1 << 8;
+/// Suppresses instruction breakpoints (e.g. hardware breakpoints) for the next instruction.
+pub const RESUME_FLAG: u32 = 1 << 16;
+
+pub const EXCEPTION_CODE_SINGLE_STEP: NTSTATUS = EXCEPTION_SINGLE_STEP;
+pub const EXCEPTION_CODE_BREAKPOINT: NTSTATUS = EXCEPTION_BREAKPOINT;
+/// Raised instead of `EXCEPTION_CODE_SINGLE_STEP` and `EXCEPTION_CODE_BREAKPOINT` by 32-bit code in a WOW64 process.
+pub const EXCEPTION_CODE_WX86_SINGLE_STEP: NTSTATUS = STATUS_WX86_SINGLE_STEP;
+pub const EXCEPTION_CODE_WX86_BREAKPOINT: NTSTATUS = STATUS_WX86_BREAKPOINT;
+/// Raised the first time that a `PAGE_GUARD` page is accessed, which also removes the guard. See `set_page_guard`.
+pub const EXCEPTION_CODE_GUARD_PAGE: NTSTATUS = EXCEPTION_GUARD_PAGE;
+
+/// Maps the WOW64 single step and breakpoint exception codes to the native ones, so that they are handled the same way.
+pub fn get_native_exception_code(code: NTSTATUS, is_wow64: bool) -> NTSTATUS {
+    match code {
+        EXCEPTION_CODE_WX86_SINGLE_STEP if is_wow64 => EXCEPTION_CODE_SINGLE_STEP,
+        EXCEPTION_CODE_WX86_BREAKPOINT if is_wow64 => EXCEPTION_CODE_BREAKPOINT,
+        _ => code,
+    }
+}
+
+/// Memory protection is set, and memory is readable or not, in whole pages.
+pub const PAGE_SIZE: u64 = 0x1000;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ThreadId(u32);
+
+impl fmt::Display for ThreadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<u32> for ThreadId {
+    fn from(id: u32) -> Self {
+        ThreadId(id)
+    }
+}
+
+impl fmt::LowerHex for ThreadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for ThreadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ProcessId(u32);
+
+impl fmt::Display for ProcessId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<u32> for ProcessId {
+    fn from(id: u32) -> Self {
+        ProcessId(id)
+    }
+}
+
+impl fmt::LowerHex for ProcessId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for ProcessId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+/// Gets the last platform error code and returns an error message containing the code and the message matching the code.
+pub fn get_last_platform_error_message() -> String {
+    let error_code = unsafe { GetLastError() } ;
+    let mut error_message_buffer = Vec::<u16>::with_capacity(1024);
+    unsafe {
+        let error_message_buffer_uninitialized = error_message_buffer.spare_capacity_mut();
+        let message_len = FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS /*dwFlags*/,
+            None /*lpsource*/,
+            error_code.0 /*dwMessageId*/,
+            0 /*dwLanguageId*/,
+            PWSTR::from_raw(error_message_buffer_uninitialized.as_mut_ptr().cast()) /*lpBuffer*/,
+            error_message_buffer_uninitialized.len() as u32 /*nSize*/,
+            None /*Arguments*/
+        );
+
+        error_message_buffer.set_len(message_len as usize);
+    }
+
+    let error_message_os = OsString::from_wide(&error_message_buffer);
+    let error_message = error_message_os.into_string()
+        .unwrap_or(String::from(""));
+    let trimmed_error_message = error_message.trim();
+    format!("OS error {code}: {trimmed_error_message}", code = error_code.0)
+}
+
+/// Converts a `String` into a null-terminated wide (u16) encoded string.
+/// This is useful for passing into Windows C APIs.
+pub fn convert_string_to_u16(input: &str) -> Vec<u16> {
+    let mut buffer: Vec<u16> = OsStr::new(&input)
+        .encode_wide()
+        .collect();
+
+    // Add the null terminator.
+    buffer.push(0);
+
+    buffer
+}
+
+pub fn close_handle(handle: HANDLE) -> Result<(), String> {
+    let ret = unsafe {
+        CloseHandle(handle)
+    };
+    ret.map_err(|error| format!("CloseHandle failed: {error}"))
+}
+
+/// Used to automatically close a handle when dropped.
+pub struct AutoClosedHandle(HANDLE);
+
+impl Drop for AutoClosedHandle {
+    fn drop(&mut self) {
+        // There's nothing to do about a handle that can't be closed, and it shouldn't end the session.
+        let _ = close_handle(self.0);
+    }
+}
+
+impl AutoClosedHandle {
+    pub fn handle(&self) -> HANDLE {
+        self.0
+    }
+}
+
+/// Opens a thread, e.g. to get its registers. This fails if the thread has exited.
+pub fn open_thread(thread_id: &ThreadId) -> Result<AutoClosedHandle, String> {
+    let handle = unsafe {
+        OpenThread(
+            THREAD_GET_CONTEXT | THREAD_SET_CONTEXT | THREAD_SUSPEND_RESUME | THREAD_QUERY_INFORMATION /*dwDesiredAccess*/,
+            FALSE /*bInheritHandle*/,
+            thread_id.0
+        )
+    };
+    handle
+        .map(AutoClosedHandle)
+        .map_err(|error| format!("Could not open thread {thread_id}: OpenThread failed: {error}"))
+}
+
+/// Options that control how a process is launched for debugging.
+#[derive(Default)]
+pub struct LaunchOptions {
+    /// Also debug processes that the target creates.
+    pub follow_child_processes: bool,
+    /// The target's working directory. Defaults to the debugger's.
+    pub current_directory: Option<String>,
+    /// Added to the debugger's environment, replacing variables with the same name.
+    pub environment_variables: Vec<(String, String)>,
+    /// Create the target with its initial thread suspended, so that nothing runs until it is resumed.
+    pub start_suspended: bool,
+}
+
+/// A process launched for debugging.
+pub struct LaunchedProcess {
+    pub process: AutoClosedHandle,
+    pub process_id: ProcessId,
+    /// The initial thread, if the process was started suspended. Pass it to `resume_thread` to start the process.
+    pub suspended_thread: Option<AutoClosedHandle>,
+}
+
+/// Builds a Unicode environment block from the debugger's environment with `overrides` applied.
+/// The block is a sequence of null-terminated `name=value` strings, sorted by name, terminated by an extra null.
+fn build_environment_block(overrides: &[(String, String)]) -> Vec<u16> {
+    let mut variables: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+    for (name, value) in overrides {
+        // Environment variable names are case-insensitive on Windows.
+        variables.retain(|(existing_name, _)| !existing_name.eq_ignore_ascii_case(name));
+        variables.push((OsString::from(name), OsString::from(value)));
+    }
+    variables.sort_by_key(|(name, _)| name.to_ascii_uppercase());
+
+    let mut block = Vec::<u16>::new();
+    for (name, value) in variables {
+        block.extend(name.encode_wide());
+        block.push('=' as u16);
+        block.extend(value.encode_wide());
+        block.push(0);
+    }
+    block.push(0);
+    block
+}
+
+/// Launches a process for debugging.
+pub fn launch_process_for_debugging(target_command_line_args: &[String], options: &LaunchOptions) -> Result<LaunchedProcess, String> {
+    let target_command_line_buffer = target_command_line_args.join(" ");
+    println!("Debugging {target_command_line_buffer}\n");
+    let mut target_command_line_buffer_u16 = convert_string_to_u16(&target_command_line_buffer);
+
+    let mut startup_info: STARTUPINFOEXW = unsafe { std::mem::zeroed() };
+    startup_info.StartupInfo.cb = std::mem::size_of::<STARTUPINFOEXW>() as u32;
+    let mut process_info: PROCESS_INFORMATION = unsafe { std::mem::zeroed() };
+    let mut creation_flags = CREATE_NEW_CONSOLE | CREATE_UNICODE_ENVIRONMENT;
+    creation_flags |= if options.follow_child_processes {
+        DEBUG_PROCESS
+    } else {
+        DEBUG_ONLY_THIS_PROCESS
+    };
+    if options.start_suspended {
+        creation_flags |= CREATE_SUSPENDED;
+    }
+
+    // Only pass an environment block when there is something to change, so that the target otherwise inherits ours as-is.
+    let environment_block = if options.environment_variables.is_empty() {
+        None
+    } else {
+        Some(build_environment_block(&options.environment_variables))
+    };
+    let current_directory_u16 = options.current_directory.as_deref().map(convert_string_to_u16);
+
+    let ret = unsafe {
+        CreateProcessW(
+            PCWSTR::null() /*lpApplicationName*/,
+            PWSTR(target_command_line_buffer_u16.as_mut_ptr()),
+            None /*lpProcessAttributes*/,
+            None /*lpThreadAttributes*/,
+            FALSE /*bInheritHandles*/,
+            creation_flags /*dwCreationFlags*/,
+            environment_block.as_ref().map(|block| block.as_ptr() as *const c_void) /*lpEnvironment*/,
+            current_directory_u16.as_ref().map_or(PCWSTR::null(), |directory| PCWSTR(directory.as_ptr())) /*lpCurrentDirectory*/,
+            &startup_info.StartupInfo,
+            &mut process_info,
+        )
+    };
+    ret.map_err(|error| format!("Failed to start process \"{target_command_line_buffer}\": CreateProcessW failed: {error}"))?;
+
+    let process = AutoClosedHandle(process_info.hProcess);
+    let thread = AutoClosedHandle(process_info.hThread);
+    Ok(LaunchedProcess {
+        process,
+        process_id: ProcessId(process_info.dwProcessId),
+        suspended_thread: options.start_suspended.then_some(thread),
+    })
+}
+
+/// A range of pages in a process's virtual address space that share the same state, protection, and type.
+pub struct MemoryRegion {
+    pub base_address: u64,
+    /// The base address of the allocation that the region is part of.
+    pub allocation_base: u64,
+    pub size: u64,
+    info: MEMORY_BASIC_INFORMATION,
+}
+
+impl MemoryRegion {
+    pub fn is_free(&self) -> bool {
+        self.info.State == MEM_FREE
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.info.Type == MEM_IMAGE
+    }
+
+    /// Whether the region's memory can be read, i.e. it is committed, and isn't a no-access or guard page.
+    pub fn is_readable(&self) -> bool {
+        let protection = self.info.Protect.0;
+        self.info.State == MEM_COMMIT && protection & 0xFF != PAGE_NOACCESS.0 && protection & PAGE_GUARD.0 == 0
+    }
+
+    /// Whether code can run in the region, as opposed to it only holding data.
+    pub fn is_executable(&self) -> bool {
+        let protection = self.info.Protect.0 & 0xFF;
+        [PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE, PAGE_EXECUTE_WRITECOPY].iter().any(|execute| execute.0 == protection)
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        match self.info.State {
+            MEM_COMMIT => "MEM_COMMIT",
+            MEM_FREE => "MEM_FREE",
+            MEM_RESERVE => "MEM_RESERVE",
+            _ => "<unknown>",
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.info.Type {
+            MEM_IMAGE => "MEM_IMAGE",
+            MEM_MAPPED => "MEM_MAPPED",
+            MEM_PRIVATE => "MEM_PRIVATE",
+            _ => "",
+        }
+    }
+
+    /// Describes the protection, e.g. `PAGE_READWRITE | PAGE_GUARD`. Reserved and free regions don't have any.
+    pub fn protection_name(&self) -> String {
+        format_page_protection(self.info.Protect.0)
+    }
+}
+
+/// The page protections, which are in the low byte of the protection. Exactly one of them is set.
+const PAGE_PROTECTIONS: [(PAGE_PROTECTION_FLAGS, &str); 8] = [
+    (PAGE_NOACCESS, "PAGE_NOACCESS"),
+    (PAGE_READONLY, "PAGE_READONLY"),
+    (PAGE_READWRITE, "PAGE_READWRITE"),
+    (PAGE_WRITECOPY, "PAGE_WRITECOPY"),
+    (PAGE_EXECUTE, "PAGE_EXECUTE"),
+    (PAGE_EXECUTE_READ, "PAGE_EXECUTE_READ"),
+    (PAGE_EXECUTE_READWRITE, "PAGE_EXECUTE_READWRITE"),
+    (PAGE_EXECUTE_WRITECOPY, "PAGE_EXECUTE_WRITECOPY"),
+];
+
+/// Flags that can be combined with a page protection.
+const PAGE_PROTECTION_MODIFIERS: [(PAGE_PROTECTION_FLAGS, &str); 3] = [
+    (PAGE_GUARD, "PAGE_GUARD"),
+    (PAGE_NOCACHE, "PAGE_NOCACHE"),
+    (PAGE_WRITECOMBINE, "PAGE_WRITECOMBINE"),
+];
+
+/// Describes a page protection, e.g. `PAGE_READWRITE | PAGE_GUARD`, or returns an empty string for no protection.
+pub fn format_page_protection(protection: u32) -> String {
+    if protection & 0xFF == 0 {
+        return String::new();
+    }
+    let mut name = String::from(PAGE_PROTECTIONS.iter().find(|(value, _)| value.0 == protection & 0xFF).map_or("<unknown>", |(_, name)| name));
+    for (modifier, modifier_name) in PAGE_PROTECTION_MODIFIERS {
+        if protection & modifier.0 != 0 {
+            name.push_str(" | ");
+            name.push_str(modifier_name);
+        }
+    }
+    name
+}
+
+/// Parses a page protection, which is names separated by `|`, with or without the `PAGE_` prefix and in any case, e.g.
+/// `PAGE_READWRITE` or `execute_read|guard`, or a number, e.g. `0x40`.
+pub fn parse_page_protection(text: &str) -> Result<u32, String> {
+    if let Some(hex) = text.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid page protection {text}"));
+    }
+    if let Ok(value) = text.parse::<u32>() {
+        return Ok(value);
+    }
+    text.split('|').try_fold(0, |protection, part| {
+        let part = part.trim().to_ascii_uppercase();
+        let name = if part.starts_with("PAGE_") { part } else { format!("PAGE_{part}") };
+        PAGE_PROTECTIONS.iter().chain(PAGE_PROTECTION_MODIFIERS.iter())
+            .find(|(_, known_name)| *known_name == name)
+            .map(|(value, _)| protection | value.0)
+            .ok_or_else(|| format!("Unknown page protection {name}"))
+    })
+}
+
+/// Allocates `size` bytes of memory in the process, rounded up to whole pages, which are zeroed, and returns its address.
+/// The protection defaults to `PAGE_EXECUTE_READWRITE`, so that the memory can hold code as well as data.
+pub fn allocate_memory(process: HANDLE, size: u64, protection: Option<u32>) -> Result<u64, String> {
+    let protection = PAGE_PROTECTION_FLAGS(protection.unwrap_or(PAGE_EXECUTE_READWRITE.0));
+    let address = unsafe { VirtualAllocEx(process, None, size as usize, MEM_COMMIT | MEM_RESERVE, protection) };
+    if address.is_null() {
+        return Err(format!("Could not allocate {size:#x} bytes: VirtualAllocEx failed: {}", get_last_platform_error_message()));
+    }
+    Ok(address as u64)
+}
+
+/// Changes the protection of the pages that contain `size` bytes at `address`, and returns the first page's previous
+/// protection.
+pub fn set_memory_protection(process: HANDLE, address: u64, size: u64, protection: u32) -> Result<u32, String> {
+    let mut old_protection = PAGE_PROTECTION_FLAGS(0);
+    unsafe { VirtualProtectEx(process, address as *const c_void, size as usize, PAGE_PROTECTION_FLAGS(protection), &mut old_protection) }
+        .map_err(|error| format!("Could not change the protection at {address:#018x}: VirtualProtectEx failed: {error}"))?;
+    Ok(old_protection.0)
+}
+
+/// Gets the memory region that contains `address`, or `None` if the address is beyond the end of the address space.
+pub fn query_memory_region(process: HANDLE, address: u64) -> Option<MemoryRegion> {
+    let mut info: MEMORY_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+    let ret = unsafe {
+        VirtualQueryEx(
+            process,
+            Some(address as *const c_void),
+            &mut info,
+            std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+    if ret == 0 {
+        return None;
+    }
+
+    Some(MemoryRegion {
+        base_address: info.BaseAddress as u64,
+        allocation_base: info.AllocationBase as u64,
+        size: info.RegionSize as u64,
+        info,
+    })
+}
+
+/// Adds or removes `PAGE_GUARD` on the page that contains `address`, keeping the rest of its protection.
+/// Returns whether the page was a guard page before.
+pub fn set_page_guard(process: HANDLE, address: u64, guard: bool) -> Result<bool, String> {
+    let region = query_memory_region(process, address).ok_or_else(|| format!("No memory at {address:#018x}"))?;
+    if region.info.State != MEM_COMMIT {
+        return Err(format!("The memory at {address:#018x} is not committed"));
+    }
+
+    let protection = region.info.Protect;
+    let was_guarded = protection.0 & PAGE_GUARD.0 != 0;
+    let new_protection = if guard {
+        PAGE_PROTECTION_FLAGS(protection.0 | PAGE_GUARD.0)
+    } else {
+        PAGE_PROTECTION_FLAGS(protection.0 & !PAGE_GUARD.0)
+    };
+    if new_protection != protection {
+        let mut old_protection = PAGE_PROTECTION_FLAGS(0);
+        unsafe { VirtualProtectEx(process, address as *const c_void, 1, new_protection, &mut old_protection) }
+            .map_err(|error| format!("Could not change the protection at {address:#018x}: VirtualProtectEx failed: {error}"))?;
+    }
+    Ok(was_guarded)
+}
+
+/// Gets all of the memory regions in the process's address space, in order, including free ones.
+pub fn enumerate_memory_regions(process: HANDLE) -> Vec<MemoryRegion> {
+    let mut regions = Vec::new();
+    let mut address = 0;
+    while let Some(region) = query_memory_region(process, address) {
+        address = region.base_address + region.size;
+        regions.push(region);
+    }
+    regions
+}
+
+/// Gets the thread's description, which programs set via `SetThreadDescription`. Returns `None` if it doesn't have one.
+pub fn get_thread_description(thread: &AutoClosedHandle) -> Option<String> {
+    let description = unsafe { GetThreadDescription(thread.handle()) }.ok()?;
+    let description_string = unsafe { description.to_string() }.ok();
+    unsafe { LocalFree(HLOCAL(description.0.cast())) };
+    description_string.filter(|description| !description.is_empty())
+}
+
+/// Matches the undocumented `THREAD_BASIC_INFORMATION` returned by `NtQueryInformationThread`.
+#[repr(C)]
+struct ThreadBasicInformationData {
+    exit_status: NTSTATUS,
+    teb_base_address: *mut c_void,
+    client_id_process: HANDLE,
+    client_id_thread: HANDLE,
+    affinity_mask: usize,
+    priority: i32,
+    base_priority: i32,
+}
+
+/// Gets the address of the thread environment block (TEB) in the thread's process.
+pub fn get_thread_environment_block_address(thread: &AutoClosedHandle) -> Result<u64, String> {
+    let mut info: ThreadBasicInformationData = unsafe { std::mem::zeroed() };
+    let status = unsafe {
+        NtQueryInformationThread(
+            thread.handle(),
+            ThreadBasicInformation,
+            ptr::addr_of_mut!(info).cast(),
+            std::mem::size_of::<ThreadBasicInformationData>() as u32,
+            ptr::null_mut(),
+        )
+    };
+    status.ok().map_err(|error| format!("NtQueryInformationThread failed: {error}"))?;
+    Ok(info.teb_base_address as u64)
+}
+
+/// Gets how many times the thread has been suspended.
+/// There is no API to query it directly, so suspend the thread (which returns the previous count) and then undo that.
+pub fn get_thread_suspend_count(thread: &AutoClosedHandle) -> Result<u32, String> {
+    let previous_count = unsafe { SuspendThread(thread.handle()) };
+    if previous_count == u32::MAX {
+        return Err(format!("SuspendThread failed: {}", get_last_platform_error_message()));
+    }
+    resume_thread(thread)?;
+    Ok(previous_count)
+}
+
+/// Increments the thread's suspend count. It doesn't run while the count is non-zero.
+pub fn suspend_thread(thread: &AutoClosedHandle) -> Result<(), String> {
+    let ret = unsafe { SuspendThread(thread.handle()) };
+    if ret == u32::MAX {
+        return Err(format!("SuspendThread failed: {}", get_last_platform_error_message()));
+    }
+    Ok(())
+}
+
+/// Decrements the thread's suspend count, e.g. to start a thread that was created suspended.
+pub fn resume_thread(thread: &AutoClosedHandle) -> Result<(), String> {
+    let ret = unsafe { ResumeThread(thread.handle()) };
+    if ret == u32::MAX {
+        return Err(format!("ResumeThread failed: {}", get_last_platform_error_message()));
+    }
+    Ok(())
+}
+
+/// Attaches to a running process, and returns a handle to it.
+pub fn attach_to_process(process_id: ProcessId) -> Result<AutoClosedHandle, String> {
+    let handle = unsafe {
+        OpenProcess(
+            PROCESS_ALL_ACCESS /*dwDesiredAccess*/,
+            FALSE /*bInheritHandle*/,
+            process_id.0
+        )
+    };
+    let handle = handle
+        .map(AutoClosedHandle)
+        .map_err(|error| format!("Failed to open process {process_id}: OpenProcess failed: {error}"))?;
+
+    let ret = unsafe { DebugActiveProcess(process_id.0) };
+    ret.map_err(|error| format!("Failed to attach to process {process_id}: DebugActiveProcess failed: {error}"))?;
+
+    Ok(handle)
+}
+
+/// Matches the undocumented `SYSTEM_HANDLE_TABLE_ENTRY_INFO_EX` returned by `NtQuerySystemInformation`.
+#[repr(C)]
+struct SystemHandleTableEntry {
+    object: *mut c_void,
+    unique_process_id: usize,
+    handle_value: usize,
+    granted_access: u32,
+    creator_back_trace_index: u16,
+    object_type_index: u16,
+    handle_attributes: u32,
+    reserved: u32,
+}
+
+/// Matches the undocumented `SYSTEM_HANDLE_INFORMATION_EX`, which is followed by `number_of_handles` entries.
+#[repr(C)]
+struct SystemHandleInformation {
+    number_of_handles: usize,
+    reserved: usize,
+    handles: [SystemHandleTableEntry; 1],
+}
+
+/// The undocumented information class for `SYSTEM_HANDLE_INFORMATION_EX`.
+const SYSTEM_EXTENDED_HANDLE_INFORMATION: SYSTEM_INFORMATION_CLASS = SYSTEM_INFORMATION_CLASS(64);
+/// `ObjectNameInformation`, which the `windows` crate doesn't define.
+const OBJECT_NAME_INFORMATION_CLASS: OBJECT_INFORMATION_CLASS = OBJECT_INFORMATION_CLASS(1);
+
+pub const HANDLE_ATTRIBUTE_PROTECT_FROM_CLOSE: u32 = 0x1;
+pub const HANDLE_ATTRIBUTE_INHERIT: u32 = 0x2;
+
+pub struct HandleEntry {
+    pub value: u64,
+    pub granted_access: u32,
+    /// `HANDLE_ATTRIBUTE_*` flags.
+    pub attributes: u32,
+    /// The type of the object that the handle refers to, e.g. `File` or `Event`. `None` if it couldn't be queried.
+    pub type_name: Option<String>,
+    /// The name of the object, e.g. a file's path. `None` if it is unnamed or couldn't be queried.
+    pub object_name: Option<String>,
+}
+
+/// Lists the handles that the process has open, in order.
+pub fn enumerate_handles(process: HANDLE) -> Result<Vec<HandleEntry>, String> {
+    // The system's handle table doesn't have a fixed size, so grow the buffer until it fits. `u64`s keep it aligned.
+    let mut buffer = vec![0u64; 0x10000];
+    loop {
+        let mut return_length: u32 = 0;
+        let status = unsafe {
+            NtQuerySystemInformation(
+                SYSTEM_EXTENDED_HANDLE_INFORMATION,
+                buffer.as_mut_ptr().cast(),
+                (buffer.len() * std::mem::size_of::<u64>()) as u32,
+                &mut return_length,
+            )
+        };
+        if status == STATUS_INFO_LENGTH_MISMATCH {
+            // Handles may be opened before the next try, so leave some room.
+            let needed = (return_length as usize).div_ceil(std::mem::size_of::<u64>());
+            buffer.resize(needed.max(buffer.len()) * 2, 0);
+            continue;
+        }
+        status.ok().map_err(|error| format!("Could not list the handles: NtQuerySystemInformation failed: {error}"))?;
+        break;
+    }
+
+    let process_id = unsafe { GetProcessId(process) } as usize;
+    let info = buffer.as_ptr() as *const SystemHandleInformation;
+    let entries = unsafe {
+        std::slice::from_raw_parts(std::ptr::addr_of!((*info).handles).cast::<SystemHandleTableEntry>(), (*info).number_of_handles)
+    };
+    let mut handles: Vec<HandleEntry> = entries.iter()
+        .filter(|entry| entry.unique_process_id == process_id)
+        .map(|entry| {
+            let (type_name, object_name) = query_handle_object(process, entry.handle_value);
+            HandleEntry {
+                value: entry.handle_value as u64,
+                granted_access: entry.granted_access,
+                attributes: entry.handle_attributes,
+                type_name,
+                object_name,
+            }
+        })
+        .collect();
+    handles.sort_by_key(|handle| handle.value);
+    Ok(handles)
+}
+
+/// Gets the type and name of the object that one of the process's handles refers to, by duplicating the handle.
+fn query_handle_object(process: HANDLE, handle_value: usize) -> (Option<String>, Option<String>) {
+    let mut duplicate = HANDLE::default();
+    let ret = unsafe {
+        DuplicateHandle(
+            process,
+            HANDLE(handle_value as *mut c_void),
+            GetCurrentProcess(),
+            &mut duplicate,
+            0 /*dwDesiredAccess*/,
+            FALSE /*bInheritHandle*/,
+            DUPLICATE_SAME_ACCESS,
+        )
+    };
+    if ret.is_err() {
+        return (None, None);
+    }
+    let duplicate = AutoClosedHandle(duplicate);
+
+    let type_name = query_object_string(duplicate.handle(), ObjectTypeInformation);
+    // Querying the name of a pipe can block forever (e.g. while another thread reads from it), so don't.
+    let is_pipe = type_name.as_deref() == Some("File") && unsafe { GetFileType(duplicate.handle()) } == FILE_TYPE_PIPE;
+    let object_name = if is_pipe {
+        None
+    } else {
+        query_object_string(duplicate.handle(), OBJECT_NAME_INFORMATION_CLASS)
+    };
+    (type_name, object_name.filter(|name| !name.is_empty()))
+}
+
+/// Queries information about an object that starts with a `UNICODE_STRING`, like its type or name.
+fn query_object_string(handle: HANDLE, information_class: OBJECT_INFORMATION_CLASS) -> Option<String> {
+    let mut buffer = vec![0u64; 0x200];
+    let mut return_length: u32 = 0;
+    let query = |buffer: &mut Vec<u64>, return_length: &mut u32| unsafe {
+        NtQueryObject(
+            handle,
+            information_class,
+            Some(buffer.as_mut_ptr().cast()),
+            (buffer.len() * std::mem::size_of::<u64>()) as u32,
+            Some(return_length),
+        )
+    };
+    let mut status = query(&mut buffer, &mut return_length);
+    if status == STATUS_INFO_LENGTH_MISMATCH || status == STATUS_BUFFER_OVERFLOW {
+        buffer.resize((return_length as usize).div_ceil(std::mem::size_of::<u64>()), 0);
+        status = query(&mut buffer, &mut return_length);
+    }
+    status.ok().ok()?;
+
+    let string = unsafe { &*(buffer.as_ptr() as *const UNICODE_STRING) };
+    if string.Buffer.is_null() {
+        return Some(String::new());
+    }
+    let chars = unsafe { std::slice::from_raw_parts(string.Buffer.0, string.Length as usize / 2) };
+    Some(String::from_utf16_lossy(chars))
+}
+
+pub struct ProcessEntry {
+    pub id: ProcessId,
+    pub parent_id: ProcessId,
+    /// The executable's file name, e.g. `notepad.exe`.
+    pub name: String,
+}
+
+/// Lists the processes running on the system.
+pub fn enumerate_processes() -> Result<Vec<ProcessEntry>, String> {
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) };
+    let snapshot = snapshot
+        .map(AutoClosedHandle)
+        .map_err(|error| format!("Could not list the processes: CreateToolhelp32Snapshot failed: {error}"))?;
+
+    let mut processes = Vec::<ProcessEntry>::new();
+    let mut entry: PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+    entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+    let mut ret = unsafe { Process32FirstW(snapshot.handle(), &mut entry) };
+    while ret.is_ok() {
+        let name_len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+        processes.push(ProcessEntry {
+            id: ProcessId(entry.th32ProcessID),
+            parent_id: ProcessId(entry.th32ParentProcessID),
+            name: String::from_utf16_lossy(&entry.szExeFile[..name_len]),
+        });
+        ret = unsafe { Process32NextW(snapshot.handle(), &mut entry) };
+    }
+
+    Ok(processes)
+}
+
+/// The process to break into when the user presses Ctrl+C. These are globals because they're used by the console control handler.
+static BREAK_IN_PROCESS: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static TARGET_RUNNING: AtomicBool = AtomicBool::new(false);
+static BREAK_IN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "system" fn console_ctrl_handler(ctrl_type: u32) -> BOOL {
+    if ctrl_type != CTRL_C_EVENT && ctrl_type != CTRL_BREAK_EVENT {
+        return FALSE;
+    }
+
+    // Ignore Ctrl+C at the prompt, rather than terminating the debugger (and the target).
+    request_break_in();
+    TRUE
+}
+
+/// Breaks into the process passed to `install_break_in_handler` if it is running, like Ctrl+C does.
+/// Returns whether it was running.
+pub fn request_break_in() -> bool {
+    if !TARGET_RUNNING.load(Ordering::SeqCst) {
+        return false;
+    }
+    let process = HANDLE(BREAK_IN_PROCESS.load(Ordering::SeqCst));
+    if unsafe { DebugBreakProcess(process) }.is_ok() {
+        BREAK_IN_REQUESTED.store(true, Ordering::SeqCst);
+    }
+    true
+}
+
+/// Makes Ctrl+C break into `process` while it is running, by raising a breakpoint exception in it.
+/// See `set_target_running` and `take_break_in_request`.
+pub fn install_break_in_handler(process: &AutoClosedHandle) -> Result<(), String> {
+    BREAK_IN_PROCESS.store(process.handle().0, Ordering::SeqCst);
+    let ret = unsafe { SetConsoleCtrlHandler(Some(console_ctrl_handler), TRUE) };
+    ret.map_err(|error| format!("Could not handle Ctrl+C: SetConsoleCtrlHandler failed: {error}"))
+}
+
+/// Tells the Ctrl+C handler whether the target is running (as opposed to stopped at the prompt).
+pub fn set_target_running(running: bool) {
+    TARGET_RUNNING.store(running, Ordering::SeqCst);
+}
+
+/// Returns whether the user requested a break-in since the last call.
+pub fn take_break_in_request() -> bool {
+    BREAK_IN_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+// Required because `windows::Win32::System::Diagnostics::Debug::CONTEXT` has a bug where is needs to be aligned but is not.
+// The issues is tracked by https://github.com/microsoft/win32metadata/issues/1044
+// Once that is fixed this can be deleted and we can use `CONTEXT` direclty.
+#[repr(align(16))]
+pub struct AlignedContext {
+    pub context: CONTEXT,
+}
+
+pub fn get_thread_id(thread_handle: HANDLE) -> Result<ThreadId, String> {
+    let id = unsafe { GetThreadId(thread_handle) };
+    if id == 0 {
+        return Err(format!("GetThreadId failed: {}", get_last_platform_error_message()));
+    }
+    Ok(ThreadId(id))
+}
+
+/// Gets the thread's registers. For a 32-bit thread in a WOW64 process (`is_wow64`), this is the 32-bit context rather
+/// than the context of the 64-bit emulation layer, widened into a `CONTEXT` so that it can be used like any other.
+pub fn get_thread_context(thread: &AutoClosedHandle, is_wow64: bool) -> Result<AlignedContext, String> {
+    let mut ctx: AlignedContext = unsafe { std::mem::zeroed() };
+    if is_wow64 {
+        let wow64_context = get_wow64_thread_context(thread)?;
+        widen_wow64_context(&wow64_context, &mut ctx.context);
+        return Ok(ctx);
+    }
+
+    ctx.context.ContextFlags = CONTEXT_ALL_AMD64;
+
+    let ret = unsafe { GetThreadContext(thread.handle(), &mut ctx.context) };
+    ret.map_err(|error| format!("Could not get the thread's registers: GetThreadContext failed: {error}"))?;
+    tracing::trace!("Got the thread's registers with context flags {:#x}", ctx.context.ContextFlags.0);
+
+    Ok(ctx)
+}
+
+/// Sets the thread's registers. See `get_thread_context` for how WOW64 threads are handled.
+pub fn set_thread_context(thread: &AutoClosedHandle, context: &CONTEXT, is_wow64: bool) -> Result<(), String> {
+    if is_wow64 {
+        // Start from the current context so that the registers that `CONTEXT` doesn't carry over (e.g. the x87 state) are kept.
+        let mut wow64_context = get_wow64_thread_context(thread)?;
+        narrow_to_wow64_context(context, &mut wow64_context);
+        let ret = unsafe { Wow64SetThreadContext(thread.handle(), &wow64_context) };
+        return ret.map_err(|error| format!("Could not set the thread's registers: Wow64SetThreadContext failed: {error}"));
+    }
+
+    tracing::trace!("Setting the thread's registers with context flags {:#x}", context.ContextFlags.0);
+    let ret = unsafe { SetThreadContext(thread.handle(), context) };
+    ret.map_err(|error| format!("Could not set the thread's registers: SetThreadContext failed: {error}"))
+}
+
+fn get_wow64_thread_context(thread: &AutoClosedHandle) -> Result<WOW64_CONTEXT, String> {
+    let mut context = WOW64_CONTEXT { ContextFlags: WOW64_CONTEXT_ALL, ..Default::default() };
+    let ret = unsafe { Wow64GetThreadContext(thread.handle(), &mut context) };
+    ret.map_err(|error| format!("Could not get the thread's registers: Wow64GetThreadContext failed: {error}"))?;
+    tracing::trace!("Got the WOW64 thread's registers with context flags {:#x}", context.ContextFlags.0);
+    Ok(context)
+}
+
+/// Copies the integer, control, and debug registers of a 32-bit context into the corresponding 64-bit registers.
+fn widen_wow64_context(wow64_context: &WOW64_CONTEXT, context: &mut CONTEXT) {
+    context.Rax = wow64_context.Eax as u64;
+    context.Rbx = wow64_context.Ebx as u64;
+    context.Rcx = wow64_context.Ecx as u64;
+    context.Rdx = wow64_context.Edx as u64;
+    context.Rsi = wow64_context.Esi as u64;
+    context.Rdi = wow64_context.Edi as u64;
+    context.Rbp = wow64_context.Ebp as u64;
+    context.Rsp = wow64_context.Esp as u64;
+    context.Rip = wow64_context.Eip as u64;
+    context.EFlags = wow64_context.EFlags;
+    context.Dr0 = wow64_context.Dr0 as u64;
+    context.Dr1 = wow64_context.Dr1 as u64;
+    context.Dr2 = wow64_context.Dr2 as u64;
+    context.Dr3 = wow64_context.Dr3 as u64;
+    context.Dr6 = wow64_context.Dr6 as u64;
+    context.Dr7 = wow64_context.Dr7 as u64;
+}
+
+/// The inverse of `widen_wow64_context`. The upper halves of the 64-bit registers are discarded.
+fn narrow_to_wow64_context(context: &CONTEXT, wow64_context: &mut WOW64_CONTEXT) {
+    wow64_context.Eax = context.Rax as u32;
+    wow64_context.Ebx = context.Rbx as u32;
+    wow64_context.Ecx = context.Rcx as u32;
+    wow64_context.Edx = context.Rdx as u32;
+    wow64_context.Esi = context.Rsi as u32;
+    wow64_context.Edi = context.Rdi as u32;
+    wow64_context.Ebp = context.Rbp as u32;
+    wow64_context.Esp = context.Rsp as u32;
+    wow64_context.Eip = context.Rip as u32;
+    wow64_context.EFlags = context.EFlags;
+    wow64_context.Dr0 = context.Dr0 as u32;
+    wow64_context.Dr1 = context.Dr1 as u32;
+    wow64_context.Dr2 = context.Dr2 as u32;
+    wow64_context.Dr3 = context.Dr3 as u32;
+    wow64_context.Dr6 = context.Dr6 as u32;
+    wow64_context.Dr7 = context.Dr7 as u32;
+}
+
+/// Returns whether the process is a 32-bit process running under WOW64 on a 64-bit system.
+pub fn is_wow64_process(process: HANDLE) -> bool {
+    let mut is_wow64 = BOOL(0);
+    match unsafe { IsWow64Process(process, &mut is_wow64) } {
+        Ok(()) => is_wow64.as_bool(),
+        Err(error) => {
+            tracing::warn!("IsWow64Process failed, so assuming the process is not WOW64: {error}");
+            false
+        }
+    }
+}
+
+/// The details of an exception, from `EXCEPTION_RECORD`.
+#[derive(Clone)]
+pub struct ExceptionRecord {
+    pub code: NTSTATUS,
+    pub flags: u32,
+    pub address: u64,
+    /// Their meaning depends on the code. For example, for an access violation, they are whether it was a read (0),
+    /// write (1), or execute (8), and the address that was accessed.
+    pub parameters: Vec<u64>,
+    /// An associated exception record, which is chained to this one when exceptions are nested.
+    pub nested: Option<Box<ExceptionRecord>>,
+}
+
+/// The most nested exception records to read, in case the chain is corrupt.
+const MAX_NESTED_EXCEPTION_RECORDS: usize = 8;
+
+/// Reads an exception record that another one points to. `EXCEPTION_RECORD` is 19 64-bit values.
+// TODO: WOW64 processes' nested records are 32-bit `EXCEPTION_RECORD32`s.
+fn read_exception_record(memory_source: &dyn MemorySource, address: u64, depth: usize) -> Option<ExceptionRecord> {
+    let values = memory::read_memory_full_array::<u64>(memory_source, address, 19).ok()?;
+    let parameter_count = (values[3] as u32 as usize).min(values.len() - 4);
+    let nested_address = values[1];
+    Some(ExceptionRecord {
+        code: NTSTATUS(values[0] as u32 as i32),
+        flags: (values[0] >> 32) as u32,
+        address: values[2],
+        parameters: values[4..4 + parameter_count].to_vec(),
+        nested: if nested_address != 0 && depth < MAX_NESTED_EXCEPTION_RECORDS {
+            read_exception_record(memory_source, nested_address, depth + 1).map(Box::new)
+        } else {
+            None
+        },
+    })
+}
+
+#[derive(Clone)]
+pub enum DebugEvent {
+    Exception{first_chance: bool, record: ExceptionRecord},
+    /// `process_handle` is valid until the process exits.
+    CreateProcess{name: Option<String>, base_addr: u64, process_handle: HANDLE, thread_start_address: u64},
+    ExitProcess{exit_code: u32},
+    CreateThread{start_address: u64},
+    ExitThread{exit_code: u32},
+    LoadDll{name: Option<String>, base_addr: u64},
+    UnloadDll{base_addr: u64},
+    OutputDebugString(String),
+    /// System debugging error
+    Rip{error: u32, info_type: RIP_INFO_TYPE},
+}
+
+pub struct DebugEventContext {
+    pub process: ProcessId,
+    pub thread: ThreadId,
+}
+
+/// `get_memory_source` returns the memory source for a process, which is used to read event data such as strings.
+/// It may return `None` for processes that have not been created yet.
+pub fn wait_for_debug_event<'a>(get_memory_source: impl Fn(ProcessId) -> Option<&'a dyn MemorySource>) -> Result<(DebugEventContext, DebugEvent), String> {
+    let mut event: DEBUG_EVENT = unsafe { std::mem::zeroed() };
+    let ret = unsafe {
+        WaitForDebugEventEx(&mut event, INFINITE)
+    };
+    ret.map_err(|error| format!("Could not wait for a debug event: WaitForDebugEventEx failed: {error}"))?;
+
+    let context = DebugEventContext{
+        process: ProcessId(event.dwProcessId),
+        thread: ThreadId(event.dwThreadId),
+    };
+
+    let event = match event.dwDebugEventCode {
+        EXCEPTION_DEBUG_EVENT => {
+            let data = unsafe { event.u.Exception };
+            let first_chance = data.dwFirstChance != 0;
+            let exception_record = data.ExceptionRecord;
+            let parameter_count = (exception_record.NumberParameters as usize).min(exception_record.ExceptionInformation.len());
+            let nested_address = exception_record.ExceptionRecord as u64;
+            let nested = match get_memory_source(context.process) {
+                Some(memory_source) if nested_address != 0 => read_exception_record(memory_source, nested_address, 1).map(Box::new),
+                _ => None,
+            };
+            let record = ExceptionRecord {
+                code: exception_record.ExceptionCode,
+                flags: exception_record.ExceptionFlags,
+                address: exception_record.ExceptionAddress as u64,
+                parameters: exception_record.ExceptionInformation[..parameter_count].iter().map(|&parameter| parameter as u64).collect(),
+                nested,
+            };
+            (context, DebugEvent::Exception { first_chance, record })
+        }
+        CREATE_THREAD_DEBUG_EVENT => {
+            let data = unsafe { event.u.CreateThread };
+            // The event already has the thread's ID, so the handle isn't needed.
+            let _ = close_handle(data.hThread);
+            let start_address = data.lpStartAddress.map_or(0, |start_address| start_address as usize as u64);
+            (context, DebugEvent::CreateThread { start_address })
+        }
+        EXIT_THREAD_DEBUG_EVENT => {
+            let data = unsafe { event.u.ExitThread };
+            let exit_code = data.dwExitCode;
+            (context, DebugEvent::ExitThread { exit_code })
+        }
+        CREATE_PROCESS_DEBUG_EVENT => {
+            let data = unsafe { event.u.CreateProcessInfo };
+
+            // The handle path is the fill path, e.g. `\\?\C:\git\HelloWorld\hello.exe`.
+            // It might be useful to have hte full path, but it's not available for all modules in all cases.
+            // So instead use the file name.
+            let name = get_final_path_name_by_handle(data.hFile).ok().and_then(|path| {
+                Path::new(&path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            });
+
+            let base_addr = data.lpBaseOfImage as u64;
+            let process_handle = data.hProcess;
+            let thread_start_address = data.lpStartAddress.map_or(0, |start_address| start_address as usize as u64);
+
+            (context, DebugEvent::CreateProcess { name, base_addr, process_handle, thread_start_address } )
+        }
+        EXIT_PROCESS_DEBUG_EVENT => {
+            let data = unsafe { event.u.ExitProcess };
+            let exit_code = data.dwExitCode;
+            (context, DebugEvent::ExitProcess { exit_code })
+        }
+        LOAD_DLL_DEBUG_EVENT => {
+            let data = unsafe { event.u.LoadDll };
+            let base_addr = data.lpBaseOfDll as u64;
+
+            let name = match get_memory_source(context.process) {
+                Some(mem_source) if !data.lpImageName.is_null() => {
+                    let is_wide = data.fUnicode != 0;
+                    Some(memory::read_memory_string_indirect(mem_source, data.lpImageName as u64, 260, is_wide))
+                }
+                _ => None,
+            };
+            (context, DebugEvent::LoadDll { name, base_addr } )
+        }
+        UNLOAD_DLL_DEBUG_EVENT => {
+            let data = unsafe { event.u.UnloadDll };
+            let base_addr = data.lpBaseOfDll as u64;
+            (context, DebugEvent::UnloadDll { base_addr })
+        }
+        OUTPUT_DEBUG_STRING_EVENT => {
+            let data = unsafe { event.u.DebugString };
+            let is_wide = data.fUnicode != 0;
+            let address = data.lpDebugStringData.as_ptr() as u64;
+            let len = data.nDebugStringLength as usize;
+            let debug_string = match get_memory_source(context.process) {
+                Some(mem_source) => memory::read_memory_string(mem_source, address, len, is_wide),
+                None => String::from("<unknown process>"),
+            };
+            (context, DebugEvent::OutputDebugString(debug_string) )
+        }
+        RIP_EVENT => {
+            let data = unsafe { event.u.RipInfo };
+            let error = data.dwError;
+            let info_type = data.dwType;
+            (context, DebugEvent::Rip { error, info_type } )
+        }
+        code => return Err(format!("Unexpected debug event {code:?}")),
+    };
+    Ok(event)
+}
+
+pub enum DebugContinueStatus {
+    Continue,
+    ExceptionNotHandled,
+    //ReplyLater,
+}
+
+impl DebugContinueStatus {
+    fn get_win32_value(&self) -> NTSTATUS {
+        match *self {
+            DebugContinueStatus::Continue => DBG_CONTINUE,
+            DebugContinueStatus::ExceptionNotHandled => DBG_EXCEPTION_NOT_HANDLED,
+            //DebugContinueStatus::ReplyLater => DBG_REPLY_LATER,
+        }
+    }
+}
+
+pub fn continue_debug_event(context: DebugEventContext, continue_status: DebugContinueStatus) -> Result<(), String> {
+    let ret = unsafe {
+        ContinueDebugEvent(
+            context.process.0,
+            context.thread.0,
+            continue_status.get_win32_value(),
+        )
+    };
+    ret.map_err(|error| format!("Could not continue the target: ContinueDebugEvent failed: {error}"))
+}
+
+pub fn get_final_path_name_by_handle(handle: HANDLE) -> Result<String, String> {
+    let mut buffer = vec![0u16; 4096];
+    let len = unsafe { GetFinalPathNameByHandleW(handle, buffer.as_mut_slice(), GETFINALPATHNAMEBYHANDLE_FLAGS(0)) } as usize;
+    if len == 0 {
+        return Err(format!("GetFinalPathNameByHandleW failed: {}", get_last_platform_error_message()));
+    }
+    Ok(OsString::from_wide(&buffer[0..len]).to_string_lossy().to_string())
+}
+
+/// Formats the current local time, e.g. `2024-01-31 13:45:07.123`.
+pub fn format_local_time() -> String {
+    let time = unsafe { GetLocalTime() };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}", time.wYear, time.wMonth, time.wDay, time.wHour, time.wMinute, time.wSecond, time.wMilliseconds)
+}
+
+/// Lets the console interpret ANSI escape sequences, e.g. for colors. Returns false if the output isn't a console, e.g. if
+/// it is redirected to a file, or if the console is too old to support them.
+pub fn enable_virtual_terminal_processing() -> bool {
+    let Ok(output) = (unsafe { GetStdHandle(STD_OUTPUT_HANDLE) }) else {
+        return false;
+    };
+    let mut mode = CONSOLE_MODE::default();
+    if unsafe { GetConsoleMode(output, &mut mode) }.is_err() {
+        return false;
+    }
+    unsafe { SetConsoleMode(output, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) }.is_ok()
+}
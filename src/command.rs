@@ -1,71 +1,114 @@
-use std::io::Write;
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex},
+    thread,
+};
 
 use codemap::CodeMap;
 use codemap_diagnostic::{ColorConfig, Diagnostic, Emitter, Level, SpanLabel, SpanStyle};
 use rust_sitter::errors::{ParseError, ParseErrorReason};
+use debugger_core::{output, process::Process, session_log, windows_wrapper};
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
 
-#[rust_sitter::grammar("command")]
-pub mod grammar {
-    #[rust_sitter::language]
-    pub enum CommandExpr {
-        Help(#[rust_sitter::leaf(text = "help")] ()),
-        HelpAlias(#[rust_sitter::leaf(text = "h")] ()),
-        Step(#[rust_sitter::leaf(text = "step")] ()),
-        StepAlias(#[rust_sitter::leaf(text = "s")] ()),
-        Continue(#[rust_sitter::leaf(text = "continue")] ()),
-        ContinueAlias(#[rust_sitter::leaf(text = "c")] ()),
-        AddBreakpoint(#[rust_sitter::leaf(text = "breakpoint-add")] (), Box<EvalExpr>),
-        AddBreakpointAlias(#[rust_sitter::leaf(text = "ba")] (), Box<EvalExpr>),
-        RemoveBreakpoint(#[rust_sitter::leaf(text = "breakpoint-remove")] (), Box<EvalExpr>),
-        RemoveBreakpointAlias(#[rust_sitter::leaf(text = "br")] (), Box<EvalExpr>),
-        ListBreakpoint(#[rust_sitter::leaf(text = "breakpoint-list")] ()),
-        ListBreakpointAlias(#[rust_sitter::leaf(text = "bl")] ()),
-        DisplayRegisters(#[rust_sitter::leaf(text = "registers")] ()),
-        DisplayRegistersAlias(#[rust_sitter::leaf(text = "r")] ()),
-        DisplayBytes(#[rust_sitter::leaf(text = "display-bytes")] (), Box<EvalExpr>),
-        DisplayBytesAlias(#[rust_sitter::leaf(text = "db")] (), Box<EvalExpr>),
-        Evaluate(#[rust_sitter::leaf(text = "eval")] (), Box<EvalExpr>),
-        EvaluateAlias(#[rust_sitter::leaf(text = "?")] (), Box<EvalExpr>),
-        ListNearest(#[rust_sitter::leaf(text = "list-nearest")] (), Box<EvalExpr>),
-        ListNearestAlias(#[rust_sitter::leaf(text = "ln")] (), Box<EvalExpr>),
-        Quit(#[rust_sitter::leaf(text = "quit")] ()),
-        QuitAlias(#[rust_sitter::leaf(text = "q")] ()),
-    }
-
-    #[rust_sitter::language]
-    pub enum EvalExpr {
-        Number(#[rust_sitter::leaf(pattern = r"(\d+|0x[0-9a-fA-F]+)", transform = parse_int)] u64),
-
-        Symbol(#[rust_sitter::leaf(pattern = r"(([a-zA-Z0-9_@#.]+!)?[a-zA-Z0-9_@#.]+)", transform = parse_symbol)] String),
-
-        #[rust_sitter::prec_left(1)]
-        Add(
-            Box<EvalExpr>,
-            #[rust_sitter::leaf(text = "+")] (),
-            Box<EvalExpr>,
-        ),
-    }
-
-    #[rust_sitter::extra]
-    struct Whitespace {
-        #[rust_sitter::leaf(pattern = r"\s")]
-        _whitespace: (),
-    }
-
-    fn parse_int(text: &str) -> u64 {
-        let text = text.trim();
-        if text.starts_with("0x") {
-            let text = text.split_at(2).1;
-            u64::from_str_radix(text, 16).unwrap()
-        } else {
-            text.parse().unwrap()
-        }
-    }
+use crate::{
+    completion::CommandHelper,
+    event_loop::Message,
+    tui::{self, StopView, Tui},
+};
 
-    fn parse_symbol(text: &str) -> String {
-        text.to_owned()
-    }
-}
+pub use debugger_core::command::grammar;
+
+/// The prompt while the target is stopped.
+pub const PROMPT: &str = "\n> ";
+/// The prompt while the target is running, when only some commands can be run.
+pub const RUNNING_PROMPT: &str = "\n(running) > ";
+
+/// The names and aliases of the commands in `grammar::CommandExpr`, for tab completion.
+pub const COMMAND_NAMES: &[&str] = &[
+    "help", "h",
+    "break", ".break",
+    "step", "s",
+    "next", "n",
+    "step-line", "sl",
+    "next-line", "nl",
+    "finish", "gu",
+    "break-on-return", "breakret",
+    "watch-trace", "wt",
+    "trace-calls", "tracecalls",
+    "instruction-trace", "trace",
+    "execution-record", "record",
+    "execution-replay", "replay",
+    "continue", "c",
+    "breakpoint-add", "ba",
+    "breakpoint-remove", "br", "bc",
+    "breakpoint-list", "bl",
+    "hardware-breakpoint-add", "hba",
+    "hardware-breakpoint-remove", "hbr",
+    "memory-watch-add", "mwa",
+    "memory-watch-remove", "mwr",
+    "thread", "~s",
+    "freeze", "~f",
+    "thaw", "~u",
+    "threads", "~",
+    "process", "|",
+    "processes", "|*",
+    "stack", "k",
+    "stack-all", "~*k",
+    "stack-dump", "!stackdump",
+    "display-teb", "!teb",
+    "display-peb", "!peb",
+    "command-line", "!cmdline",
+    "environment", "!env",
+    "vmmap", "!address",
+    "allocate", ".alloc",
+    "set-protection", "!vprot",
+    "find-references", "refs",
+    "handles", "!handle",
+    "heaps", "!heap",
+    "registers", "r",
+    "set-register", "setreg",
+    "prompt-disassembly", "pd",
+    "assemble", "a",
+    "symbol-cache", ".symcache",
+    "reload-symbols", ".reload",
+    "module-details", "lmv",
+    "tls-callbacks", "!tls",
+    "trace-module", "tracemod",
+    "display-bytes", "db",
+    "display-words", "dw",
+    "display-dwords", "dd",
+    "display-qwords", "dq",
+    "display-pointers", "dp",
+    "display-pointers-symbols", "dps",
+    "display-ascii", "da",
+    "display-unicode", "du",
+    "eval", "?",
+    "list-nearest", "ln",
+    "examine-symbols", "x",
+    "locals", "dv",
+    "display-type", "dt",
+    "break-at-entry", ".entry",
+    "exception-record", ".exr",
+    "last-event", ".lastevent",
+    "event-history", "history",
+    "session-stats", ".stats",
+    "log-level", ".loglevel",
+    "exception-break", "sxe",
+    "exception-second-chance", "sxd",
+    "exception-ignore", "sxi",
+    "module-load-break", "sxe ld:",
+    "module-load-ignore", "sxd ld:",
+    "event-policy", "events",
+    "exception-list", "sx",
+    "debug-output", "dbgout",
+    "script", ".script",
+    "rhai-run", ".rhai",
+    "python-run", ".python",
+    "log-open", ".logopen",
+    "log-close", ".logclose",
+    "quit", "q",
+];
 
 // Copied from https://github.com/hydro-project/rust-sitter/blob/main/example/src/main.rs
 fn convert_parse_error_to_diagnostics(
@@ -116,48 +159,449 @@ fn convert_parse_error_to_diagnostics(
 }
 
 pub fn print_command_help() {
-    println!("Commands:
+    println!("Press Tab to complete command names, module names, and `module!symbol` names.
+Several commands can be given on one line, separated by `;`. For example, `breakpoint-add kernel32!CreateFileW; continue`.
+
+Commands:
     help (h): Print command help.
-    step (s): Step to the next instruction.
-    continue (c): Continue the program until the next debug event.
-    registers (r): Print the registers.
-    display-bytes (db): Display data at a memory location. For example, `display-bytes 0x123`.
-    eval (?): Add addresses. For example, `eval 0x123 + 10`.
+    break (.break): Stop the target while it is running, like Ctrl+C. Commands can be typed while the target runs, but
+        only `help`, `break`, `threads`, `log-open`, `log-close`, and `quit` run before it stops.
+    step (s): Step to the next instruction. Optionally takes a number of instructions to step. For example, `step 50`.
+    next (n): Step to the next instruction, stepping over calls. Optionally takes a number of instructions to step. For example, `next 20`.
+    step-line (sl): Step to the next source line.
+    next-line (nl): Step to the next source line, stepping over calls.
+    finish (gu): Run until the current function returns.
+    break-on-return (breakret): Set a breakpoint where the current function returns to, which only stops on this thread and is removed once it does. Unlike `finish`, this doesn't continue.
+    watch-trace (wt): Step through the current function until it returns, and then show every call that it made as a tree, with how many instructions each one executed. This is slow for functions that do a lot of work.
+    trace-calls (tracecalls): Print the calls that the current function makes as it makes them, indented by how deep they are, until it returns. Calls deeper than the given depth aren't stepped through, which is much faster than `watch-trace`. For example, `trace-calls 2`.
+    instruction-trace (trace): `start <file> [registers]` records every instruction that the current thread executes to a CSV file, with its address and `module+offset`, and optionally the registers, so that two runs can be compared. The thread is single-stepped whenever it runs, which is slow. `stop` closes the file. For example, `trace start run1.csv registers`.
+    execution-record (record): Experimental. `start` records the instructions that the current thread executes, with its integer, control, and segment registers and the memory that each instruction reads and writes, until `stop` or 100000 instructions. It can then be replayed with `execution-replay`. The thread is single-stepped whenever it runs, which is slow.
+    execution-replay (replay): Step through the last recording without the live target, showing the registers and disassembly at each step. `back [count]` and `forward [count]` move by instructions, `goto <step>` moves to a step, and `memory <address> [size]` shows memory as it was at the current step. Expressions use the recorded registers. For example, `replay back 10`.
+    continue (c): Continue the program until the next debug event. Press Ctrl+C to break in while it is running.
+    thread (~s): Switch the thread that commands such as `registers`, `step`, and `stack` operate on. For example, `thread 0x1a2c`.
+        The current thread resets to the one that raised the event each time the target stops.
+    freeze (~f): Suspend a thread so that it doesn't run when the program continues. For example, `freeze 0x1a2c`.
+    thaw (~u): Resume a thread that was frozen. For example, `thaw 0x1a2c`.
+    threads (~): List the threads, with their start address and suspend count. The current thread is marked with `.`.
+    process (|): Switch the process that commands operate on, when debugging more than one. Modules, symbols, and
+        breakpoints are looked up in the current process. For example, `process 0x1f40`.
+    processes (|*): List the debugged processes as a tree of parents and children, with their image names and whether
+        they are running. The current process is marked with `.`.
+    stack (k): Print the call stack. Functions with symbols also show their parameters and, where they can be recovered, values.
+    stack-all (~*k): Print the call stack of every thread.
+    stack-dump (!stackdump): Print every value on the current thread's stack, from the stack pointer up, with the symbols
+        of the ones in modules, and mark the ones after a call instruction, which are probably return addresses. Use it
+        to find the callers when `stack` can't walk a corrupt stack.
+    display-teb (!teb): Print the current thread's environment block (TEB), including its stack base and limit.
+    display-peb (!peb): Print the process environment block (PEB), including the image base, command line, and loaded modules.
+    command-line (!cmdline): Print the command line that the process was started with, and its current directory.
+    environment (!env): Print the process's environment variables.
+    vmmap (!address): Print the memory regions of the process, with their state, protection, and usage (image, stack, or heap).
+        Optionally takes an address to only print the region containing it. For example, `vmmap rsp`.
+    allocate (.alloc): Allocate zeroed memory in the process, e.g. for a patch or a string to pass to a function, and
+        print its address, which expressions can use as `$alloc` until the next allocation. Takes a size, which is
+        rounded up to whole pages, and optionally a protection like `set-protection`'s. The default is
+        `execute_readwrite`. For example, `allocate 0x100` and then `display-bytes $alloc`.
+    set-protection (!vprot): Change the protection of the pages that contain a range of memory, and print what it was,
+        e.g. to make code writable to patch it, or make memory inaccessible so that the next access raises an
+        exception. Takes an address, a size, and a protection, which is `PAGE_` names joined by `|`, with or without
+        the prefix, or a number. Changing a page with a memory watchpoint removes its guard. For example,
+        `set-protection rip 1 execute_readwrite` or `!vprot 0x1f2e3d4c0000 0x1000 noaccess`.
+    find-references (refs): Search the process's memory for pointers to an address, to find what refers to an object, and
+        print where each one is and what its region is used for. Add `in <start> <size>` to only search part of memory.
+        For example, `refs 0x1f2e3d4c5b60` or `refs 0x1f2e3d4c5b60 in rsp 0x1000`.
+    handles (!handle): List the process's open handles, with the type and name of the objects that they refer to, and how
+        many there are of each type. Optionally takes a handle value to print its details. For example, `handles 0x1c4`.
+    heaps (!heap): List the process's heaps and their segments, with how much of each is busy, free, and uncommitted.
+        Optionally takes a heap's address to also list its blocks. For example, `heaps 0x1c2a3b40000`.
+    registers (r): Print the registers. Add `xmm` to print the SSE registers, or `fp` to print the x87 floating-point registers.
+    set-register (setreg): Set a register of the current thread. For example, `set-register rip=0x123`.
+        Flags can be set individually. For example, `set-register zf=1`.
+    prompt-disassembly (pd): Set how many instructions to disassemble at each prompt, starting at the instruction pointer.
+        The default is 1. For example, `prompt-disassembly 5`, or `prompt-disassembly 0` to turn it off.
+    assemble (a): Assemble instructions into memory, one per line, starting at an address. Enter an empty line to stop.
+        For example, `assemble $ip` and then `nop` or `jmp 0x7ff6c0de1234`. Supports general-purpose registers, immediates,
+        and branches to absolute addresses, but not memory operands.
+    symbol-cache (.symcache): Print the directory that downloaded symbols are cached in. Optionally takes a new directory,
+        which applies to modules loaded afterwards. For example, `symbol-cache \"C:\\Symbols\"`.
+    reload-symbols (.reload): Find the PDBs again, e.g. after changing the symbol path or copying a PDB into place.
+        Optionally takes a module to only reload its symbols. For example, `reload-symbols ntdll.dll`.
+    module-details (lmv): Print a module's version, timestamp, checksum, entry point, PDB, and sections.
+        For example, `module-details ntdll.dll`.
+    tls-callbacks (!tls): List a module's TLS callbacks, which the loader calls before its entry point. Add `break` to
+        also set a breakpoint on each of them. For example, `tls-callbacks app.exe break`.
+    trace-module (tracemod): Log every call to a module's exported functions, with the first four arguments on x64,
+        without stopping. Remove them with `breakpoint-remove *`. For example, `trace-module kernelbase.dll`.
+    display-bytes (db): Display bytes at a memory location. Optionally takes a count. For example, `display-bytes 0x123 32`.
+        Memory that can't be read is shown as `??`, here and in the other displays below.
+    display-words (dw): Display 16-bit values at a memory location. Optionally takes a count.
+    display-dwords (dd): Display 32-bit values at a memory location. Optionally takes a count.
+    display-qwords (dq): Display 64-bit values at a memory location. Optionally takes a count.
+    display-pointers (dp): Display pointer-sized values at a memory location. Optionally takes a count.
+    display-pointers-symbols (dps): Display pointer-sized values at a memory location, one per line, with the symbol that each one points to. Useful for reading the stack or a vtable. Optionally takes a count. For example, `dps rsp 20`.
+    display-ascii (da): Display a null-terminated ASCII string. Optionally takes a maximum length. To follow a pointer to a string, use `poi`, e.g. `da poi(rsp+8)`.
+    display-unicode (du): Display a null-terminated UTF-16 string. Optionally takes a maximum length.
+    eval (?): Evaluate an expression. For example, `eval 0x123 + 10`.
+        Expressions support `+`, `-`, `*`, `/`, and `%` with the usual precedence, and parentheses, e.g. `eval (rsp + 0x10) * 8`.
+        Arithmetic wraps around on overflow. The bitwise operators `&`, `|`, `^`, `<<`, and `>>`, and the comparisons `==`, `!=`,
+        `<`, and `>`, are also supported, with C's precedence. Comparisons are 1 if true and 0 if false, e.g. for conditional
+        breakpoints like `breakpoint-add 0x123 if rcx == 0`.
+        `poi(<expr>)` reads a pointer-sized value from memory. For example, `display-bytes poi(rsp)`.
+        A module name is its base address, so RVAs can be used like `ntdll+0x1a2b`.
+        Expressions can use registers, e.g. `eval rsp + 0x20`, and the pseudo-registers `$ip`, `$sp`,
+        `$retaddr` (the return address), `$exr` (the address of the last exception), `$peb`, and `$alloc` (the memory
+        that `allocate` last allocated). For example, `display-bytes $sp`.
     list-nearest (ln): List the symbol nearest to the address. For example, `list-nearest 0x123`.
+    examine-symbols (x): List the exports and symbols that match a pattern, with their addresses. `*` matches any characters
+        and `?` matches one character. For example, `examine-symbols kernel32!CreateFile*`.
+    locals (dv): List the parameters and local variables of the current function, with their locations and raw
+        pointer-sized values. Requires the module's PDB.
+    display-type (dt): Print the fields of a type from the module's PDB, with their offsets and types. Optionally takes
+        an address to also print the values of the fields there. For example, `display-type ntdll.dll!_PEB $peb`.
     breakpoint-add (ba): Add a breakpoint. For example, `breakpoint-add ntdll.dll!RtlUserThreadStart`.
-    breakpoint-remove (br): Remove a breakpoint. For example, `breakpoint-remove ntdll.dll!RtlUserThreadStart`.
-    breakpoint-list (bl): List breakpoints.
+        Add `if <expr>` to only stop when the expression is non-zero. For example, `breakpoint-add 0x123 if 0x456`.
+        Breakpoints on symbols in modules that are not loaded yet are set when the module loads.
+        Symbols without a module, e.g. `breakpoint-add main`, are searched for in every loaded module.
+        Add `log \"<message>\"` to print the message and continue instead of stopping. Registers can be formatted
+        into the message, e.g. `breakpoint-add kernel32.dll!CreateFileW log \"rip={{rip:#x}} rcx={{rcx:#x}}\"`.
+    breakpoint-remove (br, bc): Remove a breakpoint by its id from `breakpoint-list` (written with a `#`), its address, or its symbol, or with `*`, remove all breakpoints from every process, including hardware breakpoints and memory watchpoints. For example, `breakpoint-remove #2`, `breakpoint-remove ntdll.dll!RtlUserThreadStart`, or `bc *`.
+    breakpoint-list (bl): List breakpoints, with their ids, what was typed to set them, how many times they were hit, and their conditions and log messages.
+    hardware-breakpoint-add (hba): Add a hardware breakpoint to the current process, which does not modify the target's code. At most 4 can be set. For example, `hardware-breakpoint-add ntdll.dll!RtlUserThreadStart`.
+    hardware-breakpoint-remove (hbr): Remove a hardware breakpoint. For example, `hardware-breakpoint-remove ntdll.dll!RtlUserThreadStart`.
+    memory-watch-add (mwa): Stop when a range of memory is written to, or with `any`, accessed at all. Unlike hardware breakpoints, the range can be any size, but everything else on the same pages slows down. For example, `memory-watch-add 0x12345000 0x200 write`.
+    memory-watch-remove (mwr): Remove a memory watchpoint, by its start address. For example, `memory-watch-remove 0x12345000`.
+    break-at-entry (.entry): Stop once at main or wmain if the EXE has symbols for them, or otherwise at the EXE's entry
+        point, so that the program's own code hasn't run yet. Use it at the initial breakpoint, or pass `--break-at-entry`.
+    exception-record (.exr): Print the last exception's code, flags, and parameters, and any exception records chained to it.
+    last-event (.lastevent): Print the debug event that the debugger stopped for again, with the address and module
+        involved, after its message has scrolled away.
+    event-history (history): Print the most recent debug events, including the ones that didn't stop, with the time
+        since the session started and the process and thread ids. Optionally takes a count. For example, `history 100`.
+        The single steps and temporary breakpoints that the debugger uses, e.g. for `wt`, are only counted.
+    session-stats (.stats): Print how long the target has run and been stopped for, how many debug events, breakpoint
+        hits, and single steps there have been, and how much of the target's memory was read, to see how much the
+        debugger slows the target down.
+    log-level (.loglevel): Print or set how much of the debugger's internal logging is shown, e.g. where symbols were
+        looked for and when breakpoints are written: `off`, `error`, `warn`, `info`, `debug`, or `trace`. The default
+        is `warn`, or `debug` with `--verbose`. For example, `log-level trace`.
+    exception-break (sxe): Stop when the target raises the exception, before its handlers run. This is the default.
+        For example, `exception-break 0xc0000005`.
+    exception-second-chance (sxd): Only stop for the exception if the target doesn't handle it.
+    exception-ignore (sxi): Never stop for the exception. The target's handlers still see it.
+    module-load-break (sxe ld:): Stop when a module whose name matches a pattern is loaded, e.g. to set breakpoints in it
+        before its code runs. `*` matches any characters and `?` matches one character. For example, `sxe ld:plugin*`.
+        Other modules load without stopping.
+    module-load-ignore (sxd ld:): Stop the module-load-break with the same pattern.
+    event-policy (events): Choose whether an event stops at the prompt, is logged and continues, or is ignored. The
+        events are create-process, initial-breakpoint, create-thread, exit-thread, load-module, unload-module, and
+        output-debug-string.
+        For example, `event-policy log create-thread`. By default, load-module is logged and the others stop.
+    exception-list (sx): List the common exceptions and the ones with a policy, and whether they stop, and the
+        module-load-break patterns and event policies.
+    debug-output (dbgout): List the settings for the target's OutputDebugString output. The settings take a regex:
+        `debug-output filter \"<Regex>\"`: Only show output that matches one of the filters.
+        `debug-output suppress \"<Regex>\"`: Hide output that matches, even if it matches a filter.
+        `debug-output highlight \"<Regex>\"`: Make output that matches stand out.
+        `debug-output clear`: Remove all filters, suppressions, and highlights.
+        `debug-output history [<Count>]`: Show the last <Count> strings (default: 20), including hidden ones.
+    script (.script): Run the commands in a file, one per line, as if they were typed. Each command is echoed after `$>`.
+        Empty lines and lines starting with `#` are skipped. The script stops at the first command that
+        can't be parsed or whose expression can't be evaluated.
+        For example, `script C:\\temp\\setup.txt`. Scripts can also be run at startup with `--script <File>`.
+    rhai-run (.rhai): Run a Rhai script (https://rhai.rs) for automation that commands can't do. The script's `target`
+        variable has `reg(name)`, `ip`, `read_u8/u16/u32/u64/pointer(address)`, `read_bytes(address, len)`,
+        `read_string(address)`, `read_wstring(address)`, and `modules()`. `on_breakpoint(location, |target| ...)` adds a
+        breakpoint at a symbol or address that calls the function each time it is hit, and only stops if it returns true.
+        For example, `on_breakpoint(\"kernel32!CreateFileW\", |target| {{ print(target.read_wstring(target.reg(\"rcx\"))); false }})`.
+    python-run (.python): Run a Python script, e.g. an existing triage script. Requires a build with `--features python`.
+        The script can `import debugger` to get `read_memory(address, len)`, `eval(expression)`,
+        `set_breakpoint(expression)`, and `get_stack()`, which returns `(stack_pointer, instruction_pointer, symbol)` tuples.
+        For example, `python-run C:\\temp\\triage.py`.
+    log-open (.logopen): Copy all output, and the commands that produce it, to a log file with a timestamp on each line.
+        Replaces the file's contents. For example, `log-open C:\\temp\\session.log`.
+    log-close (.logclose): Stop copying output to the log file.
     quit (q): Quit.");
 }
 
-pub fn read_command() -> grammar::CommandExpr {
-    let stdin = std::io::stdin();
-    loop {
-        print!("\n> ");
-        std::io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        stdin.read_line(&mut input).unwrap();
-        let input = input.trim().to_string();
-
-        if !input.is_empty() {
-            match grammar::parse(&input) {
-                Ok(expr) => return expr,
-                Err(errors) => {
-                    // Convert the errors to diagnostics and emit them.
-                    // Copied from https://github.com/hydro-project/rust-sitter/blob/main/example/src/main.rs
-
-                    let mut code_map = CodeMap::new();
-                    let file_span = code_map.add_file(String::from("<input>"), input);
-                    let mut diagnostics = vec![];
-                    for error in errors {
-                        convert_parse_error_to_diagnostics(&file_span.span, &error, &mut diagnostics)
+/// Reads commands from the console, with line editing and history. The history is saved between sessions.
+/// Commands from a script are read before the console.
+/// Lines are read on a separate thread, so that commands can be typed while the target runs. They are sent to the
+/// debugger's event loop as `Message::Line`.
+pub struct CommandReader {
+    /// What reads lines, until the input thread starts and takes it.
+    line_source: Option<LineSource>,
+    /// Sends the prompts for lines to read to the input thread.
+    line_requests: Option<Sender<String>>,
+    messages: Sender<Message>,
+    /// Whether the input thread is reading a line that hasn't been received yet.
+    line_requested: bool,
+    /// Shared with the line editor's helper on the input thread.
+    completions: CommandHelper,
+    /// What the full-screen mode's panes show, if it is on. Shared with the input thread.
+    tui_view: Option<Arc<Mutex<StopView>>>,
+    script_lines: VecDeque<String>,
+    /// The rest of the commands from a line with several commands separated by `;`.
+    pending_commands: VecDeque<grammar::CommandExpr>,
+}
+
+/// Reads lines on the input thread.
+enum LineSource {
+    Editor(Box<Editor<CommandHelper, DefaultHistory>>),
+    Tui(Box<Tui>),
+}
+
+impl LineSource {
+    /// Reads a line, or `None` at the end of input (e.g. Ctrl+Z on Windows).
+    fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let editor = match self {
+            LineSource::Editor(editor) => editor,
+            LineSource::Tui(tui) => return tui.read_line(prompt),
+        };
+        loop {
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    add_to_history(editor, line.trim());
+                    return Some(line);
+                }
+                // The line editor reads Ctrl+C itself, so break into the target if it is running, like the console's
+                // handler does. Otherwise, it just discards the line.
+                Err(ReadlineError::Interrupted) => {
+                    windows_wrapper::request_break_in();
+                }
+                Err(ReadlineError::Eof) => return None,
+                Err(e) => {
+                    print_error!("Could not read the command: {e}");
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+fn get_history_path() -> PathBuf {
+    match std::env::var_os("LOCALAPPDATA") {
+        Some(local_app_data) => PathBuf::from(local_app_data).join("debugger").join("history.txt"),
+        None => std::env::temp_dir().join("debugger").join("history.txt"),
+    }
+}
+
+fn add_to_history(editor: &mut Editor<CommandHelper, DefaultHistory>, line: &str) {
+    if !line.is_empty() && editor.add_history_entry(line).unwrap_or(false) {
+        let history_path = get_history_path();
+        if let Some(directory) = history_path.parent() {
+            let _ = std::fs::create_dir_all(directory);
+        }
+        if let Err(e) = editor.save_history(&history_path) {
+            print_error!("Could not save the command history to {}: {e}", history_path.display());
+        }
+    }
+}
+
+impl CommandReader {
+    /// Creates a reader that sends the lines that it reads to `messages`.
+    pub fn new(messages: Sender<Message>) -> CommandReader {
+        let completions = CommandHelper::default();
+        let mut editor = Editor::new().expect("Could not create the line editor");
+        editor.set_helper(Some(completions.clone()));
+        // There's no history the first time.
+        let _ = editor.load_history(&get_history_path());
+        CommandReader {
+            line_source: Some(LineSource::Editor(Box::new(editor))),
+            line_requests: None,
+            messages,
+            line_requested: false,
+            completions,
+            tui_view: None,
+            script_lines: VecDeque::new(),
+            pending_commands: VecDeque::new(),
+        }
+    }
+
+    /// Switches to the full-screen mode. See `tui`. Must be called before any lines are read.
+    pub fn enable_tui(&mut self) -> Result<(), String> {
+        let tui = Tui::new()?;
+        self.tui_view = Some(tui.view());
+        self.line_source = Some(LineSource::Tui(Box::new(tui)));
+        Ok(())
+    }
+
+    pub fn is_tui(&self) -> bool {
+        self.tui_view.is_some()
+    }
+
+    /// Updates what the full-screen mode's panes show about where the target stopped.
+    pub fn set_stop_view(&mut self, view: StopView) {
+        if let Some(tui_view) = &self.tui_view {
+            *tui_view.lock().unwrap() = view;
+        }
+    }
+
+    /// Updates the module and symbol names that tab completes to those in `process`.
+    pub fn update_completions(&mut self, process: &Process) {
+        self.completions.update_modules(process);
+    }
+
+    /// Queues the commands in a script file to run as if they were typed, one per line. Empty lines and lines starting with
+    /// `#` are skipped. A script can run another script, which runs before the rest of the first script.
+    pub fn run_script(&mut self, path: &str) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("Could not read script {path}: {e}"))?;
+        for line in contents.lines().rev() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                self.script_lines.push_front(line.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips the rest of the script and of the current line, e.g. because a command failed, since later commands likely
+    /// depend on it.
+    pub fn stop_script(&mut self) {
+        self.pending_commands.clear();
+        if !self.script_lines.is_empty() {
+            self.script_lines.clear();
+            println!("Stopped the script because of the error");
+        }
+    }
+
+    /// Starts reading a line on the input thread, unless it is already reading one. The line is sent as a `Message::Line`,
+    /// which should be passed to `add_line`.
+    pub fn request_line(&mut self, prompt: &str) {
+        if self.line_requested {
+            return;
+        }
+        if self.line_requests.is_none() {
+            let (line_requests, prompts) = mpsc::channel::<String>();
+            let mut line_source = self.line_source.take().expect("The input thread has not started");
+            let messages = self.messages.clone();
+            thread::spawn(move || {
+                for prompt in prompts {
+                    if messages.send(Message::Line(line_source.read_line(&prompt))).is_err() {
+                        return;
                     }
+                }
+            });
+            self.line_requests = Some(line_requests);
+        }
+        // The thread only stops when this reader is dropped.
+        self.line_requests.as_ref().unwrap().send(prompt.to_string()).unwrap();
+        self.line_requested = true;
+    }
 
-                    let mut emitter = Emitter::stderr(ColorConfig::Always, Some(&code_map));
+    /// Parses a line that was read by the input thread, and queues its commands. `None` is the end of input, which quits.
+    pub fn add_line(&mut self, line: Option<String>) {
+        self.line_requested = false;
+        match line {
+            Some(line) => {
+                let line = line.trim();
+                if !line.is_empty() {
+                    if self.is_tui() {
+                        // Show which command the output in the output pane is from.
+                        println!("\n> {line}");
+                    } else {
+                        // The line editor echoes what was typed, but not through `println!`, so log it here.
+                        session_log::write(&format!("\n> {line}\n"));
+                    }
+                    self.parse_line(line);
+                }
+            }
+            None => self.pending_commands.push_back(grammar::CommandExpr::Quit(())),
+        }
+    }
+
+    /// Returns the next command from the lines that were typed, if any. This doesn't read scripts, which only run while
+    /// the target is stopped.
+    pub fn take_typed_command(&mut self) -> Option<grammar::CommandExpr> {
+        self.pending_commands.pop_front()
+    }
+
+    fn parse_line(&mut self, input: &str) {
+        match grammar::parse(input) {
+            Ok(command_list) => self.pending_commands.extend(command_list.commands),
+            Err(errors) => {
+                // Convert the errors to diagnostics and emit them.
+                // Copied from https://github.com/hydro-project/rust-sitter/blob/main/example/src/main.rs
+
+                let mut code_map = CodeMap::new();
+                let file_span = code_map.add_file(String::from("<input>"), input.to_string());
+                let mut diagnostics = vec![];
+                for error in errors {
+                    convert_parse_error_to_diagnostics(&file_span.span, &error, &mut diagnostics)
+                }
+
+                if self.is_tui() {
+                    // Writing to stderr would draw over the screen, so show the errors in the output pane.
+                    let mut text = Vec::new();
+                    Emitter::vec(&mut text, Some(&code_map)).emit(&diagnostics);
+                    print!("{}", String::from_utf8_lossy(&text));
+                } else {
+                    let color_config = if output::is_color_enabled() { ColorConfig::Always } else { ColorConfig::Never };
+                    let mut emitter = Emitter::stderr(color_config, Some(&code_map));
                     emitter.emit(&diagnostics);
                 }
+                self.stop_script();
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Returns the next command to run while the target is stopped: the rest of the current line, then the script, and
+    /// then a line typed by the user, which is received from `messages`.
+    pub fn read_command(&mut self, messages: &Receiver<Message>) -> grammar::CommandExpr {
+        loop {
+            if let Some(command) = self.pending_commands.pop_front() {
+                return command;
+            }
+
+            match self.script_lines.pop_front() {
+                Some(line) => {
+                    // Echo the command, so that the output shows what it's from.
+                    println!("\n$> {line}");
+                    self.parse_line(&line);
+                }
+                None => {
+                    self.request_line(PROMPT);
+                    match messages.recv() {
+                        Ok(Message::Line(line)) => self.add_line(line),
+                        // The target is stopped, so its debug events wait until it continues.
+                        Ok(Message::DebugEvent(_) | Message::Stopped | Message::Prompt { .. }) => {
+                            unreachable!("Received a debug event while the target is stopped")
+                        }
+                        Err(_) => return grammar::CommandExpr::Quit(()),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads a line of input for something other than a command, e.g. the instructions for `assemble` or an answer to a
+    /// `Message::Prompt`. Returns `None` at the end of input. Scripts can't provide these lines.
+    /// Must only be called while no debug events can be received, i.e. while the target is stopped or being started.
+    pub fn read_line(&mut self, prompt: &str, messages: &Receiver<Message>) -> Option<String> {
+        self.request_line(prompt);
+        match messages.recv() {
+            Ok(Message::Line(line)) => {
+                self.line_requested = false;
+                if let Some(line) = &line {
+                    if self.is_tui() {
+                        println!("{prompt}{line}");
+                    } else {
+                        session_log::write(&format!("{prompt}{line}\n"));
+                    }
+                }
+                line
+            }
+            Ok(Message::DebugEvent(_) | Message::Stopped | Message::Prompt { .. }) => {
+                unreachable!("Received a debug event while reading input")
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+impl Drop for CommandReader {
+    fn drop(&mut self) {
+        if self.is_tui() {
+            tui::restore();
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::{Arc, Mutex};
+
+use debugger_core::process::Process;
+use rustyline::{
+    completion::Completer,
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::Validator,
+    Context,
+    Helper,
+};
+
+use crate::command::COMMAND_NAMES;
+
+/// Characters that end a word in an expression, e.g. the `(` in `poi(rsp)`.
+const WORD_DELIMITERS: &[char] = &[' ', '\t', '(', ')', '+', '-', '*', '/', '%', '&', '|', '^', '<', '>', '=', '"'];
+
+/// The names that a module's symbols can be completed from.
+struct CompletionModule {
+    address: u64,
+    /// The file name, e.g. `ntdll.dll`.
+    name: String,
+    /// Whether `symbols` includes the PDB's functions, rather than just the exports.
+    has_symbol_index: bool,
+    symbols: Vec<String>,
+}
+
+/// Completes command names at the start of the line, and module names and `module!symbol` names after that.
+/// Clones share the modules, so that they can be updated from the debugger's thread while the line editor's thread
+/// completes them.
+#[derive(Clone, Default)]
+pub struct CommandHelper {
+    modules: Arc<Mutex<Vec<CompletionModule>>>,
+}
+
+fn get_file_name(path: &str) -> &str {
+    path.rsplit('\\').next().unwrap_or(path)
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.len() >= prefix.len() && text.is_char_boundary(prefix.len()) && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+impl CommandHelper {
+    /// Updates the modules to match the process. The symbol names are only copied for modules that are new, or whose PDB's
+    /// functions have been read since the last update, because there can be a lot of them.
+    pub fn update_modules(&self, process: &Process) {
+        let mut modules = self.modules.lock().unwrap();
+        let mut old_modules = std::mem::take(&mut *modules);
+        for module in process.iterate_modules() {
+            let name = get_file_name(&module.name);
+            let existing = old_modules.iter().position(|old| {
+                old.address == module.address && old.name == name && old.has_symbol_index == module.has_symbol_index()
+            });
+            let completion_module = match existing {
+                Some(index) => old_modules.swap_remove(index),
+                None => {
+                    let mut symbols = module.get_loaded_symbol_names();
+                    symbols.sort();
+                    symbols.dedup();
+                    CompletionModule {
+                        address: module.address,
+                        name: name.to_string(),
+                        has_symbol_index: module.has_symbol_index(),
+                        symbols,
+                    }
+                }
+            };
+            modules.push(completion_module);
+        }
+    }
+
+    fn complete_word(&self, word: &str, is_command: bool) -> Vec<String> {
+        if is_command {
+            return COMMAND_NAMES.iter()
+                .filter(|name| name.starts_with(word))
+                .map(|name| name.to_string())
+                .collect();
+        }
+
+        let modules = self.modules.lock().unwrap();
+        match word.split_once('!') {
+            Some((module_name, symbol_prefix)) => {
+                // Like the rest of the name resolution, the module can be given with or without its extension.
+                let module = modules.iter().find(|module| {
+                    let stem = module.name.rsplit_once('.').map_or(module.name.as_str(), |(stem, _)| stem);
+                    module.name.eq_ignore_ascii_case(module_name) || stem.eq_ignore_ascii_case(module_name)
+                });
+                match module {
+                    Some(module) => module.symbols.iter()
+                        .filter(|symbol| starts_with_ignore_case(symbol, symbol_prefix))
+                        .map(|symbol| format!("{module_name}!{symbol}"))
+                        .collect(),
+                    None => Vec::new(),
+                }
+            }
+            None => {
+                let mut names: Vec<String> = modules.iter()
+                    .filter(|module| starts_with_ignore_case(&module.name, word))
+                    .map(|module| module.name.clone())
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            }
+        }
+    }
+}
+
+impl Completer for CommandHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let before_cursor = &line[..pos];
+        let command_start = before_cursor.len() - before_cursor.trim_start().len();
+        // The command name can contain characters that are operators in expressions, e.g. `-` in `breakpoint-add`.
+        let is_command = !before_cursor[command_start..].contains(char::is_whitespace);
+        let start = if is_command {
+            command_start
+        } else {
+            before_cursor.rfind(WORD_DELIMITERS).map_or(0, |index| index + 1)
+        };
+        Ok((start, self.complete_word(&before_cursor[start..], is_command)))
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+use debugger_core::output;
+use regex::Regex;
+
+/// Keep this many of the most recent strings, so that a noisy target doesn't use unbounded memory.
+const MAX_HISTORY: usize = 1000;
+
+/// How many strings `debug-output history` shows if no count is given.
+pub const DEFAULT_HISTORY_COUNT: usize = 20;
+
+/// Captures the target's `OutputDebugString` strings, and decides which are shown and how.
+pub struct DebugOutput {
+    /// Every captured string, including the ones that weren't shown. Oldest first.
+    history: VecDeque<String>,
+    /// If there are any, only strings that match one of them are shown.
+    filters: Vec<Regex>,
+    /// Strings that match one of these are not shown, even if they match a filter.
+    suppressions: Vec<Regex>,
+    highlights: Vec<Regex>,
+}
+
+fn parse_regex(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|error| format!("Invalid regex {pattern}: {error}"))
+}
+
+impl DebugOutput {
+    pub fn new() -> DebugOutput {
+        DebugOutput { history: VecDeque::new(), filters: Vec::new(), suppressions: Vec::new(), highlights: Vec::new() }
+    }
+
+    pub fn add_filter(&mut self, pattern: &str) -> Result<(), String> {
+        self.filters.push(parse_regex(pattern)?);
+        Ok(())
+    }
+
+    pub fn add_suppression(&mut self, pattern: &str) -> Result<(), String> {
+        self.suppressions.push(parse_regex(pattern)?);
+        Ok(())
+    }
+
+    pub fn add_highlight(&mut self, pattern: &str) -> Result<(), String> {
+        self.highlights.push(parse_regex(pattern)?);
+        Ok(())
+    }
+
+    /// Removes all filters, suppressions, and highlights. The history is kept.
+    pub fn clear(&mut self) {
+        self.filters.clear();
+        self.suppressions.clear();
+        self.highlights.clear();
+    }
+
+    /// Records the string, and returns whether it should be shown.
+    pub fn capture(&mut self, text: &str) -> bool {
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(text.to_string());
+
+        (self.filters.is_empty() || self.filters.iter().any(|filter| filter.is_match(text)))
+            && !self.suppressions.iter().any(|suppression| suppression.is_match(text))
+    }
+
+    /// Formats the string for display, highlighting it if it matches a highlight.
+    pub fn format(&self, text: &str) -> String {
+        let text = text.trim_end_matches(['\r', '\n']);
+        if self.highlights.iter().any(|highlight| highlight.is_match(text)) {
+            output::paint(output::Style::Highlight, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Prints up to `count` of the most recent strings, including the ones that weren't shown when they were captured.
+    pub fn display_history(&self, count: usize) {
+        if self.history.is_empty() {
+            println!("No debug output has been captured");
+            return;
+        }
+        for text in self.history.iter().skip(self.history.len().saturating_sub(count)) {
+            println!("DebugOut: {}", self.format(text));
+        }
+    }
+
+    pub fn list(&self) {
+        if self.filters.is_empty() && self.suppressions.is_empty() && self.highlights.is_empty() {
+            println!("All debug output is shown");
+        }
+        for filter in self.filters.iter() {
+            println!("filter    {filter}");
+        }
+        for suppression in self.suppressions.iter() {
+            println!("suppress  {suppression}");
+        }
+        for highlight in self.highlights.iter() {
+            println!("highlight {highlight}");
+        }
+        println!("{} strings in the history", self.history.len());
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use debugger_core::{
+    exceptions,
+    name_resolution,
+    process::Process,
+    windows_wrapper::{DebugEvent, ExceptionRecord, ProcessId, ThreadId},
+};
+
+/// Keep this many of the most recent events, so that a long session doesn't use unbounded memory.
+const MAX_HISTORY: usize = 10000;
+
+/// How many events `event-history` shows if no count is given.
+pub const DEFAULT_HISTORY_COUNT: usize = 50;
+
+struct HistoryEntry {
+    /// How long after the session started the event happened.
+    time: Duration,
+    process_id: ProcessId,
+    thread_id: ThreadId,
+    /// Described when the event happens, because the modules that are needed to describe it may be unloaded later.
+    description: String,
+}
+
+/// Records every debug event, including the ones that didn't stop, to show what led up to a crash.
+/// The single steps and temporary breakpoints that the debugger causes, e.g. for `wt` or `trace`, are only counted,
+/// since there can be millions of them and they would push the target's own events out of the history.
+pub struct EventHistory {
+    start: Instant,
+    /// Oldest first.
+    events: VecDeque<HistoryEntry>,
+    debugger_event_count: u64,
+}
+
+impl EventHistory {
+    pub fn new() -> EventHistory {
+        EventHistory { start: Instant::now(), events: VecDeque::new(), debugger_event_count: 0 }
+    }
+
+    /// Records the event. Call this before the event is handled, so that e.g. an unloaded module's name is still known.
+    /// Exceptions are recorded with `record_exception` or `record_debugger_event` instead, once it is known whether the
+    /// debugger caused them.
+    pub fn record(&mut self, process_id: ProcessId, thread_id: ThreadId, event: &DebugEvent, process: &mut Process) {
+        let description = describe_event(event, process);
+        self.add(process_id, thread_id, description);
+    }
+
+    pub fn record_exception(&mut self, process_id: ProcessId, thread_id: ThreadId, first_chance: bool, record: &ExceptionRecord, process: &mut Process) {
+        let description = describe_exception(first_chance, record, process);
+        self.add(process_id, thread_id, description);
+    }
+
+    /// Counts a single step or temporary breakpoint hit that the debugger caused.
+    pub fn record_debugger_event(&mut self) {
+        self.debugger_event_count += 1;
+    }
+
+    fn add(&mut self, process_id: ProcessId, thread_id: ThreadId, description: String) {
+        if self.events.len() == MAX_HISTORY {
+            self.events.pop_front();
+        }
+        self.events.push_back(HistoryEntry { time: self.start.elapsed(), process_id, thread_id, description });
+    }
+
+    /// Prints up to `count` of the most recent events.
+    pub fn display(&self, count: usize) {
+        if self.events.is_empty() {
+            println!("No debug events have happened");
+            return;
+        }
+        for entry in self.events.iter().skip(self.events.len().saturating_sub(count)) {
+            println!(
+                "{:>10.3}s {:x}.{:x} {}",
+                entry.time.as_secs_f64(),
+                entry.process_id,
+                entry.thread_id,
+                entry.description,
+            );
+        }
+        if self.debugger_event_count > 0 {
+            println!("Not shown: {} single steps and temporary breakpoint hits caused by the debugger", self.debugger_event_count);
+        }
+    }
+}
+
+fn describe_address(address: u64, process: &mut Process) -> String {
+    name_resolution::resolve_address_to_name(address, process).unwrap_or_else(|| format!("{address:#018x}"))
+}
+
+fn describe_exception(first_chance: bool, record: &ExceptionRecord, process: &mut Process) -> String {
+    let code = record.code.0 as u32;
+    let name = exceptions::get_exception_name(code).map(|name| format!(" ({name})")).unwrap_or_default();
+    let chance = if first_chance { "first chance" } else { "second chance" };
+    format!("Exception {code:#010x}{name}, {chance}, at {}", describe_address(record.address, process))
+}
+
+fn describe_event(event: &DebugEvent, process: &mut Process) -> String {
+    match event {
+        DebugEvent::Exception { first_chance, record } => describe_exception(*first_chance, record, process),
+        DebugEvent::CreateProcess { name, base_addr, .. } => {
+            format!("Process created: {} at {base_addr:#018x}", name.as_deref().unwrap_or("<unknown>"))
+        }
+        DebugEvent::ExitProcess { exit_code } => format!("Process exited with code {exit_code}"),
+        DebugEvent::CreateThread { start_address } => format!("Thread created at {}", describe_address(*start_address, process)),
+        DebugEvent::ExitThread { exit_code } => format!("Thread exited with code {exit_code}"),
+        DebugEvent::LoadDll { name, base_addr } => {
+            format!("Module loaded: {} at {base_addr:#018x}", name.as_deref().unwrap_or("<unknown>"))
+        }
+        DebugEvent::UnloadDll { base_addr } => match process.get_containing_module(*base_addr) {
+            Some(module) => format!("Module unloaded: {} at {base_addr:#018x}", module.name),
+            None => format!("Module unloaded at {base_addr:#018x}"),
+        },
+        DebugEvent::OutputDebugString(debug_string) => format!("DebugOut: {}", debug_string.trim_end_matches(['\r', '\n'])),
+        DebugEvent::Rip { error, info_type } => format!("RIP error {error}, type {}", info_type.0),
+    }
+}
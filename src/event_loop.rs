@@ -0,0 +1,117 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use debugger_core::{
+    memory::{self, MemorySource},
+    windows_wrapper::{self, AutoClosedHandle, DebugContinueStatus, DebugEvent, DebugEventContext, ProcessId},
+};
+
+// Debug events are waited for on their own thread, so that the user can type commands while the target runs.
+// Windows requires the thread that starts debugging a process to be the one that waits for and continues its events, so
+// the target is also launched or attached to on that thread.
+// The debug event thread and the input thread (see `CommandReader`) send `Message`s to the debugger's thread, which runs
+// the commands and decides how to continue each event. While the target is being started, the debug event thread can
+// ask the user for input with `prompt`, which the debugger's thread reads with the `CommandReader`, so that only one
+// thread reads the console.
+
+/// What the debugger's thread receives.
+pub enum Message {
+    DebugEvent(Box<ReceivedDebugEvent>),
+    /// A line typed by the user, or `None` at the end of input.
+    Line(Option<String>),
+    /// The debug event thread stopped, e.g. because the target couldn't be started, so no more events will be sent.
+    Stopped,
+    /// Asks for a line while the target is being started. See `prompt`.
+    Prompt { prompt: String, reply: Sender<Option<String>> },
+}
+
+pub struct ReceivedDebugEvent {
+    pub context: DebugEventContext,
+    pub event: DebugEvent,
+}
+
+// SAFETY: The event's handles, e.g. the process handle of `DebugEvent::CreateProcess`, can be used from any thread.
+unsafe impl Send for ReceivedDebugEvent {}
+
+/// The thread that waits for debug events. After each event that it sends, it waits for `continue_event` before waiting
+/// for the next one.
+pub struct DebugEventThread {
+    continue_requests: Sender<(DebugEventContext, DebugContinueStatus)>,
+}
+
+impl DebugEventThread {
+    /// Starts the thread, which calls `start` to launch or attach to the target, and then sends its debug events to
+    /// `messages`. `start` can ask the user for input by passing `messages` to `prompt`. If `start` returns `None`, or
+    /// events can no longer be waited for, the thread sends `Message::Stopped` and stops.
+    pub fn start(
+        start: impl FnOnce(&Sender<Message>) -> Option<AutoClosedHandle> + Send + 'static,
+        messages: Sender<Message>,
+    ) -> DebugEventThread {
+        let (continue_requests, continue_receiver) = mpsc::channel::<(DebugEventContext, DebugContinueStatus)>();
+        thread::spawn(move || {
+            let Some(process_handle) = start(&messages) else {
+                let _ = messages.send(Message::Stopped);
+                return;
+            };
+            if let Err(e) = windows_wrapper::install_break_in_handler(&process_handle) {
+                print_error!("{e}");
+            }
+            windows_wrapper::set_target_running(true);
+
+            // Used to read the strings in events. The debugger's thread has its own, which cache what they read.
+            let mut memory_sources = HashMap::<ProcessId, Box<dyn MemorySource>>::new();
+            loop {
+                let (context, event) = match windows_wrapper::wait_for_debug_event(|process_id| {
+                    memory_sources.get(&process_id).map(|memory_source| memory_source.as_ref())
+                }) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        print_error!("{e}");
+                        let _ = messages.send(Message::Stopped);
+                        return;
+                    }
+                };
+                windows_wrapper::set_target_running(false);
+                match &event {
+                    DebugEvent::CreateProcess { process_handle, .. } => {
+                        memory_sources.insert(context.process, memory::make_live_memory_source(*process_handle));
+                    }
+                    DebugEvent::ExitProcess { .. } => {
+                        memory_sources.remove(&context.process);
+                    }
+                    _ => {}
+                }
+
+                // Stop when the debugger's thread is done, which drops its end of the channels.
+                if messages.send(Message::DebugEvent(Box::new(ReceivedDebugEvent { context, event }))).is_err() {
+                    return;
+                }
+                let Ok((context, continue_status)) = continue_receiver.recv() else {
+                    return;
+                };
+                windows_wrapper::set_target_running(true);
+                if let Err(e) = windows_wrapper::continue_debug_event(context, continue_status) {
+                    print_error!("{e}");
+                }
+            }
+        });
+        DebugEventThread { continue_requests }
+    }
+
+    /// Lets the thread continue from the event that it sent last, and wait for the next one.
+    pub fn continue_event(&self, context: DebugEventContext, continue_status: DebugContinueStatus) {
+        // If the thread stopped, there are no more events to continue.
+        let _ = self.continue_requests.send((context, continue_status));
+    }
+}
+
+/// Asks the user for a line, from the `start` function of `DebugEventThread::start`. Returns `None` at the end of input,
+/// or if the debugger's thread stopped.
+pub fn prompt(messages: &Sender<Message>, prompt: &str) -> Option<String> {
+    let (reply, replies) = mpsc::channel();
+    messages.send(Message::Prompt { prompt: prompt.to_string(), reply }).ok()?;
+    replies.recv().ok()?
+}
@@ -0,0 +1,122 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use debugger_core::{
+    arch::ThreadContext,
+    memory::MemorySource,
+    process::Process,
+    windows_wrapper::{ProcessId, ThreadId},
+};
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+use crate::recording::Recording;
+
+// Each instruction that the traced thread executes is written as a CSV line with its address, and where it is as
+// `module+offset`, which stays the same between runs even if the module is loaded somewhere else. Optionally, the
+// registers before the instruction executes are written too. Two runs can then be compared with a diff tool.
+
+/// The registers that are written with `registers`.
+const REGISTERS: [&str; 17] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "eflags",
+];
+
+/// Where the traced instructions go.
+enum TraceOutput {
+    /// A CSV file. See `trace start`.
+    File { path: String, file: BufWriter<File>, registers: bool },
+    /// A recording that can be replayed. See `record start`.
+    Recording(Recording),
+}
+
+/// Records every instruction that a thread executes, by single-stepping it.
+pub struct InstructionTrace {
+    pub process_id: ProcessId,
+    pub thread_id: ThreadId,
+    /// Whether the thread is being stepped to trace it, rather than by a command such as `step`, which stops after it.
+    pub auto_step: bool,
+    output: TraceOutput,
+    count: u64,
+}
+
+impl InstructionTrace {
+    pub fn start(path: &str, process_id: ProcessId, thread_id: ThreadId, registers: bool) -> Result<InstructionTrace, String> {
+        let file = File::create(path).map_err(|e| format!("Could not create trace file {path}: {e}"))?;
+        let output = TraceOutput::File { path: path.to_string(), file: BufWriter::new(file), registers };
+        let mut trace = InstructionTrace { process_id, thread_id, auto_step: false, output, count: 0 };
+        let mut header = String::from("address,location");
+        if registers {
+            for name in REGISTERS {
+                header += &format!(",{name}");
+            }
+        }
+        trace.write_line(&header)?;
+        Ok(trace)
+    }
+
+    /// Starts recording the thread's instructions and the memory that they access, to replay them later.
+    pub fn start_recording(process_id: ProcessId, thread_id: ThreadId, recording: Recording) -> InstructionTrace {
+        InstructionTrace { process_id, thread_id, auto_step: false, output: TraceOutput::Recording(recording), count: 0 }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.output, TraceOutput::Recording(_))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        match &mut self.output {
+            TraceOutput::File { path, file, .. } => writeln!(file, "{line}").map_err(|e| format!("Could not write to trace file {path}: {e}")),
+            TraceOutput::Recording(_) => Ok(()),
+        }
+    }
+
+    /// Records the instruction that the thread is about to execute.
+    pub fn record(&mut self, context: &CONTEXT, process: &Process, memory_source: &dyn MemorySource) -> Result<(), String> {
+        match &mut self.output {
+            TraceOutput::File { registers, .. } => {
+                let address = context.instruction_pointer();
+                let location = match process.get_containing_module(address) {
+                    Some(module) => format!("{}+{:#x}", module.name, address - module.address),
+                    None => String::new(),
+                };
+                let mut line = format!("{address:#018x},{location}");
+                if *registers {
+                    for name in REGISTERS {
+                        match context.get_register(name) {
+                            Some(value) => line += &format!(",{value:#x}"),
+                            None => line += ",",
+                        }
+                    }
+                }
+                self.write_line(&line)?;
+            }
+            TraceOutput::Recording(recording) => recording.record(context, memory_source)?,
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Finishes writing the file, and says how many instructions it has. Returns the recording, if it is one, after
+    /// reading what its last instruction wrote from `memory_source`, if it can still be read.
+    pub fn stop(self, memory_source: Option<&dyn MemorySource>) -> Option<Recording> {
+        match self.output {
+            TraceOutput::File { path, mut file, .. } => {
+                match file.flush() {
+                    Ok(()) => println!("Wrote {} instructions to {path}", self.count),
+                    Err(e) => print_error!("Could not write to trace file {path}: {e}"),
+                }
+                None
+            }
+            TraceOutput::Recording(mut recording) => {
+                if let Some(memory_source) = memory_source {
+                    recording.finish(memory_source);
+                }
+                println!("Recorded {} instructions. Use `execution-replay` to step through them.", self.count);
+                Some(recording)
+            }
+        }
+    }
+}
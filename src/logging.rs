@@ -0,0 +1,48 @@
+use std::{io, sync::OnceLock};
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, Registry};
+
+// Internal details, e.g. where symbols were looked for and when breakpoints are written, are logged with `tracing`, so
+// that they can be shown when troubleshooting without cluttering the normal output. The level is chosen with
+// `--verbose` and the `log-level` command. Log messages are printed with `print!`, so that they also go to the session
+// log and to the full-screen mode's output pane.
+
+/// Only problems are shown unless more is asked for.
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// The level for `--verbose`.
+pub const VERBOSE_LEVEL: LevelFilter = LevelFilter::DEBUG;
+
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+struct PrintWriter;
+
+impl io::Write for PrintWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        print!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn init(level: LevelFilter) {
+    let (filter, handle) = reload::Layer::new(level);
+    // The session log already timestamps each line.
+    let format = fmt::layer().with_writer(|| PrintWriter).without_time();
+    if tracing_subscriber::registry().with(filter).with(format).try_init().is_ok() {
+        let _ = LEVEL.set(handle);
+    }
+}
+
+pub fn get_level() -> LevelFilter {
+    LEVEL.get().and_then(|handle| handle.clone_current()).unwrap_or(LevelFilter::OFF)
+}
+
+pub fn set_level(level: LevelFilter) -> Result<(), String> {
+    let handle = LEVEL.get().ok_or("Logging was not initialized")?;
+    handle.reload(level).map_err(|e| format!("Could not change the log level: {e}"))
+}
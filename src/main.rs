@@ -1,51 +1,264 @@
 use std::{
     collections::HashMap,
     env,
+    rc::Rc,
+    sync::mpsc::{self, Sender},
 };
 
+use arch::{Architecture, ThreadContext};
 use memory::MemorySource;
+use memory_display::DisplayFormat;
+use tracing::level_filters::LevelFilter;
+use windows::Win32::{Foundation::HANDLE, System::Diagnostics::Debug::CONTEXT};
 use windows_wrapper::{
+    AlignedContext,
     AutoClosedHandle,
     DebugContinueStatus,
     DebugEvent,
+    ExceptionRecord,
+    LaunchOptions,
+    ProcessEntry,
     ThreadId,
     ProcessId,
 };
 
-mod breakpoint;
+// The library's `print!`, `println!`, and `print_error!` replace the standard macros in the whole crate, so that all
+// output is also written to the session log.
+#[macro_use]
+extern crate debugger_core;
+
 mod command;
-mod eval;
-mod memory;
-mod module;
-mod name_resolution;
-mod process;
-mod registers;
-mod windows_wrapper;
+mod completion;
+mod debug_output;
+mod event_history;
+mod event_loop;
+mod instruction_trace;
+mod logging;
+#[cfg(feature = "python")]
+mod python;
+mod recording;
+mod scripting;
+mod session_stats;
+mod tui;
+
+use debugger_core::{
+    arch,
+    assembler,
+    breakpoint,
+    call_trace,
+    disassembly,
+    engine,
+    environment_blocks,
+    eval,
+    event_filters,
+    exceptions,
+    handles,
+    heaps,
+    locals,
+    memory,
+    memory_display,
+    memory_map,
+    minidump,
+    module,
+    module_details,
+    name_resolution,
+    output,
+    process,
+    registers,
+    session_log,
+    stack,
+    symbols,
+    types,
+    watchpoints,
+    windows_wrapper,
+};
 
 use breakpoint::BreakpointManager;
-use command::grammar::{CommandExpr, EvalExpr};
+use call_trace::CallTrace;
+use debug_output::DebugOutput;
+use engine::{Debuggee, ExceptionEvent, StepKind, ThreadState, ThreadStates};
+use event_history::EventHistory;
+use event_loop::{DebugEventThread, Message, ReceivedDebugEvent};
+use event_filters::{EventFilters, EventKind, EventPolicy, ExceptionPolicy};
+use instruction_trace::InstructionTrace;
+use command::grammar::{CommandExpr, DebugEventName, DebugOutputCommand, EvalExpr, EventPolicyName, LogLevelName, RecordAction, RegisterSet, ReplayAction, TlsCallbacksAction, TraceAction, WatchAccessName};
+use module::{ExportTarget, Module};
 use process::Process;
+use recording::Recording;
+use scripting::{PendingCallback, ScriptEngine, ScriptTarget};
+use session_stats::SessionStats;
+use watchpoints::WatchAccess;
+
+fn show_usage() {
+    let command_line_args: Vec<String> = env::args().collect();
+
+    // The 1st argument is the name of the program
+    let program_name = &command_line_args[0];
+
+    println!("Usage: {program_name} [Options] [Launch-Options] <Command-Line>");
+    println!("       {program_name} [Options] --attach-name <Process-Name>");
+    println!("       {program_name} [Options] --dump <Dump-File>");
+    println!();
+    println!("Options:");
+    println!("  --script <File>      Run the commands in the file at the first prompt");
+    println!("  --no-color           Don't color the output. Setting the NO_COLOR environment variable also turns it off.");
+    println!("  --verbose            Show the debugger's internal logging, e.g. where symbols were looked for. See `log-level`.");
+    println!("  --tui                Full-screen mode, with panes for the disassembly, source, registers, and output");
+    println!("  --skip-initial-breakpoint  Continue past the breakpoint that Windows raises when the process starts or is");
+    println!("                       attached to, instead of stopping at it");
+    println!();
+    println!("Launch options:");
+    println!("  --follow-children    Also debug processes that the target creates");
+    println!("  --cwd <Directory>    Run the target in the given working directory");
+    println!("  --env <Name>=<Value> Set an environment variable for the target. May be repeated.");
+    println!("  --start-suspended    Create the target suspended, and wait for enter before resuming it");
+    println!("  --break-at-entry     Stop at main or wmain if the EXE has symbols for them, or otherwise at its entry point");
+}
+
+/// What to debug, as chosen on the command line.
+enum DebugTarget {
+    Launch {
+        command_line_args: Vec<String>,
+        options: LaunchOptions,
+    },
+    AttachByName(String),
+    Dump(String),
+}
+
+/// Options that apply to the whole debugging session, regardless of the target.
+#[derive(Default)]
+struct SessionOptions {
+    /// A file of commands to run at the first prompt.
+    script: Option<String>,
+    skip_initial_breakpoint: bool,
+    /// Stop before the program's own code runs. See `add_entry_breakpoint`.
+    break_at_entry: bool,
+    /// Use the full-screen mode. See `tui`.
+    tui: bool,
+}
+
+/// The parsed command line.
+struct CommandLine {
+    target: DebugTarget,
+    session: SessionOptions,
+    no_color: bool,
+    verbose: bool,
+}
 
-#[derive(Debug)]
-struct ThreadState {
-    expect_step_exception: bool,
+/// Gets the value that follows the option at `index`, and advances `index` past it.
+fn get_option_value<'a>(args: &'a [String], index: &mut usize) -> Result<&'a String, String> {
+    let option = &args[*index];
+    *index += 1;
+    args.get(*index).ok_or_else(|| format!("Missing value for {option}"))
 }
 
-impl ThreadState {
-    pub fn new() -> Self {
-        ThreadState{
-            expect_step_exception: false,
+fn parse_command_line(args: &[String]) -> Result<CommandLine, String> {
+    let mut options = LaunchOptions::default();
+    let mut session = SessionOptions::default();
+    let mut no_color = false;
+    let mut verbose = false;
+    let mut index = 0;
+    while index < args.len() && args[index].starts_with("--") {
+        match args[index].as_str() {
+            "--attach-name" => {
+                let process_name = get_option_value(args, &mut index)?;
+                if index + 1 != args.len() {
+                    return Err(String::from("--attach-name cannot be combined with other arguments"));
+                }
+                return Ok(CommandLine { target: DebugTarget::AttachByName(process_name.clone()), session, no_color, verbose });
+            }
+            "--dump" => {
+                let dump_path = get_option_value(args, &mut index)?;
+                if index + 1 != args.len() {
+                    return Err(String::from("--dump cannot be combined with other arguments"));
+                }
+                return Ok(CommandLine { target: DebugTarget::Dump(dump_path.clone()), session, no_color, verbose });
+            }
+            "--follow-children" => options.follow_child_processes = true,
+            "--cwd" => options.current_directory = Some(get_option_value(args, &mut index)?.clone()),
+            "--env" => {
+                let variable = get_option_value(args, &mut index)?;
+                match variable.split_once('=') {
+                    Some((name, value)) if !name.is_empty() => options.environment_variables.push((name.to_string(), value.to_string())),
+                    _ => return Err(format!("Expected --env <Name>=<Value>, but got {variable}")),
+                }
+            }
+            "--start-suspended" => options.start_suspended = true,
+            "--break-at-entry" => session.break_at_entry = true,
+            "--script" => session.script = Some(get_option_value(args, &mut index)?.clone()),
+            "--skip-initial-breakpoint" => session.skip_initial_breakpoint = true,
+            "--no-color" => no_color = true,
+            "--verbose" => verbose = true,
+            "--tui" => session.tui = true,
+            option => return Err(format!("Unknown option {option}")),
         }
+        index += 1;
+    }
+
+    if index == args.len() {
+        return Err(String::from("Missing the command line to debug"));
     }
+
+    Ok(CommandLine {
+        target: DebugTarget::Launch {
+            command_line_args: args[index..].to_vec(),
+            options,
+        },
+        session,
+        no_color,
+        verbose,
+    })
 }
 
-fn show_usage() {
-    let command_line_args: Vec<String> = env::args().collect();
+fn display_registers(context: CONTEXT, previous_context: Option<&CONTEXT>, register_set: Option<RegisterSet>, is_wow64: bool) {
+    match register_set {
+        None if is_wow64 => registers::display_all_x86(context, previous_context),
+        None => context.display_registers(previous_context),
+        Some(RegisterSet::Vector(_)) => registers::display_vector(context),
+        Some(RegisterSet::FloatingPoint(_)) => registers::display_floating_point(context),
+    }
+}
 
-    // The 1st argument is the name of the program
-    let program_name = &command_line_args[0];
+/// Splits a display-memory command (e.g. `db`) into its format, address, and optional count.
+/// Returns `None` for other commands.
+fn get_display_memory_args(command: CommandExpr) -> Option<(DisplayFormat, Box<EvalExpr>, Option<Box<EvalExpr>>)> {
+    match command {
+        CommandExpr::DisplayBytes(_, expr, count) | CommandExpr::DisplayBytesAlias(_, expr, count) => Some((DisplayFormat::Bytes, expr, count)),
+        CommandExpr::DisplayWords(_, expr, count) | CommandExpr::DisplayWordsAlias(_, expr, count) => Some((DisplayFormat::Words, expr, count)),
+        CommandExpr::DisplayDwords(_, expr, count) | CommandExpr::DisplayDwordsAlias(_, expr, count) => Some((DisplayFormat::Dwords, expr, count)),
+        CommandExpr::DisplayQwords(_, expr, count) | CommandExpr::DisplayQwordsAlias(_, expr, count) => Some((DisplayFormat::Qwords, expr, count)),
+        CommandExpr::DisplayPointers(_, expr, count) | CommandExpr::DisplayPointersAlias(_, expr, count) => Some((DisplayFormat::Pointers, expr, count)),
+        CommandExpr::DisplayPointersWithSymbols(_, expr, count) | CommandExpr::DisplayPointersWithSymbolsAlias(_, expr, count) => Some((DisplayFormat::PointersWithSymbols, expr, count)),
+        CommandExpr::DisplayAscii(_, expr, count) | CommandExpr::DisplayAsciiAlias(_, expr, count) => Some((DisplayFormat::Ascii, expr, count)),
+        CommandExpr::DisplayUnicode(_, expr, count) | CommandExpr::DisplayUnicodeAlias(_, expr, count) => Some((DisplayFormat::Unicode, expr, count)),
+        _ => None,
+    }
+}
+
+fn run_display_memory_command(
+    format: DisplayFormat,
+    address_expr: &EvalExpr,
+    count_expr: Option<&EvalExpr>,
+    eval_context: &mut eval::EvalContext,
+) {
+    let address = eval::evaluate_expression(address_expr, eval_context);
+    let count = match count_expr {
+        Some(count_expr) => eval::evaluate_expression(count_expr, eval_context),
+        None => Ok(format.default_count()),
+    };
+    match (address, count) {
+        (Ok(address), Ok(count)) => memory_display::display_memory(format, address, count, eval_context.process, eval_context.memory_source),
+        (Err(e), _) | (_, Err(e)) => print_error!("Could not evaluate expression: {e}"),
+    }
+}
 
-    println!("Usage: {program_name} <Command-Line>");
+/// The debug event that the debugger last stopped for. See `display_last_event`.
+struct LastEvent {
+    process_id: ProcessId,
+    thread_id: ThreadId,
+    event: DebugEvent,
+    /// The thread's instruction pointer when the event happened.
+    instruction_pointer: u64,
 }
 
 fn load_module_at_address(
@@ -53,68 +266,801 @@ fn load_module_at_address(
     memory_source: &dyn MemorySource,
     base_address: u64,
     module_name: Option<String>,
+    policy: EventPolicy,
 ) {
     let module = process.add_module(base_address, module_name, memory_source).unwrap();
-    println!("LoadModule: {base_address:#x}   {name}", name = module.name);
+    if policy != EventPolicy::Ignore {
+        println!("LoadModule: {base_address:#x}   {name}", name = module.name);
+    }
+}
+
+/// Adds a breakpoint for a script's callback, unless there already is one. Returns its address.
+fn add_script_breakpoint(
+    pending: PendingCallback,
+    scripting: &mut ScriptEngine,
+    breakpoints: &mut BreakpointManager,
+    process: &mut Process,
+    memory_source: &dyn MemorySource,
+) -> Result<u64, String> {
+    let address = match pending.location.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).map_err(|e| e.to_string())?,
+        None => name_resolution::resolve_name_to_address(&pending.location, process)?,
+    };
+    if !breakpoints.contains_breakpoint(address) {
+        breakpoints.add_breakpoint(address, Some(pending.location.clone()), None, None, memory_source)?;
+    }
+    scripting.add_callback(address, pending);
+    Ok(address)
+}
+
+fn get_event_kind(event: &DebugEventName) -> EventKind {
+    match event {
+        DebugEventName::CreateProcess(_) => EventKind::CreateProcess,
+        DebugEventName::InitialBreakpoint(_) => EventKind::InitialBreakpoint,
+        DebugEventName::CreateThread(_) => EventKind::CreateThread,
+        DebugEventName::ExitThread(_) => EventKind::ExitThread,
+        DebugEventName::LoadModule(_) => EventKind::LoadModule,
+        DebugEventName::UnloadModule(_) => EventKind::UnloadModule,
+        DebugEventName::OutputDebugString(_) => EventKind::OutputDebugString,
+    }
+}
+
+fn get_event_policy(policy: &EventPolicyName) -> EventPolicy {
+    match policy {
+        EventPolicyName::Stop(_) => EventPolicy::Stop,
+        EventPolicyName::Log(_) => EventPolicy::Log,
+        EventPolicyName::Ignore(_) => EventPolicy::Ignore,
+    }
+}
+
+/// Prints the debugged processes as a tree of parents and children. `stopped_process_id` is the process whose event is
+/// being handled. Only its threads are suspended, so the other processes keep running while at the prompt.
+fn list_processes(debuggees: &HashMap<ProcessId, Debuggee>, stopped_process_id: Option<ProcessId>, current_process_id: Option<ProcessId>) {
+    // Debug events don't say which process created which, so find the parents in a snapshot of the system's processes.
+    let parent_ids: HashMap<ProcessId, ProcessId> = match windows_wrapper::enumerate_processes() {
+        Ok(processes) => processes.into_iter().map(|process| (process.id, process.parent_id)).collect(),
+        Err(e) => {
+            print_error!("Could not find the parent processes: {e}");
+            HashMap::new()
+        }
+    };
+    // Processes whose parent isn't debugged are at the top, under `None`.
+    let mut children = HashMap::<Option<ProcessId>, Vec<ProcessId>>::new();
+    for process_id in debuggees.keys() {
+        let parent_id = parent_ids.get(process_id).copied()
+            .filter(|parent_id| parent_id != process_id && debuggees.contains_key(parent_id));
+        children.entry(parent_id).or_default().push(*process_id);
+    }
+    for siblings in children.values_mut() {
+        // Reversed, so that they are popped in order.
+        siblings.sort_by(|a, b| b.cmp(a));
+    }
+
+    let mut stack: Vec<(ProcessId, usize)> = children.get(&None).into_iter().flatten().map(|process_id| (*process_id, 0)).collect();
+    while let Some((process_id, depth)) = stack.pop() {
+        let debuggee = &debuggees[&process_id];
+        let current_marker = if Some(process_id) == current_process_id { "." } else { " " };
+        let name = debuggee.process.get_containing_module(debuggee.exe_address).map_or("<unknown>", |module| module.name.as_str());
+        let state = if Some(process_id) == stopped_process_id { "Stopped" } else { "Running" };
+        println!("{current_marker} {indent}Process {process_id} ({process_id:#x})  {name}  {state}", indent = "  ".repeat(depth));
+        stack.extend(children.get(&Some(process_id)).into_iter().flatten().map(|child_id| (*child_id, depth + 1)));
+    }
+}
+
+/// Opens the thread that commands operate on after switching to a process: the thread that raised the event, if the
+/// process raised it, and otherwise the process's first thread.
+fn open_process_thread(
+    process_id: ProcessId,
+    debuggee: &Debuggee,
+    thread_states: &ThreadStates,
+    event_process_id: ProcessId,
+    event_thread_id: ThreadId,
+) -> Result<(ThreadId, Rc<AutoClosedHandle>, AlignedContext), String> {
+    let thread_id = if process_id == event_process_id {
+        event_thread_id
+    } else {
+        *debuggee.process.iterate_threads().next().ok_or("The process has no threads")?
+    };
+    let thread = engine::get_thread_handle(thread_states, process_id, thread_id)?;
+    let thread_context = windows_wrapper::get_thread_context(&thread, debuggee.process.is_wow64)?;
+    Ok((thread_id, thread, thread_context))
+}
+
+/// Formats the thread ID, along with the thread's description if it has one.
+fn format_thread_name(thread_id: ThreadId, thread: &AutoClosedHandle) -> String {
+    match windows_wrapper::get_thread_description(thread) {
+        Some(description) => format!("{thread_id:#x} \"{description}\""),
+        None => format!("{thread_id:#x}"),
+    }
+}
+
+/// Prints the symbols that match a `module!symbol` pattern.
+fn examine_symbols(pattern: &str, process: &mut Process) {
+    match name_resolution::find_matching_symbols(pattern, process) {
+        Ok(symbols) if symbols.is_empty() => println!("No symbols match {pattern}"),
+        Ok(symbols) => {
+            for (address, name) in symbols {
+                println!("{} {}", output::address(address), output::symbol(&name));
+            }
+        }
+        Err(e) => print_error!("{e}"),
+    }
+}
+
+fn display_module_details(module_name: &str, process: &mut Process, memory_source: &dyn MemorySource) {
+    match process.get_module_by_name_mut(module_name) {
+        Some(module) => {
+            if let Err(e) = module_details::display_module_details(module, memory_source) {
+                print_error!("Could not display the module details: {e}");
+            }
+        }
+        None => print_error!("Could not find module {module_name}"),
+    }
+}
+
+/// Prints a module's TLS callbacks, which run before its entry point. If `breakpoints` is given, also sets a breakpoint on
+/// each one, so that they can be stepped through before the loader calls them.
+fn display_tls_callbacks(
+    module_name: &str,
+    mut breakpoints: Option<&mut BreakpointManager>,
+    process: &mut Process,
+    memory_source: &dyn MemorySource,
+) {
+    let callbacks = match process.get_module_by_name_mut(module_name) {
+        Some(module) => module.read_tls_callbacks(memory_source),
+        None => {
+            print_error!("Could not find module {module_name}");
+            return;
+        }
+    };
+    let callbacks = match callbacks {
+        Ok(callbacks) => callbacks,
+        Err(e) => {
+            print_error!("Could not read the TLS callbacks: {e}");
+            return;
+        }
+    };
+    if callbacks.is_empty() {
+        println!("{module_name} has no TLS callbacks");
+        return;
+    }
+
+    for address in callbacks {
+        let name = name_resolution::resolve_address_to_name(address, process).map_or_else(|| output::address(address), |name| output::symbol(&name));
+        println!("{} {name}", output::address(address));
+        if let Some(breakpoints) = breakpoints.as_deref_mut() {
+            // Don't complain about the ones that already have a breakpoint, so that this can be run more than once.
+            if !breakpoints.contains_breakpoint(address) {
+                if let Err(e) = breakpoints.add_breakpoint(address, None, None, None, memory_source) {
+                    print_error!("Could not add a breakpoint at {address:#018x}: {e}");
+                }
+            }
+        }
+    }
+}
+
+/// Removes the software breakpoints and memory watchpoints from every process, and the hardware breakpoints from every
+/// process's threads, for `bc *`. Says whether any couldn't be removed.
+fn remove_all_breakpoints(
+    debuggees: &mut HashMap<ProcessId, Debuggee>,
+    scripting: &mut ScriptEngine,
+    thread_states: &ThreadStates,
+    current_process_id: ProcessId,
+    current_thread_id: ThreadId,
+    current_thread: &AutoClosedHandle,
+    current_context: &mut CONTEXT,
+) {
+    let mut kept_count = 0;
+    let mut all_hardware_removed = true;
+    for (process_id, debuggee) in debuggees.iter_mut() {
+        debuggee.hardware_breakpoints.remove_all_breakpoints();
+        let (removed, kept) = debuggee.breakpoints.remove_all_breakpoints(debuggee.memory_source.as_ref());
+        for address in removed.iter() {
+            scripting.remove_callbacks(*address);
+        }
+        kept_count += kept;
+        kept_count += debuggee.watchpoints.remove_all_watchpoints(debuggee.process_handle);
+        all_hardware_removed &= if *process_id == current_process_id {
+            engine::apply_hardware_breakpoints(&debuggee.hardware_breakpoints, thread_states, *process_id, &debuggee.process, current_thread_id, current_thread, current_context)
+        } else {
+            engine::apply_hardware_breakpoints_to_process(&debuggee.hardware_breakpoints, thread_states, *process_id, &debuggee.process)
+        };
+    }
+
+    if kept_count > 0 {
+        print_error!("Could not remove {kept_count} breakpoints");
+    } else if !all_hardware_removed {
+        print_error!("Could not remove the hardware breakpoints from every thread");
+    } else {
+        println!("Removed all breakpoints");
+    }
+}
+
+/// Stops an instruction trace, and keeps it to replay if it's a recording. `memory_source` is the traced process's
+/// memory, if it can still be read.
+fn stop_instruction_trace(trace: InstructionTrace, recording: &mut Option<Recording>, memory_source: Option<&dyn MemorySource>) {
+    if let Some(stopped) = trace.stop(memory_source) {
+        *recording = Some(stopped);
+    }
+}
+
+/// Moves through a recording for `execution-replay`, and shows the step that it moves to, or shows the recorded memory.
+/// Expressions are evaluated with the recorded registers and memory.
+fn run_replay_command(recording: &mut Recording, action: Option<ReplayAction>, process: &mut Process) {
+    fn evaluate(expr: &EvalExpr, recording: &Recording, process: &mut Process) -> Option<u64> {
+        let mut eval_context = eval::EvalContext {
+            process,
+            register_context: &recording.context(),
+            memory_source: recording,
+            teb_address: recording.teb_address(),
+            last_exception_address: None,
+        };
+        eval::evaluate_expression(expr, &mut eval_context)
+            .inspect_err(|e| print_error!("Could not evaluate expression: {e}"))
+            .ok()
+    }
+    let evaluate_count = |count: Option<Box<EvalExpr>>, process: &mut Process| match count {
+        Some(count) => evaluate(&count, recording, process).map(|count| count as usize),
+        None => Some(1),
+    };
+
+    let position = recording.position();
+    let step = match action {
+        None => Some(position),
+        Some(ReplayAction::Back(_, count)) => evaluate_count(count, process).map(|count| position.saturating_sub(count)),
+        Some(ReplayAction::Forward(_, count)) => evaluate_count(count, process).map(|count| position.saturating_add(count)),
+        Some(ReplayAction::Goto(_, step)) => evaluate(&step, recording, process).map(|step| step as usize),
+        Some(ReplayAction::Memory(_, address, size)) => {
+            let address = evaluate(&address, recording, process);
+            let size = match size {
+                Some(size) => evaluate(&size, recording, process),
+                None => Some(DisplayFormat::Bytes.default_count()),
+            };
+            if let (Some(address), Some(size)) = (address, size) {
+                memory_display::display_memory(DisplayFormat::Bytes, address, size, process, recording);
+            }
+            return;
+        }
+    };
+    let Some(step) = step else {
+        return;
+    };
+
+    recording.move_to(step);
+    let context = recording.context();
+    let address = context.instruction_pointer();
+    match name_resolution::resolve_address_to_name(address, process) {
+        Some(name) => println!("[Step {} of {}] {}", recording.position(), recording.step_count(), output::symbol(&name)),
+        None => println!("[Step {} of {}] {}", recording.position(), recording.step_count(), output::address(address)),
+    }
+    display_registers(context, recording.previous_context().as_ref(), None, recording.bitness() == 32);
+    disassembly::display_instructions(recording, address, 1, recording.bitness());
+}
+
+/// Adds a tracepoint on every function that a module exports, which logs the call with the first four arguments, which
+/// x64 passes in registers. 32-bit code passes them on the stack, so only the call is logged.
+fn trace_module_exports(
+    module_name: &str,
+    breakpoints: &mut BreakpointManager,
+    process: &mut Process,
+    process_handle: HANDLE,
+    memory_source: &dyn MemorySource,
+) {
+    let is_wow64 = process.is_wow64;
+    let Some(module) = process.get_module_by_name_mut(module_name) else {
+        print_error!("Could not find module {module_name}");
+        return;
+    };
+
+    let mut count = 0;
+    for export in module.exports.iter() {
+        let ExportTarget::Rva(address) = export.target else {
+            continue;
+        };
+        // Breakpoints on exported variables would corrupt them.
+        if !windows_wrapper::query_memory_region(process_handle, address).is_some_and(|region| region.is_executable()) {
+            continue;
+        }
+        // Different names can be exported for the same function.
+        if breakpoints.contains_breakpoint(address) {
+            continue;
+        }
+
+        let name = format!("{}!{export}", module.name);
+        // Braces in the name would be mistaken for registers.
+        let escaped_name = name.replace('{', "{{").replace('}', "}}");
+        let message = if is_wow64 {
+            escaped_name
+        } else {
+            format!("{escaped_name}(rcx={{rcx:#x}}, rdx={{rdx:#x}}, r8={{r8:#x}}, r9={{r9:#x}})")
+        };
+        match breakpoints.add_breakpoint(address, Some(name), None, Some(message), memory_source) {
+            Ok(()) => count += 1,
+            Err(e) => print_error!("Could not add a tracepoint on {export}: {e}"),
+        }
+    }
+    println!("Added tracepoints on {count} functions. Use `breakpoint-remove *` to remove them.");
 }
 
-fn main_debugger_loop(process_handle: AutoClosedHandle) {
-    let mut thread_states = HashMap::<(ProcessId, ThreadId), ThreadState>::new();
-    let mem_source = memory::make_live_memory_source(process_handle.handle());
-    // TODO: Currently this assumes that there is only a single process. Add support for multiple processes.
-    let mut process = Process::new();
-    let mut breakpoints = BreakpointManager::new();
+fn reload_symbols(module_name: Option<&str>, process: &mut Process) {
+    match module_name {
+        Some(module_name) => match process.get_module_by_name_mut(module_name) {
+            Some(module) => reload_module_symbols(module),
+            None => print_error!("Could not find module {module_name}"),
+        },
+        None => {
+            for module in process.iterate_modules_mut() {
+                reload_module_symbols(module);
+            }
+        }
+    }
+}
+
+fn reload_module_symbols(module: &mut Module) {
+    module.reload_symbols();
+    match module.get_pdb().map(|_| ()).map_err(String::clone) {
+        Ok(()) => println!("Loaded symbols for {}", module.name),
+        Err(e) => println!("No symbols for {}: {e}", module.name),
+    }
+}
+
+fn list_threads(thread_states: &ThreadStates, process_id: ProcessId, process: &mut Process, current_thread_id: ThreadId) {
+    let threads: Vec<(ThreadId, u64, bool)> = process.get_threads().iter().map(|thread| (thread.id, thread.start_address, thread.frozen)).collect();
+    for (thread_id, start_address, frozen) in threads {
+        let current_marker = if thread_id == current_thread_id { "." } else { " " };
+        let start = name_resolution::resolve_address_to_name(start_address, process)
+            .unwrap_or_else(|| format!("{start_address:#018x}"));
+        let frozen_marker = if frozen { "  Frozen" } else { "" };
+        let thread = match engine::get_thread_handle(thread_states, process_id, thread_id) {
+            Ok(thread) => thread,
+            Err(e) => {
+                println!("{current_marker} Thread {thread_id:#x}  Start: {start}  {e}{frozen_marker}");
+                continue;
+            }
+        };
+        let suspend_count = match windows_wrapper::get_thread_suspend_count(&thread) {
+            Ok(suspend_count) => suspend_count.to_string(),
+            Err(e) => format!("unknown ({e})"),
+        };
+        println!("{current_marker} Thread {thread_name}  Start: {start}  Suspend count: {suspend_count}{frozen_marker}", thread_name = format_thread_name(thread_id, &thread));
+    }
+}
+
+/// Suspends (freezes) or resumes (thaws) a thread for the user, so that it doesn't run while other threads are stepped.
+fn set_thread_frozen(thread_states: &ThreadStates, process_id: ProcessId, process: &mut Process, thread_id: u64, frozen: bool) {
+    let thread_info = match u32::try_from(thread_id).ok().and_then(|id| process.get_thread_mut(ThreadId::from(id))) {
+        Some(thread_info) => thread_info,
+        None => {
+            println!("No thread {thread_id:#x} in the process");
+            return;
+        }
+    };
+    if thread_info.frozen == frozen {
+        println!("Thread {thread_id:#x} is already {state}", state = if frozen { "frozen" } else { "thawed" });
+        return;
+    }
+
+    let result = engine::get_thread_handle(thread_states, process_id, thread_info.id).and_then(|thread| {
+        if frozen {
+            windows_wrapper::suspend_thread(&thread)
+        } else {
+            windows_wrapper::resume_thread(&thread)
+        }
+    });
+    match result {
+        Ok(()) => thread_info.frozen = frozen,
+        Err(e) => print_error!("Could not {action} thread {thread_id:#x}: {e}", action = if frozen { "freeze" } else { "thaw" }),
+    }
+}
+
+fn display_last_event(last_event: &LastEvent, process: &mut Process) {
+    let LastEvent { process_id, thread_id, event, instruction_pointer } = last_event;
+    let (description, address) = match event {
+        DebugEvent::Exception { first_chance, record } => {
+            let code = record.code.0 as u32;
+            let name = exceptions::get_exception_name(code).map(|name| format!(" ({name})")).unwrap_or_default();
+            let chance = if *first_chance { "first chance" } else { "second chance" };
+            (format!("Exception code {code:#010x}{name}, {chance}"), Some(record.address))
+        }
+        DebugEvent::CreateProcess { base_addr, .. } => (String::from("Process created"), Some(*base_addr)),
+        DebugEvent::ExitProcess { exit_code } => (format!("Process exited with code {exit_code}"), None),
+        DebugEvent::CreateThread { start_address } => (String::from("Thread created"), Some(*start_address)),
+        DebugEvent::ExitThread { exit_code } => (format!("Thread exited with code {exit_code}"), None),
+        DebugEvent::LoadDll { base_addr, .. } => (String::from("Module loaded"), Some(*base_addr)),
+        DebugEvent::UnloadDll { base_addr } => (String::from("Module unloaded"), Some(*base_addr)),
+        DebugEvent::OutputDebugString(debug_string) => (format!("Debug output: {debug_string}"), None),
+        DebugEvent::Rip { error, info_type } => (format!("RIP error {error}, type {}", info_type.0), None),
+    };
+
+    println!("Last event: {description} in process {process_id:#x}, thread {thread_id:#x}");
+    let mut describe_address = |address: u64| {
+        name_resolution::resolve_address_to_name(address, process).map_or_else(|| output::address(address), |name| output::symbol(&name))
+    };
+    if let Some(address) = address {
+        println!("  Address: {}", describe_address(address));
+    }
+    println!("  Instruction pointer: {}", describe_address(*instruction_pointer));
+    if let Some(module) = address.and_then(|address| process.get_containing_module(address)) {
+        println!("  Module: {} at {}", module.name, output::address(module.address));
+    }
+}
+
+fn display_exception_record(last_exception: Option<&ExceptionRecord>, process: &mut Process) {
+    match last_exception {
+        Some(record) => exceptions::display_exception_record(record, process),
+        None => println!("No exception has been raised"),
+    }
+}
+
+/// Sets what to do when the exception with the code `code` is raised. `code` is `None` if it failed to evaluate.
+fn set_exception_policy(event_filters: &mut EventFilters, code: Option<u64>, policy: ExceptionPolicy) {
+    match code.map(u32::try_from) {
+        None => {}
+        Some(Ok(code)) => {
+            event_filters.set_exception_policy(code, policy);
+            println!("Exception {code:#010x}: {policy}");
+        }
+        Some(Err(_)) => print_error!("Exception codes are 32-bit"),
+    }
+}
+
+fn open_log(path: &str) {
+    match session_log::open(path) {
+        Ok(()) => println!("Logging to {path}"),
+        Err(e) => print_error!("{e}"),
+    }
+}
+
+fn close_log() {
+    match session_log::close() {
+        Some(path) => println!("Closed the log {path}"),
+        None => println!("No log is open"),
+    }
+}
+
+/// Prints the log level, after changing it to `level` if one is given.
+fn set_log_level(level: Option<LogLevelName>) {
+    if let Some(level) = level {
+        let level = match level {
+            LogLevelName::Off(_) => LevelFilter::OFF,
+            LogLevelName::Error(_) => LevelFilter::ERROR,
+            LogLevelName::Warn(_) => LevelFilter::WARN,
+            LogLevelName::Info(_) => LevelFilter::INFO,
+            LogLevelName::Debug(_) => LevelFilter::DEBUG,
+            LogLevelName::Trace(_) => LevelFilter::TRACE,
+        };
+        if let Err(e) = logging::set_level(level) {
+            print_error!("{e}");
+            return;
+        }
+    }
+    println!("Log level: {}", logging::get_level().to_string().to_lowercase());
+}
+
+fn create_command_reader(options: &SessionOptions, messages: Sender<Message>) -> command::CommandReader {
+    let mut command_reader = command::CommandReader::new(messages);
+    if options.tui {
+        if let Err(e) = command_reader.enable_tui() {
+            print_error!("{e}");
+        }
+    }
+    if let Some(script) = &options.script {
+        if let Err(e) = command_reader.run_script(script) {
+            print_error!("{e}");
+        }
+    }
+    command_reader
+}
+
+/// Runs a command that was typed while the target is running. Only commands that don't need a stopped thread can run.
+/// Returns `false` to quit.
+fn run_command_while_running(command: CommandExpr, debuggees: &mut HashMap<ProcessId, Debuggee>, thread_states: &ThreadStates) -> bool {
+    match command {
+        CommandExpr::Help(_) | CommandExpr::HelpAlias(_) => command::print_command_help(),
+        CommandExpr::BreakIn(_) | CommandExpr::BreakInAlias(_) => {
+            if windows_wrapper::request_break_in() {
+                println!("Breaking in");
+            }
+        }
+        CommandExpr::ListThreads(_) | CommandExpr::ListThreadsAlias(_) => {
+            for (process_id, debuggee) in debuggees.iter_mut() {
+                println!("Process {process_id}");
+                // No thread is current while the target is running.
+                list_threads(thread_states, *process_id, &mut debuggee.process, ThreadId::from(0));
+            }
+        }
+        CommandExpr::ListProcesses(_) | CommandExpr::ListProcessesAlias(_) => list_processes(debuggees, None, None),
+        CommandExpr::OpenLog(_, path) | CommandExpr::OpenLogAlias(_, path) => open_log(&path.path),
+        CommandExpr::CloseLog(_) | CommandExpr::CloseLogAlias(_) => close_log(),
+        CommandExpr::LogLevel(_, level) | CommandExpr::LogLevelAlias(_, level) => set_log_level(level),
+        CommandExpr::Quit(_) | CommandExpr::QuitAlias(_) => return false,
+        _ => print_error!("The target is running. Use `break` to stop it first."),
+    }
+    true
+}
+
+/// Debugs the target that `start` launches or attaches to, on the debug event thread. See `event_loop`.
+fn main_debugger_loop(start: impl FnOnce(&Sender<Message>) -> Option<AutoClosedHandle> + Send + 'static, options: &SessionOptions) {
+    let (message_sender, messages) = mpsc::channel();
+    let event_thread = DebugEventThread::start(start, message_sender.clone());
+
+    let mut thread_states = ThreadStates::new();
+    let mut debuggees = HashMap::<ProcessId, Debuggee>::new();
+    let mut scripting = ScriptEngine::new();
+    // How many instructions to disassemble at each prompt, starting at the instruction pointer.
+    let mut prompt_disassembly_count: usize = 1;
+    let mut last_exception: Option<ExceptionRecord> = None;
+    let mut event_filters = EventFilters::new();
+    if options.skip_initial_breakpoint {
+        event_filters.set_event_policy(EventKind::InitialBreakpoint, EventPolicy::Log);
+    }
+    let mut debug_output = DebugOutput::new();
+    let mut event_history = EventHistory::new();
+    let mut session_stats = SessionStats::new();
+    let mut instruction_trace: Option<InstructionTrace> = None;
+    // The last recording that `execution-record` made, to replay.
+    let mut recording: Option<Recording> = None;
+    let mut command_reader = create_command_reader(options, message_sender);
 
     loop {
-        let (event_context, debug_event) = windows_wrapper::wait_for_debug_event(mem_source.as_ref());
+        // Run the commands that are typed while the target runs, until the next debug event.
+        let (event_context, debug_event) = loop {
+            // Until the process is created, the input may be needed to start it, e.g. with `--start-suspended`. See
+            // `Message::Prompt`.
+            if !debuggees.is_empty() {
+                command_reader.request_line(command::RUNNING_PROMPT);
+            }
+            match messages.recv() {
+                Ok(Message::DebugEvent(received)) => {
+                    let ReceivedDebugEvent { context, event } = *received;
+                    break (context, event);
+                }
+                Ok(Message::Line(line)) => {
+                    command_reader.add_line(line);
+                    while let Some(command) = command_reader.take_typed_command() {
+                        if !run_command_while_running(command, &mut debuggees, &thread_states) {
+                            return;
+                        }
+                    }
+                }
+                Ok(Message::Prompt { prompt, reply }) => {
+                    let _ = reply.send(command_reader.read_line(&prompt, &messages));
+                }
+                Ok(Message::Stopped) | Err(_) => return,
+            }
+        };
+        session_stats.record_event(&debug_event);
         let mut continue_status = DebugContinueStatus::Continue;
+        let mut skip_prompt = false;
+        let mut step_completed = false;
+        // Whether the thread executed an instruction because we single-stepped it.
+        let mut stepped = false;
+        let mut process_exited = false;
+
+        // Register the process.
+        if let DebugEvent::CreateProcess { process_handle, base_addr, .. } = &debug_event {
+            assert!(!debuggees.contains_key(&event_context.process));
+            debuggees.insert(event_context.process, Debuggee::new(*process_handle, *base_addr));
+        }
+        let debuggee = debuggees.get_mut(&event_context.process)
+            .unwrap_or_else(|| panic!("Debug event for unknown process {process_id:#x}", process_id = event_context.process));
+        let is_wow64 = debuggee.process.is_wow64;
+
+        let opened = engine::get_thread_handle(&thread_states, event_context.process, event_context.thread).and_then(|thread| {
+            windows_wrapper::get_thread_context(&thread, is_wow64).map(|thread_context| (thread, thread_context))
+        });
+        let (mut thread, mut thread_context) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                // The event can't be handled without its thread, so let the target continue.
+                print_error!("Could not handle the debug event: {e}");
+                session_stats.record_continue();
+                event_thread.continue_event(event_context, continue_status);
+                continue;
+            }
+        };
 
+        let last_event = LastEvent {
+            process_id: event_context.process,
+            thread_id: event_context.thread,
+            event: debug_event.clone(),
+            instruction_pointer: thread_context.context.instruction_pointer(),
+        };
+        if !matches!(debug_event, DebugEvent::Exception { .. }) {
+            event_history.record(event_context.process, event_context.thread, &debug_event, &mut debuggee.process);
+        }
         match debug_event {
-            DebugEvent::Exception { first_chance, code } => {
+            DebugEvent::Exception { first_chance, record } => {
                 let chance_string = if first_chance {
-                    "second chance"
-                } else {
                     "first chance"
+                } else {
+                    "second chance"
                 };
 
-                // Assume that the first EXCEPTION_SINGLE_STEP exception from a thread after we step (via trap) is from our trap.
                 let thread_state = thread_states.get_mut(&(event_context.process, event_context.thread))
-                    .unwrap_or_else(|| panic!("Exception code {code_num:#x} ({chance_string}) for unknown process {process_id:#x}, thread {thread_id:#x}", code_num = code.0, process_id = event_context.process, thread_id = event_context.thread));
-                if thread_state.expect_step_exception && code == windows_wrapper::EXCEPTION_CODE_SINGLE_STEP {
-                    thread_state.expect_step_exception = false;
+                    .unwrap_or_else(|| panic!("Exception code {code_num:#x} ({chance_string}) for unknown process {process_id:#x}, thread {thread_id:#x}", code_num = record.code.0, process_id = event_context.process, thread_id = event_context.thread));
+                let exception_event = engine::handle_exception(&record, thread_state, debuggee, &mut thread_context.context);
+                let Debuggee { process, memory_source: mem_source, process_handle: debuggee_handle, entry_breakpoint, breakpoints, .. } = &mut *debuggee;
+                // Don't fill the history with the steps and temporary breakpoints of e.g. `wt`, which can be millions.
+                let caused_by_debugger = match exception_event {
+                    ExceptionEvent::Stepped { .. } => true,
+                    ExceptionEvent::TemporaryBreakpoint { address } => *entry_breakpoint != Some(address),
+                    _ => false,
+                };
+                if caused_by_debugger {
+                    event_history.record_debugger_event();
                 } else {
-                    println!("Exception code {code_num:#x} ({chance_string})", code_num = code.0);
-                    continue_status = DebugContinueStatus::ExceptionNotHandled;
+                    event_history.record_exception(event_context.process, event_context.thread, first_chance, &record, process);
+                }
+                match exception_event {
+                    ExceptionEvent::Stepped { resume } => {
+                        stepped = true;
+                        session_stats.record_single_step();
+                        skip_prompt = resume;
+                        step_completed = !resume;
+                    }
+                    ExceptionEvent::HardwareBreakpoint { address } => {
+                        println!("Hardware breakpoint hit at {address:#018x}");
+                        session_stats.record_breakpoint_hit();
+                        engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                    }
+                    ExceptionEvent::TemporaryBreakpoint { address } => {
+                        // Temporary breakpoints are used for stepping, so stop quietly.
+                        engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                        if *entry_breakpoint == Some(address) {
+                            *entry_breakpoint = None;
+                            let name = name_resolution::resolve_address_to_name(address, process).map_or_else(|| output::address(address), |name| output::symbol(&name));
+                            println!("Reached the program's entry point at {name}");
+                        } else {
+                            step_completed = true;
+                        }
+                    }
+                    ExceptionEvent::Breakpoint { address } => {
+                        session_stats.record_breakpoint_hit();
+
+                        // Other threads step past thread-scoped breakpoints, as if their condition were false.
+                        let condition_met = breakpoints.applies_to_thread(address, event_context.thread) && match breakpoints.get_condition(address) {
+                            Some(condition) => {
+                                let mut eval_context = eval::EvalContext{
+                                    process,
+                                    register_context: &thread_context.context,
+                                    memory_source: mem_source.as_ref(),
+                                    teb_address: windows_wrapper::get_thread_environment_block_address(&thread).ok(),
+                                    last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                                };
+                                match eval::evaluate_expression(condition, &mut eval_context) {
+                                    Ok(val) => val != 0,
+                                    Err(e) => {
+                                        print_error!("Could not evaluate breakpoint condition: {e}");
+                                        true
+                                    }
+                                }
+                            }
+                            None => true,
+                        };
+
+                        // Tracepoints log and continue instead of stopping.
+                        let log_message = breakpoints.get_log_message(address).filter(|_| condition_met);
+                        if let Some(message) = log_message {
+                            println!("{}", breakpoint::format_log_message(message, &thread_context.context));
+                        }
+
+                        // Breakpoints with script callbacks only stop if a callback asks to.
+                        let script_stop = (condition_met && scripting.has_callbacks(address)).then(|| {
+                            let target = ScriptTarget::new(&thread_context.context, memory::make_live_memory_source(*debuggee_handle), process);
+                            scripting.call_breakpoint_callbacks(address, target).unwrap_or_else(|e| {
+                                print_error!("{e}");
+                                true
+                            })
+                        });
+
+                        if (condition_met && log_message.is_none() && script_stop != Some(false)) || thread_state.breakpoint_to_rearm.is_none() {
+                            println!("Breakpoint hit at {address:#018x}");
+                            if breakpoints.is_one_shot(address) {
+                                // It's already disarmed, so this just forgets it.
+                                if let Err(e) = breakpoints.remove_breakpoint(address, mem_source.as_ref()) {
+                                    print_error!("Could not remove one-shot breakpoint at {address:#018x}: {e}");
+                                }
+                                thread_state.breakpoint_to_rearm = None;
+                            }
+                        } else {
+                            // Step past the breakpoint and continue without prompting.
+                            thread_state.begin_continue(&mut thread_context.context);
+                            skip_prompt = true;
+                        }
+                        engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                    }
+                    ExceptionEvent::Watchpoint { hit: Some(hit) } => {
+                        let access = if hit.is_write { "Write" } else { "Access" };
+                        let name = name_resolution::resolve_address_to_name(record.address, process).map_or_else(|| output::address(record.address), |name| output::symbol(&name));
+                        println!("{access} to {:#018x} (memory watchpoint at {:#018x}) by {name}", hit.access_address, hit.watchpoint_address);
+                    }
+                    ExceptionEvent::Watchpoint { hit: None } => {
+                        // The access was to another part of a watched page.
+                        engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                        skip_prompt = true;
+                    }
+                    ExceptionEvent::InitialBreakpoint => {
+                        let policy = event_filters.get_event_policy(EventKind::InitialBreakpoint);
+                        if policy != EventPolicy::Ignore {
+                            println!("Initial breakpoint on thread {}", format_thread_name(event_context.thread, &thread));
+                        }
+                        skip_prompt = policy != EventPolicy::Stop;
+                    }
+                    ExceptionEvent::BreakIn => println!("Break-in (Ctrl+C)"),
+                    ExceptionEvent::Other { code } => {
+                        let exception_policy = event_filters.get_exception_policy(code);
+                        if exception_policy != ExceptionPolicy::Ignore {
+                            println!("Exception code {code_num:#x} ({chance_string}) on thread {thread_name}", code_num = record.code.0, thread_name = format_thread_name(event_context.thread, &thread));
+                        }
+                        if event_filters.should_break_on_exception(code, first_chance) {
+                            last_exception = Some(record.clone());
+                        } else {
+                            skip_prompt = true;
+                        }
+                        continue_status = DebugContinueStatus::ExceptionNotHandled;
+                    }
                 }
             }
-            DebugEvent::CreateThread => {
-                println!("Thread created: {:#x}", event_context.thread);
+            DebugEvent::CreateThread { start_address } => {
+                let policy = event_filters.get_event_policy(EventKind::CreateThread);
+                if policy != EventPolicy::Ignore {
+                    println!("Thread created: {}", format_thread_name(event_context.thread, &thread));
+                }
+                skip_prompt = policy != EventPolicy::Stop;
 
-                process.add_thread(event_context.thread);
+                debuggee.process.add_thread(event_context.thread, start_address);
 
                 // Register the thread.
                 assert!(!thread_states.contains_key(&(event_context.process, event_context.thread)));
-                thread_states.insert((event_context.process, event_context.thread), ThreadState::new());
+                thread_states.insert((event_context.process, event_context.thread), ThreadState::new(thread.clone()));
+
+                debuggee.hardware_breakpoints.apply_to_context(&mut thread_context.context);
+                engine::set_thread_context(&thread, &thread_context.context, is_wow64);
             }
             DebugEvent::ExitThread { exit_code } => {
-                println!("Thread {thread_id:#x} (from process: {process_id:#x}) exited with code: {exit_code}", process_id = event_context.process, thread_id = event_context.thread);
+                let policy = event_filters.get_event_policy(EventKind::ExitThread);
+                if policy != EventPolicy::Ignore {
+                    println!("Thread {thread_id:#x} (from process: {process_id:#x}) exited with code: {exit_code}", process_id = event_context.process, thread_id = event_context.thread);
+                }
+                skip_prompt = policy != EventPolicy::Stop;
 
-                process.remove_thread(event_context.thread);
+                debuggee.process.remove_thread(event_context.thread);
 
                 // Unregister the thread.
                 assert!(thread_states.contains_key(&(event_context.process, event_context.thread)));
                 thread_states.remove(&(event_context.process, event_context.thread));
             }
-            DebugEvent::CreateProcess { name, base_addr } => {
-                println!("Process created: {:#x}", event_context.process);
+            DebugEvent::CreateProcess { name, base_addr, thread_start_address, .. } => {
+                let policy = event_filters.get_event_policy(EventKind::CreateProcess);
+                if policy != EventPolicy::Ignore {
+                    println!("Process created: {:#x}", event_context.process);
+                }
+                skip_prompt = policy != EventPolicy::Stop;
 
                 // Register the thread.
                 assert!(!thread_states.contains_key(&(event_context.process, event_context.thread)));
-                thread_states.insert((event_context.process, event_context.thread), ThreadState::new());
+                thread_states.insert((event_context.process, event_context.thread), ThreadState::new(thread.clone()));
 
-                load_module_at_address(&mut process, mem_source.as_ref(), base_addr, name);
+                load_module_at_address(&mut debuggee.process, debuggee.memory_source.as_ref(), base_addr, name, policy);
+                match debuggee.process.get_containing_module(base_addr).map(|module| module.architecture) {
+                    // WOW64 processes run 32-bit x86 code on x64, and their threads' 32-bit contexts are used.
+                    Some(Some(Architecture::X86)) if debuggee.process.is_wow64 => println!("The process is running under WOW64"),
+                    Some(Some(architecture)) if architecture != Architecture::X64 => {
+                        println!("Warning: the process is {architecture}, but the debugger only supports x64 (and x86 under WOW64). Registers and stepping will not work correctly.");
+                    }
+                    Some(None) => println!("Warning: the process's architecture is unknown"),
+                    _ => {}
+                }
 
-                process.add_thread(event_context.thread);
+                debuggee.process.add_thread(event_context.thread, thread_start_address);
+
+                if options.break_at_entry {
+                    match engine::add_entry_breakpoint(&mut debuggee.process, base_addr, &mut debuggee.breakpoints, debuggee.memory_source.as_ref()) {
+                        Ok(address) => debuggee.entry_breakpoint = Some(address),
+                        Err(e) => print_error!("Could not break at the entry point: {e}"),
+                    }
+                }
             }
             DebugEvent::ExitProcess { exit_code } => {
                 println!("ExitProcess: code: {exit_code} process: {process_id:#x}", process_id = event_context.process);
@@ -123,73 +1069,542 @@ fn main_debugger_loop(process_handle: AutoClosedHandle) {
                 assert!(thread_states.contains_key(&(event_context.process, event_context.thread)));
                 thread_states.remove(&(event_context.process, event_context.thread));
 
-                // Exit the debug loop.
-                break;
+                process_exited = true;
+                skip_prompt = true;
             }
             DebugEvent::LoadDll { name, base_addr } => {
-                load_module_at_address(&mut process, mem_source.as_ref(), base_addr, name);
+                let policy = event_filters.get_event_policy(EventKind::LoadModule);
+                load_module_at_address(&mut debuggee.process, debuggee.memory_source.as_ref(), base_addr, name, policy);
+                debuggee.breakpoints.resolve_deferred_breakpoints(&mut debuggee.process, debuggee.memory_source.as_ref());
+                let module_name = debuggee.process.get_containing_module(base_addr).map(|module| module.name.as_str()).unwrap_or_default();
+                skip_prompt = policy != EventPolicy::Stop && !event_filters.should_break_on_module_load(module_name);
             }
-            DebugEvent::UnloadDll => {
-                println!("UnloadDll")
+            DebugEvent::UnloadDll { base_addr } => {
+                let policy = event_filters.get_event_policy(EventKind::UnloadModule);
+                match debuggee.process.remove_module(base_addr) {
+                    _ if policy == EventPolicy::Ignore => {}
+                    Some(module) => println!("UnloadModule: {base_addr:#x}   {name}", name = module.name),
+                    None => println!("UnloadModule: {base_addr:#x}"),
+                }
+                skip_prompt = policy != EventPolicy::Stop;
             }
             DebugEvent::OutputDebugString(debug_string) => {
-                println!("DebugOut: {debug_string}");
+                let policy = event_filters.get_event_policy(EventKind::OutputDebugString);
+                let show = debug_output.capture(&debug_string);
+                if show && policy != EventPolicy::Ignore {
+                    println!("DebugOut: {}", debug_output.format(&debug_string));
+                }
+                // Hidden output doesn't stop, since the user asked not to see it.
+                skip_prompt = !show || policy != EventPolicy::Stop;
             }
             DebugEvent::Rip { error, info_type } => println!("RipEvent: error: {error}, type: {}", info_type.0),
         }
 
-        let thread = windows_wrapper::open_thread(&event_context.thread);
-        let mut thread_context = windows_wrapper::get_thread_context(&thread);
+        let Debuggee { process, memory_source: mem_source, breakpoints, .. } = debuggee;
 
-        let mut continue_execution = false;
+        // Record the traced thread's instructions. Steps that were only taken to trace it don't stop.
+        if let Some(trace) = instruction_trace.as_mut().filter(|trace| trace.process_id == event_context.process && trace.thread_id == event_context.thread) {
+            if stepped {
+                if let Err(e) = trace.record(&thread_context.context, process, mem_source.as_ref()) {
+                    print_error!("{e}");
+                    stop_instruction_trace(instruction_trace.take().unwrap(), &mut recording, Some(mem_source.as_ref()));
+                }
+            }
+            if let Some(trace) = instruction_trace.as_mut() {
+                if step_completed && trace.auto_step {
+                    step_completed = false;
+                    skip_prompt = true;
+                }
+            }
+        }
+
+        // Repeat a `step` or `next` command that was given a count, or that is stepping by source line, or keep tracing
+        // with `watch-trace`.
+        if let Some(thread_state) = thread_states.get_mut(&(event_context.process, event_context.thread)) {
+            if engine::continue_step_command(thread_state, step_completed, skip_prompt, is_wow64, &mut thread_context.context, process, breakpoints, mem_source.as_ref()) {
+                engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                skip_prompt = true;
+            }
+        }
+
+        // Commands operate on the process and thread that raised the event, unless the user switches to another one.
+        let mut current_process_id = event_context.process;
+        let mut current_thread_id = event_context.thread;
+        let mut continue_execution = skip_prompt;
+        if !continue_execution {
+            // Commands such as `step` stop as usual while tracing.
+            if let Some(trace) = instruction_trace.as_mut() {
+                trace.auto_step = false;
+            }
+        }
         while !continue_execution {
-            if let Some(sym) = name_resolution::resolve_address_to_name(thread_context.context.Rip, &mut process) {
+            let process_count = debuggees.len();
+            let Debuggee { process, memory_source: mem_source, process_handle: debuggee_handle, exe_address, entry_breakpoint, breakpoints, hardware_breakpoints, watchpoints, .. } = debuggees.get_mut(&current_process_id)
+                .unwrap_or_else(|| panic!("Missing process {current_process_id:#x}"));
+            let is_wow64 = process.is_wow64;
+            let mut switch_to_process = None;
+            let mut show_processes = false;
+            let mut remove_all = false;
+            let mut show_last_event = false;
+
+            let thread_name = match process_count {
+                1 => format_thread_name(current_thread_id, &thread),
+                _ => format!("{} in process {current_process_id:#x}", format_thread_name(current_thread_id, &thread)),
+            };
+            let title = if let Some(sym) = name_resolution::resolve_address_to_name(thread_context.context.instruction_pointer(), process) {
                 // Print the thread and symbol.
-                println!("Thread: {:#x} {sym}", event_context.thread);
+                println!("Thread: {thread_name} {}", output::symbol(&sym));
+                format!("Thread: {thread_name} {sym}")
             } else {
                 // Print the thread and instruction pointer.
-                println!("[Thread: {:#x}, IP: {:#018x}]", event_context.thread, thread_context.context.Rip);
-            }
-
-            let mut eval_expr = |expr: Box<EvalExpr>| -> Option<u64> {
-                let mut eval_context = eval::EvalContext{ process: &mut process };
-                let result = eval::evaluate_expression(*expr, &mut eval_context);
+                println!("[Thread: {thread_name}, IP: {}]", output::address(thread_context.context.instruction_pointer()));
+                format!("Thread: {thread_name}")
+            };
+            if command_reader.is_tui() {
+                // The panes show the source and disassembly.
+                let previous_context = thread_states.get(&(current_process_id, current_thread_id))
+                    .and_then(|thread_state| thread_state.previous_context.as_ref());
+                command_reader.set_stop_view(tui::StopView::new(title, &thread_context.context, previous_context, process, mem_source.as_ref(), is_wow64));
+            } else {
+                if let Some(source_line) = name_resolution::resolve_address_to_line(thread_context.context.instruction_pointer(), process) {
+                    println!("{source_line}");
+                }
+                if prompt_disassembly_count > 0 {
+                    disassembly::display_instructions(mem_source.as_ref(), thread_context.context.instruction_pointer(), prompt_disassembly_count, disassembly::get_bitness(is_wow64));
+                }
+            }
+
+            command_reader.update_completions(process);
+            let teb_address = windows_wrapper::get_thread_environment_block_address(&thread).ok();
+            // Stops a script at a command whose expression can't be evaluated.
+            let mut eval_failed = false;
+            let mut eval_expr = |expr: Box<EvalExpr>| -> Option<u64> {
+                let mut eval_context = eval::EvalContext{
+                    process,
+                    register_context: &thread_context.context,
+                    memory_source: mem_source.as_ref(),
+                    teb_address,
+                    last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                };
+                let result = eval::evaluate_expression(&expr, &mut eval_context);
                 match result {
                     Ok(val) => Some(val),
                     Err(e) => {
-                        println!("Could not evaluate expression: {e}");
+                        print_error!("Could not evaluate expression: {e}");
+                        eval_failed = true;
                         None
                     }
                 }
             };
 
-            match command::read_command() {
+            let command = command_reader.read_command(&messages);
+            match command {
                 CommandExpr::Help(_) | CommandExpr::HelpAlias(_) => {
                     command::print_command_help();
                 }
-                CommandExpr::Step(_) | CommandExpr::StepAlias(_) => {
-                    // Set the trap flag context, which will throw an EXCEPTION_SINGLE_STEP exception after executing the next instruction.
-                    thread_context.context.EFlags |= windows_wrapper::TRAP_FLAG;
-                    windows_wrapper::set_thread_context(&thread, &thread_context.context);
-
-                    let thread_state = thread_states.get_mut(&(event_context.process, event_context.thread))
-                        .unwrap_or_else(|| panic!("Cannot step because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = event_context.process, thread_id = event_context.thread));
-                    thread_state.expect_step_exception = true;
-                    continue_execution = true;
+                CommandExpr::BreakIn(_) | CommandExpr::BreakInAlias(_) => {
+                    println!("The target is already stopped");
+                }
+                CommandExpr::Step(_, count) | CommandExpr::StepAlias(_, count) => {
+                    let count = match count {
+                        Some(count) => eval_expr(count),
+                        None => Some(1),
+                    };
+                    if let Some(count) = count {
+                        let thread_state = thread_states.get_mut(&(current_process_id, current_thread_id))
+                            .unwrap_or_else(|| panic!("Cannot step because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = current_process_id, thread_id = current_thread_id));
+                        continue_execution = engine::run_step_command(StepKind::Into, count, thread_state, &thread, is_wow64, &mut thread_context.context, breakpoints, mem_source.as_ref());
+                    }
+                }
+                CommandExpr::Next(_, count) | CommandExpr::NextAlias(_, count) => {
+                    let count = match count {
+                        Some(count) => eval_expr(count),
+                        None => Some(1),
+                    };
+                    if let Some(count) = count {
+                        let thread_state = thread_states.get_mut(&(current_process_id, current_thread_id))
+                            .unwrap_or_else(|| panic!("Cannot step because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = current_process_id, thread_id = current_thread_id));
+                        continue_execution = engine::run_step_command(StepKind::Over, count, thread_state, &thread, is_wow64, &mut thread_context.context, breakpoints, mem_source.as_ref());
+                    }
+                }
+                CommandExpr::StepLine(_) | CommandExpr::StepLineAlias(_) | CommandExpr::NextLine(_) | CommandExpr::NextLineAlias(_) => {
+                    let step_kind = match command {
+                        CommandExpr::NextLine(_) | CommandExpr::NextLineAlias(_) => StepKind::Over,
+                        _ => StepKind::Into,
+                    };
+                    match name_resolution::resolve_address_to_line(thread_context.context.instruction_pointer(), process) {
+                        Some(source_line) => {
+                            let thread_state = thread_states.get_mut(&(current_process_id, current_thread_id))
+                                .unwrap_or_else(|| panic!("Cannot step because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = current_process_id, thread_id = current_thread_id));
+                            continue_execution = engine::run_step_command(step_kind, 1, thread_state, &thread, is_wow64, &mut thread_context.context, breakpoints, mem_source.as_ref());
+                            if continue_execution {
+                                thread_state.step_source_line = Some(source_line);
+                            }
+                        }
+                        None => print_error!("Cannot step by source line: no line information for {:#018x}", thread_context.context.instruction_pointer()),
+                    }
+                }
+                CommandExpr::Finish(_) | CommandExpr::FinishAlias(_) => {
+                    let thread_state = thread_states.get_mut(&(current_process_id, current_thread_id))
+                        .unwrap_or_else(|| panic!("Cannot step out because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = current_process_id, thread_id = current_thread_id));
+                    match stack::unwind_context(&thread_context.context, process, mem_source.as_ref()) {
+                        Ok(caller_context) => {
+                            let return_address = caller_context.instruction_pointer();
+                            if !breakpoints.contains_breakpoint(return_address) {
+                                if let Err(e) = breakpoints.add_temporary_breakpoint(return_address, mem_source.as_ref()) {
+                                    print_error!("Could not set a breakpoint at the return address: {e}");
+                                }
+                            }
+                            thread_state.begin_continue(&mut thread_context.context);
+                            engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                            continue_execution = true;
+                        }
+                        Err(e) => print_error!("Cannot step out: {e}"),
+                    }
+                }
+                CommandExpr::WatchTrace(_) | CommandExpr::WatchTraceAlias(_) => {
+                    let thread_state = thread_states.get_mut(&(current_process_id, current_thread_id))
+                        .unwrap_or_else(|| panic!("Cannot trace because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = current_process_id, thread_id = current_thread_id));
+                    let call_trace = CallTrace::new(thread_context.context.instruction_pointer(), None, false, process, mem_source.as_ref(), disassembly::get_bitness(is_wow64));
+                    continue_execution = engine::run_step_command(StepKind::Into, 1, thread_state, &thread, is_wow64, &mut thread_context.context, breakpoints, mem_source.as_ref());
+                    if continue_execution {
+                        println!("Tracing until the function returns...");
+                        thread_state.call_trace = Some(call_trace);
+                    }
+                }
+                CommandExpr::TraceCalls(_, depth) | CommandExpr::TraceCallsAlias(_, depth) => match eval_expr(depth) {
+                    Some(0) => print_error!("The depth must be at least 1"),
+                    Some(depth) => {
+                        let thread_state = thread_states.get_mut(&(current_process_id, current_thread_id))
+                            .unwrap_or_else(|| panic!("Cannot trace because missing thread state for process {process_id:#x}, thread {thread_id:#x}", process_id = current_process_id, thread_id = current_thread_id));
+                        let call_trace = CallTrace::new(thread_context.context.instruction_pointer(), Some(depth as usize), true, process, mem_source.as_ref(), disassembly::get_bitness(is_wow64));
+                        continue_execution = engine::run_step_command(StepKind::Into, 1, thread_state, &thread, is_wow64, &mut thread_context.context, breakpoints, mem_source.as_ref());
+                        if continue_execution {
+                            thread_state.call_trace = Some(call_trace);
+                        }
+                    }
+                    None => {}
+                },
+                CommandExpr::InstructionTrace(_, action) | CommandExpr::InstructionTraceAlias(_, action) => match action {
+                    TraceAction::Start(_, path, registers) => {
+                        if let Some(trace) = instruction_trace.take() {
+                            let memory_source = (trace.process_id == current_process_id).then_some(mem_source.as_ref());
+                            stop_instruction_trace(trace, &mut recording, memory_source);
+                        }
+                        let started = InstructionTrace::start(&path.path, current_process_id, current_thread_id, registers.is_some())
+                            .and_then(|mut trace| trace.record(&thread_context.context, process, mem_source.as_ref()).map(|()| trace));
+                        match started {
+                            Ok(trace) => {
+                                println!("Tracing thread {current_thread_id:#x} to {}. It is single-stepped whenever it runs, until `instruction-trace stop`.", path.path);
+                                instruction_trace = Some(trace);
+                            }
+                            Err(e) => print_error!("{e}"),
+                        }
+                    }
+                    TraceAction::Stop(_) => match instruction_trace.take() {
+                        Some(trace) => {
+                            let memory_source = (trace.process_id == current_process_id).then_some(mem_source.as_ref());
+                            stop_instruction_trace(trace, &mut recording, memory_source);
+                        }
+                        None => print_error!("Not tracing"),
+                    },
+                },
+                CommandExpr::ExecutionRecord(_, action) | CommandExpr::ExecutionRecordAlias(_, action) => match action {
+                    RecordAction::Start(_) => {
+                        if let Some(trace) = instruction_trace.take() {
+                            let memory_source = (trace.process_id == current_process_id).then_some(mem_source.as_ref());
+                            stop_instruction_trace(trace, &mut recording, memory_source);
+                        }
+                        let mut trace = InstructionTrace::start_recording(current_process_id, current_thread_id, Recording::new(is_wow64, teb_address));
+                        match trace.record(&thread_context.context, process, mem_source.as_ref()) {
+                            Ok(()) => {
+                                println!("Recording thread {current_thread_id:#x}. It is single-stepped whenever it runs, until `execution-record stop`.");
+                                instruction_trace = Some(trace);
+                            }
+                            Err(e) => print_error!("{e}"),
+                        }
+                    }
+                    RecordAction::Stop(_) => {
+                        if let Some(trace) = instruction_trace.take_if(|trace| trace.is_recording()) {
+                            let memory_source = (trace.process_id == current_process_id).then_some(mem_source.as_ref());
+                            stop_instruction_trace(trace, &mut recording, memory_source);
+                        } else {
+                            print_error!("Not recording");
+                        }
+                    }
+                },
+                CommandExpr::ExecutionReplay(_, action) | CommandExpr::ExecutionReplayAlias(_, action) => match recording.as_mut() {
+                    Some(recording) => run_replay_command(recording, action, process),
+                    None => print_error!("Nothing has been recorded. Use `execution-record start` to record."),
+                },
+                CommandExpr::BreakOnReturn(_) | CommandExpr::BreakOnReturnAlias(_) => {
+                    match stack::unwind_context(&thread_context.context, process, mem_source.as_ref()) {
+                        Ok(caller_context) => {
+                            let return_address = caller_context.instruction_pointer();
+                            match breakpoints.add_one_shot_breakpoint(return_address, current_thread_id, mem_source.as_ref()) {
+                                Ok(id) => {
+                                    let name = name_resolution::resolve_address_to_name(return_address, process).map_or_else(|| output::address(return_address), |name| output::symbol(&name));
+                                    println!("Breakpoint #{id} set at the return address {name}, for this thread only");
+                                }
+                                Err(e) => print_error!("Could not set a breakpoint at the return address: {e}"),
+                            }
+                        }
+                        Err(e) => print_error!("Cannot find the return address: {e}"),
+                    }
                 }
                 CommandExpr::Continue(_) | CommandExpr::ContinueAlias(_) => {
+                    if let Some(thread_state) = thread_states.get_mut(&(current_process_id, current_thread_id)) {
+                        thread_state.begin_continue(&mut thread_context.context);
+                        engine::set_thread_context(&thread, &thread_context.context, is_wow64);
+                    }
                     continue_execution = true;
                 }
-                CommandExpr::DisplayRegisters(_) | CommandExpr::DisplayRegistersAlias(_) => {
-                    registers::display_all(thread_context.context);
+                CommandExpr::SwitchThread(_, expr) | CommandExpr::SwitchThreadAlias(_, expr) => {
+                    if let Some(value) = eval_expr(expr) {
+                        match u32::try_from(value).map(ThreadId::from) {
+                            Ok(thread_id) if process.iterate_threads().any(|id| *id == thread_id) => {
+                                let opened = engine::get_thread_handle(&thread_states, current_process_id, thread_id).and_then(|new_thread| {
+                                    windows_wrapper::get_thread_context(&new_thread, is_wow64).map(|new_context| (new_thread, new_context))
+                                });
+                                match opened {
+                                    Ok((new_thread, new_context)) => {
+                                        thread = new_thread;
+                                        thread_context = new_context;
+                                        current_thread_id = thread_id;
+                                    }
+                                    Err(e) => print_error!("Could not switch to thread {thread_id:#x}: {e}"),
+                                }
+                            }
+                            _ => println!("No thread {value:#x} in the process"),
+                        }
+                    }
+                }
+                CommandExpr::SwitchProcess(_, expr) | CommandExpr::SwitchProcessAlias(_, expr) => {
+                    // The process is switched to after the command, once nothing is borrowed from the current one.
+                    switch_to_process = eval_expr(expr);
+                }
+                CommandExpr::ListProcesses(_) | CommandExpr::ListProcessesAlias(_) => {
+                    show_processes = true;
+                }
+                CommandExpr::FreezeThread(_, expr) | CommandExpr::FreezeThreadAlias(_, expr) => {
+                    if let Some(thread_id) = eval_expr(expr) {
+                        set_thread_frozen(&thread_states, current_process_id, process, thread_id, true);
+                    }
+                }
+                CommandExpr::ThawThread(_, expr) | CommandExpr::ThawThreadAlias(_, expr) => {
+                    if let Some(thread_id) = eval_expr(expr) {
+                        set_thread_frozen(&thread_states, current_process_id, process, thread_id, false);
+                    }
+                }
+                CommandExpr::ListThreads(_) | CommandExpr::ListThreadsAlias(_) => {
+                    list_threads(&thread_states, current_process_id, process, current_thread_id);
+                }
+                CommandExpr::Stack(_) | CommandExpr::StackAlias(_) => {
+                    stack::print_stack(&thread_context.context, process, mem_source.as_ref());
+                }
+                CommandExpr::StackAll(_) | CommandExpr::StackAllAlias(_) => {
+                    let thread_ids: Vec<ThreadId> = process.iterate_threads().copied().collect();
+                    for thread_id in thread_ids {
+                        println!("\nThread {thread_id:#x}");
+                        if thread_id == current_thread_id {
+                            // Use the cached context, which may have been modified while at the prompt.
+                            stack::print_stack(&thread_context.context, process, mem_source.as_ref());
+                        } else {
+                            let other_thread_context = engine::get_thread_handle(&thread_states, current_process_id, thread_id)
+                                .and_then(|other_thread| windows_wrapper::get_thread_context(&other_thread, is_wow64));
+                            match other_thread_context {
+                                Ok(other_thread_context) => stack::print_stack(&other_thread_context.context, process, mem_source.as_ref()),
+                                Err(e) => print_error!("{e}"),
+                            }
+                        }
+                    }
+                }
+                CommandExpr::StackDump(_) | CommandExpr::StackDumpAlias(_) => {
+                    let stack_limits = windows_wrapper::get_thread_environment_block_address(&thread).and_then(|teb_address| if is_wow64 {
+                        environment_blocks::get_wow64_stack_limits(teb_address, mem_source.as_ref())
+                    } else {
+                        environment_blocks::get_stack_limits(teb_address, mem_source.as_ref())
+                    });
+                    match stack_limits {
+                        Ok((_, stack_base)) => stack::print_stack_dump(&thread_context.context, stack_base, process, mem_source.as_ref()),
+                        Err(e) => print_error!("Could not find the stack: {e}"),
+                    }
+                }
+                CommandExpr::DisplayTeb(_) | CommandExpr::DisplayTebAlias(_) => {
+                    let result = windows_wrapper::get_thread_environment_block_address(&thread)
+                        .and_then(|teb_address| environment_blocks::display_teb(teb_address, mem_source.as_ref()));
+                    if let Err(e) = result {
+                        print_error!("Could not display the TEB: {e}");
+                    }
+                }
+                CommandExpr::DisplayPeb(_) | CommandExpr::DisplayPebAlias(_) => {
+                    let result = windows_wrapper::get_thread_environment_block_address(&thread)
+                        .and_then(|teb_address| environment_blocks::get_peb_address(teb_address, mem_source.as_ref()))
+                        .and_then(|peb_address| environment_blocks::display_peb(peb_address, mem_source.as_ref()));
+                    if let Err(e) = result {
+                        print_error!("Could not display the PEB: {e}");
+                    }
+                }
+                CommandExpr::DisplayCommandLine(_) | CommandExpr::DisplayCommandLineAlias(_) => {
+                    let result = windows_wrapper::get_thread_environment_block_address(&thread)
+                        .and_then(|teb_address| environment_blocks::get_peb_address(teb_address, mem_source.as_ref()))
+                        .and_then(|peb_address| environment_blocks::display_command_line(peb_address, mem_source.as_ref()));
+                    if let Err(e) = result {
+                        print_error!("Could not display the command line: {e}");
+                    }
+                }
+                CommandExpr::DisplayEnvironment(_) | CommandExpr::DisplayEnvironmentAlias(_) => {
+                    let result = windows_wrapper::get_thread_environment_block_address(&thread)
+                        .and_then(|teb_address| environment_blocks::get_peb_address(teb_address, mem_source.as_ref()))
+                        .and_then(|peb_address| environment_blocks::display_environment(peb_address, mem_source.as_ref()));
+                    if let Err(e) = result {
+                        print_error!("Could not display the environment: {e}");
+                    }
+                }
+                CommandExpr::Heaps(_, expr) | CommandExpr::HeapsAlias(_, expr) => {
+                    let heap_address = match expr {
+                        Some(expr) => eval_expr(expr).map(Some),
+                        None => Some(None),
+                    };
+                    if let Some(heap_address) = heap_address {
+                        let result = windows_wrapper::get_thread_environment_block_address(&thread)
+                            .and_then(|teb_address| environment_blocks::get_peb_address(teb_address, mem_source.as_ref()))
+                            .and_then(|peb_address| heaps::display_heaps(peb_address, heap_address, is_wow64, mem_source.as_ref()));
+                        if let Err(e) = result {
+                            print_error!("Could not display the heaps: {e}");
+                        }
+                    }
+                }
+                CommandExpr::MemoryMap(_, expr) | CommandExpr::MemoryMapAlias(_, expr) => {
+                    match expr {
+                        Some(expr) => {
+                            if let Some(address) = eval_expr(expr) {
+                                memory_map::display_memory_region(*debuggee_handle, address, process, mem_source.as_ref());
+                            }
+                        }
+                        None => memory_map::display_memory_map(*debuggee_handle, process, mem_source.as_ref()),
+                    }
+                }
+                CommandExpr::Allocate(_, size, protection) | CommandExpr::AllocateAlias(_, size, protection) => {
+                    if let Some(size) = eval_expr(size) {
+                        let result = protection.map(|protection| windows_wrapper::parse_page_protection(&protection.name)).transpose()
+                            .and_then(|protection| windows_wrapper::allocate_memory(*debuggee_handle, size, protection));
+                        match result {
+                            Ok(address) => {
+                                println!("Allocated {size:#x} bytes at {} ($alloc)", output::address(address));
+                                process.last_allocation = Some(address);
+                            }
+                            Err(e) => print_error!("{e}"),
+                        }
+                    }
                 }
-                CommandExpr::DisplayBytes(_, expr) | CommandExpr::DisplayBytesAlias(_, expr) => {
+                CommandExpr::SetProtection(_, address, size, protection) | CommandExpr::SetProtectionAlias(_, address, size, protection) => {
+                    if let (Some(address), Some(size)) = (eval_expr(address), eval_expr(size)) {
+                        let result = windows_wrapper::parse_page_protection(&protection.name)
+                            .and_then(|protection| windows_wrapper::set_memory_protection(*debuggee_handle, address, size, protection).map(|old_protection| (protection, old_protection)));
+                        match result {
+                            Ok((protection, old_protection)) => println!(
+                                "Changed the protection at {} from {} to {}",
+                                output::address(address),
+                                windows_wrapper::format_page_protection(old_protection),
+                                windows_wrapper::format_page_protection(protection),
+                            ),
+                            Err(e) => print_error!("{e}"),
+                        }
+                    }
+                }
+                CommandExpr::FindReferences(_, expr, range) | CommandExpr::FindReferencesAlias(_, expr, range) => {
+                    let target = eval_expr(expr);
+                    let range = match range {
+                        Some(range) => match (eval_expr(range.start), eval_expr(range.size)) {
+                            (Some(start), Some(size)) => Some(Some((start, size))),
+                            _ => None,
+                        },
+                        None => Some(None),
+                    };
+                    if let (Some(target), Some(range)) = (target, range) {
+                        memory_map::display_references(target, range, *debuggee_handle, process, mem_source.as_ref());
+                    }
+                }
+                CommandExpr::Handles(_, expr) | CommandExpr::HandlesAlias(_, expr) => {
+                    match expr {
+                        Some(expr) => {
+                            if let Some(value) = eval_expr(expr) {
+                                handles::display_handle(*debuggee_handle, value);
+                            }
+                        }
+                        None => handles::display_handles(*debuggee_handle),
+                    }
+                }
+                CommandExpr::DisplayRegisters(_, register_set) | CommandExpr::DisplayRegistersAlias(_, register_set) => {
+                    let previous_context = thread_states.get(&(current_process_id, current_thread_id))
+                        .and_then(|thread_state| thread_state.previous_context.as_ref());
+                    display_registers(thread_context.context, previous_context, register_set, is_wow64);
+                }
+                CommandExpr::SetRegister(_, assignment) | CommandExpr::SetRegisterAlias(_, assignment) => {
+                    if let Some(value) = eval_expr(assignment.value) {
+                        let result = thread_context.context.set_register(&assignment.register, value)
+                            .and_then(|()| windows_wrapper::set_thread_context(&thread, &thread_context.context, is_wow64));
+                        if let Err(e) = result {
+                            print_error!("Could not set register: {e}");
+                        }
+                    }
+                }
+                CommandExpr::Assemble(_, expr) | CommandExpr::AssembleAlias(_, expr) => {
                     if let Some(address) = eval_expr(expr) {
-                        let bytes = mem_source.read_raw_memory(address, 16);
-                        for byte in bytes {
-                            print!("{byte:02X} ");
+                        let bitness = disassembly::get_bitness(is_wow64);
+                        assembler::run_assemble_prompt(address, bitness, breakpoints, mem_source.as_ref(), &mut |prompt| {
+                            command_reader.read_line(prompt, &messages)
+                        });
+                    }
+                }
+                CommandExpr::SymbolCache(_, directory) | CommandExpr::SymbolCacheAlias(_, directory) => {
+                    if let Some(directory) = directory {
+                        symbols::set_cache_directory(std::path::PathBuf::from(directory.value));
+                    }
+                    println!("Symbol cache: {}", symbols::get_cache_directory().display());
+                }
+                CommandExpr::ReloadSymbols(_, module_name) | CommandExpr::ReloadSymbolsAlias(_, module_name) => {
+                    reload_symbols(module_name.as_ref().map(|module_name| module_name.name.as_str()), process);
+                }
+                CommandExpr::ModuleDetails(_, module_name) | CommandExpr::ModuleDetailsAlias(_, module_name) => {
+                    display_module_details(&module_name.name, process, mem_source.as_ref());
+                }
+                CommandExpr::TlsCallbacks(_, module_name, action) | CommandExpr::TlsCallbacksAlias(_, module_name, action) => {
+                    let breakpoints = action.map(|TlsCallbacksAction::Break(_)| &mut *breakpoints);
+                    display_tls_callbacks(&module_name.name, breakpoints, process, mem_source.as_ref());
+                }
+                CommandExpr::TraceModule(_, module_name) | CommandExpr::TraceModuleAlias(_, module_name) => {
+                    trace_module_exports(&module_name.name, breakpoints, process, *debuggee_handle, mem_source.as_ref());
+                }
+                CommandExpr::PromptDisassembly(_, expr) | CommandExpr::PromptDisassemblyAlias(_, expr) => {
+                    if let Some(count) = eval_expr(expr) {
+                        // Limit it arbitrarily so that a typo doesn't flood the console at every prompt.
+                        const MAX_PROMPT_DISASSEMBLY_COUNT: u64 = 50;
+                        if count > MAX_PROMPT_DISASSEMBLY_COUNT {
+                            println!("At most {MAX_PROMPT_DISASSEMBLY_COUNT} instructions can be shown at each prompt");
+                        } else {
+                            prompt_disassembly_count = count as usize;
                         }
-                        println!();
+                    }
+                }
+                CommandExpr::DisplayBytes(..) | CommandExpr::DisplayBytesAlias(..)
+                | CommandExpr::DisplayWords(..) | CommandExpr::DisplayWordsAlias(..)
+                | CommandExpr::DisplayDwords(..) | CommandExpr::DisplayDwordsAlias(..)
+                | CommandExpr::DisplayQwords(..) | CommandExpr::DisplayQwordsAlias(..)
+                | CommandExpr::DisplayPointers(..) | CommandExpr::DisplayPointersAlias(..)
+                | CommandExpr::DisplayPointersWithSymbols(..) | CommandExpr::DisplayPointersWithSymbolsAlias(..)
+                | CommandExpr::DisplayAscii(..) | CommandExpr::DisplayAsciiAlias(..)
+                | CommandExpr::DisplayUnicode(..) | CommandExpr::DisplayUnicodeAlias(..) => {
+                    if let Some((format, expr, count)) = get_display_memory_args(command) {
+                        let mut eval_context = eval::EvalContext{
+                            process,
+                            register_context: &thread_context.context,
+                            memory_source: mem_source.as_ref(),
+                            teb_address,
+                            last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                        };
+                        run_display_memory_command(format, &expr, count.as_deref(), &mut eval_context);
                     }
                 }
                 CommandExpr::Evaluate(_, expr) | CommandExpr::EvaluateAlias(_, expr) => {
@@ -199,51 +1614,707 @@ fn main_debugger_loop(process_handle: AutoClosedHandle) {
                 }
                 CommandExpr::ListNearest(_, expr) | CommandExpr::ListNearestAlias(_, expr) => {
                     if let Some(val) = eval_expr(expr) {
-                        if let Some(sym) = name_resolution::resolve_address_to_name(val, &mut process) {
-                            println!("{sym}");
+                        if let Some(sym) = name_resolution::resolve_address_to_name(val, process) {
+                            println!("{}", output::symbol(&sym));
                         } else {
                             println!("No symbol found");
                         }
                     }
                 }
-                CommandExpr::AddBreakpoint(_, expr) | CommandExpr::AddBreakpointAlias(_, expr) => {
+                CommandExpr::ExamineSymbols(_, pattern) | CommandExpr::ExamineSymbolsAlias(_, pattern) => {
+                    examine_symbols(&pattern.pattern, process);
+                }
+                CommandExpr::DisplayLocals(_) | CommandExpr::DisplayLocalsAlias(_) => {
+                    locals::display_locals(&thread_context.context, process, mem_source.as_ref());
+                }
+                CommandExpr::DisplayType(_, type_name, expr) | CommandExpr::DisplayTypeAlias(_, type_name, expr) => {
+                    match expr.map(&mut eval_expr) {
+                        // The expression failed to evaluate, and the error was already printed.
+                        Some(None) => {}
+                        address => {
+                            if let Err(e) = types::display_type(&type_name.name, address.flatten(), process, mem_source.as_ref()) {
+                                print_error!("{e}");
+                            }
+                        }
+                    }
+                }
+                CommandExpr::AddBreakpoint(_, expr, condition, log) | CommandExpr::AddBreakpointAlias(_, expr, condition, log) => {
+                    let condition = condition.map(|condition| *condition.condition);
+                    let log_message = log.map(|log| log.message);
+                    let mut eval_context = eval::EvalContext{
+                        process,
+                        register_context: &thread_context.context,
+                        memory_source: mem_source.as_ref(),
+                        teb_address,
+                        last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                    };
+                    match eval::evaluate_expression(&expr, &mut eval_context) {
+                        Ok(addr) => {
+                            // A plain address is already shown, so only remember symbols and expressions.
+                            let location = (!matches!(*expr, EvalExpr::Number(_))).then(|| expr.to_string());
+                            if let Err(e) = breakpoints.add_breakpoint(addr, location, condition, log_message, mem_source.as_ref()) {
+                                print_error!("Could not add breakpoint: {e}");
+                            }
+                        }
+                        Err(e) => match *expr {
+                            // Allow setting breakpoints in modules that will be loaded later.
+                            EvalExpr::Symbol(symbol) if name_resolution::is_symbol_in_unloaded_module(&symbol, process) => {
+                                println!("Module not loaded yet. The breakpoint will be set when it is loaded.");
+                                breakpoints.add_deferred_breakpoint(symbol, condition, log_message);
+                            }
+                            _ => print_error!("Could not evaluate expression: {e}"),
+                        }
+                    }
+                }
+                CommandExpr::RemoveBreakpoint(_, expr) | CommandExpr::RemoveBreakpointAlias(_, expr) | CommandExpr::ClearBreakpointAlias(_, expr) => {
+                    // Ids are written `#<id>`, so that they can't be mistaken for addresses. The grammar lexes them as symbols,
+                    // since symbols can contain `#`.
+                    let id = match &*expr {
+                        EvalExpr::Symbol(symbol) => symbol.strip_prefix('#').and_then(|id| id.parse::<u32>().ok()),
+                        _ => None,
+                    };
+                    if let Some(id) = id {
+                        match breakpoints.remove_breakpoint_by_id(id, mem_source.as_ref()) {
+                            Ok(Some(addr)) => {
+                                scripting.remove_callbacks(addr);
+                                let name = name_resolution::resolve_address_to_name(addr, process).map_or_else(|| output::address(addr), |name| output::symbol(&name));
+                                println!("Removed breakpoint #{id} at {name}");
+                            }
+                            Ok(None) => println!("Removed deferred breakpoint #{id}"),
+                            Err(e) => print_error!("Could not remove breakpoint: {e}"),
+                        }
+                    } else {
+                        let mut eval_context = eval::EvalContext{
+                            process,
+                            register_context: &thread_context.context,
+                            memory_source: mem_source.as_ref(),
+                            teb_address,
+                            last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                        };
+                        match eval::evaluate_expression(&expr, &mut eval_context) {
+                            Ok(addr) => match breakpoints.find_id(addr) {
+                                Some(id) => match breakpoints.remove_breakpoint(addr, mem_source.as_ref()) {
+                                    Ok(()) => {
+                                        scripting.remove_callbacks(addr);
+                                        let name = name_resolution::resolve_address_to_name(addr, process).map_or_else(|| output::address(addr), |name| output::symbol(&name));
+                                        println!("Removed breakpoint #{id} at {name}");
+                                    }
+                                    Err(e) => print_error!("Could not remove breakpoint: {e}"),
+                                },
+                                None => print_error!("No breakpoint at {addr:#018x}"),
+                            },
+                            Err(e) => match *expr {
+                                EvalExpr::Symbol(symbol) => match breakpoints.remove_deferred_breakpoint(&symbol) {
+                                    Some(id) => println!("Removed deferred breakpoint #{id} on {symbol}"),
+                                    None => print_error!("Could not evaluate expression: {e}"),
+                                },
+                                _ => print_error!("Could not evaluate expression: {e}"),
+                            }
+                        }
+                    }
+                }
+                CommandExpr::RemoveAllBreakpoints(..) | CommandExpr::RemoveAllBreakpointsAlias(..) | CommandExpr::ClearAllBreakpointsAlias(..) => {
+                    // The breakpoints of every process are removed after the command, once nothing is borrowed from the
+                    // current one.
+                    remove_all = true;
+                }
+                CommandExpr::ListBreakpoint(_) | CommandExpr::ListBreakpointAlias(_) => {
+                    breakpoints.list_breakpoints(process);
+                    hardware_breakpoints.list_breakpoints(process);
+                    watchpoints.list_watchpoints(process);
+                }
+                CommandExpr::AddHardwareBreakpoint(_, expr) | CommandExpr::AddHardwareBreakpointAlias(_, expr) => {
                     if let Some(addr) = eval_expr(expr) {
-                        breakpoints.add_breakpoint(addr);
+                        match hardware_breakpoints.add_breakpoint(addr) {
+                            Ok(()) => {
+                                engine::apply_hardware_breakpoints(hardware_breakpoints, &thread_states, current_process_id, process, current_thread_id, &thread, &mut thread_context.context);
+                            }
+                            Err(e) => print_error!("Could not add hardware breakpoint: {e}"),
+                        }
                     }
                 }
-                CommandExpr::RemoveBreakpoint(_, expr) | CommandExpr::RemoveBreakpointAlias(_, expr) => {
+                CommandExpr::RemoveHardwareBreakpoint(_, expr) | CommandExpr::RemoveHardwareBreakpointAlias(_, expr) => {
                     if let Some(addr) = eval_expr(expr) {
-                        breakpoints.remove_breakpoint(addr);
+                        match hardware_breakpoints.remove_breakpoint(addr) {
+                            Ok(()) => {
+                                engine::apply_hardware_breakpoints(hardware_breakpoints, &thread_states, current_process_id, process, current_thread_id, &thread, &mut thread_context.context);
+                            }
+                            Err(e) => print_error!("Could not remove hardware breakpoint: {e}"),
+                        }
                     }
                 }
-                CommandExpr::ListBreakpoint(_) | CommandExpr::ListBreakpointAlias(_) => {
-                    breakpoints.list_breakpoints(&mut process);
+                CommandExpr::AddMemoryWatchpoint(_, address, size, access) | CommandExpr::AddMemoryWatchpointAlias(_, address, size, access) => {
+                    if let (Some(address), Some(size)) = (eval_expr(address), eval_expr(size)) {
+                        let access = match access {
+                            Some(WatchAccessName::Any(_)) => WatchAccess::Any,
+                            Some(WatchAccessName::Write(_)) | None => WatchAccess::Write,
+                        };
+                        if let Err(e) = watchpoints.add_watchpoint(*debuggee_handle, address, size, access) {
+                            print_error!("Could not add memory watchpoint: {e}");
+                        }
+                    }
+                }
+                CommandExpr::RemoveMemoryWatchpoint(_, expr) | CommandExpr::RemoveMemoryWatchpointAlias(_, expr) => {
+                    if let Some(address) = eval_expr(expr) {
+                        if let Err(e) = watchpoints.remove_watchpoint(*debuggee_handle, address) {
+                            print_error!("Could not remove memory watchpoint: {e}");
+                        }
+                    }
                 }
+                CommandExpr::DisplayExceptionRecord(_) | CommandExpr::DisplayExceptionRecordAlias(_) => {
+                    display_exception_record(last_exception.as_ref(), process);
+                }
+                CommandExpr::DisplayLastEvent(_) | CommandExpr::DisplayLastEventAlias(_) => show_last_event = true,
+                CommandExpr::SessionStats(_) | CommandExpr::SessionStatsAlias(_) => {
+                    session_stats.display();
+                }
+                CommandExpr::EventHistory(_, count) | CommandExpr::EventHistoryAlias(_, count) => {
+                    let count = match count {
+                        Some(count) => eval_expr(count).map(|count| count as usize),
+                        None => Some(event_history::DEFAULT_HISTORY_COUNT),
+                    };
+                    if let Some(count) = count {
+                        event_history.display(count);
+                    }
+                }
+                CommandExpr::BreakAtEntry(_) | CommandExpr::BreakAtEntryAlias(_) => {
+                    match engine::add_entry_breakpoint(process, *exe_address, breakpoints, mem_source.as_ref()) {
+                        Ok(address) => {
+                            *entry_breakpoint = Some(address);
+                            let name = name_resolution::resolve_address_to_name(address, process).map_or_else(|| output::address(address), |name| output::symbol(&name));
+                            println!("Will stop at {name}");
+                        }
+                        Err(e) => print_error!("{e}"),
+                    }
+                }
+                CommandExpr::BreakOnException(_, expr) | CommandExpr::BreakOnExceptionAlias(_, expr) => {
+                    set_exception_policy(&mut event_filters, eval_expr(expr), ExceptionPolicy::Break);
+                }
+                CommandExpr::BreakOnSecondChanceException(_, expr) | CommandExpr::BreakOnSecondChanceExceptionAlias(_, expr) => {
+                    set_exception_policy(&mut event_filters, eval_expr(expr), ExceptionPolicy::SecondChance);
+                }
+                CommandExpr::IgnoreException(_, expr) | CommandExpr::IgnoreExceptionAlias(_, expr) => {
+                    set_exception_policy(&mut event_filters, eval_expr(expr), ExceptionPolicy::Ignore);
+                }
+                CommandExpr::BreakOnModuleLoad(_, pattern) | CommandExpr::BreakOnModuleLoadAlias(_, _, pattern) => {
+                    event_filters.add_module_load_pattern(&pattern.pattern);
+                    println!("Will stop when a module matching {} is loaded", pattern.pattern);
+                }
+                CommandExpr::IgnoreModuleLoad(_, pattern) | CommandExpr::IgnoreModuleLoadAlias(_, _, pattern) => {
+                    if !event_filters.remove_module_load_pattern(&pattern.pattern) {
+                        print_error!("No module-load-break for {}", pattern.pattern);
+                    }
+                }
+                CommandExpr::SetEventPolicy(_, policy, event) | CommandExpr::SetEventPolicyAlias(_, policy, event) => {
+                    let (event, policy) = (get_event_kind(&event), get_event_policy(&policy));
+                    event_filters.set_event_policy(event, policy);
+                    println!("{event}: {policy}");
+                }
+                CommandExpr::ListExceptionPolicies(_) | CommandExpr::ListExceptionPoliciesAlias(_) => {
+                    event_filters.list();
+                }
+                CommandExpr::DebugOutput(_, command) | CommandExpr::DebugOutputAlias(_, command) => {
+                    let result = match command {
+                        None => {
+                            debug_output.list();
+                            Ok(())
+                        }
+                        Some(DebugOutputCommand::Filter(_, pattern)) => debug_output.add_filter(&pattern.value),
+                        Some(DebugOutputCommand::Suppress(_, pattern)) => debug_output.add_suppression(&pattern.value),
+                        Some(DebugOutputCommand::Highlight(_, pattern)) => debug_output.add_highlight(&pattern.value),
+                        Some(DebugOutputCommand::Clear(_)) => {
+                            debug_output.clear();
+                            Ok(())
+                        }
+                        Some(DebugOutputCommand::History(_, count)) => {
+                            let count = match count {
+                                Some(count) => eval_expr(count).map(|count| count as usize),
+                                None => Some(debug_output::DEFAULT_HISTORY_COUNT),
+                            };
+                            if let Some(count) = count {
+                                debug_output.display_history(count);
+                            }
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = result {
+                        print_error!("{e}");
+                    }
+                }
+                CommandExpr::RunScript(_, path) | CommandExpr::RunScriptAlias(_, path) => {
+                    if let Err(e) = command_reader.run_script(&path.path) {
+                        print_error!("{e}");
+                    }
+                }
+                CommandExpr::RunRhaiScript(_, path) | CommandExpr::RunRhaiScriptAlias(_, path) => {
+                    let target = ScriptTarget::new(&thread_context.context, memory::make_live_memory_source(*debuggee_handle), process);
+                    match scripting.run_file(&path.path, target) {
+                        Ok(pending_callbacks) => {
+                            for pending in pending_callbacks {
+                                let location = pending.location.clone();
+                                match add_script_breakpoint(pending, &mut scripting, breakpoints, process, mem_source.as_ref()) {
+                                    Ok(address) => println!("Added a breakpoint at {} for the script", output::address(address)),
+                                    Err(e) => print_error!("Could not add a breakpoint at {location} for the script: {e}"),
+                                }
+                            }
+                        }
+                        Err(e) => print_error!("{e}"),
+                    }
+                }
+                #[cfg(feature = "python")]
+                CommandExpr::RunPythonScript(_, path) | CommandExpr::RunPythonScriptAlias(_, path) => {
+                    let target = python::PythonTarget {
+                        process,
+                        breakpoints,
+                        context: &thread_context.context,
+                        memory_source: memory::make_live_memory_source(*debuggee_handle),
+                        teb_address,
+                        last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                    };
+                    if let Err(e) = python::run_file(&path.path, target) {
+                        print_error!("{e}");
+                    }
+                }
+                #[cfg(not(feature = "python"))]
+                CommandExpr::RunPythonScript(_, _) | CommandExpr::RunPythonScriptAlias(_, _) => {
+                    print_error!("Python scripts aren't supported by this build. Rebuild with `--features python`.");
+                }
+                CommandExpr::OpenLog(_, path) | CommandExpr::OpenLogAlias(_, path) => open_log(&path.path),
+                CommandExpr::CloseLog(_) | CommandExpr::CloseLogAlias(_) => close_log(),
+                CommandExpr::LogLevel(_, level) | CommandExpr::LogLevelAlias(_, level) => set_log_level(level),
                 CommandExpr::Quit(_) | CommandExpr::QuitAlias(_) => {
                     // The process will be terminated since we didn't detach.
                     return;
                 }
             }
+            if eval_failed {
+                command_reader.stop_script();
+            }
+
+            if show_processes {
+                list_processes(&debuggees, Some(event_context.process), Some(current_process_id));
+            }
+            if remove_all {
+                remove_all_breakpoints(&mut debuggees, &mut scripting, &thread_states, current_process_id, current_thread_id, &thread, &mut thread_context.context);
+            }
+            // The event may be from a process other than the current one, whose modules are needed to describe it.
+            if show_last_event {
+                if let Some(debuggee) = debuggees.get_mut(&last_event.process_id) {
+                    display_last_event(&last_event, &mut debuggee.process);
+                }
+            }
+            if let Some(value) = switch_to_process {
+                let debuggee = u32::try_from(value).ok().map(ProcessId::from)
+                    .and_then(|process_id| debuggees.get(&process_id).map(|debuggee| (process_id, debuggee)));
+                match debuggee {
+                    Some((process_id, debuggee)) => {
+                        match open_process_thread(process_id, debuggee, &thread_states, event_context.process, event_context.thread) {
+                            Ok((thread_id, new_thread, new_context)) => {
+                                current_process_id = process_id;
+                                current_thread_id = thread_id;
+                                thread = new_thread;
+                                thread_context = new_context;
+                            }
+                            Err(e) => print_error!("Could not switch to process {process_id:#x}: {e}"),
+                        }
+                    }
+                    None => println!("Not debugging a process {value:#x}"),
+                }
+            }
+        }
+        if let Some(thread_state) = thread_states.get_mut(&(current_process_id, current_thread_id)) {
+            thread_state.previous_context = Some(thread_context.context);
+        }
+
+        if process_exited {
+            // Unregister the process.
+            debuggees.remove(&event_context.process);
+            thread_states.retain(|(process_id, _), _| *process_id != event_context.process);
+
+            // Exit the debug loop once there are no more processes to debug.
+            if debuggees.is_empty() {
+                break;
+            }
+        }
+
+        // The traced thread runs by single-stepping, so that every instruction is recorded.
+        if let Some(trace) = instruction_trace.as_mut() {
+            match thread_states.get_mut(&(trace.process_id, trace.thread_id)) {
+                Some(thread_state) if !thread_state.expect_step_exception => {
+                    let is_wow64 = debuggees.get(&trace.process_id).is_some_and(|debuggee| debuggee.process.is_wow64);
+                    match windows_wrapper::get_thread_context(&thread_state.handle, is_wow64) {
+                        Ok(mut thread_context) => {
+                            thread_state.begin_step(&mut thread_context.context);
+                            engine::set_thread_context(&thread_state.handle, &thread_context.context, is_wow64);
+                            trace.auto_step = true;
+                        }
+                        Err(e) => print_error!("Could not step the traced thread: {e}"),
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    println!("The traced thread exited");
+                    let memory_source = debuggees.get(&trace.process_id).map(|debuggee| debuggee.memory_source.as_ref());
+                    stop_instruction_trace(instruction_trace.take().unwrap(), &mut recording, memory_source);
+                }
+            }
+        }
+        for (process_id, debuggee) in debuggees.iter() {
+            engine::begin_continue_stopped_threads(&mut thread_states, *process_id, debuggee.process.is_wow64);
+        }
+
+        // Continuing lets every process run, which can change any of their memory.
+        for debuggee in debuggees.values() {
+            debuggee.memory_source.invalidate_cache();
+        }
+
+        session_stats.record_continue();
+        event_thread.continue_event(event_context, continue_status);
+    }
+}
+
+fn launch_and_debug_process(target_command_line_args: Vec<String>, options: LaunchOptions, session: &SessionOptions) {
+    main_debugger_loop(move |messages| {
+        let launched = match windows_wrapper::launch_process_for_debugging(&target_command_line_args, &options) {
+            Ok(launched) => launched,
+            Err(e) => {
+                print_error!("{e}");
+                return None;
+            }
+        };
+        if let Some(thread) = &launched.suspended_thread {
+            // Nothing in the target has run yet, which gives the user a chance to set up external tools against it.
+            let prompt = format!("Process {process_id} ({process_id:#x}) was started suspended. Press enter to resume it.", process_id = launched.process_id);
+            if event_loop::prompt(messages, &prompt).is_none() {
+                // The process is killed when the debugger exits.
+                println!("Not resuming the process");
+                return None;
+            }
+            if let Err(e) = windows_wrapper::resume_thread(thread) {
+                print_error!("Could not resume the process: {e}");
+                return None;
+            }
+        }
+        Some(launched.process)
+    }, session);
+}
+
+/// Asks the user to pick one of several processes. Returns `None` if they cancel. See `event_loop::prompt`.
+fn choose_process(processes: &[ProcessEntry], messages: &Sender<Message>) -> Option<ProcessId> {
+    println!("Multiple processes match:");
+    for (index, process) in processes.iter().enumerate() {
+        println!("  [{index}] {name} process {id} ({id:#x}), parent {parent_id} ({parent_id:#x})", name = process.name, id = process.id, parent_id = process.parent_id);
+    }
+
+    let prompt = format!("Choose a process [0-{max_index}], or press enter to cancel: ", max_index = processes.len() - 1);
+    loop {
+        let input = event_loop::prompt(messages, &prompt)?;
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
         }
 
-        windows_wrapper::continue_debug_event(event_context, continue_status);
+        match input.parse::<usize>() {
+            Ok(index) if index < processes.len() => return Some(processes[index].id),
+            _ => println!("Invalid choice: {input}"),
+        }
     }
 }
 
-fn launch_and_debug_process(target_command_line_args: &[String]) {
-    let process = windows_wrapper::launch_process_for_debugging(target_command_line_args);
-    main_debugger_loop(process);
+fn attach_by_name_and_debug_process(process_name: &str, session: &SessionOptions) {
+    let processes = match windows_wrapper::enumerate_processes() {
+        Ok(processes) => processes,
+        Err(e) => {
+            print_error!("{e}");
+            return;
+        }
+    };
+    let matches: Vec<ProcessEntry> = processes
+        .into_iter()
+        .filter(|process| process.name.eq_ignore_ascii_case(process_name))
+        .collect();
+
+    if matches.is_empty() {
+        println!("No process named {process_name} is running");
+        return;
+    }
+
+    let process_name = process_name.to_string();
+    // The choice is read on the debug event thread, which must be the one that attaches.
+    main_debugger_loop(move |messages| {
+        let process_id = match matches.len() {
+            1 => matches[0].id,
+            _ => choose_process(&matches, messages)?,
+        };
+        println!("Attaching to {process_name} (process {process_id})\n");
+        windows_wrapper::attach_to_process(process_id)
+            .map_err(|e| print_error!("{e}"))
+            .ok()
+    }, session);
+}
+
+/// Debugs a minidump file. Only commands that inspect the target are available, since it can't run.
+fn debug_dump(dump_path: &str, options: &SessionOptions) {
+    let dump = match minidump::load_minidump(dump_path) {
+        Ok(dump) => dump,
+        Err(e) => {
+            print_error!("Could not load the dump: {e}");
+            return;
+        }
+    };
+    println!("Debugging dump {dump_path}\n");
+    // There are no debug events, so the messages are just the lines that are typed.
+    let (message_sender, messages) = mpsc::channel();
+    let mut command_reader = create_command_reader(options, message_sender);
+
+    let memory_source = dump.memory_source.as_ref();
+    let mut process = Process::new(false);
+    for module in dump.modules.iter() {
+        // Small dumps don't include the images, so their exports and PDBs can't be found.
+        if memory_source.read_raw_memory(module.base_address, 2).len() < 2 {
+            println!("Module not in dump: {base_address:#x} {size:#x} {name}", base_address = module.base_address, size = module.size, name = module.name);
+            continue;
+        }
+        match process.add_module(module.base_address, Some(module.name.clone()), memory_source) {
+            Ok(_) => println!("LoadModule: {base_address:#x}   {name}", base_address = module.base_address, name = module.name),
+            Err(e) => print_error!("Could not load module {name}: {e}", name = module.name),
+        }
+    }
+    for thread in dump.threads.iter() {
+        process.add_thread(thread.id, 0);
+    }
+
+    // The threads' contexts are 64-bit, so the process is debugged as a 64-bit one, but the 32-bit stacks and heaps
+    // aren't available.
+    let is_wow64 = dump.is_wow64();
+    if is_wow64 {
+        println!("The dump is of a WOW64 process. Only its threads' 64-bit contexts and stacks are available.");
+    }
+
+    let mut current_thread_index = 0;
+    let last_exception = dump.exception.as_ref().map(|exception| &exception.record);
+    if let Some(exception) = &dump.exception {
+        println!("Exception code {code:#x} at {address:#018x} on thread {thread:#x}", code = exception.record.code.0, address = exception.record.address, thread = exception.thread);
+        current_thread_index = dump.threads.iter().position(|thread| thread.id == exception.thread).unwrap_or(0);
+    }
+    if dump.threads.is_empty() {
+        println!("The dump doesn't contain any threads");
+        return;
+    }
+
+    loop {
+        let current_thread = &dump.threads[current_thread_index];
+        let context = &current_thread.context;
+        let title = if let Some(sym) = name_resolution::resolve_address_to_name(context.instruction_pointer(), &mut process) {
+            println!("Thread: {:#x} {}", current_thread.id, output::symbol(&sym));
+            format!("Thread: {:#x} {sym}", current_thread.id)
+        } else {
+            println!("[Thread: {:#x}, IP: {}]", current_thread.id, output::address(context.instruction_pointer()));
+            format!("Thread: {:#x}", current_thread.id)
+        };
+        if command_reader.is_tui() {
+            command_reader.set_stop_view(tui::StopView::new(title, context, None, &mut process, memory_source, false));
+        } else {
+            disassembly::display_instructions(memory_source, context.instruction_pointer(), 1, disassembly::get_bitness(false));
+        }
+
+        command_reader.update_completions(&process);
+        // Stops a script at a command whose expression can't be evaluated.
+        let mut eval_failed = false;
+        let mut eval_expr = |expr: Box<EvalExpr>| -> Option<u64> {
+            let mut eval_context = eval::EvalContext{
+                process: &mut process,
+                register_context: context,
+                memory_source,
+                teb_address: Some(current_thread.teb),
+                last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+            };
+            match eval::evaluate_expression(&expr, &mut eval_context) {
+                Ok(val) => Some(val),
+                Err(e) => {
+                    print_error!("Could not evaluate expression: {e}");
+                    eval_failed = true;
+                    None
+                }
+            }
+        };
+
+        match command_reader.read_command(&messages) {
+            CommandExpr::Help(_) | CommandExpr::HelpAlias(_) => {
+                command::print_command_help();
+            }
+            CommandExpr::SwitchThread(_, expr) | CommandExpr::SwitchThreadAlias(_, expr) => {
+                if let Some(value) = eval_expr(expr) {
+                    match dump.threads.iter().position(|thread| u32::try_from(value).map(ThreadId::from) == Ok(thread.id)) {
+                        Some(index) => current_thread_index = index,
+                        None => println!("No thread {value:#x} in the dump"),
+                    }
+                }
+            }
+            CommandExpr::ListThreads(_) | CommandExpr::ListThreadsAlias(_) => {
+                for (index, thread) in dump.threads.iter().enumerate() {
+                    let current_marker = if index == current_thread_index { "." } else { " " };
+                    println!("{current_marker} Thread {:#x}  TEB: {:#018x}  Suspend count: {}", thread.id, thread.teb, thread.suspend_count);
+                }
+            }
+            CommandExpr::Stack(_) | CommandExpr::StackAlias(_) => {
+                stack::print_stack(context, &mut process, memory_source);
+            }
+            CommandExpr::StackAll(_) | CommandExpr::StackAllAlias(_) => {
+                for thread in dump.threads.iter() {
+                    println!("\nThread {:#x}", thread.id);
+                    stack::print_stack(&thread.context, &mut process, memory_source);
+                }
+            }
+            CommandExpr::StackDump(_) | CommandExpr::StackDumpAlias(_) => {
+                if is_wow64 {
+                    println!("The 32-bit stacks of WOW64 dumps aren't supported. Showing the thread's 64-bit stack.");
+                }
+                match environment_blocks::get_stack_limits(current_thread.teb, memory_source) {
+                    Ok((_, stack_base)) => stack::print_stack_dump(context, stack_base, &mut process, memory_source),
+                    Err(e) => print_error!("Could not find the stack: {e}"),
+                }
+            }
+            CommandExpr::DisplayTeb(_) | CommandExpr::DisplayTebAlias(_) => {
+                if let Err(e) = environment_blocks::display_teb(current_thread.teb, memory_source) {
+                    print_error!("Could not display the TEB: {e}");
+                }
+            }
+            CommandExpr::DisplayPeb(_) | CommandExpr::DisplayPebAlias(_) => {
+                let result = environment_blocks::get_peb_address(current_thread.teb, memory_source)
+                    .and_then(|peb_address| environment_blocks::display_peb(peb_address, memory_source));
+                if let Err(e) = result {
+                    print_error!("Could not display the PEB: {e}");
+                }
+            }
+            CommandExpr::DisplayCommandLine(_) | CommandExpr::DisplayCommandLineAlias(_) => {
+                let result = environment_blocks::get_peb_address(current_thread.teb, memory_source)
+                    .and_then(|peb_address| environment_blocks::display_command_line(peb_address, memory_source));
+                if let Err(e) = result {
+                    print_error!("Could not display the command line: {e}");
+                }
+            }
+            CommandExpr::DisplayEnvironment(_) | CommandExpr::DisplayEnvironmentAlias(_) => {
+                let result = environment_blocks::get_peb_address(current_thread.teb, memory_source)
+                    .and_then(|peb_address| environment_blocks::display_environment(peb_address, memory_source));
+                if let Err(e) = result {
+                    print_error!("Could not display the environment: {e}");
+                }
+            }
+            CommandExpr::Heaps(_, expr) | CommandExpr::HeapsAlias(_, expr) => {
+                let heap_address = match expr {
+                    Some(expr) => eval_expr(expr).map(Some),
+                    None => Some(None),
+                };
+                if let Some(heap_address) = heap_address {
+                    let result = environment_blocks::get_peb_address(current_thread.teb, memory_source)
+                        .and_then(|peb_address| heaps::display_heaps(peb_address, heap_address, is_wow64, memory_source));
+                    if let Err(e) = result {
+                        print_error!("Could not display the heaps: {e}");
+                    }
+                }
+            }
+            CommandExpr::DisplayRegisters(_, register_set) | CommandExpr::DisplayRegistersAlias(_, register_set) => {
+                display_registers(*context, None, register_set, false);
+            }
+            CommandExpr::Evaluate(_, expr) | CommandExpr::EvaluateAlias(_, expr) => {
+                if let Some(val) = eval_expr(expr) {
+                    println!(" = {val:#x}");
+                }
+            }
+            CommandExpr::ListNearest(_, expr) | CommandExpr::ListNearestAlias(_, expr) => {
+                if let Some(val) = eval_expr(expr) {
+                    if let Some(sym) = name_resolution::resolve_address_to_name(val, &mut process) {
+                        println!("{}", output::symbol(&sym));
+                    } else {
+                        println!("No symbol found");
+                    }
+                }
+            }
+            CommandExpr::ExamineSymbols(_, pattern) | CommandExpr::ExamineSymbolsAlias(_, pattern) => {
+                examine_symbols(&pattern.pattern, &mut process);
+            }
+            CommandExpr::DisplayLocals(_) | CommandExpr::DisplayLocalsAlias(_) => {
+                locals::display_locals(context, &mut process, memory_source);
+            }
+            CommandExpr::ReloadSymbols(_, module_name) | CommandExpr::ReloadSymbolsAlias(_, module_name) => {
+                reload_symbols(module_name.as_ref().map(|module_name| module_name.name.as_str()), &mut process);
+            }
+            CommandExpr::ModuleDetails(_, module_name) | CommandExpr::ModuleDetailsAlias(_, module_name) => {
+                display_module_details(&module_name.name, &mut process, memory_source);
+            }
+            CommandExpr::TlsCallbacks(_, module_name, None) | CommandExpr::TlsCallbacksAlias(_, module_name, None) => {
+                display_tls_callbacks(&module_name.name, None, &mut process, memory_source);
+            }
+            CommandExpr::DisplayType(_, type_name, expr) | CommandExpr::DisplayTypeAlias(_, type_name, expr) => {
+                match expr.map(&mut eval_expr) {
+                    // The expression failed to evaluate, and the error was already printed.
+                    Some(None) => {}
+                    address => {
+                        if let Err(e) = types::display_type(&type_name.name, address.flatten(), &mut process, memory_source) {
+                            print_error!("{e}");
+                        }
+                    }
+                }
+            }
+            CommandExpr::DisplayExceptionRecord(_) | CommandExpr::DisplayExceptionRecordAlias(_) => {
+                display_exception_record(last_exception, &mut process);
+            }
+            CommandExpr::RunScript(_, path) | CommandExpr::RunScriptAlias(_, path) => {
+                if let Err(e) = command_reader.run_script(&path.path) {
+                    print_error!("{e}");
+                }
+            }
+            CommandExpr::OpenLog(_, path) | CommandExpr::OpenLogAlias(_, path) => open_log(&path.path),
+            CommandExpr::CloseLog(_) | CommandExpr::CloseLogAlias(_) => close_log(),
+            CommandExpr::LogLevel(_, level) | CommandExpr::LogLevelAlias(_, level) => set_log_level(level),
+            CommandExpr::Quit(_) | CommandExpr::QuitAlias(_) => {
+                return;
+            }
+            command => match get_display_memory_args(command) {
+                Some((format, expr, count)) => {
+                    let mut eval_context = eval::EvalContext{
+                        process: &mut process,
+                        register_context: context,
+                        memory_source,
+                        teb_address: Some(current_thread.teb),
+                        last_exception_address: last_exception.as_ref().map(|exception| exception.address),
+                    };
+                    run_display_memory_command(format, &expr, count.as_deref(), &mut eval_context);
+                }
+                None => println!("That command is not available when debugging a dump"),
+            },
+        }
+        if eval_failed {
+            command_reader.stop_script();
+        }
+    }
 }
 
 fn main() {
     let full_command_line_args: Vec<String> = env::args().collect();
     // The 1st argument is the name of the program
-    let target_command_line_args = &full_command_line_args[1..];
+    let args = &full_command_line_args[1..];
 
-    if target_command_line_args.is_empty() {
+    if args.is_empty() {
         show_usage();
         return;
-    };
+    }
 
-    launch_and_debug_process(target_command_line_args)
-}
\ No newline at end of file
+    match parse_command_line(args) {
+        Ok(CommandLine { target, session, no_color, verbose }) => {
+            // The full-screen mode shows the output as plain text.
+            output::init(no_color || session.tui);
+            logging::init(if verbose { logging::VERBOSE_LEVEL } else { logging::DEFAULT_LEVEL });
+            match target {
+                DebugTarget::Launch { command_line_args, options } => launch_and_debug_process(command_line_args, options, &session),
+                DebugTarget::AttachByName(process_name) => attach_by_name_and_debug_process(&process_name, &session),
+                DebugTarget::Dump(dump_path) => debug_dump(&dump_path, &session),
+            }
+        }
+        Err(e) => {
+            println!("{e}\n");
+            show_usage();
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::ffi::CString;
+
+use debugger_core::{
+    breakpoint::BreakpointManager,
+    command::grammar::{self, CommandExpr, EvalExpr},
+    eval,
+    memory::MemorySource,
+    name_resolution,
+    process::Process,
+    stack,
+};
+use pyo3::{exceptions::PyRuntimeError, prelude::*, types::{PyBytes, PyDict}};
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+// Hosts Python, so that existing Python debugging and triage scripts can be run against the target.
+// Scripts `import debugger` to get `read_memory(address, len)`, `eval(expression)`, `set_breakpoint(expression)`,
+// and `get_stack()`. Only built with the `python` feature.
+
+/// The debugger's state that a Python script can use while it runs.
+pub struct PythonTarget<'a> {
+    pub process: &'a mut Process,
+    pub breakpoints: &'a mut BreakpointManager,
+    pub context: &'a CONTEXT,
+    pub memory_source: Box<dyn MemorySource>,
+    pub teb_address: Option<u64>,
+    pub last_exception_address: Option<u64>,
+}
+
+/// Holds the target's state while a script runs. Python objects can't borrow, so the state is moved in and back out.
+#[pyclass(unsendable)]
+struct Session {
+    process: Process,
+    breakpoints: BreakpointManager,
+    context: CONTEXT,
+    memory_source: Box<dyn MemorySource>,
+    teb_address: Option<u64>,
+    last_exception_address: Option<u64>,
+}
+
+impl Session {
+    fn evaluate(&mut self, expr: &EvalExpr) -> Result<u64, String> {
+        let mut eval_context = eval::EvalContext {
+            process: &mut self.process,
+            register_context: &self.context,
+            memory_source: self.memory_source.as_ref(),
+            teb_address: self.teb_address,
+            last_exception_address: self.last_exception_address,
+        };
+        eval::evaluate_expression(expr, &mut eval_context)
+    }
+}
+
+fn parse_expression(expression: &str) -> PyResult<Box<EvalExpr>> {
+    // Expressions can't be parsed on their own, so parse them as the argument of `eval`.
+    match grammar::parse(&format!("eval {expression}")) {
+        Ok(mut command_list) if command_list.commands.len() == 1 => match command_list.commands.pop() {
+            Some(CommandExpr::Evaluate(_, expr)) => Ok(expr),
+            _ => Err(PyRuntimeError::new_err(format!("Could not parse expression: {expression}"))),
+        },
+        _ => Err(PyRuntimeError::new_err(format!("Could not parse expression: {expression}"))),
+    }
+}
+
+#[pymethods]
+impl Session {
+    /// Reads up to `len` bytes. Stops at the first byte that can't be read.
+    fn read_memory<'py>(&self, py: Python<'py>, address: u64, len: usize) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.memory_source.read_raw_memory(address, len))
+    }
+
+    /// Evaluates an expression like the `eval` command, e.g. `eval("rsp+8")`.
+    fn eval(&mut self, expression: &str) -> PyResult<u64> {
+        let expr = parse_expression(expression)?;
+        self.evaluate(&expr).map_err(PyRuntimeError::new_err)
+    }
+
+    /// Adds a breakpoint like the `breakpoint-add` command. Returns its address, or `None` if it will be set when its
+    /// module is loaded.
+    fn set_breakpoint(&mut self, expression: &str) -> PyResult<Option<u64>> {
+        let expr = parse_expression(expression)?;
+        match self.evaluate(&expr) {
+            Ok(address) => {
+                if !self.breakpoints.contains_breakpoint(address) {
+                    self.breakpoints.add_breakpoint(address, Some(expression.to_string()), None, None, self.memory_source.as_ref())
+                        .map_err(PyRuntimeError::new_err)?;
+                }
+                Ok(Some(address))
+            }
+            Err(e) => match *expr {
+                EvalExpr::Symbol(symbol) if name_resolution::is_symbol_in_unloaded_module(&symbol, &mut self.process) => {
+                    self.breakpoints.add_deferred_breakpoint(symbol, None, None);
+                    Ok(None)
+                }
+                _ => Err(PyRuntimeError::new_err(e)),
+            },
+        }
+    }
+
+    /// Returns the call stack as a list of `(stack_pointer, instruction_pointer, symbol)`, starting with the current frame.
+    /// `symbol` is `None` if the address has no symbol.
+    fn get_stack(&mut self) -> Vec<(u64, u64, Option<String>)> {
+        let mut frames = Vec::new();
+        let mut context = self.context;
+        for _ in 0..stack::MAX_STACK_FRAMES {
+            frames.push((context.Rsp, context.Rip, name_resolution::resolve_address_to_name(context.Rip, &mut self.process)));
+            match stack::unwind_context(&context, &self.process, self.memory_source.as_ref()) {
+                // Stop at the bottom of the stack, or when the unwind went wrong, like `stack` does.
+                Ok(caller_context) if caller_context.Rip != 0 && caller_context.Rsp > context.Rsp => context = caller_context,
+                _ => break,
+            }
+        }
+        frames
+    }
+}
+
+/// Replaces `sys.stdout` and `sys.stderr`, so that the script's output is also written to the session log.
+#[pyclass]
+struct Output;
+
+#[pymethods]
+impl Output {
+    fn write(&self, text: &str) -> usize {
+        print!("{text}");
+        text.len()
+    }
+
+    fn flush(&self) {}
+}
+
+/// Runs a Python script file.
+pub fn run_file(path: &str, target: PythonTarget) -> Result<(), String> {
+    let code = std::fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+    let code = CString::new(code).map_err(|_| format!("{path} contains a null character"))?;
+
+    Python::with_gil(|py| {
+        let is_wow64 = target.process.is_wow64;
+        let session = Session {
+            process: std::mem::replace(target.process, Process::new(is_wow64)),
+            breakpoints: std::mem::take(target.breakpoints),
+            context: *target.context,
+            memory_source: target.memory_source,
+            teb_address: target.teb_address,
+            last_exception_address: target.last_exception_address,
+        };
+        let session = Bound::new(py, session).map_err(|e| e.to_string())?;
+
+        let result = run_code(py, path, &code, &session);
+
+        // Give the state back, even if the script failed.
+        let mut session = session.borrow_mut();
+        *target.process = std::mem::replace(&mut session.process, Process::new(is_wow64));
+        *target.breakpoints = std::mem::take(&mut session.breakpoints);
+
+        result.map_err(|e| {
+            let traceback = e.traceback(py).and_then(|traceback| traceback.format().ok()).unwrap_or_default();
+            format!("Python error: {traceback}{e}")
+        })
+    })
+}
+
+fn run_code(py: Python, path: &str, code: &CString, session: &Bound<Session>) -> PyResult<()> {
+    let module = PyModule::new(py, "debugger")?;
+    for function in ["read_memory", "eval", "set_breakpoint", "get_stack"] {
+        module.add(function, session.getattr(function)?)?;
+    }
+    let sys = py.import("sys")?;
+    sys.getattr("modules")?.set_item("debugger", &module)?;
+    let output = Bound::new(py, Output)?;
+    sys.setattr("stdout", &output)?;
+    sys.setattr("stderr", &output)?;
+
+    let globals = PyDict::new(py);
+    globals.set_item("__name__", "__main__")?;
+    globals.set_item("__file__", path)?;
+    py.run(code, Some(&globals), None)
+}
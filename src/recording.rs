@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use debugger_core::{
+    arch::ThreadContext,
+    disassembly,
+    environment_blocks,
+    memory::MemorySource,
+};
+use windows::Win32::System::Diagnostics::Debug::{
+    CONTEXT,
+    CONTEXT_CONTROL_AMD64,
+    CONTEXT_INTEGER_AMD64,
+    CONTEXT_SEGMENTS_AMD64,
+};
+
+// A recording is made by single-stepping a thread, like `instruction-trace`. Before each instruction executes, its
+// registers are saved, along with the bytes of the instruction and of the memory that it accesses. Once it has
+// executed, the memory that it wrote is read again. That's enough to show the registers, disassembly, and the memory
+// that the thread used at any point in the recording, after the target has moved on or even exited.
+//
+// Only the recorded thread's accesses are captured, so memory that other threads or the kernel change shows the value
+// that the recorded thread last saw.
+
+/// Recordings are kept in memory, so stop before they use too much of it.
+const MAX_RECORDED_STEPS: usize = 100_000;
+
+/// The integer, control, and segment registers of a step. A whole `CONTEXT` is more than 1 KB, mostly for the vector and
+/// floating point registers, which would limit how long a recording can be.
+#[derive(Clone, Copy)]
+struct RecordedRegisters {
+    /// RAX through R15, in the order of `CONTEXT`.
+    general: [u64; 16],
+    rip: u64,
+    eflags: u32,
+    /// CS, DS, ES, FS, GS, and SS.
+    segments: [u16; 6],
+}
+
+impl RecordedRegisters {
+    fn new(context: &CONTEXT) -> RecordedRegisters {
+        RecordedRegisters {
+            general: [
+                context.Rax, context.Rcx, context.Rdx, context.Rbx, context.Rsp, context.Rbp, context.Rsi, context.Rdi,
+                context.R8, context.R9, context.R10, context.R11, context.R12, context.R13, context.R14, context.R15,
+            ],
+            rip: context.Rip,
+            eflags: context.EFlags,
+            segments: [context.SegCs, context.SegDs, context.SegEs, context.SegFs, context.SegGs, context.SegSs],
+        }
+    }
+
+    /// A context with these registers. The other registers are zero.
+    fn to_context(self) -> CONTEXT {
+        let [rax, rcx, rdx, rbx, rsp, rbp, rsi, rdi, r8, r9, r10, r11, r12, r13, r14, r15] = self.general;
+        let [seg_cs, seg_ds, seg_es, seg_fs, seg_gs, seg_ss] = self.segments;
+        CONTEXT {
+            ContextFlags: CONTEXT_CONTROL_AMD64 | CONTEXT_INTEGER_AMD64 | CONTEXT_SEGMENTS_AMD64,
+            Rax: rax, Rcx: rcx, Rdx: rdx, Rbx: rbx, Rsp: rsp, Rbp: rbp, Rsi: rsi, Rdi: rdi,
+            R8: r8, R9: r9, R10: r10, R11: r11, R12: r12, R13: r13, R14: r14, R15: r15,
+            Rip: self.rip,
+            EFlags: self.eflags,
+            SegCs: seg_cs, SegDs: seg_ds, SegEs: seg_es, SegFs: seg_fs, SegGs: seg_gs, SegSs: seg_ss,
+            ..Default::default()
+        }
+    }
+}
+
+/// The instructions that a thread executed, and the memory that they accessed. See `execution-record`.
+pub struct Recording {
+    bitness: u32,
+    teb_address: Option<u64>,
+    /// The registers before each instruction executed.
+    steps: Vec<RecordedRegisters>,
+    /// Each recorded byte's values, as `(step, value)` in step order, with only the steps where the value changed.
+    memory: HashMap<u64, Vec<(usize, u8)>>,
+    /// The memory that the last recorded instruction writes, which is read again once it has executed.
+    pending_writes: Vec<(u64, usize)>,
+    /// The step that replay commands show.
+    position: usize,
+}
+
+impl Recording {
+    pub fn new(is_wow64: bool, teb_address: Option<u64>) -> Recording {
+        Recording {
+            bitness: disassembly::get_bitness(is_wow64),
+            teb_address,
+            steps: Vec::new(),
+            memory: HashMap::new(),
+            pending_writes: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn bitness(&self) -> u32 {
+        self.bitness
+    }
+
+    pub fn teb_address(&self) -> Option<u64> {
+        self.teb_address
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Records the instruction that the thread is about to execute, and the memory that it accesses.
+    pub fn record(&mut self, context: &CONTEXT, memory_source: &dyn MemorySource) -> Result<(), String> {
+        if self.steps.len() >= MAX_RECORDED_STEPS {
+            return Err(format!("The recording is full, at {MAX_RECORDED_STEPS} instructions"));
+        }
+        let step = self.steps.len();
+        self.save_pending_writes(step, memory_source);
+
+        let address = context.instruction_pointer();
+        self.save_memory(step, address, &memory_source.read_raw_memory(address, disassembly::MAX_INSTRUCTION_LENGTH));
+        let segment_base = match (self.teb_address, self.bitness) {
+            (Some(teb_address), 32) => teb_address + environment_blocks::WOW64_TEB32_OFFSET,
+            (Some(teb_address), _) => teb_address,
+            (None, _) => 0,
+        };
+        // The instruction may not decode, e.g. if it's about to raise an exception, which is still worth recording.
+        if let Ok(instruction) = disassembly::decode_instruction(memory_source, address, self.bitness) {
+            for access in disassembly::get_memory_accesses(&instruction, |name| context.get_register(name), segment_base) {
+                self.save_memory(step, access.address, &memory_source.read_raw_memory(access.address, access.size));
+                if access.is_write {
+                    self.pending_writes.push((access.address, access.size));
+                }
+            }
+        }
+        self.steps.push(RecordedRegisters::new(context));
+        Ok(())
+    }
+
+    /// Reads the memory that the last recorded instruction writes, for when no more instructions will be recorded.
+    /// The values are saved for the step after the last one.
+    pub fn finish(&mut self, memory_source: &dyn MemorySource) {
+        let step = self.steps.len();
+        self.save_pending_writes(step, memory_source);
+    }
+
+    fn save_pending_writes(&mut self, step: usize, memory_source: &dyn MemorySource) {
+        for (address, size) in std::mem::take(&mut self.pending_writes) {
+            self.save_memory(step, address, &memory_source.read_raw_memory(address, size));
+        }
+    }
+
+    fn save_memory(&mut self, step: usize, address: u64, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            let values = self.memory.entry(address + offset as u64).or_default();
+            if values.last().map(|(_, value)| value) != Some(byte) {
+                values.push((step, *byte));
+            }
+        }
+    }
+
+    fn read_byte(&self, address: u64) -> Option<u8> {
+        let values = self.memory.get(&address)?;
+        // Before the thread first accessed a byte, it had the value that the thread first saw.
+        let index = values.partition_point(|(step, _)| *step <= self.position).max(1);
+        Some(values[index - 1].1)
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves to a step, which is clamped to the recording.
+    pub fn move_to(&mut self, step: usize) {
+        self.position = step.min(self.steps.len().saturating_sub(1));
+    }
+
+    /// The registers at the current step. Only the integer, control, and segment registers are recorded.
+    pub fn context(&self) -> CONTEXT {
+        self.steps[self.position].to_context()
+    }
+
+    /// The registers at the step before the current one, to highlight what the last instruction changed.
+    pub fn previous_context(&self) -> Option<CONTEXT> {
+        self.position.checked_sub(1).map(|step| self.steps[step].to_context())
+    }
+}
+
+/// Reads memory as it was at the current step.
+impl MemorySource for Recording {
+    fn read_memory(&self, address: u64, len: usize) -> Result<Vec<Option<u8>>, String> {
+        Ok((0..len as u64).map(|offset| self.read_byte(address + offset)).collect())
+    }
+
+    fn read_raw_memory(&self, address: u64, len: usize) -> Vec<u8> {
+        (0..len as u64).map_while(|offset| self.read_byte(address + offset)).collect()
+    }
+
+    fn write_raw_memory(&self, address: u64, _data: &[u8]) -> Result<(), String> {
+        Err(format!("Cannot write to {address:#x} because the target is a recording"))
+    }
+}
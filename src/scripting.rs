@@ -0,0 +1,180 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use debugger_core::{
+    arch::ThreadContext,
+    memory::{self, MemorySource},
+    process::Process,
+};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, FnPtr, Map, Scope, AST};
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+// Runs Rhai scripts (https://rhai.rs) for custom automation, e.g. dumping a structure every time a function is hit.
+// Scripts get a `target` variable for reading registers and memory, and can register callbacks with
+// `on_breakpoint(location, |target| ...)`. A callback's breakpoint stops only if the callback returns `true`.
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// How many characters `read_string` and `read_wstring` read at most.
+const MAX_STRING_LENGTH: usize = 1024;
+
+/// The state of the target when a script runs, as seen by the script.
+#[derive(Clone)]
+pub struct ScriptTarget {
+    context: CONTEXT,
+    memory_source: Rc<dyn MemorySource>,
+    pointer_size: usize,
+    /// `(name, address, size)` of each loaded module.
+    modules: Rc<Vec<(String, u64, u64)>>,
+}
+
+impl ScriptTarget {
+    pub fn new(context: &CONTEXT, memory_source: Box<dyn MemorySource>, process: &Process) -> ScriptTarget {
+        ScriptTarget {
+            context: *context,
+            memory_source: Rc::from(memory_source),
+            pointer_size: process.pointer_size(),
+            modules: Rc::new(process.iterate_modules().map(|module| (module.name.clone(), module.address, module.size)).collect()),
+        }
+    }
+
+    fn read<const N: usize>(&self, address: i64) -> ScriptResult<[u8; N]> {
+        let address = address as u64;
+        self.memory_source.read_raw_memory(address, N)
+            .try_into()
+            .map_err(|_| format!("Could not read {N} bytes at {address:#018x}").into())
+    }
+
+    fn read_pointer(&self, address: i64) -> ScriptResult<i64> {
+        if self.pointer_size == 4 {
+            Ok(u32::from_le_bytes(self.read(address)?) as i64)
+        } else {
+            Ok(u64::from_le_bytes(self.read(address)?) as i64)
+        }
+    }
+
+    fn read_string(&self, address: i64, is_wide: bool) -> String {
+        if is_wide {
+            memory::read_memory_string(self.memory_source.as_ref(), address as u64, MAX_STRING_LENGTH, true)
+        } else {
+            // The target's strings may not be UTF-8, so don't fail on them.
+            let mut bytes = self.memory_source.read_raw_memory(address as u64, MAX_STRING_LENGTH);
+            if let Some(null_pos) = bytes.iter().position(|&byte| byte == 0) {
+                bytes.truncate(null_pos);
+            }
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+    }
+
+    fn modules(&self) -> Array {
+        self.modules.iter()
+            .map(|(name, address, size)| {
+                let mut module = Map::new();
+                module.insert("name".into(), name.clone().into());
+                module.insert("address".into(), (*address as i64).into());
+                module.insert("size".into(), (*size as i64).into());
+                module.into()
+            })
+            .collect()
+    }
+}
+
+/// A breakpoint callback that a script registered, which the caller must resolve to an address with `add_callback`.
+pub struct PendingCallback {
+    /// A symbol, e.g. `kernel32!CreateFileW`, or an address.
+    pub location: String,
+    function: FnPtr,
+    ast: Rc<AST>,
+}
+
+struct BreakpointCallback {
+    address: u64,
+    function: FnPtr,
+    /// The script that defined the function, which is needed to call it.
+    ast: Rc<AST>,
+}
+
+pub struct ScriptEngine {
+    engine: Engine,
+    /// Callbacks that the running script has registered with `on_breakpoint`.
+    pending_callbacks: Rc<RefCell<Vec<(String, FnPtr)>>>,
+    callbacks: Vec<BreakpointCallback>,
+}
+
+impl ScriptEngine {
+    pub fn new() -> ScriptEngine {
+        let mut engine = Engine::new();
+        engine.on_print(|text| println!("{text}"));
+        engine.on_debug(|text, _, _| println!("{text}"));
+
+        engine.register_type_with_name::<ScriptTarget>("Target")
+            .register_fn("reg", |target: &mut ScriptTarget, name: &str| -> ScriptResult<i64> {
+                target.context.get_register(name)
+                    .map(|value| value as i64)
+                    .ok_or_else(|| format!("Unknown register {name}").into())
+            })
+            .register_get("ip", |target: &mut ScriptTarget| target.context.instruction_pointer() as i64)
+            .register_fn("read_u8", |target: &mut ScriptTarget, address: i64| -> ScriptResult<i64> { Ok(u8::from_le_bytes(target.read(address)?) as i64) })
+            .register_fn("read_u16", |target: &mut ScriptTarget, address: i64| -> ScriptResult<i64> { Ok(u16::from_le_bytes(target.read(address)?) as i64) })
+            .register_fn("read_u32", |target: &mut ScriptTarget, address: i64| -> ScriptResult<i64> { Ok(u32::from_le_bytes(target.read(address)?) as i64) })
+            .register_fn("read_u64", |target: &mut ScriptTarget, address: i64| -> ScriptResult<i64> { Ok(u64::from_le_bytes(target.read(address)?) as i64) })
+            .register_fn("read_pointer", |target: &mut ScriptTarget, address: i64| target.read_pointer(address))
+            .register_fn("read_bytes", |target: &mut ScriptTarget, address: i64, len: i64| -> Blob {
+                target.memory_source.read_raw_memory(address as u64, len.max(0) as usize)
+            })
+            .register_fn("read_string", |target: &mut ScriptTarget, address: i64| target.read_string(address, false))
+            .register_fn("read_wstring", |target: &mut ScriptTarget, address: i64| target.read_string(address, true))
+            .register_fn("modules", |target: &mut ScriptTarget| target.modules());
+
+        let pending_callbacks = Rc::new(RefCell::new(Vec::new()));
+        let pending = pending_callbacks.clone();
+        engine.register_fn("on_breakpoint", move |location: &str, function: FnPtr| {
+            pending.borrow_mut().push((location.to_string(), function));
+        });
+        let pending = pending_callbacks.clone();
+        engine.register_fn("on_breakpoint", move |address: i64, function: FnPtr| {
+            pending.borrow_mut().push((format!("{address:#x}"), function));
+        });
+
+        ScriptEngine { engine, pending_callbacks, callbacks: Vec::new() }
+    }
+
+    /// Runs a script file. Returns the breakpoint callbacks that it registered.
+    pub fn run_file(&mut self, path: &str, target: ScriptTarget) -> Result<Vec<PendingCallback>, String> {
+        let ast = self.engine.compile_file(PathBuf::from(path)).map_err(|e| format!("Could not load {path}: {e}"))?;
+        let mut scope = Scope::new();
+        scope.push("target", target);
+        let result = self.engine.run_ast_with_scope(&mut scope, &ast);
+
+        // Take the callbacks even if the script failed, so that they don't carry over to the next script.
+        let ast = Rc::new(ast);
+        let pending = self.pending_callbacks.borrow_mut()
+            .drain(..)
+            .map(|(location, function)| PendingCallback { location, function, ast: ast.clone() })
+            .collect();
+        result.map_err(|e| format!("Script error: {e}"))?;
+        Ok(pending)
+    }
+
+    pub fn add_callback(&mut self, address: u64, pending: PendingCallback) {
+        self.callbacks.push(BreakpointCallback { address, function: pending.function, ast: pending.ast });
+    }
+
+    pub fn remove_callbacks(&mut self, address: u64) {
+        self.callbacks.retain(|callback| callback.address != address);
+    }
+
+    pub fn has_callbacks(&self, address: u64) -> bool {
+        self.callbacks.iter().any(|callback| callback.address == address)
+    }
+
+    /// Calls the callbacks for a breakpoint. Returns whether any of them asked to stop.
+    pub fn call_breakpoint_callbacks(&self, address: u64, target: ScriptTarget) -> Result<bool, String> {
+        let mut stop = false;
+        for callback in self.callbacks.iter().filter(|callback| callback.address == address) {
+            let result = callback.function.call::<Dynamic>(&self.engine, &callback.ast, (target.clone(),))
+                .map_err(|e| format!("Error in breakpoint callback {}: {e}", callback.function.fn_name()))?;
+            stop |= result.as_bool() == Ok(true);
+        }
+        Ok(stop)
+    }
+}
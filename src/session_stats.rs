@@ -0,0 +1,90 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use debugger_core::{
+    memory,
+    windows_wrapper::DebugEvent,
+};
+
+/// Counts what happened during the session, to understand how much the debugger slows the target down, e.g. on long
+/// scripted runs. See `session-stats`.
+pub struct SessionStats {
+    start: Instant,
+    /// Keyed by the kind of event, e.g. `exception`.
+    event_counts: BTreeMap<&'static str, u64>,
+    breakpoint_hits: u64,
+    single_steps: u64,
+    /// How long the target ran for, not counting the current run.
+    running_time: Duration,
+    /// When the target was last continued, while it runs.
+    running_since: Option<Instant>,
+}
+
+impl SessionStats {
+    /// Starts counting. The target is running, since it is started before the first debug event.
+    pub fn new() -> SessionStats {
+        let start = Instant::now();
+        SessionStats {
+            start,
+            event_counts: BTreeMap::new(),
+            breakpoint_hits: 0,
+            single_steps: 0,
+            running_time: Duration::ZERO,
+            running_since: Some(start),
+        }
+    }
+
+    /// Records a debug event, which stops the target.
+    pub fn record_event(&mut self, event: &DebugEvent) {
+        if let Some(running_since) = self.running_since.take() {
+            self.running_time += running_since.elapsed();
+        }
+        let kind = match event {
+            DebugEvent::Exception { .. } => "exception",
+            DebugEvent::CreateProcess { .. } => "create process",
+            DebugEvent::ExitProcess { .. } => "exit process",
+            DebugEvent::CreateThread { .. } => "create thread",
+            DebugEvent::ExitThread { .. } => "exit thread",
+            DebugEvent::LoadDll { .. } => "load module",
+            DebugEvent::UnloadDll { .. } => "unload module",
+            DebugEvent::OutputDebugString(_) => "debug output",
+            DebugEvent::Rip { .. } => "RIP",
+        };
+        *self.event_counts.entry(kind).or_default() += 1;
+    }
+
+    /// Records that the target continued after a debug event.
+    pub fn record_continue(&mut self) {
+        self.running_since = Some(Instant::now());
+    }
+
+    /// Records a breakpoint that the target hit, whether or not it stopped.
+    pub fn record_breakpoint_hit(&mut self) {
+        self.breakpoint_hits += 1;
+    }
+
+    /// Records a thread that the debugger single-stepped, e.g. for `step`, or to step past a breakpoint.
+    pub fn record_single_step(&mut self) {
+        self.single_steps += 1;
+    }
+
+    pub fn display(&self) {
+        let total_time = self.start.elapsed();
+        let running_time = self.running_time + self.running_since.map_or(Duration::ZERO, |running_since| running_since.elapsed());
+        let stopped_time = total_time.saturating_sub(running_time);
+        let percent = |time: Duration| if total_time.is_zero() { 0.0 } else { time.as_secs_f64() / total_time.as_secs_f64() * 100.0 };
+
+        println!("Session time:     {:.3}s", total_time.as_secs_f64());
+        println!("  Running:        {:.3}s ({:.1}%)", running_time.as_secs_f64(), percent(running_time));
+        println!("  Stopped:        {:.3}s ({:.1}%)", stopped_time.as_secs_f64(), percent(stopped_time));
+        println!("Debug events:     {}", self.event_counts.values().sum::<u64>());
+        for (kind, count) in self.event_counts.iter() {
+            println!("  {:<16}{count}", format!("{kind}:"));
+        }
+        println!("Breakpoint hits:  {}", self.breakpoint_hits);
+        println!("Single steps:     {}", self.single_steps);
+        println!("Memory read:      {} bytes", memory::get_bytes_read());
+    }
+}
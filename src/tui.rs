@@ -0,0 +1,307 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use debugger_core::{
+    arch::ThreadContext,
+    disassembly,
+    memory::MemorySource,
+    module::SourceLine,
+    name_resolution,
+    output,
+    process::Process,
+    registers,
+    windows_wrapper,
+};
+use ratatui::{
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+        terminal,
+    },
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    DefaultTerminal,
+    Frame,
+};
+use windows::Win32::System::Diagnostics::Debug::CONTEXT;
+
+// The full-screen mode (`--tui`), which shows the disassembly, source, and registers at each stop in panes above the
+// output of the commands and the command line.
+// The debugger's output is captured (see `output::start_capture`) and shown in the output pane. Commands are read on the
+// input thread (see `CommandReader`), which redraws the screen as output arrives. The terminal is in raw mode while a
+// command is being typed, so Ctrl+C is read as a key, and breaks into the target if it is running.
+
+/// How many instructions to disassemble at each stop. The pane shows as many as fit.
+const DISASSEMBLY_COUNT: usize = 64;
+/// How many lines of output to keep for scrolling back.
+const MAX_OUTPUT_LINES: usize = 10000;
+/// How often to redraw while waiting for a key, to show new output.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const X64_REGISTERS: [&str; 18] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "rip",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "eflags",
+];
+const X86_REGISTERS: [&str; 10] = ["eax", "ebx", "ecx", "edx", "esi", "edi", "ebp", "esp", "eip", "eflags"];
+
+/// A source file around the current line.
+pub struct SourceView {
+    pub location: SourceLine,
+    /// The file's lines, or `None` if it couldn't be read.
+    pub lines: Option<Vec<String>>,
+}
+
+/// What the panes show about where the target stopped.
+#[derive(Default)]
+pub struct StopView {
+    /// Describes the thread and where it is, e.g. `Thread: main (0x1234) cmd!main`.
+    pub title: String,
+    /// The instructions starting at the instruction pointer, as `(address, text)`.
+    pub disassembly: Vec<(u64, String)>,
+    /// `(name, value, changed)` of each register. `changed` is whether it differs from when the thread last stopped.
+    pub registers: Vec<(&'static str, String, bool)>,
+    pub source: Option<SourceView>,
+}
+
+impl StopView {
+    pub fn new(
+        title: String,
+        context: &CONTEXT,
+        previous_context: Option<&CONTEXT>,
+        process: &mut Process,
+        memory_source: &dyn MemorySource,
+        is_wow64: bool,
+    ) -> StopView {
+        let instruction_pointer = context.instruction_pointer();
+        let disassembly = disassembly::format_instructions(memory_source, instruction_pointer, DISASSEMBLY_COUNT, disassembly::get_bitness(is_wow64));
+
+        let register_names: &[&'static str] = if is_wow64 { &X86_REGISTERS } else { &X64_REGISTERS };
+        let registers = register_names.iter()
+            .filter_map(|&name| {
+                let value = registers::get_register(context, name)?;
+                let changed = previous_context.and_then(|previous| registers::get_register(previous, name)).is_some_and(|previous| previous != value);
+                let text = if is_wow64 || name == "eflags" { format!("{value:08x}") } else { format!("{value:016x}") };
+                Some((name, text, changed))
+            })
+            .collect();
+
+        let source = name_resolution::resolve_address_to_line(instruction_pointer, process).map(|location| {
+            let lines = std::fs::read_to_string(&location.file).ok().map(|contents| contents.lines().map(String::from).collect());
+            SourceView { location, lines }
+        });
+
+        StopView { title, disassembly, registers, source }
+    }
+}
+
+pub struct Tui {
+    terminal: DefaultTerminal,
+    /// Set by the debugger's thread at each stop.
+    view: Arc<Mutex<StopView>>,
+    /// The output, one entry per line. The last line is the one that output is being added to.
+    output: VecDeque<String>,
+    /// How many lines the output pane is scrolled back from the end.
+    output_scroll: usize,
+    input: String,
+    /// The position of the cursor in `input`, in characters.
+    cursor: usize,
+    history: Vec<String>,
+    /// The entry in `history` that is shown while browsing it with the arrow keys.
+    history_index: Option<usize>,
+}
+
+impl Tui {
+    /// Switches the terminal to the full-screen mode, and starts capturing the debugger's output.
+    pub fn new() -> Result<Tui, String> {
+        let terminal = ratatui::try_init().map_err(|e| format!("Could not start the full-screen mode: {e}"))?;
+        let _ = terminal::disable_raw_mode();
+        output::start_capture();
+        Ok(Tui {
+            terminal,
+            view: Arc::default(),
+            output: VecDeque::from([String::new()]),
+            output_scroll: 0,
+            input: String::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: None,
+        })
+    }
+
+    pub fn view(&self) -> Arc<Mutex<StopView>> {
+        self.view.clone()
+    }
+
+    fn add_output(&mut self, text: &str) {
+        let text = output::strip_colors(text);
+        let mut parts = text.split('\n');
+        if let Some(first) = parts.next() {
+            // There's always at least one line.
+            self.output.back_mut().unwrap().push_str(first);
+        }
+        self.output.extend(parts.map(String::from));
+        while self.output.len() > MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    /// Reads a command line, or `None` at the end of input (Ctrl+D or Ctrl+Z).
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        if let Err(e) = terminal::enable_raw_mode() {
+            print_error!("Could not read the command: {e}");
+            return None;
+        }
+        let line = self.read_line_raw(prompt.trim_start());
+        let _ = terminal::disable_raw_mode();
+        line
+    }
+
+    fn read_line_raw(&mut self, prompt: &str) -> Option<String> {
+        self.output_scroll = 0;
+        loop {
+            let captured = output::take_captured();
+            self.add_output(&captured);
+            let view = self.view.lock().unwrap();
+            let result = self.terminal.draw(|frame| draw(frame, &view, &self.output, self.output_scroll, prompt, &self.input, self.cursor));
+            drop(view);
+            if let Err(e) = result {
+                print_error!("Could not draw the screen: {e}");
+                return None;
+            }
+
+            let key = match event::poll(REDRAW_INTERVAL).and_then(|ready| if ready { event::read().map(Some) } else { Ok(None) }) {
+                Ok(Some(Event::Key(key))) if key.kind == KeyEventKind::Press => key,
+                // Redraw, e.g. to show new output or after the terminal is resized.
+                Ok(_) => continue,
+                Err(e) => {
+                    print_error!("Could not read the command: {e}");
+                    return None;
+                }
+            };
+            let byte_index = |input: &str, cursor: usize| input.char_indices().nth(cursor).map_or(input.len(), |(index, _)| index);
+            match key.code {
+                KeyCode::Enter => {
+                    let line = std::mem::take(&mut self.input);
+                    self.cursor = 0;
+                    self.history_index = None;
+                    if !line.trim().is_empty() {
+                        self.history.push(line.clone());
+                    }
+                    return Some(line);
+                }
+                // Like the console, Ctrl+C breaks into the target if it is running, and otherwise discards the line.
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) && !windows_wrapper::request_break_in() => {
+                    self.input.clear();
+                    self.cursor = 0;
+                }
+                KeyCode::Char('d' | 'z') if key.modifiers.contains(KeyModifiers::CONTROL) && self.input.is_empty() => return None,
+                // Don't type other control keys.
+                KeyCode::Char(_) if key.modifiers.contains(KeyModifiers::CONTROL) => {}
+                KeyCode::Char(c) => {
+                    self.input.insert(byte_index(&self.input, self.cursor), c);
+                    self.cursor += 1;
+                }
+                KeyCode::Backspace if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.input.remove(byte_index(&self.input, self.cursor));
+                }
+                KeyCode::Delete if self.cursor < self.input.chars().count() => {
+                    self.input.remove(byte_index(&self.input, self.cursor));
+                }
+                KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+                KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.chars().count()),
+                KeyCode::Home => self.cursor = 0,
+                KeyCode::End => self.cursor = self.input.chars().count(),
+                KeyCode::Up | KeyCode::Down if !self.history.is_empty() => {
+                    let index = match (key.code, self.history_index) {
+                        (KeyCode::Up, None) => Some(self.history.len() - 1),
+                        (KeyCode::Up, Some(index)) => Some(index.saturating_sub(1)),
+                        (_, Some(index)) if index + 1 < self.history.len() => Some(index + 1),
+                        _ => None,
+                    };
+                    self.history_index = index;
+                    self.input = index.map(|index| self.history[index].clone()).unwrap_or_default();
+                    self.cursor = self.input.chars().count();
+                }
+                KeyCode::PageUp => self.output_scroll = (self.output_scroll + 10).min(self.output.len().saturating_sub(1)),
+                KeyCode::PageDown => self.output_scroll = self.output_scroll.saturating_sub(10),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Leaves the full-screen mode. The input thread can't be stopped while it waits for a key, so this is called by the
+/// debugger's thread when it is done.
+pub fn restore() {
+    ratatui::restore();
+    // Show what was printed after the last command, e.g. that the target exited. It was already logged.
+    std::print!("{}", output::stop_capture());
+}
+
+fn draw(frame: &mut Frame, view: &StopView, output: &VecDeque<String>, output_scroll: usize, prompt: &str, input: &str, cursor: usize) {
+    let [top, output_area, input_area] = Layout::vertical([Constraint::Percentage(50), Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+    let [disassembly_area, source_area, registers_area] = Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1), Constraint::Length(26)]).areas(top);
+
+    let disassembly: Vec<Line> = view.disassembly.iter()
+        .enumerate()
+        .map(|(index, (address, text))| {
+            let line = Line::from(vec![Span::styled(format!("{address:016x} "), Style::new().fg(Color::Cyan)), Span::raw(text.as_str())]);
+            // The first instruction is the one at the instruction pointer.
+            if index == 0 { line.style(Style::new().add_modifier(Modifier::REVERSED)) } else { line }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(disassembly).block(Block::bordered().title(view.title.as_str())), disassembly_area);
+
+    draw_source(frame, view.source.as_ref(), source_area);
+
+    let registers: Vec<Line> = view.registers.iter()
+        .map(|(name, value, changed)| {
+            let style = if *changed { Style::new().fg(Color::LightRed) } else { Style::new() };
+            Line::from(vec![Span::raw(format!("{name:>6} ")), Span::styled(value.as_str(), style)])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(registers).block(Block::bordered().title("Registers")), registers_area);
+
+    // Show the end of the output, or further back if it is scrolled.
+    let height = output_area.height.saturating_sub(2) as usize;
+    let end = output.len().saturating_sub(output_scroll);
+    let lines: Vec<Line> = output.range(end.saturating_sub(height)..end).map(|line| Line::raw(line.as_str())).collect();
+    let title = if output_scroll > 0 { format!("Output (scrolled back {output_scroll} lines)") } else { String::from("Output") };
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), output_area);
+
+    frame.render_widget(Paragraph::new(format!("{prompt}{input}")).block(Block::bordered().title("Command")), input_area);
+    let prompt_width = prompt.chars().count() as u16;
+    frame.set_cursor_position(Position::new(input_area.x + 1 + prompt_width + cursor as u16, input_area.y + 1));
+}
+
+/// Shows the source around the current line, which is highlighted and kept in the middle of the pane.
+fn draw_source(frame: &mut Frame, source: Option<&SourceView>, area: Rect) {
+    let Some(source) = source else {
+        frame.render_widget(Paragraph::new("No source line").block(Block::bordered().title("Source")), area);
+        return;
+    };
+    let block = Block::bordered().title(source.location.to_string());
+    let Some(lines) = &source.lines else {
+        frame.render_widget(Paragraph::new("Could not read the source file").block(block), area);
+        return;
+    };
+
+    // Line numbers start at 1.
+    let current_index = (source.location.line as usize).saturating_sub(1);
+    let height = area.height.saturating_sub(2) as usize;
+    let start = current_index.saturating_sub(height / 2);
+    let lines: Vec<Line> = lines.iter()
+        .enumerate()
+        .skip(start)
+        .take(height)
+        .map(|(index, text)| {
+            let line = Line::raw(format!("{:>5} {text}", index + 1));
+            if index == current_index { line.style(Style::new().add_modifier(Modifier::REVERSED)) } else { line }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}